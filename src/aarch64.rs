@@ -0,0 +1,456 @@
+// Copyright 2022 The Venice Authors. All rights reserved.
+// Use of this source code is governed by an MIT-style license that can be
+// found in the LICENSE file.
+//
+// Compiles a VIL program into AArch64 (AAPCS64) assembly text. This is another implementation of
+// the `backend::Backend` trait, alongside `x86.rs`, `riscv.rs`, and `bytecode.rs`; see backend.rs's
+// module comment for the VIL -> machine code pipeline every backend plugs into.
+//
+// Like riscv.rs and unlike x86.rs, this backend maps each VIL register directly onto a fixed
+// physical register (see `REGISTERS`) instead of running its own live-interval allocator over
+// physical registers. It also has no machine-code encoder -- `--emit=asm` is as far as the
+// pipeline can take a `--target aarch64` program today, since there is no AArch64 assembler or
+// linker wired up yet.
+//
+// One simplification worth calling out: pushing/popping a single register around a call or in the
+// prologue/epilogue always moves `sp` by 16 bytes rather than 8, even though each slot only needs
+// 8. AAPCS64 requires `sp` to stay 16-byte aligned at all times (not just across calls, the way
+// SysV x86-64 only requires it at a `call`), and pairing registers up to halve the wasted space
+// would make this module's control flow diverge from riscv.rs's for no benefit this backend's
+// scope (text-only, no real assembler run over it) would ever make use of.
+
+use super::backend::Backend;
+use super::vil;
+use std::fmt;
+
+/// Tells codegen.rs's allocator this target has 14 usable VIL registers (see
+/// `backend::BackendConfig`) -- the same cap as `x86::X86Config` and `riscv::RiscvConfig`, even
+/// though `REGISTERS` below has 16 entries, since indices 14 and 15 are `sp`/`x29` (the stack and
+/// frame pointers), which are only ever touched directly by `prologue`/`epilogue`, never handed
+/// out to an ordinary VIL register.
+pub struct Aarch64Config;
+
+impl super::backend::BackendConfig for Aarch64Config {
+    fn register_count(&self) -> u8 {
+        14
+    }
+}
+
+pub fn generate(vil: &vil::Program) -> Result<Program, String> {
+    let mut generator = Generator::new();
+    generator.program.externs = vil.externs.clone();
+    super::backend::generate(&mut generator, vil);
+
+    for (string_name, string_value) in &vil.strings {
+        generator.program.data.push(Data {
+            name: string_name.clone(),
+            value: string_value.clone(),
+        });
+    }
+
+    Ok(generator.program)
+}
+
+pub struct Program {
+    externs: Vec<String>,
+    blocks: Vec<Block>,
+    data: Vec<Data>,
+}
+
+pub struct Block {
+    global: bool,
+    label: String,
+    instructions: Vec<Instruction>,
+}
+
+pub struct Data {
+    name: String,
+    value: String,
+}
+
+/// A single AArch64 instruction, in the same operand shape the assembler mnemonics take. `Raw`
+/// exists for directives whose operand count varies too much to give each its own variant -- the
+/// same reasoning as `riscv::Instruction::Raw`.
+pub enum Instruction {
+    Raw(String),
+}
+
+/// VIL register index -> AArch64 register name. Indices 7 and 13 both map to `x0`: those are
+/// `vil::Register`'s own param-0 and return/scratch indices (see vil.rs's
+/// `SCRATCH2_REGISTER_INDEX`/`RETURN_REGISTER_INDEX` comment), which already double up onto a
+/// single x86 register each; AAPCS64's calling convention uses `x0` for both of those roles too,
+/// so the same doubling falls out naturally here, just as it does in riscv.rs.
+const REGISTERS: &[&str] = &[
+    "x9", "x10", "x19", "x20", "x21", "x22", "x23", "x0", "x1", "x2", "x3", "x4", "x5", "x0", "sp",
+    "x29",
+];
+
+const CALLER_SAVE_REGISTERS: &[u8] = &[0, 1];
+const CALLEE_SAVE_REGISTERS: &[u8] = &[2, 3, 4, 5, 6];
+
+fn register_name(r: vil::Register) -> &'static str {
+    REGISTERS[r.index() as usize]
+}
+
+struct Generator {
+    program: Program,
+    frame_size: i32,
+    /// AArch64's `cmp`/`fcmp` write to the flags register rather than a GPR, same as x86's
+    /// `Cmp`/`Test` and unlike a GPR-result comparison, so there's nothing to lower `Cmp`/`FCmp`
+    /// to on their own: `lower_cmp`/`lower_fcmp` just remember their operands here for the
+    /// `JumpIf` that VIL always emits immediately afterward to pick up and lower into a single
+    /// compare-and-branch sequence.
+    last_cmp: Option<(vil::Register, vil::Register, bool)>,
+}
+
+impl Generator {
+    fn new() -> Self {
+        Generator {
+            program: Program {
+                externs: Vec::new(),
+                blocks: Vec::new(),
+                data: Vec::new(),
+            },
+            frame_size: 0,
+            last_cmp: None,
+        }
+    }
+
+    fn push(&mut self, instruction: Instruction) {
+        let index = self.program.blocks.len() - 1;
+        self.program.blocks[index].instructions.push(instruction);
+    }
+
+    fn raw(&mut self, text: String) {
+        self.push(Instruction::Raw(text));
+    }
+}
+
+impl Backend for Generator {
+    fn start_function(&mut self, declaration: &vil::FunctionDeclaration) {
+        // This backend has no spill-slot allocator of its own (see the module comment), so the
+        // frame is just the one VIL already asked for.
+        self.frame_size = declaration.stack_frame_size;
+
+        self.program.blocks.push(Block {
+            global: declaration.name == "venice_main",
+            label: declaration.name.clone(),
+            instructions: Vec::new(),
+        });
+    }
+
+    fn start_block(&mut self, name: &str) {
+        self.program.blocks.push(Block {
+            global: false,
+            label: String::from(name),
+            instructions: Vec::new(),
+        });
+    }
+
+    fn prologue(&mut self) {
+        // Reserve the frame, then save the link register and the caller's frame pointer at its
+        // top -- the standard AAPCS64 function-entry sequence.
+        let total = self.frame_size + 16;
+        self.raw(format!("sub sp, sp, #{}", total));
+        self.raw(format!("str x30, [sp, #{}]", total - 8));
+        self.raw(format!("str x29, [sp, #{}]", total - 16));
+        self.raw(format!("add x29, sp, #{}", total));
+
+        for callee_save in CALLEE_SAVE_REGISTERS {
+            self.raw(String::from("sub sp, sp, #16"));
+            self.raw(format!("str {}, [sp]", REGISTERS[*callee_save as usize]));
+        }
+    }
+
+    fn epilogue(&mut self) {
+        for callee_save in CALLEE_SAVE_REGISTERS.iter().rev() {
+            self.raw(format!("ldr {}, [sp]", REGISTERS[*callee_save as usize]));
+            self.raw(String::from("add sp, sp, #16"));
+        }
+
+        let total = self.frame_size + 16;
+        self.raw(format!("ldr x30, [sp, #{}]", total - 8));
+        self.raw(format!("ldr x29, [sp, #{}]", total - 16));
+        self.raw(format!("add sp, sp, #{}", total));
+        self.raw(String::from("ret"));
+    }
+
+    fn lower_param(&mut self, i: u8, stack_offset: i32) {
+        self.raw(format!(
+            "str {}, [x29, #{}]",
+            REGISTERS[self.param_register(i) as usize],
+            stack_offset
+        ));
+    }
+
+    fn lower_set(&mut self, r: vil::Register, imm: &vil::Immediate) {
+        match imm {
+            vil::Immediate::Integer(x) => self.raw(format!("mov {}, #{}", register_name(r), x)),
+            // `adr` is a real load-address instruction (PC-relative, within its +/-1MB range),
+            // playing the same role riscv.rs's `la` pseudo-instruction does.
+            vil::Immediate::Label(s) => self.raw(format!("adr {}, {}", register_name(r), s)),
+            // Floats travel through the same GPRs as integers (see vil.rs's `Immediate::Float`
+            // doc comment), so a float literal is just its bit pattern loaded the same way an
+            // integer one would be; the instructions that actually operate on it reinterpret
+            // those bits when they move them into a float register.
+            vil::Immediate::Float(x) => {
+                self.raw(format!("mov {}, #{}", register_name(r), x.to_bits() as i64))
+            }
+        }
+    }
+
+    fn lower_move(&mut self, r1: vil::Register, r2: vil::Register) {
+        self.raw(format!("mov {}, {}", register_name(r1), register_name(r2)));
+    }
+
+    fn lower_binary(
+        &mut self,
+        op: vil::BinaryOp,
+        r1: vil::Register,
+        r2: vil::Register,
+        r3: vil::Register,
+    ) {
+        let (d, a, b) = (register_name(r1), register_name(r2), register_name(r3));
+        let mnemonic = match op {
+            vil::BinaryOp::Add => "add",
+            vil::BinaryOp::Sub => "sub",
+            vil::BinaryOp::Mul => "mul",
+            // `sdiv` leaves a separate `msub` step for the remainder, unlike x86's combined
+            // `div`; Venice only needs the quotient here.
+            vil::BinaryOp::Div => "sdiv",
+            vil::BinaryOp::FAdd
+            | vil::BinaryOp::FSub
+            | vil::BinaryOp::FMul
+            | vil::BinaryOp::FDiv => {
+                // AArch64's arithmetic instructions only work on its own `d`-register file, so the
+                // operands' bit patterns have to be moved in (`fmov`) and the result moved back
+                // out around the actual D-register op; `d0`/`d1` are free to use as scratch here
+                // since Venice doesn't otherwise pass arguments in float registers.
+                let fmnemonic = match op {
+                    vil::BinaryOp::FAdd => "fadd",
+                    vil::BinaryOp::FSub => "fsub",
+                    vil::BinaryOp::FMul => "fmul",
+                    vil::BinaryOp::FDiv => "fdiv",
+                    _ => unreachable!(),
+                };
+                self.raw(format!("fmov d0, {}", a));
+                self.raw(format!("fmov d1, {}", b));
+                self.raw(format!("{} d0, d0, d1", fmnemonic));
+                self.raw(format!("fmov {}, d0", d));
+                return;
+            }
+        };
+        self.raw(format!("{} {}, {}, {}", mnemonic, d, a, b));
+    }
+
+    fn lower_unary(&mut self, op: vil::UnaryOp, r1: vil::Register, r2: vil::Register) {
+        let (d, a) = (register_name(r1), register_name(r2));
+        match op {
+            vil::UnaryOp::Negate => self.raw(format!("neg {}, {}", d, a)),
+            // There's no single instruction for "is zero" the way riscv's `seqz` is, so this
+            // takes the standard AArch64 two-step: compare against zero, then materialize the
+            // flag as a 0/1 value with `cset`.
+            vil::UnaryOp::LogicalNot => {
+                self.raw(format!("cmp {}, #0", a));
+                self.raw(format!("cset {}, eq", d));
+            }
+            vil::UnaryOp::FNegate => {
+                self.raw(format!("fmov d0, {}", a));
+                self.raw(String::from("fneg d0, d0"));
+                self.raw(format!("fmov {}, d0", d));
+            }
+        }
+    }
+
+    fn lower_load(&mut self, r: vil::Register, offset: vil::MemoryOffset) {
+        self.raw(format!("ldr {}, [x29, #{}]", register_name(r), offset));
+    }
+
+    fn lower_store(&mut self, r: vil::Register, offset: vil::MemoryOffset) {
+        self.raw(format!("str {}, [x29, #{}]", register_name(r), offset));
+    }
+
+    fn lower_cmp(&mut self, r1: vil::Register, r2: vil::Register) {
+        self.last_cmp = Some((r1, r2, false));
+    }
+
+    fn lower_fcmp(&mut self, r1: vil::Register, r2: vil::Register) {
+        self.last_cmp = Some((r1, r2, true));
+    }
+
+    fn lower_cmp_ordering(&mut self, r1: vil::Register, r2: vil::Register, r3: vil::Register) {
+        // The same `cmp`-then-`cset` two-step `lower_unary`'s `LogicalNot` uses, run twice (once
+        // for "greater", once for "less", using `x11` -- free for the same reason riscv.rs's `t2`
+        // is -- as scratch) and subtracted to land on -1, 0, or 1.
+        let (d, a, b) = (register_name(r1), register_name(r2), register_name(r3));
+        self.raw(format!("cmp {}, {}", a, b));
+        self.raw(format!("cset {}, gt", d));
+        self.raw(String::from("cset x11, lt"));
+        self.raw(format!("sub {}, {}, x11", d, d));
+    }
+
+    fn lower_fcmp_ordering(&mut self, r1: vil::Register, r2: vil::Register, r3: vil::Register) {
+        let (d, a, b) = (register_name(r1), register_name(r2), register_name(r3));
+        self.raw(format!("fmov d0, {}", a));
+        self.raw(format!("fmov d1, {}", b));
+        self.raw(String::from("fcmp d0, d1"));
+        self.raw(format!("cset {}, gt", d));
+        self.raw(String::from("cset x11, lt"));
+        self.raw(format!("sub {}, {}, x11", d, d));
+    }
+
+    fn lower_call(
+        &mut self,
+        destination: vil::Register,
+        label: &vil::Label,
+        offsets: &[vil::MemoryOffset],
+        _variadic: bool,
+    ) {
+        // Unlike x86.rs, this backend doesn't yet materialize overflow arguments on the stack --
+        // codegen.rs itself no longer caps argument count (see its `generate_call_expression` doc
+        // comment), so a call with more than six arguments would otherwise silently alias two VIL
+        // arguments onto the same `x`-register instead of failing loudly.
+        if offsets.len() > 6 {
+            panic!("internal error: aarch64 backend cannot yet handle more than 6 arguments");
+        }
+
+        for caller_save in CALLER_SAVE_REGISTERS {
+            self.raw(String::from("sub sp, sp, #16"));
+            self.raw(format!("str {}, [sp]", REGISTERS[*caller_save as usize]));
+        }
+
+        for (i, offset) in offsets.iter().enumerate() {
+            let param = REGISTERS[self.param_register(u8::try_from(i).unwrap()) as usize];
+            self.raw(format!("ldr {}, [x29, #{}]", param, offset));
+        }
+
+        self.raw(format!("bl {}", label.0));
+
+        for caller_save in CALLER_SAVE_REGISTERS.iter().rev() {
+            self.raw(format!("ldr {}, [sp]", REGISTERS[*caller_save as usize]));
+            self.raw(String::from("add sp, sp, #16"));
+        }
+
+        self.raw(format!("mov {}, x0", register_name(destination)));
+    }
+
+    fn lower_jump(&mut self, label: &vil::Label) {
+        self.raw(format!("b {}", label.0));
+    }
+
+    fn lower_jump_if(
+        &mut self,
+        condition: vil::JumpCondition,
+        true_label: &vil::Label,
+        false_label: &vil::Label,
+    ) {
+        let (r1, r2, is_float) = self
+            .last_cmp
+            .take()
+            .expect("internal error: JumpIf with no preceding Cmp");
+
+        // Unlike riscv.rs, which only gets `beq`/`bne`/`blt`/`bge` and has to swap operands to
+        // emulate `ble`/`bgt`, AArch64's condition codes cover all six comparisons directly (and
+        // the same mnemonics apply whether the preceding flags-setting instruction was `cmp` or
+        // `fcmp`), so there's no operand-swapping or missing-mnemonic case to work around here.
+        self.raw(format!(
+            "{} {}, {}",
+            if is_float { "fcmp" } else { "cmp" },
+            register_name(r1),
+            register_name(r2)
+        ));
+        let cond = match condition {
+            vil::JumpCondition::Eq => "eq",
+            vil::JumpCondition::Neq => "ne",
+            vil::JumpCondition::Lt => "lt",
+            vil::JumpCondition::Gt => "gt",
+            vil::JumpCondition::Lte => "le",
+            vil::JumpCondition::Gte => "ge",
+        };
+        self.raw(format!("b.{} {}", cond, true_label.0));
+        self.raw(format!("b {}", false_label.0));
+    }
+
+    fn lower_jump_ordering(
+        &mut self,
+        r: vil::Register,
+        less_label: &vil::Label,
+        equal_label: &vil::Label,
+        greater_label: &vil::Label,
+    ) {
+        let reg = register_name(r);
+        self.raw(format!("cmp {}, #0", reg));
+        self.raw(format!("b.lt {}", less_label.0));
+        self.raw(format!("b.eq {}", equal_label.0));
+        self.raw(format!("b {}", greater_label.0));
+    }
+
+    fn lower_syscall(
+        &mut self,
+        destination: vil::Register,
+        number: i64,
+        offsets: &[vil::MemoryOffset],
+    ) {
+        if offsets.len() > 6 {
+            panic!("internal error: syscall cannot take more than 6 arguments");
+        }
+
+        // Linux's AArch64 syscall convention passes arguments in x0-x5 (the same registers as the
+        // regular calling convention) and the syscall number in x8, then traps with `svc #0`; the
+        // result comes back in x0, same as a normal call's return value.
+        for (i, offset) in offsets.iter().enumerate() {
+            let param = REGISTERS[self.param_register(u8::try_from(i).unwrap()) as usize];
+            self.raw(format!("ldr {}, [x29, #{}]", param, offset));
+        }
+
+        self.raw(format!("mov x8, #{}", number));
+        self.raw(String::from("svc #0"));
+        self.raw(format!("mov {}, x0", register_name(destination)));
+    }
+
+    fn param_register(&self, i: u8) -> u8 {
+        i + 7
+    }
+}
+
+impl fmt::Display for Program {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for block in &self.blocks {
+            writeln!(f, "{}", block)?;
+        }
+
+        for datum in &self.data {
+            writeln!(f, "{}", datum)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl fmt::Display for Block {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.global {
+            writeln!(f, ".globl {}", self.label)?;
+        }
+
+        writeln!(f, "{}:", self.label)?;
+        for instruction in &self.instructions {
+            writeln!(f, "  {}", instruction)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Instruction::Raw(text) => write!(f, "{}", text),
+        }
+    }
+}
+
+impl fmt::Display for Data {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, ".{}:\n  .string {:?}", self.name, self.value)
+    }
+}
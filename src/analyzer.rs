@@ -9,12 +9,25 @@ use super::ast;
 use super::common;
 use super::errors;
 use super::ptree;
+use super::visitor::{self, VisitorMut};
 use std::collections::HashMap;
 
 /// Analyzes the parse tree into an abstract syntax tree.
 pub fn analyze(ptree: &ptree::Program) -> Result<ast::Program, Vec<errors::VeniceError>> {
+    analyze_with_resolver(ptree, None)
+}
+
+/// Like `analyze`, but falls back to `resolver` to look up any name that the file's own top-level
+/// declarations don't define -- the hook that lets a file reference symbols and types exported by
+/// another, separately-analyzed module (see `SymbolResolver`).
+pub fn analyze_with_resolver(
+    ptree: &ptree::Program,
+    resolver: Option<Box<dyn SymbolResolver>>,
+) -> Result<ast::Program, Vec<errors::VeniceError>> {
     let mut analyzer = Analyzer::new();
+    analyzer.resolver = resolver;
     let mut program = analyzer.analyze_program(ptree);
+    analyzer.resolve_program_types(&mut program);
     allocate_registers_in_program(&mut program);
 
     if !analyzer.errors.is_empty() {
@@ -24,14 +37,91 @@ pub fn analyze(ptree: &ptree::Program) -> Result<ast::Program, Vec<errors::Venic
     }
 }
 
+/// Looks up a name that a file's own top-level declarations don't define -- the extension point
+/// that lets the analyzer see into another, already-analyzed Venice module (or an external
+/// library) without merging its declarations into this file's parse tree. Consulted as a fallback,
+/// once the local `symbols`/`types` tables have already come up empty, from `analyze_symbol`,
+/// `analyze_call_expression`, and `resolve_type`.
+pub trait SymbolResolver {
+    /// Looks up a value (function, const, ...) exported by another module.
+    fn resolve_value(&self, name: &str) -> Option<ast::SymbolEntry>;
+
+    /// Looks up a named type (e.g. a record) exported by another module.
+    fn resolve_type(&self, name: &str) -> Option<ast::Type>;
+}
+
+/// A `SymbolResolver` backed by the fixed, already-computed interface of a set of modules -- the
+/// functions, consts, and record types each one exports, indexed by name. It doesn't analyze those
+/// modules itself; the caller is expected to have analyzed each one ahead of time and to have
+/// copied its exported symbols in here (via `add_value`/`add_type`) before analyzing a file that
+/// imports from it.
+pub struct ModuleInterfaceResolver {
+    values: HashMap<String, ast::SymbolEntry>,
+    types: HashMap<String, ast::Type>,
+}
+
+impl ModuleInterfaceResolver {
+    pub fn new() -> Self {
+        ModuleInterfaceResolver {
+            values: HashMap::new(),
+            types: HashMap::new(),
+        }
+    }
+
+    /// Exports a function, const, or other value under `name`, with `unique_name` as the linker
+    /// symbol codegen should call/reference instead of compiling a local definition for it.
+    pub fn add_value(&mut self, name: &str, unique_name: &str, type_: ast::Type) {
+        self.values.insert(
+            String::from(name),
+            ast::SymbolEntry::external(unique_name, type_),
+        );
+    }
+
+    /// Exports a record (or other named) type under `name`.
+    pub fn add_type(&mut self, name: &str, type_: ast::Type) {
+        self.types.insert(String::from(name), type_);
+    }
+}
+
+impl SymbolResolver for ModuleInterfaceResolver {
+    fn resolve_value(&self, name: &str) -> Option<ast::SymbolEntry> {
+        self.values.get(name).cloned()
+    }
+
+    fn resolve_type(&self, name: &str) -> Option<ast::Type> {
+        self.types.get(name).cloned()
+    }
+}
+
 struct Analyzer {
     symbols: SymbolTable,
     types: SymbolTable,
     current_function_return_type: Option<ast::Type>,
+    /// Where `current_function_return_type`'s annotation appears in the source, so a `return`
+    /// statement's type-mismatch diagnostic can point back at it as a secondary label.
+    current_function_return_type_location: Option<common::Location>,
     current_function_info: Option<ast::FunctionInfo>,
     errors: Vec<errors::VeniceError>,
     unique_name_counter: u64,
     current_stack_offset: i32,
+    /// Where each top-level declaration was first seen, so that a redefinition can point back at
+    /// it as a secondary span.
+    declaration_locations: HashMap<String, common::Location>,
+    /// Every declared enum's variants, in declaration order, keyed by the enum's name -- looked up
+    /// when resolving a `match` arm's variant pattern to its tag and payload type.
+    enums: HashMap<String, Vec<ast::EnumVariant>>,
+    /// Assigns each `ast::Expression` its `ExprId`, in the order the analyzer builds them.
+    expr_id_counter: u32,
+    /// Bindings accumulated by `unify` for the `Type::Variable`s that `fresh_type_var` hands out
+    /// to a `let` whose value's type can't be pinned down any more directly -- a classic
+    /// Hindley-Milner substitution, though monomorphic: there is no let-generalization, so a
+    /// variable is either bound to one concrete type everywhere or reported as underconstrained.
+    substitution: HashMap<u32, ast::Type>,
+    next_type_var: u32,
+    /// Consulted as a fallback when a name isn't found in `symbols`/`types`, so a file can
+    /// reference symbols and types exported by another, separately-analyzed module. `None` when
+    /// analyzing a standalone file with no imports.
+    resolver: Option<Box<dyn SymbolResolver>>,
 }
 
 impl Analyzer {
@@ -40,13 +130,40 @@ impl Analyzer {
             symbols: SymbolTable::builtin_globals(),
             types: SymbolTable::builtin_types(),
             current_function_return_type: None,
+            current_function_return_type_location: None,
             current_function_info: None,
             errors: Vec::new(),
             unique_name_counter: 0,
             current_stack_offset: 0,
+            declaration_locations: HashMap::new(),
+            enums: HashMap::new(),
+            expr_id_counter: 0,
+            substitution: HashMap::new(),
+            next_type_var: 0,
+            resolver: None,
         }
     }
 
+    /// Builds an expression the same way `ast::Expression::new` does, but also claims it a fresh
+    /// `ExprId` -- every expression the analyzer constructs should go through this rather than
+    /// `ast::Expression::new` directly, so that `ExprId`s are actually unique across the program.
+    fn new_expression(
+        &mut self,
+        kind: ast::ExpressionKind,
+        type_: ast::Type,
+        span: common::Span,
+    ) -> ast::Expression {
+        let mut expr = ast::Expression::new(kind, type_, span);
+        expr.id = self.claim_expr_id();
+        expr
+    }
+
+    fn claim_expr_id(&mut self) -> ast::ExprId {
+        let id = ast::ExprId(self.expr_id_counter);
+        self.expr_id_counter += 1;
+        id
+    }
+
     fn analyze_program(&mut self, ptree: &ptree::Program) -> ast::Program {
         // Do a first pass over the top-level declarations so that function declarations are
         // "hoisted", i.e. you can reference a function before it is defined in a file.
@@ -69,13 +186,128 @@ impl Analyzer {
         use ptree::Declaration::*;
         match declaration {
             Function(d) => self.add_function_declaration_to_symbol_table(d),
-            Const(_d) => {
-                panic!("internal error: const declarations are not yet supported");
+            Const(d) => self.add_const_declaration_to_symbol_table(d),
+            Record(d) => self.add_record_declaration_to_symbol_table(d),
+            Enum(d) => self.add_enum_declaration_to_symbol_table(d),
+        }
+    }
+
+    fn add_record_declaration_to_symbol_table(
+        &mut self,
+        declaration: &ptree::RecordDeclaration,
+    ) -> ast::SymbolEntry {
+        match self.declaration_locations.get(&declaration.name).cloned() {
+            Some(previous_location) => {
+                self.error_redefinition(
+                    &declaration.name,
+                    declaration.location.clone(),
+                    previous_location,
+                );
+            }
+            None => {
+                self.declaration_locations
+                    .insert(declaration.name.clone(), declaration.location.clone());
+            }
+        }
+
+        // Field types are resolved here, unlike enum variant payloads (resolved later in
+        // `analyze_enum_declaration`), since a field's type is always a simple named-type
+        // annotation with nothing later in the declaration to depend on.
+        let mut fields = Vec::new();
+        let mut seen = std::collections::HashSet::new();
+        for field in &declaration.fields {
+            if !seen.insert(field.name.clone()) {
+                let msg = format!(
+                    "duplicate field {} in record {}",
+                    field.name, declaration.name
+                );
+                self.error(&msg, declaration.location.clone());
+                continue;
+            }
+            fields.push((field.name.clone(), self.resolve_type(&field.type_)));
+        }
+
+        let entry = ast::SymbolEntry {
+            unique_name: String::new(),
+            type_: ast::Type::Record {
+                name: declaration.name.clone(),
+                fields,
+            },
+            constant: true,
+            external: false,
+            syscall: None,
+            stack_offset: 0,
+            span: common::Span::at(declaration.location.clone()),
+        };
+        self.types.insert(&declaration.name, entry.clone());
+        entry
+    }
+
+    fn add_const_declaration_to_symbol_table(
+        &mut self,
+        declaration: &ptree::ConstDeclaration,
+    ) -> ast::SymbolEntry {
+        match self.declaration_locations.get(&declaration.symbol).cloned() {
+            Some(previous_location) => {
+                self.error_redefinition(
+                    &declaration.symbol,
+                    declaration.location.clone(),
+                    previous_location,
+                );
+            }
+            None => {
+                self.declaration_locations
+                    .insert(declaration.symbol.clone(), declaration.location.clone());
+            }
+        }
+
+        // `analyze_const_declaration` re-resolves the type and re-inserts its own entry once it
+        // runs, so this placeholder only needs to be good enough to let a function defined earlier
+        // in the file reference the const before its own declaration is reached.
+        let type_ = self.resolve_type(&declaration.type_);
+        let unique_name = self.claim_unique_name(&declaration.symbol);
+        let entry = ast::SymbolEntry {
+            unique_name,
+            type_,
+            constant: true,
+            external: false,
+            syscall: None,
+            stack_offset: 0,
+            span: common::Span::at(declaration.location.clone()),
+        };
+        self.symbols.insert(&declaration.symbol, entry.clone());
+        entry
+    }
+
+    fn add_enum_declaration_to_symbol_table(
+        &mut self,
+        declaration: &ptree::EnumDeclaration,
+    ) -> ast::SymbolEntry {
+        match self.declaration_locations.get(&declaration.name).cloned() {
+            Some(previous_location) => {
+                self.error_redefinition(
+                    &declaration.name,
+                    declaration.location.clone(),
+                    previous_location,
+                );
             }
-            Record(_d) => {
-                panic!("internal error: record declarations are not yet supported");
+            None => {
+                self.declaration_locations
+                    .insert(declaration.name.clone(), declaration.location.clone());
             }
         }
+
+        let entry = ast::SymbolEntry {
+            unique_name: String::new(),
+            type_: ast::Type::Enum(declaration.name.clone()),
+            constant: true,
+            external: false,
+            syscall: None,
+            stack_offset: 0,
+            span: common::Span::at(declaration.location.clone()),
+        };
+        self.types.insert(&declaration.name, entry.clone());
+        entry
     }
 
     fn add_function_declaration_to_symbol_table(
@@ -103,8 +335,25 @@ impl Analyzer {
             },
             constant: true,
             external: false,
+            syscall: None,
             stack_offset: 0,
+            span: common::Span::at(declaration.location.clone()),
         };
+
+        match self.declaration_locations.get(&declaration.name).cloned() {
+            Some(previous_location) => {
+                self.error_redefinition(
+                    &declaration.name,
+                    declaration.location.clone(),
+                    previous_location,
+                );
+            }
+            None => {
+                self.declaration_locations
+                    .insert(declaration.name.clone(), declaration.location.clone());
+            }
+        }
+
         self.symbols.insert(&declaration.name, entry.clone());
         entry
     }
@@ -119,7 +368,43 @@ impl Analyzer {
             Function(d) => self.analyze_function_declaration(d, entry),
             Const(d) => self.analyze_const_declaration(d, entry),
             Record(d) => self.analyze_record_declaration(d, entry),
+            Enum(d) => self.analyze_enum_declaration(d, entry),
+        }
+    }
+
+    fn analyze_enum_declaration(
+        &mut self,
+        declaration: &ptree::EnumDeclaration,
+        entry: ast::SymbolEntry,
+    ) -> ast::Declaration {
+        let mut variants = Vec::new();
+        let mut seen = std::collections::HashSet::new();
+        for (tag, variant) in declaration.variants.iter().enumerate() {
+            if !seen.insert(variant.name.clone()) {
+                let msg = format!(
+                    "duplicate variant {} in enum {}",
+                    variant.name, declaration.name
+                );
+                self.error(&msg, declaration.location.clone());
+                continue;
+            }
+
+            let payload = variant.payload.as_ref().map(|t| self.resolve_type(t));
+            variants.push(ast::EnumVariant {
+                name: variant.name.clone(),
+                tag: tag as i64,
+                payload,
+            });
         }
+
+        self.enums
+            .insert(declaration.name.clone(), variants.clone());
+
+        ast::Declaration::Enum(ast::EnumDeclaration {
+            name: entry,
+            variants,
+            span: common::Span::at(declaration.location.clone()),
+        })
     }
 
     fn analyze_function_declaration(
@@ -139,7 +424,11 @@ impl Analyzer {
                 type_: t.clone(),
                 constant: false,
                 external: false,
+                syscall: None,
                 stack_offset,
+                // `ptree::FunctionParameter` doesn't carry its own location yet, so there's
+                // nothing more precise to point at than an empty span.
+                span: common::Span::empty(),
             };
 
             stack_frame_size += t.stack_size();
@@ -157,11 +446,16 @@ impl Analyzer {
                 .insert(&ptree_parameter.name, ast_parameter.name.clone());
         }
 
-        self.current_function_info = Some(ast::FunctionInfo { stack_frame_size });
+        self.current_function_info = Some(ast::FunctionInfo {
+            stack_frame_size,
+            max_register_needed: 0,
+        });
         self.current_function_return_type = Some(return_type.clone());
+        self.current_function_return_type_location = Some(declaration.return_type.location.clone());
         self.current_stack_offset = stack_offset;
         let body = self.analyze_block(&declaration.body);
         self.current_function_return_type = None;
+        self.current_function_return_type_location = None;
         self.current_stack_offset = -8;
 
         // Pop off the function body's scope.
@@ -173,6 +467,7 @@ impl Analyzer {
             return_type,
             body,
             info: self.current_function_info.as_ref().unwrap().clone(),
+            span: common::Span::at(declaration.location.clone()),
         })
     }
 
@@ -183,9 +478,7 @@ impl Analyzer {
     ) -> ast::Declaration {
         let value = self.analyze_expression(&declaration.value);
         let declared_type = self.resolve_type(&declaration.type_);
-        if !declared_type.matches(&value.type_) {
-            self.error_type_mismatch(&declared_type, &value.type_, declaration.location.clone());
-        }
+        let _ = self.unify(&declared_type, &value.type_, declaration.location.clone());
 
         let unique_name = self.claim_unique_name(&declaration.symbol);
         let entry = ast::SymbolEntry {
@@ -193,7 +486,9 @@ impl Analyzer {
             type_: declared_type.clone(),
             constant: true,
             external: false,
+            syscall: None,
             stack_offset: 0,
+            span: common::Span::at(declaration.location.clone()),
         };
         self.symbols.insert(&declaration.symbol, entry.clone());
 
@@ -201,22 +496,47 @@ impl Analyzer {
             symbol: entry,
             type_: declared_type,
             value,
+            span: common::Span::at(declaration.location.clone()),
         })
     }
 
     fn analyze_record_declaration(
         &mut self,
-        _declaration: &ptree::RecordDeclaration,
-        _const_entry: ast::SymbolEntry,
+        declaration: &ptree::RecordDeclaration,
+        entry: ast::SymbolEntry,
     ) -> ast::Declaration {
-        // TODO
-        panic!("internal error: record declarations are not yet supported");
+        // The fields (with duplicates already dropped and types already resolved) were computed
+        // once in `add_record_declaration_to_symbol_table`; read them back off the entry's type
+        // rather than walking `declaration.fields` again.
+        let fields = if let ast::Type::Record { fields, .. } = &entry.type_ {
+            fields
+                .iter()
+                .map(|(name, type_)| ast::RecordField {
+                    name: name.clone(),
+                    type_: type_.clone(),
+                })
+                .collect()
+        } else {
+            Vec::new()
+        };
+
+        ast::Declaration::Record(ast::RecordDeclaration {
+            name: entry,
+            fields,
+            span: common::Span::at(declaration.location.clone()),
+        })
     }
 
     fn analyze_block(&mut self, block: &[ptree::Statement]) -> Vec<ast::Statement> {
         let mut ret = Vec::new();
         for stmt in block {
-            ret.push(self.analyze_statement(stmt));
+            // `match` is pure sugar over `let`/`if` (see `analyze_match_statement`), so it expands
+            // to more than one `ast::Statement`; every other statement kind produces exactly one.
+            if let ptree::Statement::Match(s) = stmt {
+                ret.extend(self.analyze_match_statement(s));
+            } else {
+                ret.push(self.analyze_statement(stmt));
+            }
         }
         ret
     }
@@ -227,6 +547,9 @@ impl Analyzer {
             Let(s) => self.analyze_let_statement(s),
             Assign(s) => self.analyze_assign_statement(s),
             If(s) => self.analyze_if_statement(s),
+            Match(_) => {
+                panic!("internal error: match statements must be expanded by analyze_block")
+            }
             While(s) => self.analyze_while_statement(s),
             For(s) => self.analyze_for_statement(s),
             Return(s) => self.analyze_return_statement(s),
@@ -235,20 +558,408 @@ impl Analyzer {
         }
     }
 
-    fn analyze_let_statement(&mut self, stmt: &ptree::LetStatement) -> ast::Statement {
+    /// Desugars `match <value> { case <pattern> { ... } ... }` into a `let` binding for the
+    /// scrutinee (evaluated once, even though it may be compared against several patterns)
+    /// followed by a chain of `if`/`else` statements, one per arm, each nested in the previous
+    /// arm's `else_body` -- `ast::IfStatement` has no `elif` of its own (see
+    /// `analyze_if_statement`), so this is the only shape available to chain conditions in.
+    ///
+    /// A `Pattern::Wildcard` arm becomes the innermost `else_body` instead of another comparison,
+    /// and a `Pattern::Variant` arm compares the subject's tag (via the `venice_enum_tag` runtime
+    /// call) and, if the pattern binds a name, prepends a `let` for the payload (via
+    /// `venice_enum_payload`) to the arm's body.
+    ///
+    /// The match is rejected as non-exhaustive unless the last arm is a wildcard -- except when
+    /// the subject is an enum type, where covering every declared variant is exhaustive on its
+    /// own and no catch-all is required.
+    fn analyze_match_statement(&mut self, stmt: &ptree::MatchStatement) -> Vec<ast::Statement> {
         let value = self.analyze_expression(&stmt.value);
-        let declared_type = self.resolve_type(&stmt.type_);
-        if !declared_type.matches(&value.type_) {
-            self.error_type_mismatch(&declared_type, &value.type_, stmt.location.clone());
+
+        let unique_name = self.claim_unique_name("match_subject");
+        let subject = ast::SymbolEntry {
+            unique_name,
+            type_: value.type_.clone(),
+            constant: false,
+            external: false,
+            syscall: None,
+            stack_offset: self.current_stack_offset,
+            // This symbol doesn't appear anywhere in the source text -- it only exists because
+            // `match` desugars to a `let` binding -- so the whole `match` stands in for it.
+            span: common::Span::at(stmt.location.clone()),
+        };
+        self.current_function_info
+            .as_mut()
+            .unwrap()
+            .stack_frame_size += subject.type_.stack_size();
+        self.current_stack_offset -= subject.type_.stack_size() as i32;
+
+        let subject_type = subject.type_.clone();
+        let let_statement = ast::Statement::Let(ast::LetStatement {
+            symbol: subject.clone(),
+            type_: subject_type.clone(),
+            value,
+            span: common::Span::at(stmt.location.clone()),
+        });
+
+        // Arms after the first unconditional one (a wildcard or a record pattern) can never run:
+        // report them, and build the `if` chain from the reachable arms only.
+        let reachable = match stmt.arms.iter().position(|arm| {
+            matches!(arm.pattern, ptree::Pattern::Wildcard | ptree::Pattern::Record { .. })
+        }) {
+            Some(i) => {
+                for arm in &stmt.arms[i + 1..] {
+                    self.error(
+                        "unreachable match arm after catch-all `_` pattern",
+                        arm.location.clone(),
+                    );
+                }
+                &stmt.arms[..=i]
+            }
+            None => &stmt.arms[..],
+        };
+
+        let mut has_wildcard = false;
+        let mut covered_variants = std::collections::HashSet::new();
+        let mut else_body: Vec<ast::Statement> = Vec::new();
+        for arm in reachable.iter().rev() {
+            match &arm.pattern {
+                ptree::Pattern::Wildcard => {
+                    has_wildcard = true;
+                    else_body = self.analyze_block(&arm.body);
+                }
+                ptree::Pattern::Literal(pattern_expr) => {
+                    let pattern_value = self.analyze_expression(pattern_expr);
+                    if !subject_type.matches(&pattern_value.type_) {
+                        self.error_type_mismatch(
+                            &subject_type,
+                            &pattern_value.type_,
+                            pattern_expr.location.clone(),
+                        );
+                    }
+
+                    let condition = self.new_expression(
+                        ast::ExpressionKind::Comparison(ast::ComparisonExpression {
+                            op: common::ComparisonOpType::Equals,
+                            left: Box::new(self.new_expression(
+                                ast::ExpressionKind::Symbol(subject.clone()),
+                                subject_type.clone(),
+                                common::Span::at(arm.location.clone()),
+                            )),
+                            right: Box::new(pattern_value),
+                        }),
+                        ast::Type::Boolean,
+                        common::Span::at(arm.location.clone()),
+                    );
+                    let body = self.analyze_block(&arm.body);
+                    else_body = vec![ast::Statement::If(ast::IfStatement {
+                        condition,
+                        body,
+                        else_body,
+                        span: common::Span::at(arm.location.clone()),
+                    })];
+                }
+                ptree::Pattern::Record { name, fields } => {
+                    let record_fields = match &subject_type {
+                        ast::Type::Record {
+                            name: subject_name,
+                            fields,
+                        } if subject_name == name => Some(fields.clone()),
+                        ast::Type::Record {
+                            name: subject_name, ..
+                        } => {
+                            let msg = format!(
+                                "cannot match record pattern {} against record of type {}",
+                                name, subject_name
+                            );
+                            self.error(&msg, arm.location.clone());
+                            None
+                        }
+                        _ => {
+                            let msg = format!(
+                                "cannot match record pattern {} against non-record type {}",
+                                name, subject_type
+                            );
+                            self.error(&msg, arm.location.clone());
+                            None
+                        }
+                    };
+
+                    // A record pattern always matches (a record type has no variant tag to check
+                    // at runtime), so -- like `Wildcard` -- it's unconditional: later arms are
+                    // unreachable and the bound fields just feed straight into the arm's body.
+                    has_wildcard = true;
+
+                    let mut body = Vec::new();
+                    if let Some(record_fields) = record_fields {
+                        for field_name in fields {
+                            let field_type = match record_fields
+                                .iter()
+                                .find(|(n, _)| n == field_name)
+                                .map(|(_, t)| t.clone())
+                            {
+                                Some(field_type) => field_type,
+                                None => {
+                                    let msg =
+                                        format!("record {} has no field {}", name, field_name);
+                                    self.error(&msg, arm.location.clone());
+                                    continue;
+                                }
+                            };
+
+                            let mut offset = 0;
+                            for (n, t) in &record_fields {
+                                if n == field_name {
+                                    break;
+                                }
+                                offset += t.storage_size() as i32;
+                            }
+
+                            let value = self.new_expression(
+                                ast::ExpressionKind::Attribute(ast::AttributeExpression {
+                                    value: Box::new(self.new_expression(
+                                        ast::ExpressionKind::Symbol(subject.clone()),
+                                        subject_type.clone(),
+                                        common::Span::at(arm.location.clone()),
+                                    )),
+                                    attribute: field_name.clone(),
+                                    offset,
+                                }),
+                                field_type.clone(),
+                                common::Span::at(arm.location.clone()),
+                            );
+
+                            let unique_name = self.claim_unique_name(field_name);
+                            let binding_entry = ast::SymbolEntry {
+                                unique_name,
+                                type_: field_type.clone(),
+                                constant: false,
+                                external: false,
+                                syscall: None,
+                                stack_offset: self.current_stack_offset,
+                                span: common::Span::at(arm.location.clone()),
+                            };
+                            self.current_function_info
+                                .as_mut()
+                                .unwrap()
+                                .stack_frame_size += field_type.storage_size();
+                            self.current_stack_offset -= field_type.storage_size() as i32;
+
+                            self.symbols.insert(field_name, binding_entry.clone());
+                            body.push(ast::Statement::Let(ast::LetStatement {
+                                symbol: binding_entry,
+                                type_: field_type,
+                                value,
+                                span: common::Span::at(arm.location.clone()),
+                            }));
+                        }
+                    }
+                    body.extend(self.analyze_block(&arm.body));
+                    else_body = body;
+                }
+                ptree::Pattern::Variant { name, binding } => {
+                    let enum_name = match &subject_type {
+                        ast::Type::Enum(n) => Some(n.clone()),
+                        _ => {
+                            let msg = format!(
+                                "cannot match variant pattern {} against non-enum type {}",
+                                name, subject_type
+                            );
+                            self.error(&msg, arm.location.clone());
+                            None
+                        }
+                    };
+
+                    let variant = enum_name
+                        .as_ref()
+                        .and_then(|n| self.enums.get(n))
+                        .and_then(|variants| variants.iter().find(|v| &v.name == name).cloned());
+
+                    let variant = match variant {
+                        Some(variant) => variant,
+                        None => {
+                            if let Some(enum_name) = &enum_name {
+                                let msg =
+                                    format!("no variant named {} in enum {}", name, enum_name);
+                                self.error(&msg, arm.location.clone());
+                            }
+                            // The arm's body may still contain independent errors worth reporting,
+                            // even though there's no variant to build a meaningful `if` around.
+                            self.analyze_block(&arm.body);
+                            continue;
+                        }
+                    };
+
+                    if !covered_variants.insert(variant.name.clone()) {
+                        let msg = format!("duplicate match arm for variant {}", variant.name);
+                        self.error(&msg, arm.location.clone());
+                    }
+
+                    let tag = self.new_expression(
+                        ast::ExpressionKind::Call(ast::CallExpression {
+                            function: ast::SymbolEntry {
+                                unique_name: String::from("venice_enum_tag"),
+                                type_: ast::Type::Error,
+                                constant: true,
+                                external: true,
+                                syscall: None,
+                                stack_offset: 0,
+                                span: common::Span::empty(),
+                            },
+                            arguments: vec![self.new_expression(
+                                ast::ExpressionKind::Symbol(subject.clone()),
+                                subject_type.clone(),
+                                common::Span::at(arm.location.clone()),
+                            )],
+                            variadic: false,
+                        }),
+                        ast::Type::I64,
+                        common::Span::at(arm.location.clone()),
+                    );
+                    let condition = self.new_expression(
+                        ast::ExpressionKind::Comparison(ast::ComparisonExpression {
+                            op: common::ComparisonOpType::Equals,
+                            left: Box::new(tag),
+                            right: Box::new(self.new_expression(
+                                ast::ExpressionKind::Integer(variant.tag),
+                                ast::Type::I64,
+                                common::Span::at(arm.location.clone()),
+                            )),
+                        }),
+                        ast::Type::Boolean,
+                        common::Span::at(arm.location.clone()),
+                    );
+
+                    let mut body = Vec::new();
+                    if let Some(binding_name) = binding {
+                        match &variant.payload {
+                            Some(payload_type) => {
+                                let unique_name = self.claim_unique_name(binding_name);
+                                let binding_entry = ast::SymbolEntry {
+                                    unique_name,
+                                    type_: payload_type.clone(),
+                                    constant: false,
+                                    external: false,
+                                    syscall: None,
+                                    stack_offset: self.current_stack_offset,
+                                    span: common::Span::at(arm.location.clone()),
+                                };
+                                self.current_function_info
+                                    .as_mut()
+                                    .unwrap()
+                                    .stack_frame_size += payload_type.stack_size();
+                                self.current_stack_offset -= payload_type.stack_size() as i32;
+
+                                let payload = self.new_expression(
+                                    ast::ExpressionKind::Call(ast::CallExpression {
+                                        function: ast::SymbolEntry {
+                                            unique_name: String::from("venice_enum_payload"),
+                                            type_: ast::Type::Error,
+                                            constant: true,
+                                            external: true,
+                                            syscall: None,
+                                            stack_offset: 0,
+                                            span: common::Span::empty(),
+                                        },
+                                        arguments: vec![self.new_expression(
+                                            ast::ExpressionKind::Symbol(subject.clone()),
+                                            subject_type.clone(),
+                                            common::Span::at(arm.location.clone()),
+                                        )],
+                                        variadic: false,
+                                    }),
+                                    payload_type.clone(),
+                                    common::Span::at(arm.location.clone()),
+                                );
+
+                                self.symbols.insert(binding_name, binding_entry.clone());
+                                body.push(ast::Statement::Let(ast::LetStatement {
+                                    symbol: binding_entry,
+                                    type_: payload_type.clone(),
+                                    value: payload,
+                                    span: common::Span::at(arm.location.clone()),
+                                }));
+                            }
+                            None => {
+                                let msg =
+                                    format!("variant {} has no payload to bind", variant.name);
+                                self.error(&msg, arm.location.clone());
+                            }
+                        }
+                    }
+                    body.extend(self.analyze_block(&arm.body));
+
+                    else_body = vec![ast::Statement::If(ast::IfStatement {
+                        condition,
+                        body,
+                        else_body,
+                        span: common::Span::at(arm.location.clone()),
+                    })];
+                }
+            }
+        }
+
+        if !has_wildcard {
+            if let ast::Type::Enum(name) = &subject_type {
+                let missing: Vec<String> = self
+                    .enums
+                    .get(name)
+                    .cloned()
+                    .unwrap_or_default()
+                    .into_iter()
+                    .filter(|variant| !covered_variants.contains(&variant.name))
+                    .map(|variant| variant.name)
+                    .collect();
+                if !missing.is_empty() {
+                    let msg = format!(
+                        "match statement is not exhaustive: missing variant(s) {}",
+                        missing.join(", ")
+                    );
+                    self.error(&msg, stmt.location.clone());
+                }
+            } else {
+                self.error(
+                    "match statement is not exhaustive: add a catch-all `_` arm",
+                    stmt.location.clone(),
+                );
+            }
         }
 
+        let mut statements = vec![let_statement];
+        statements.extend(else_body);
+        statements
+    }
+
+    fn analyze_let_statement(&mut self, stmt: &ptree::LetStatement) -> ast::Statement {
+        let value = self.analyze_expression(&stmt.value);
+        let declared_type = match &stmt.type_ {
+            Some(type_) => self.resolve_type(type_),
+            // The inference pass fills in every `let` statement's annotation it can determine
+            // from the value's syntax alone before the analyzer runs; reaching this branch means
+            // it couldn't (and already reported why), or the annotation genuinely depends on more
+            // context than a single expression -- either way, a fresh variable lets `unify` below
+            // pin it down from `value`'s type instead of guessing.
+            None => self.fresh_type_var(),
+        };
+        let _ = match &stmt.type_ {
+            Some(type_) => self.unify_with_context(
+                &declared_type,
+                &value.type_,
+                stmt.value.location.clone(),
+                "expected because of this annotation",
+                type_.location.clone(),
+            ),
+            None => self.unify(&declared_type, &value.type_, stmt.location.clone()),
+        };
+
         let unique_name = self.claim_unique_name(&stmt.symbol);
         let entry = ast::SymbolEntry {
             unique_name,
             type_: declared_type.clone(),
             constant: false,
             external: false,
+            syscall: None,
             stack_offset: self.current_stack_offset,
+            span: common::Span::at(stmt.location.clone()),
         };
 
         self.symbols.insert(&stmt.symbol, entry.clone());
@@ -262,23 +973,51 @@ impl Analyzer {
             symbol: entry,
             type_: declared_type,
             value,
+            span: common::Span::at(stmt.location.clone()),
         })
     }
 
     fn analyze_assign_statement(&mut self, stmt: &ptree::AssignStatement) -> ast::Statement {
+        let symbol = if let ptree::ExpressionKind::Symbol(symbol) = &stmt.target.kind {
+            symbol
+        } else {
+            // TODO: support assigning to index and attribute expressions once the rest of the
+            // pipeline can desugar them.
+            self.error(
+                "assignment to this kind of expression is not yet supported",
+                stmt.target.location.clone(),
+            );
+            return ast::Statement::Error(common::Span::at(stmt.target.location.clone()));
+        };
+
         let value = self.analyze_expression(&stmt.value);
-        if let Some(entry) = self.symbols.get(&stmt.symbol) {
-            if !entry.type_.matches(&value.type_) {
-                self.error_type_mismatch(&entry.type_, &value.type_, stmt.location.clone());
-            }
+        if stmt.op.is_some() {
+            // TODO: desugar compound assignment (`+=`, `-=`, etc.) into a binary operation once
+            // the parser's support for it is exercised by the rest of the pipeline.
+            self.error(
+                "compound assignment is not yet supported",
+                stmt.location.clone(),
+            );
+            return ast::Statement::Error(common::Span::at(stmt.location.clone()));
+        }
+
+        if let Some(entry) = self.symbols.get(symbol) {
+            let _ = self.unify_with_context(
+                &entry.type_,
+                &value.type_,
+                stmt.value.location.clone(),
+                "the symbol was declared with this type",
+                entry.span.start.clone(),
+            );
             ast::Statement::Assign(ast::AssignStatement {
                 symbol: entry.clone(),
                 value,
+                span: common::Span::at(stmt.location.clone()),
             })
         } else {
-            let msg = format!("assignment to unknown symbol {}", stmt.symbol);
+            let msg = format!("assignment to unknown symbol {}", symbol);
             self.error(&msg, stmt.location.clone());
-            ast::Statement::Error
+            ast::Statement::Error(common::Span::at(stmt.location.clone()))
         }
     }
 
@@ -292,34 +1031,35 @@ impl Analyzer {
             );
         }
         let body = self.analyze_block(&stmt.if_clause.body);
-        let else_body = self.analyze_block(&stmt.else_body);
 
-        if !stmt.elif_clauses.is_empty() {
-            self.error(
-                "not implemented",
-                stmt.elif_clauses[0].condition.location.clone(),
-            );
-            ast::Statement::Error
-            /*
-            for elif_clause in &stmt.elif_clauses {
-                let elif_condition = self.analyze_expression(&elif_clause.condition)?;
-                if !elif_condition.type_.matches(&ast::Type::Boolean) {
-                    self.error_type_mismatch(
-                        &ast::Type::Boolean,
-                        &elif_condition.type_,
-                        elif_clause.condition.location.clone(),
-                    );
-                }
-                let elif_body = self.analyze_block(&mut elif_clause.body)?;
+        // `elif` isn't its own AST node: it's lowered here into a chain of two-armed
+        // `IfStatement`s, built from the innermost clause outward, so the rest of the pipeline
+        // (codegen included) only ever has to understand a plain `if`/`else`.
+        let mut else_body = self.analyze_block(&stmt.else_body);
+        for elif_clause in stmt.elif_clauses.iter().rev() {
+            let elif_condition = self.analyze_expression(&elif_clause.condition);
+            if !elif_condition.type_.matches(&ast::Type::Boolean) {
+                self.error_type_mismatch(
+                    &ast::Type::Boolean,
+                    &elif_condition.type_,
+                    elif_clause.condition.location.clone(),
+                );
             }
-            */
-        } else {
-            ast::Statement::If(ast::IfStatement {
-                condition,
-                body,
+            let elif_body = self.analyze_block(&elif_clause.body);
+            else_body = vec![ast::Statement::If(ast::IfStatement {
+                condition: elif_condition,
+                body: elif_body,
                 else_body,
-            })
+                span: common::Span::at(elif_clause.condition.location.clone()),
+            })];
         }
+
+        ast::Statement::If(ast::IfStatement {
+            condition,
+            body,
+            else_body,
+            span: common::Span::at(stmt.location.clone()),
+        })
     }
 
     fn analyze_while_statement(&mut self, stmt: &ptree::WhileStatement) -> ast::Statement {
@@ -332,7 +1072,11 @@ impl Analyzer {
             );
         }
         let body = self.analyze_block(&stmt.body);
-        ast::Statement::While(ast::WhileStatement { condition, body })
+        ast::Statement::While(ast::WhileStatement {
+            condition,
+            body,
+            span: common::Span::at(stmt.location.clone()),
+        })
     }
 
     fn analyze_for_statement(&mut self, stmt: &ptree::ForStatement) -> ast::Statement {
@@ -344,12 +1088,19 @@ impl Analyzer {
         let value = self.analyze_expression(&stmt.value);
         // TODO: Can the clone here be avoided?
         if let Some(expected_return_type) = self.current_function_return_type.clone() {
-            if !expected_return_type.matches(&value.type_) {
-                self.error_type_mismatch(
-                    &expected_return_type,
-                    &value.type_,
-                    stmt.location.clone(),
-                );
+            match self.current_function_return_type_location.clone() {
+                Some(return_type_location) => {
+                    let _ = self.unify_with_context(
+                        &expected_return_type,
+                        &value.type_,
+                        stmt.value.location.clone(),
+                        "the function's return type is declared here",
+                        return_type_location,
+                    );
+                }
+                None => {
+                    let _ = self.unify(&expected_return_type, &value.type_, stmt.location.clone());
+                }
             }
         } else {
             self.error(
@@ -357,7 +1108,10 @@ impl Analyzer {
                 stmt.location.clone(),
             );
         }
-        ast::Statement::Return(ast::ReturnStatement { value })
+        ast::Statement::Return(ast::ReturnStatement {
+            value,
+            span: common::Span::at(stmt.location.clone()),
+        })
     }
 
     fn analyze_assert_statement(&mut self, stmt: &ptree::AssertStatement) -> ast::Statement {
@@ -369,38 +1123,56 @@ impl Analyzer {
                 stmt.condition.location.clone(),
             );
         }
-        ast::Statement::Assert(ast::AssertStatement { condition })
+        ast::Statement::Assert(ast::AssertStatement {
+            condition,
+            span: common::Span::at(stmt.location.clone()),
+        })
     }
 
     fn analyze_expression(&mut self, expr: &ptree::Expression) -> ast::Expression {
+        let span = common::Span::new(expr.location.clone(), expr.end_location.clone());
         use ptree::ExpressionKind::*;
         match &expr.kind {
             Boolean(x) => {
-                ast::Expression::new(ast::ExpressionKind::Boolean(*x), ast::Type::Boolean)
+                self.new_expression(ast::ExpressionKind::Boolean(*x), ast::Type::Boolean, span)
+            }
+            Integer(x, suffix) => {
+                let type_ = integer_suffix_type(*suffix);
+                if suffix.is_some() && !integer_fits_in_type(*x, &type_) {
+                    let msg = format!("integer literal {} does not fit in type {}", x, type_);
+                    self.error(&msg, expr.location.clone());
+                }
+                self.new_expression(ast::ExpressionKind::Integer(*x), type_, span)
             }
-            Integer(x) => ast::Expression::new(ast::ExpressionKind::Integer(*x), ast::Type::I64),
-            String(x) => ast::Expression::new(
+            Float(x) => self.new_expression(ast::ExpressionKind::Float(*x), ast::Type::F64, span),
+            String(x) => self.new_expression(
                 ast::ExpressionKind::Call(ast::CallExpression {
                     function: ast::SymbolEntry {
                         unique_name: std::string::String::from("venice_string_new"),
                         type_: ast::Type::Error,
                         constant: true,
                         external: true,
+                        syscall: None,
                         stack_offset: 0,
+                        // Synthesized to call the runtime's string constructor; it doesn't
+                        // correspond to anything the user wrote.
+                        span: common::Span::empty(),
                     },
-                    arguments: vec![ast::Expression::new(
+                    arguments: vec![self.new_expression(
                         ast::ExpressionKind::String(x.clone()),
                         // Technically this should have a different type from the overall type of
                         // the expression, because it is a raw string literal rather than a
                         // `venice_string_t` runtime object, but since nothing accesses its type it
                         // doesn't really matter.
                         ast::Type::String,
+                        span.clone(),
                     )],
                     variadic: false,
                 }),
                 ast::Type::String,
+                span,
             ),
-            Symbol(ref e) => self.analyze_symbol(e, &expr.location),
+            Symbol(ref e) => self.analyze_symbol(e, &expr.location, &expr.end_location),
             Binary(ref e) => self.analyze_binary_expression(e),
             Comparison(ref e) => self.analyze_comparison_expression(e),
             Unary(ref e) => self.analyze_unary_expression(e),
@@ -412,12 +1184,21 @@ impl Analyzer {
             Tuple(ref e) => self.analyze_tuple_literal(e),
             Map(ref e) => self.analyze_map_literal(e),
             Record(ref e) => self.analyze_record_literal(e),
+            ListComprehension(ref e) => self.analyze_list_comprehension(e),
         }
     }
 
-    fn analyze_symbol(&mut self, name: &str, location: &common::Location) -> ast::Expression {
+    fn analyze_symbol(
+        &mut self,
+        name: &str,
+        location: &common::Location,
+        end_location: &common::Location,
+    ) -> ast::Expression {
+        let span = common::Span::new(location.clone(), end_location.clone());
         if let Some(entry) = self.symbols.get(name) {
-            ast::Expression::new(ast::ExpressionKind::Symbol(entry.clone()), entry.type_)
+            self.new_expression(ast::ExpressionKind::Symbol(entry.clone()), entry.type_, span)
+        } else if let Some(entry) = self.resolver.as_ref().and_then(|r| r.resolve_value(name)) {
+            self.new_expression(ast::ExpressionKind::Symbol(entry.clone()), entry.type_, span)
         } else {
             self.error("unknown symbol", location.clone());
             ast::EXPRESSION_ERROR.clone()
@@ -427,6 +1208,7 @@ impl Analyzer {
     fn analyze_binary_expression(&mut self, expr: &ptree::BinaryExpression) -> ast::Expression {
         let left = self.analyze_expression(&expr.left);
         let right = self.analyze_expression(&expr.right);
+        let span = common::Span::new(expr.left.location.clone(), expr.right.end_location.clone());
 
         use common::BinaryOpType::*;
         match expr.op {
@@ -440,13 +1222,14 @@ impl Analyzer {
                         );
                         ast::EXPRESSION_ERROR.clone()
                     } else {
-                        ast::Expression::new(
+                        self.new_expression(
                             ast::ExpressionKind::Binary(ast::BinaryExpression {
                                 op: common::BinaryOpType::Concat,
                                 left: Box::new(left),
                                 right: Box::new(right),
                             }),
                             ast::Type::String,
+                            span,
                         )
                     }
                 }
@@ -460,13 +1243,14 @@ impl Analyzer {
                         ast::EXPRESSION_ERROR.clone()
                     } else {
                         let type_ = left.type_.clone();
-                        ast::Expression::new(
+                        self.new_expression(
                             ast::ExpressionKind::Binary(ast::BinaryExpression {
                                 op: common::BinaryOpType::Concat,
                                 left: Box::new(left),
                                 right: Box::new(right),
                             }),
                             type_,
+                            span,
                         )
                     }
                 }
@@ -485,16 +1269,21 @@ impl Analyzer {
                 );
 
                 // `and` expressions are converted to `if` expressions.
-                ast::Expression::new(
+                let false_value = self.new_expression(
+                    ast::ExpressionKind::Boolean(false),
+                    ast::Type::Boolean,
+                    // Synthesized to desugar `and`; there's no token in the source for this
+                    // literal `false` to point at, so it borrows the whole expression's span.
+                    span.clone(),
+                );
+                self.new_expression(
                     ast::ExpressionKind::If(ast::IfExpression {
                         condition: Box::new(left),
                         true_value: Box::new(right),
-                        false_value: Box::new(ast::Expression::new(
-                            ast::ExpressionKind::Boolean(false),
-                            ast::Type::Boolean,
-                        )),
+                        false_value: Box::new(false_value),
                     }),
                     ast::Type::Boolean,
+                    span,
                 )
             }
             Or => {
@@ -506,28 +1295,41 @@ impl Analyzer {
                 );
 
                 // `or` expressions are converted to `if` expressions.
-                ast::Expression::new(
+                let true_value = self.new_expression(
+                    ast::ExpressionKind::Boolean(true),
+                    ast::Type::Boolean,
+                    // Synthesized to desugar `or`; see the `and` case above.
+                    span.clone(),
+                );
+                self.new_expression(
                     ast::ExpressionKind::If(ast::IfExpression {
                         condition: Box::new(left),
-                        true_value: Box::new(ast::Expression::new(
-                            ast::ExpressionKind::Boolean(true),
-                            ast::Type::Boolean,
-                        )),
+                        true_value: Box::new(true_value),
                         false_value: Box::new(right),
                     }),
                     ast::Type::Boolean,
+                    span,
                 )
             }
             _ => {
-                self.assert_type(&left.type_, &ast::Type::I64, expr.left.location.clone());
-                self.assert_type(&right.type_, &ast::Type::I64, expr.right.location.clone());
-                ast::Expression::new(
+                // Arithmetic operators are polymorphic over every numeric type (`i64`, the sized
+                // integer types, and `float`): the left operand's type decides which one the whole
+                // expression is, and the right operand must agree.
+                let type_ = if left.type_.is_numeric() {
+                    left.type_.clone()
+                } else {
+                    ast::Type::I64
+                };
+                let _ = self.unify(&type_, &left.type_, expr.left.location.clone());
+                let _ = self.unify(&type_, &right.type_, expr.right.location.clone());
+                self.new_expression(
                     ast::ExpressionKind::Binary(ast::BinaryExpression {
                         op: expr.op,
                         left: Box::new(left),
                         right: Box::new(right),
                     }),
-                    ast::Type::I64,
+                    type_,
+                    span,
                 )
             }
         }
@@ -539,30 +1341,40 @@ impl Analyzer {
     ) -> ast::Expression {
         let left = self.analyze_expression(&expr.left);
         let right = self.analyze_expression(&expr.right);
+        let span = common::Span::new(expr.left.location.clone(), expr.right.end_location.clone());
 
         use common::ComparisonOpType::*;
         match expr.op {
             Equals | NotEquals => {
-                self.assert_type(&left.type_, &right.type_, expr.left.location.clone());
-                ast::Expression::new(
+                let _ = self.unify(&right.type_, &left.type_, expr.left.location.clone());
+                self.new_expression(
                     ast::ExpressionKind::Comparison(ast::ComparisonExpression {
                         op: expr.op,
                         left: Box::new(left),
                         right: Box::new(right),
                     }),
                     ast::Type::Boolean,
+                    span,
                 )
             }
             LessThan | LessThanEquals | GreaterThan | GreaterThanEquals => {
-                self.assert_type(&left.type_, &ast::Type::I64, expr.left.location.clone());
-                self.assert_type(&right.type_, &ast::Type::I64, expr.right.location.clone());
-                ast::Expression::new(
+                // As in `analyze_binary_expression`, the left operand's type picks the numeric
+                // type and the right operand must agree.
+                let type_ = if left.type_.is_numeric() {
+                    left.type_.clone()
+                } else {
+                    ast::Type::I64
+                };
+                let _ = self.unify(&type_, &left.type_, expr.left.location.clone());
+                let _ = self.unify(&type_, &right.type_, expr.right.location.clone());
+                self.new_expression(
                     ast::ExpressionKind::Comparison(ast::ComparisonExpression {
                         op: expr.op,
                         left: Box::new(left),
                         right: Box::new(right),
                     }),
                     ast::Type::Boolean,
+                    span,
                 )
             }
         }
@@ -570,21 +1382,24 @@ impl Analyzer {
 
     fn analyze_unary_expression(&mut self, expr: &ptree::UnaryExpression) -> ast::Expression {
         let operand = self.analyze_expression(&expr.operand);
+        let span = common::Span::new(expr.location.clone(), expr.operand.end_location.clone());
 
         use common::UnaryOpType::*;
         match expr.op {
             Negate => {
-                self.assert_type(
-                    &operand.type_,
-                    &ast::Type::I64,
-                    expr.operand.location.clone(),
-                );
-                ast::Expression::new(
+                let type_ = if operand.type_.is_numeric() {
+                    operand.type_.clone()
+                } else {
+                    ast::Type::I64
+                };
+                self.assert_type(&operand.type_, &type_, expr.operand.location.clone());
+                self.new_expression(
                     ast::ExpressionKind::Unary(ast::UnaryExpression {
                         op: expr.op,
                         operand: Box::new(operand),
                     }),
-                    ast::Type::I64,
+                    type_,
+                    span,
                 )
             }
             Not => {
@@ -593,23 +1408,34 @@ impl Analyzer {
                     &ast::Type::Boolean,
                     expr.operand.location.clone(),
                 );
-                ast::Expression::new(
+                self.new_expression(
                     ast::ExpressionKind::Unary(ast::UnaryExpression {
                         op: expr.op,
                         operand: Box::new(operand),
                     }),
                     ast::Type::Boolean,
+                    span,
                 )
             }
         }
     }
 
     fn analyze_call_expression(&mut self, expr: &ptree::CallExpression) -> ast::Expression {
-        if let Some(entry) = self.symbols.get(&expr.function) {
+        let resolved = self
+            .symbols
+            .get(&expr.function)
+            .or_else(|| self.resolver.as_ref().and_then(|r| r.resolve_value(&expr.function)));
+        if let Some(entry) = resolved {
+            // A builtin like `length` is generic over its argument's element type (see
+            // `SymbolTable::builtin_globals`), represented with a `Type::Variable` in its static
+            // signature. Since there's no let-generalization (`substitution`'s doc comment), that
+            // one variable must not be unified directly at more than one call site -- so give this
+            // call its own fresh copy before type-checking against it.
+            let instantiated_type = self.instantiate(&entry.type_);
             if let ast::Type::Function {
                 parameters,
                 return_type,
-            } = &entry.type_
+            } = instantiated_type.clone()
             {
                 if parameters.len() != expr.arguments.len() {
                     let msg = format!(
@@ -623,17 +1449,29 @@ impl Analyzer {
                 let mut arguments = Vec::new();
                 for (parameter, argument) in parameters.iter().zip(expr.arguments.iter()) {
                     let typed_argument = self.analyze_expression(argument);
-                    self.assert_type(parameter, &typed_argument.type_, argument.location.clone());
+                    // `unify`, not `assert_type`: `parameter` may contain a `Type::Variable` from
+                    // `instantiate` above, which plain `.matches()`-based checks don't understand.
+                    let _ = self.unify_with_context(
+                        &typed_argument.type_,
+                        parameter,
+                        argument.location.clone(),
+                        "the function is declared here",
+                        entry.span.start.clone(),
+                    );
                     arguments.push(typed_argument);
                 }
 
-                ast::Expression::new(
+                self.new_expression(
                     ast::ExpressionKind::Call(ast::CallExpression {
-                        function: entry.clone(),
+                        function: ast::SymbolEntry {
+                            type_: instantiated_type,
+                            ..entry.clone()
+                        },
                         arguments,
                         variadic: false,
                     }),
-                    *return_type.clone(),
+                    *return_type,
+                    common::Span::at(expr.location.clone()),
                 )
             } else {
                 let msg = format!("cannot call non-function type {}", entry.type_);
@@ -656,33 +1494,43 @@ impl Analyzer {
             List(ref t) => {
                 self.assert_type(&index.type_, &ast::Type::I64, expr.index.location.clone());
                 let type_ = *t.clone();
-                ast::Expression::new(
+                self.new_expression(
                     ast::ExpressionKind::Call(ast::CallExpression {
                         function: ast::SymbolEntry {
                             unique_name: std::string::String::from("venice_list_index"),
                             type_: ast::Type::Error,
                             constant: true,
                             external: true,
+                            syscall: None,
                             stack_offset: 0,
+                            span: common::Span::empty(),
                         },
                         arguments: vec![value, index],
                         variadic: false,
                     }),
                     type_,
+                    common::Span::at(expr.location.clone()),
                 )
             }
             Map {
                 key: key_type,
                 value: ref value_type,
             } => {
-                self.assert_type(&index.type_, key_type, expr.index.location.clone());
+                self.assert_type_with_context(
+                    &index.type_,
+                    key_type,
+                    expr.index.location.clone(),
+                    "the map being indexed is declared here",
+                    expr.value.location.clone(),
+                );
                 let type_ = *value_type.clone();
-                ast::Expression::new(
+                self.new_expression(
                     ast::ExpressionKind::Index(ast::IndexExpression {
                         value: Box::new(value),
                         index: Box::new(index),
                     }),
                     type_,
+                    common::Span::at(expr.location.clone()),
                 )
             }
             _ => {
@@ -704,12 +1552,13 @@ impl Analyzer {
                 ast::EXPRESSION_ERROR.clone()
             } else {
                 let type_ = ts[expr.index].clone();
-                ast::Expression::new(
+                self.new_expression(
                     ast::ExpressionKind::TupleIndex(ast::TupleIndexExpression {
                         value: Box::new(value),
                         index: expr.index,
                     }),
                     type_,
+                    common::Span::at(expr.location.clone()),
                 )
             }
         } else {
@@ -723,23 +1572,73 @@ impl Analyzer {
         &mut self,
         expr: &ptree::AttributeExpression,
     ) -> ast::Expression {
-        self.error("not implemented", expr.location.clone());
+        let value = self.analyze_expression(&expr.value);
+        let fields = if let ast::Type::Record { fields, .. } = &value.type_ {
+            fields.clone()
+        } else {
+            let msg = format!("cannot access field of non-record type {}", value.type_);
+            self.error(&msg, expr.value.location.clone());
+            return ast::EXPRESSION_ERROR.clone();
+        };
+
+        // The field's byte offset is the sum of `storage_size()` for every field declared before
+        // it, matching the uniform stack-slot layout `storage_size()` already assumes elsewhere.
+        let mut offset = 0;
+        for (field_name, field_type) in &fields {
+            if field_name == &expr.attribute {
+                return self.new_expression(
+                    ast::ExpressionKind::Attribute(ast::AttributeExpression {
+                        value: Box::new(value),
+                        attribute: expr.attribute.clone(),
+                        offset,
+                    }),
+                    field_type.clone(),
+                    common::Span::at(expr.location.clone()),
+                );
+            }
+            offset += field_type.storage_size() as i32;
+        }
+
+        let msg = format!("record {} has no field {}", value.type_, expr.attribute);
+        self.error(&msg, expr.location.clone());
         ast::EXPRESSION_ERROR.clone()
     }
 
     fn analyze_list_literal(&mut self, expr: &ptree::ListLiteral) -> ast::Expression {
         if expr.items.is_empty() {
-            self.error(
-                "cannot type-check empty list literal",
-                expr.location.clone(),
+            // The element type can't be read off any item, so stand it in with a fresh variable to
+            // be pinned down later by context (e.g. a `let`'s declared type or the list's first
+            // use) -- `resolve_program_types` reports an error if it's still unbound by the end.
+            let item_type = self.fresh_type_var();
+            let count = self.new_expression(
+                ast::ExpressionKind::Integer(0),
+                ast::Type::I64,
+                common::Span::at(expr.location.clone()),
+            );
+            return self.new_expression(
+                ast::ExpressionKind::Call(ast::CallExpression {
+                    function: ast::SymbolEntry {
+                        unique_name: String::from("venice_list_from_varargs"),
+                        type_: ast::Type::Error,
+                        constant: true,
+                        external: true,
+                        syscall: None,
+                        stack_offset: 0,
+                        span: common::Span::empty(),
+                    },
+                    arguments: vec![count],
+                    variadic: true,
+                }),
+                ast::Type::List(Box::new(item_type)),
+                common::Span::at(expr.location.clone()),
             );
-            return ast::EXPRESSION_ERROR.clone();
         }
 
         let mut arguments = Vec::new();
-        arguments.push(ast::Expression::new(
+        arguments.push(self.new_expression(
             ast::ExpressionKind::Integer(expr.items.len() as i64),
             ast::Type::I64,
+            common::Span::at(expr.location.clone()),
         ));
 
         let first_item = self.analyze_expression(&expr.items[0]);
@@ -755,19 +1654,22 @@ impl Analyzer {
             );
             arguments.push(typed_item);
         }
-        ast::Expression::new(
+        self.new_expression(
             ast::ExpressionKind::Call(ast::CallExpression {
                 function: ast::SymbolEntry {
                     unique_name: String::from("venice_list_from_varargs"),
                     type_: ast::Type::Error,
                     constant: true,
                     external: true,
+                    syscall: None,
                     stack_offset: 0,
+                    span: common::Span::empty(),
                 },
                 arguments,
                 variadic: true,
             }),
             ast::Type::List(Box::new(item_type)),
+            common::Span::at(expr.location.clone()),
         )
     }
 
@@ -779,16 +1681,27 @@ impl Analyzer {
             types.push(typed_item.type_.clone());
             items.push(typed_item);
         }
-        ast::Expression::new(
+        self.new_expression(
             ast::ExpressionKind::Tuple(ast::TupleLiteral { items }),
             ast::Type::Tuple(types),
+            common::Span::at(expr.location.clone()),
         )
     }
 
     fn analyze_map_literal(&mut self, expr: &ptree::MapLiteral) -> ast::Expression {
         if expr.items.is_empty() {
-            self.error("cannot type-check empty map literal", expr.location.clone());
-            return ast::EXPRESSION_ERROR.clone();
+            // As in `analyze_list_literal`, stand in fresh variables for the key/value types that
+            // can't be read off any item, to be pinned down later by context.
+            let key_type = self.fresh_type_var();
+            let value_type = self.fresh_type_var();
+            return self.new_expression(
+                ast::ExpressionKind::Map(ast::MapLiteral { items: Vec::new() }),
+                ast::Type::Map {
+                    key: Box::new(key_type),
+                    value: Box::new(value_type),
+                },
+                common::Span::at(expr.location.clone()),
+            );
         }
 
         let first_key = self.analyze_expression(&expr.items[0].0);
@@ -799,34 +1712,164 @@ impl Analyzer {
         let mut items = Vec::new();
         for i in 1..expr.items.len() {
             let typed_key = self.analyze_expression(&expr.items[i].0);
-            self.assert_type(
+            self.assert_type_with_context(
                 &typed_key.type_,
                 &key_type,
                 expr.items[i].0.location.clone(),
+                "the map's key type is established by this entry",
+                expr.items[0].0.location.clone(),
             );
 
             let typed_value = self.analyze_expression(&expr.items[i].1);
-            self.assert_type(
+            self.assert_type_with_context(
                 &typed_value.type_,
                 &value_type,
                 expr.items[i].1.location.clone(),
+                "the map's value type is established by this entry",
+                expr.items[0].1.location.clone(),
             );
 
             items.push((typed_key, typed_value));
         }
 
-        ast::Expression::new(
+        self.new_expression(
             ast::ExpressionKind::Map(ast::MapLiteral { items }),
             ast::Type::Map {
                 key: Box::new(key_type),
                 value: Box::new(value_type),
             },
+            common::Span::at(expr.location.clone()),
         )
     }
 
     fn analyze_record_literal(&mut self, expr: &ptree::RecordLiteral) -> ast::Expression {
-        // TODO
-        panic!("internal error: record literals are not yet supported");
+        let span = common::Span::at(expr.location.clone());
+        let record_entry = match self.types.get(&expr.name) {
+            Some(entry) => entry,
+            None => {
+                let msg = format!("unknown record type {}", expr.name);
+                self.error(&msg, expr.location.clone());
+                return ast::EXPRESSION_ERROR.clone();
+            }
+        };
+        let fields = match &record_entry.type_ {
+            ast::Type::Record { fields, .. } => fields.clone(),
+            _ => {
+                let msg = format!("{} is not a record type", expr.name);
+                self.error(&msg, expr.location.clone());
+                return ast::EXPRESSION_ERROR.clone();
+            }
+        };
+
+        let mut seen = std::collections::HashSet::new();
+        let mut items = Vec::new();
+        let mut has_error = false;
+        for (field_name, value_expr) in &expr.items {
+            if !seen.insert(field_name.clone()) {
+                let msg = format!("duplicate field {} in record literal", field_name);
+                self.error(&msg, value_expr.location.clone());
+                has_error = true;
+                continue;
+            }
+
+            let value = self.analyze_expression(value_expr);
+            match fields.iter().find(|(name, _)| name == field_name) {
+                Some((_, field_type)) => {
+                    self.assert_type(&value.type_, field_type, value_expr.location.clone());
+                }
+                None => {
+                    let msg = format!("record {} has no field {}", expr.name, field_name);
+                    self.error(&msg, value_expr.location.clone());
+                    has_error = true;
+                }
+            }
+            items.push((field_name.clone(), value));
+        }
+
+        for (field_name, _) in &fields {
+            if !seen.contains(field_name) {
+                let msg = format!("missing field {} in record literal of type {}", field_name, expr.name);
+                self.error(&msg, expr.location.clone());
+                has_error = true;
+            }
+        }
+
+        if has_error {
+            return ast::EXPRESSION_ERROR.clone();
+        }
+
+        self.new_expression(
+            ast::ExpressionKind::Record(ast::RecordLiteral {
+                name: record_entry,
+                items,
+            }),
+            ast::Type::Record {
+                name: expr.name.clone(),
+                fields,
+            },
+            span,
+        )
+    }
+
+    fn analyze_list_comprehension(&mut self, expr: &ptree::ListComprehension) -> ast::Expression {
+        let span = common::Span::at(expr.location.clone());
+        let iterator = self.analyze_expression(&expr.iterator);
+        let item_type = match &iterator.type_ {
+            ast::Type::List(t) => (**t).clone(),
+            _ => {
+                let msg = format!(
+                    "cannot iterate over non-list type {} in list comprehension",
+                    iterator.type_
+                );
+                self.error(&msg, expr.iterator.location.clone());
+                ast::Type::Error
+            }
+        };
+
+        self.symbols.push_scope();
+        let unique_name = self.claim_unique_name(&expr.symbol);
+        let symbol_entry = ast::SymbolEntry {
+            unique_name,
+            type_: item_type,
+            constant: false,
+            external: false,
+            syscall: None,
+            stack_offset: self.current_stack_offset,
+            span: common::Span::at(expr.location.clone()),
+        };
+        self.symbols.insert(&expr.symbol, symbol_entry.clone());
+        if let Some(info) = self.current_function_info.as_mut() {
+            info.stack_frame_size += symbol_entry.type_.stack_size();
+        }
+        self.current_stack_offset -= symbol_entry.type_.stack_size() as i32;
+
+        let condition = expr.condition.as_ref().map(|c| {
+            let condition = self.analyze_expression(c);
+            if !condition.type_.matches(&ast::Type::Boolean) {
+                self.error_type_mismatch(&ast::Type::Boolean, &condition.type_, c.location.clone());
+            }
+            Box::new(condition)
+        });
+
+        let value = self.analyze_expression(&expr.value);
+        let result_type = ast::Type::List(Box::new(value.type_.clone()));
+
+        // Pop the loop variable's scope now that the body and filter have been checked.
+        self.symbols.pop_scope();
+
+        // Only the analyzer understands this node so far: there's no `for`-statement codegen or
+        // `venice_list_append`-equivalent runtime primitive yet to lower the implied loop into,
+        // so (like `Tuple`/`Map`/`Record`) it falls through to codegen's catch-all panic for now.
+        self.new_expression(
+            ast::ExpressionKind::ListComprehension(ast::ListComprehension {
+                value: Box::new(value),
+                symbol: symbol_entry,
+                iterator: Box::new(iterator),
+                condition,
+            }),
+            result_type,
+            span,
+        )
     }
 
     fn resolve_type(&mut self, type_: &ptree::Type) -> ast::Type {
@@ -835,6 +1878,8 @@ impl Analyzer {
             Literal(s) => {
                 if let Some(entry) = self.types.get(s) {
                     entry.type_
+                } else if let Some(t) = self.resolver.as_ref().and_then(|r| r.resolve_type(s)) {
+                    t
                 } else {
                     let msg = format!("unknown type {}", s);
                     self.error(&msg, type_.location.clone());
@@ -879,11 +1924,313 @@ impl Analyzer {
         }
     }
 
+    /// Like `assert_type`, but on a mismatch also attaches a secondary label at `context_location`
+    /// (e.g. a call's callee declaration, or a map/list literal's first item) explaining why
+    /// `expected` was expected, instead of leaving the reader to guess where it came from.
+    fn assert_type_with_context(
+        &mut self,
+        actual: &ast::Type,
+        expected: &ast::Type,
+        location: common::Location,
+        context_message: &str,
+        context_location: common::Location,
+    ) {
+        if !actual.matches(expected) {
+            let errors_before = self.errors.len();
+            self.error_type_mismatch(expected, actual, location);
+            if self.errors.len() == errors_before + 1 {
+                let error = self.errors.pop().unwrap();
+                self.errors
+                    .push(error.with_label(context_message, context_location, None));
+            }
+        }
+    }
+
+    /// Hands out a fresh, as-yet-unconstrained `Type::Variable` -- used in place of a concrete
+    /// type when there's nothing more direct to fall back on, so that `unify` can still pin it
+    /// down from whatever it's later compared against.
+    fn fresh_type_var(&mut self) -> ast::Type {
+        let var = ast::Type::Variable(self.next_type_var);
+        self.next_type_var += 1;
+        var
+    }
+
+    /// Gives a generic type (one containing `Type::Variable`s, e.g. a builtin like `length`'s
+    /// static signature) a fresh set of variables distinct from any other call site's, so this
+    /// call's use of it can be unified independently. Every occurrence of the same variable number
+    /// within `t` maps to the same fresh variable, so e.g. a `T -> T` signature stays `T -> T`
+    /// after instantiation, just with a different `T`.
+    fn instantiate(&mut self, t: &ast::Type) -> ast::Type {
+        let mut mapping = HashMap::new();
+        self.instantiate_with(t, &mut mapping)
+    }
+
+    fn instantiate_with(&mut self, t: &ast::Type, mapping: &mut HashMap<u32, ast::Type>) -> ast::Type {
+        match t {
+            ast::Type::Variable(n) => {
+                if let Some(fresh) = mapping.get(n) {
+                    fresh.clone()
+                } else {
+                    let fresh = self.fresh_type_var();
+                    mapping.insert(*n, fresh.clone());
+                    fresh
+                }
+            }
+            ast::Type::List(t) => ast::Type::List(Box::new(self.instantiate_with(t, mapping))),
+            ast::Type::Map { key, value } => ast::Type::Map {
+                key: Box::new(self.instantiate_with(key, mapping)),
+                value: Box::new(self.instantiate_with(value, mapping)),
+            },
+            ast::Type::Tuple(ts) => {
+                ast::Type::Tuple(ts.iter().map(|t| self.instantiate_with(t, mapping)).collect())
+            }
+            ast::Type::Function {
+                parameters,
+                return_type,
+            } => ast::Type::Function {
+                parameters: parameters
+                    .iter()
+                    .map(|t| self.instantiate_with(t, mapping))
+                    .collect(),
+                return_type: Box::new(self.instantiate_with(return_type, mapping)),
+            },
+            _ => t.clone(),
+        }
+    }
+
+    /// Follows a `Type::Variable` through `self.substitution` to whatever it's currently bound to
+    /// (or back to itself, if it's still unbound) -- the "find" half of a union-find-backed
+    /// unifier. Leaves every other `Type` untouched.
+    fn prune(&self, t: &ast::Type) -> ast::Type {
+        match t {
+            ast::Type::Variable(n) => match self.substitution.get(n) {
+                Some(bound) => self.prune(bound),
+                None => t.clone(),
+            },
+            _ => t.clone(),
+        }
+    }
+
+    /// True if the (pruned) variable `n` appears anywhere inside `t` -- binding `n` to such a `t`
+    /// would build a type that contains itself, so `unify` refuses and reports an error instead.
+    fn occurs_in(&self, n: u32, t: &ast::Type) -> bool {
+        match self.prune(t) {
+            ast::Type::Variable(m) => m == n,
+            ast::Type::List(inner) => self.occurs_in(n, &inner),
+            ast::Type::Map { key, value } => self.occurs_in(n, &key) || self.occurs_in(n, &value),
+            ast::Type::Tuple(ts) => ts.iter().any(|t| self.occurs_in(n, t)),
+            ast::Type::Function {
+                parameters,
+                return_type,
+            } => {
+                parameters.iter().any(|t| self.occurs_in(n, t))
+                    || self.occurs_in(n, &return_type)
+            }
+            _ => false,
+        }
+    }
+
+    /// The analyzer's authoritative type check: unlike `Type::matches`, `unify` can resolve a
+    /// `Type::Variable` against whatever it's compared with, recursing structurally into
+    /// `List`/`Map`/`Tuple`/`Function` the same way `matches` does so a variable nested inside one
+    /// of those still gets bound. Reports a type-mismatch error at `location` and returns `Err`
+    /// if the two types can never be made equal.
+    fn unify(&mut self, a: &ast::Type, b: &ast::Type, location: common::Location) -> Result<(), ()> {
+        let a = self.prune(a);
+        let b = self.prune(b);
+        match (&a, &b) {
+            (ast::Type::Variable(n), ast::Type::Variable(m)) if n == m => Ok(()),
+            (ast::Type::Variable(n), _) => {
+                if self.occurs_in(*n, &b) {
+                    let msg = format!("infinite type: ?{} occurs in {}", n, b);
+                    self.error(&msg, location);
+                    return Err(());
+                }
+                self.substitution.insert(*n, b);
+                Ok(())
+            }
+            (_, ast::Type::Variable(m)) => {
+                if self.occurs_in(*m, &a) {
+                    let msg = format!("infinite type: ?{} occurs in {}", m, a);
+                    self.error(&msg, location);
+                    return Err(());
+                }
+                self.substitution.insert(*m, a);
+                Ok(())
+            }
+            (ast::Type::List(x), ast::Type::List(y)) => self.unify(x, y, location),
+            (
+                ast::Type::Map {
+                    key: k1,
+                    value: v1,
+                },
+                ast::Type::Map {
+                    key: k2,
+                    value: v2,
+                },
+            ) => {
+                self.unify(k1, k2, location.clone())?;
+                self.unify(v1, v2, location)
+            }
+            (ast::Type::Tuple(ts1), ast::Type::Tuple(ts2)) if ts1.len() == ts2.len() => {
+                for (t1, t2) in ts1.iter().zip(ts2.iter()) {
+                    self.unify(t1, t2, location.clone())?;
+                }
+                Ok(())
+            }
+            (
+                ast::Type::Function {
+                    parameters: p1,
+                    return_type: r1,
+                },
+                ast::Type::Function {
+                    parameters: p2,
+                    return_type: r2,
+                },
+            ) if p1.len() == p2.len() => {
+                for (t1, t2) in p1.iter().zip(p2.iter()) {
+                    self.unify(t1, t2, location.clone())?;
+                }
+                self.unify(r1, r2, location)
+            }
+            _ => {
+                if a.matches(&b) {
+                    Ok(())
+                } else {
+                    self.error_type_mismatch(&a, &b, location);
+                    Err(())
+                }
+            }
+        }
+    }
+
+    /// Like `unify`, but on a mismatch also attaches a secondary label at `context_location` (e.g.
+    /// a `let`'s type annotation, or a function's declared return type) explaining why that type
+    /// was expected, instead of leaving the reader to guess where `expected` came from.
+    fn unify_with_context(
+        &mut self,
+        expected: &ast::Type,
+        actual: &ast::Type,
+        location: common::Location,
+        context_message: &str,
+        context_location: common::Location,
+    ) -> Result<(), ()> {
+        let errors_before = self.errors.len();
+        let result = self.unify(expected, actual, location);
+        if result.is_err() && self.errors.len() == errors_before + 1 {
+            let error = self.errors.pop().unwrap();
+            self.errors
+                .push(error.with_label(context_message, context_location, None));
+        }
+        result
+    }
+
+    /// Replaces every `Type::Variable` in `t` with its binding in `self.substitution`, recursing
+    /// into `List`/`Map`/`Tuple`/`Function` the same way `unify` does. A variable that's still
+    /// unbound at this point never got constrained by anything, which is itself a type error.
+    fn resolve_type_fully(&mut self, t: &ast::Type, location: common::Location) -> ast::Type {
+        match self.prune(t) {
+            ast::Type::Variable(n) => {
+                let msg = format!("insufficient type information to infer ?{}", n);
+                self.error(&msg, location);
+                ast::Type::Error
+            }
+            ast::Type::List(inner) => {
+                ast::Type::List(Box::new(self.resolve_type_fully(&inner, location)))
+            }
+            ast::Type::Map { key, value } => ast::Type::Map {
+                key: Box::new(self.resolve_type_fully(&key, location.clone())),
+                value: Box::new(self.resolve_type_fully(&value, location)),
+            },
+            ast::Type::Tuple(ts) => ast::Type::Tuple(
+                ts.iter()
+                    .map(|t| self.resolve_type_fully(t, location.clone()))
+                    .collect(),
+            ),
+            ast::Type::Function {
+                parameters,
+                return_type,
+            } => ast::Type::Function {
+                parameters: parameters
+                    .iter()
+                    .map(|t| self.resolve_type_fully(t, location.clone()))
+                    .collect(),
+                return_type: Box::new(self.resolve_type_fully(&return_type, location)),
+            },
+            other => other,
+        }
+    }
+
+    /// Applies the substitution `unify` built up over the whole program, so that no
+    /// `Type::Variable` reaches register allocation or codegen. Function signatures and record
+    /// fields are fully annotated by the parser and never contain a variable, so this only ever
+    /// has real work to do on a `let` statement's inferred type and on expressions built around a
+    /// fresh variable (an empty list or map literal, a call to a generic builtin like `length`).
+    fn resolve_program_types(&mut self, program: &mut ast::Program) {
+        if self.next_type_var == 0 {
+            return;
+        }
+
+        for declaration in &mut program.declarations {
+            if let ast::Declaration::Function(d) = declaration {
+                let location = d.span.start.clone();
+                self.resolve_block_types(&mut d.body, &location);
+            }
+        }
+
+        let mut resolver = TypeResolver { analyzer: self };
+        resolver.visit_program_mut(program);
+    }
+
+    /// Resolves the one `Type` field each statement variant carries outside of a `SymbolEntry` or
+    /// `Expression` -- `LetStatement::type_` -- which `TypeResolver` doesn't reach because it's a
+    /// plain field, not a node `VisitorMut` recurses into. Recurses into every nested block so a
+    /// `let` inside an `if`/`while`/`for` body is reached too.
+    fn resolve_block_types(&mut self, block: &mut [ast::Statement], location: &common::Location) {
+        for statement in block {
+            match statement {
+                ast::Statement::Let(stmt) => {
+                    stmt.type_ = self.resolve_type_fully(&stmt.type_, location.clone());
+                }
+                ast::Statement::If(stmt) => {
+                    self.resolve_block_types(&mut stmt.body, location);
+                    self.resolve_block_types(&mut stmt.else_body, location);
+                }
+                ast::Statement::While(stmt) => {
+                    self.resolve_block_types(&mut stmt.body, location);
+                }
+                ast::Statement::For(stmt) => {
+                    self.resolve_block_types(&mut stmt.body, location);
+                }
+                ast::Statement::Assert(_)
+                | ast::Statement::Assign(_)
+                | ast::Statement::Expression(_)
+                | ast::Statement::Return(_)
+                | ast::Statement::Error(_) => {}
+            }
+        }
+    }
+
     fn error(&mut self, message: &str, location: common::Location) {
         self.errors
             .push(errors::VeniceError::new(message, location));
     }
 
+    fn error_redefinition(
+        &mut self,
+        name: &str,
+        location: common::Location,
+        previous_location: common::Location,
+    ) {
+        let message = format!("function `{}` is already defined", name);
+        let error = errors::VeniceError::new(&message, location).with_label(
+            "previously defined here",
+            previous_location,
+            None,
+        );
+        self.errors.push(error);
+    }
+
     fn error_type_mismatch(
         &mut self,
         expected: &ast::Type,
@@ -900,100 +2247,191 @@ impl Analyzer {
     }
 }
 
+/// Walks a finished `ast::Program`, via `VisitorMut`, replacing every `Expression`'s and
+/// `SymbolEntry`'s `Type` with its fully-resolved form -- the part of `resolve_program_types` that
+/// reaches into node fields a hand-written block walk would otherwise have to duplicate.
+struct TypeResolver<'a> {
+    analyzer: &'a mut Analyzer,
+}
+
+impl VisitorMut for TypeResolver<'_> {
+    fn visit_expression_mut(&mut self, expression: &mut ast::Expression) {
+        let location = expression.span.start.clone();
+        expression.type_ = self.analyzer.resolve_type_fully(&expression.type_, location);
+        visitor::walk_expression_mut(self, expression);
+    }
+
+    fn visit_symbol_mut(&mut self, symbol: &mut ast::SymbolEntry) {
+        let location = symbol.span.start.clone();
+        symbol.type_ = self.analyzer.resolve_type_fully(&symbol.type_, location);
+    }
+
+    fn visit_function_declaration_mut(&mut self, declaration: &mut ast::FunctionDeclaration) {
+        let location = declaration.span.start.clone();
+        declaration.name.type_ = self
+            .analyzer
+            .resolve_type_fully(&declaration.name.type_, location.clone());
+        declaration.return_type = self
+            .analyzer
+            .resolve_type_fully(&declaration.return_type, location.clone());
+        for parameter in &mut declaration.parameters {
+            parameter.name.type_ = self
+                .analyzer
+                .resolve_type_fully(&parameter.name.type_, location.clone());
+            parameter.type_ = self
+                .analyzer
+                .resolve_type_fully(&parameter.type_, location.clone());
+        }
+        visitor::walk_function_declaration_mut(self, declaration);
+    }
+
+    fn visit_const_declaration_mut(&mut self, declaration: &mut ast::ConstDeclaration) {
+        let location = declaration.span.start.clone();
+        declaration.type_ = self.analyzer.resolve_type_fully(&declaration.type_, location);
+        visitor::walk_const_declaration_mut(self, declaration);
+    }
+}
+
+/// Maps an integer literal's lexed suffix to its type -- `i64` for an unsuffixed literal (`None`),
+/// matching the type it had before suffixes existed.
+fn integer_suffix_type(suffix: Option<ptree::IntegerSuffix>) -> ast::Type {
+    use ptree::IntegerSuffix::*;
+    match suffix {
+        None | Some(I64) => ast::Type::I64,
+        Some(I8) => ast::Type::I8,
+        Some(I16) => ast::Type::I16,
+        Some(I32) => ast::Type::I32,
+        Some(U8) => ast::Type::U8,
+        Some(U16) => ast::Type::U16,
+        Some(U32) => ast::Type::U32,
+        Some(U64) => ast::Type::U64,
+    }
+}
+
+/// Whether an unsuffixed-i64-range literal value `x` actually fits in a narrower sized integer
+/// type once an explicit suffix (`5i8`, `300u16`, ...) names one. The lexer/parser already reject
+/// anything that doesn't fit in an `i64` (see `split_integer_suffix`'s caller), so this only needs
+/// to narrow further for the sized types below `i64` itself.
+fn integer_fits_in_type(x: i64, type_: &ast::Type) -> bool {
+    use ast::Type::*;
+    match type_ {
+        I8 => i8::try_from(x).is_ok(),
+        I16 => i16::try_from(x).is_ok(),
+        I32 => i32::try_from(x).is_ok(),
+        U8 => u8::try_from(x).is_ok(),
+        U16 => u16::try_from(x).is_ok(),
+        U32 => u32::try_from(x).is_ok(),
+        U64 => x >= 0,
+        _ => true,
+    }
+}
+
 fn allocate_registers_in_program(program: &mut ast::Program) {
     for declaration in &mut program.declarations {
-        match declaration {
-            ast::Declaration::Function(decl) => {
-                allocate_registers_in_block(&mut decl.body);
-            }
-            _ => {
-                // No need to allocate registers for other kinds of declarations.
-            }
+        if let ast::Declaration::Function(decl) = declaration {
+            let peak = allocate_registers_in_block(&mut decl.body);
+            decl.info.max_register_needed = peak;
         }
+        // No need to allocate registers for other kinds of declarations.
     }
 }
 
-fn allocate_registers_in_block(block: &mut Vec<ast::Statement>) {
+/// Walks a block, assigning every expression's Sethi-Ullman register label, and returns the peak
+/// label seen across the whole block (the most registers any single statement's expressions need
+/// at once -- statements don't overlap with each other, so this is just a max, not a sum).
+fn allocate_registers_in_block(block: &mut Vec<ast::Statement>) -> u8 {
+    let mut peak = 0;
     for statement in block {
-        match statement {
-            ast::Statement::Assert(stmt) => {
-                allocate_registers(&mut stmt.condition, 0);
-            }
-            ast::Statement::Assign(stmt) => {
-                allocate_registers(&mut stmt.value, 0);
-            }
-            ast::Statement::Expression(expr) => {
-                allocate_registers(expr, 0);
-            }
+        let label = match statement {
+            ast::Statement::Assert(stmt) => allocate_registers(&mut stmt.condition),
+            ast::Statement::Assign(stmt) => allocate_registers(&mut stmt.value),
+            ast::Statement::Expression(expr) => allocate_registers(expr),
             ast::Statement::For(stmt) => {
-                allocate_registers_in_block(&mut stmt.body);
-            }
-            ast::Statement::If(stmt) => {
-                allocate_registers(&mut stmt.condition, 0);
-                allocate_registers_in_block(&mut stmt.body);
-                allocate_registers_in_block(&mut stmt.else_body);
+                allocate_registers(&mut stmt.iterator).max(allocate_registers_in_block(&mut stmt.body))
             }
-            ast::Statement::Let(stmt) => {
-                allocate_registers(&mut stmt.value, 0);
-            }
-            ast::Statement::Return(stmt) => {
-                allocate_registers(&mut stmt.value, 0);
-            }
-            ast::Statement::While(stmt) => {
-                allocate_registers(&mut stmt.condition, 0);
-                allocate_registers_in_block(&mut stmt.body);
-            }
-            ast::Statement::Error => {}
-        }
+            ast::Statement::If(stmt) => allocate_registers(&mut stmt.condition)
+                .max(allocate_registers_in_block(&mut stmt.body))
+                .max(allocate_registers_in_block(&mut stmt.else_body)),
+            ast::Statement::Let(stmt) => allocate_registers(&mut stmt.value),
+            ast::Statement::Return(stmt) => allocate_registers(&mut stmt.value),
+            ast::Statement::While(stmt) => allocate_registers(&mut stmt.condition)
+                .max(allocate_registers_in_block(&mut stmt.body)),
+            ast::Statement::Error(_) => 0,
+        };
+        peak = peak.max(label);
     }
+    peak
 }
 
-fn allocate_registers(expr: &mut ast::Expression, register: u8) {
+/// Computes and records the Sethi-Ullman register label for `expr` and every expression nested
+/// inside it, returning the label (the minimum number of registers needed to evaluate `expr` in
+/// isolation). Codegen reads the labels back off of `max_register_needed` to both pick an
+/// evaluation order that reuses registers (see `generate_generic_binary_expression`) and to name
+/// the virtual register each expression's result lands in.
+fn allocate_registers(expr: &mut ast::Expression) -> u8 {
     use ast::ExpressionKind::*;
-    match &mut expr.kind {
-        Boolean(_) | Integer(_) | String(_) | Symbol(_) => {
-            expr.register = register;
-        }
+    let label = match &mut expr.kind {
+        Boolean(_) | Integer(_) | Float(_) | String(_) | Symbol(_) | Error => 1,
         Binary(ref mut e) => {
-            allocate_registers(&mut e.left, register);
-            allocate_registers(&mut e.right, register + 1);
-            expr.register = register + 1;
+            sethi_ullman_pair(allocate_registers(&mut e.left), allocate_registers(&mut e.right))
         }
         Comparison(ref mut e) => {
-            allocate_registers(&mut e.left, register);
-            allocate_registers(&mut e.right, register + 1);
-            expr.register = register + 1;
+            sethi_ullman_pair(allocate_registers(&mut e.left), allocate_registers(&mut e.right))
         }
-        Unary(ref mut e) => {
-            allocate_registers(&mut e.operand, register);
-            expr.register = register;
-        }
-        Call(ref mut e) => {
-            for mut argument in &mut e.arguments {
-                allocate_registers(&mut argument, register);
-            }
-            expr.register = register;
+        Index(ref mut e) => {
+            sethi_ullman_pair(allocate_registers(&mut e.value), allocate_registers(&mut e.index))
         }
-        If(ref mut e) => {
-            allocate_registers(&mut e.condition, register);
-            allocate_registers(&mut e.true_value, register);
-            allocate_registers(&mut e.false_value, register);
-            expr.register = register;
+        Unary(ref mut e) => allocate_registers(&mut e.operand),
+        TupleIndex(ref mut e) => allocate_registers(&mut e.value),
+        Attribute(ref mut e) => allocate_registers(&mut e.value),
+        If(ref mut e) => allocate_registers(&mut e.condition)
+            .max(allocate_registers(&mut e.true_value))
+            .max(allocate_registers(&mut e.false_value)),
+        Call(ref mut e) => allocate_registers_in_sequence(e.arguments.iter_mut()),
+        Tuple(ref mut e) => allocate_registers_in_sequence(e.items.iter_mut()),
+        Map(ref mut e) => {
+            allocate_registers_in_sequence(e.items.iter_mut().flat_map(|(k, v)| [k, v]))
         }
-        Index(ref mut e) => {
-            allocate_registers(&mut e.value, register);
-            allocate_registers(&mut e.index, register + 1);
-            expr.register = register + 1;
-        }
-        _ => {
-            panic!(
-                "internal error: register allocation not implemented for {:?}",
-                expr.kind
-            );
+        Record(ref mut e) => allocate_registers_in_sequence(e.items.iter_mut().map(|(_, v)| v)),
+        ListComprehension(ref mut e) => {
+            let label = allocate_registers(&mut e.iterator).max(allocate_registers(&mut e.value));
+            match &mut e.condition {
+                Some(condition) => label.max(allocate_registers(condition)),
+                None => label,
+            }
         }
+    };
+    expr.max_register_needed = label;
+    label
+}
+
+/// The Sethi-Ullman rule for a two-operand node: if both operands need the same number of
+/// registers, evaluating one forces the other to need one more register to hold the first
+/// operand's result alongside its own work; otherwise the node only ever needs as many registers
+/// as its more demanding operand.
+fn sethi_ullman_pair(left: u8, right: u8) -> u8 {
+    if left == right {
+        left.saturating_add(1)
+    } else {
+        left.max(right)
     }
 }
 
+/// The Sethi-Ullman rule for an N-ary node (call arguments, tuple/record items, map key/value
+/// pairs) whose operands are evaluated one at a time into consecutive registers, with every
+/// already-evaluated operand staying live until the node itself is done: operand `i` (0-indexed)
+/// needs `label(operand) + i` registers by the time it's evaluated, so the node's label is the
+/// max of that over every operand.
+fn allocate_registers_in_sequence<'a>(
+    exprs: impl Iterator<Item = &'a mut ast::Expression>,
+) -> u8 {
+    let mut label: u8 = 1;
+    for (i, expr) in exprs.enumerate() {
+        label = label.max(allocate_registers(expr).saturating_add(i as u8));
+    }
+    label
+}
+
 struct SymbolTable {
     environments: Vec<HashMap<String, ast::SymbolEntry>>,
 }
@@ -1008,6 +2446,17 @@ impl SymbolTable {
     pub fn builtin_types() -> Self {
         let mut symbols = HashMap::new();
         symbols.insert(String::from("i64"), ast::SymbolEntry::type_(ast::Type::I64));
+        symbols.insert(String::from("i8"), ast::SymbolEntry::type_(ast::Type::I8));
+        symbols.insert(String::from("i16"), ast::SymbolEntry::type_(ast::Type::I16));
+        symbols.insert(String::from("i32"), ast::SymbolEntry::type_(ast::Type::I32));
+        symbols.insert(String::from("u8"), ast::SymbolEntry::type_(ast::Type::U8));
+        symbols.insert(String::from("u16"), ast::SymbolEntry::type_(ast::Type::U16));
+        symbols.insert(String::from("u32"), ast::SymbolEntry::type_(ast::Type::U32));
+        symbols.insert(String::from("u64"), ast::SymbolEntry::type_(ast::Type::U64));
+        symbols.insert(
+            String::from("float"),
+            ast::SymbolEntry::type_(ast::Type::F64),
+        );
         symbols.insert(
             String::from("bool"),
             ast::SymbolEntry::type_(ast::Type::Boolean),
@@ -1080,7 +2529,10 @@ impl SymbolTable {
             ast::SymbolEntry::external(
                 "venice_list_length",
                 ast::Type::Function {
-                    parameters: vec![ast::Type::List(Box::new(ast::Type::Any))],
+                    // Generic over the list's element type -- `analyze_call_expression` gives each
+                    // call its own fresh copy of this variable via `instantiate` before type-checking,
+                    // so two calls to `length` with different element types don't conflict.
+                    parameters: vec![ast::Type::List(Box::new(ast::Type::Variable(0)))],
                     return_type: Box::new(ast::Type::I64),
                 },
             ),
@@ -1136,6 +2588,86 @@ impl SymbolTable {
             ),
         );
 
+        // Raw Linux syscall intrinsics: unlike the symbols above, these compile to a direct
+        // `syscall` instruction rather than a call into the C runtime, so a program that sticks to
+        // them (plus arithmetic and control flow) can run without libc at all. Arguments and return
+        // values are raw machine words (`i64`), the same as the kernel ABI itself deals in -- a
+        // `string` pointer or file descriptor has to be unwrapped to an `i64` before being passed
+        // to one of these.
+        symbols.insert(
+            String::from("read"),
+            ast::SymbolEntry::syscall(
+                "read",
+                0,
+                ast::Type::Function {
+                    parameters: vec![ast::Type::I64, ast::Type::I64, ast::Type::I64],
+                    return_type: Box::new(ast::Type::I64),
+                },
+            ),
+        );
+        symbols.insert(
+            String::from("write"),
+            ast::SymbolEntry::syscall(
+                "write",
+                1,
+                ast::Type::Function {
+                    parameters: vec![ast::Type::I64, ast::Type::I64, ast::Type::I64],
+                    return_type: Box::new(ast::Type::I64),
+                },
+            ),
+        );
+        symbols.insert(
+            String::from("open"),
+            ast::SymbolEntry::syscall(
+                "open",
+                2,
+                ast::Type::Function {
+                    parameters: vec![ast::Type::I64, ast::Type::I64, ast::Type::I64],
+                    return_type: Box::new(ast::Type::I64),
+                },
+            ),
+        );
+        symbols.insert(
+            String::from("close"),
+            ast::SymbolEntry::syscall(
+                "close",
+                3,
+                ast::Type::Function {
+                    parameters: vec![ast::Type::I64],
+                    return_type: Box::new(ast::Type::I64),
+                },
+            ),
+        );
+        symbols.insert(
+            String::from("mmap"),
+            ast::SymbolEntry::syscall(
+                "mmap",
+                9,
+                ast::Type::Function {
+                    parameters: vec![
+                        ast::Type::I64,
+                        ast::Type::I64,
+                        ast::Type::I64,
+                        ast::Type::I64,
+                        ast::Type::I64,
+                        ast::Type::I64,
+                    ],
+                    return_type: Box::new(ast::Type::I64),
+                },
+            ),
+        );
+        symbols.insert(
+            String::from("exit"),
+            ast::SymbolEntry::syscall(
+                "exit",
+                60,
+                ast::Type::Function {
+                    parameters: vec![ast::Type::I64],
+                    return_type: Box::new(ast::Type::Void),
+                },
+            ),
+        );
+
         SymbolTable {
             environments: vec![symbols],
         }
@@ -13,63 +13,98 @@
 //
 // The AST is produced from the parse tree by the analyzer module and converted into VIL code by
 // the codegen module.
+//
+// Every node carries a `common::Span` back to the source text it was lowered from (`declaration`,
+// `let`, etc. get one as a plain field, the same way `ptree::Expression` already tracks its own
+// `location`/`end_location`; the bare `Statement`/`Declaration` enums carry it on their `Error`
+// variant and rely on their inner struct for everything else), so that error-reporting and future
+// tooling can ask any node where it came from without re-parsing the program.
 
 use super::common;
+use serde::{Deserialize, Serialize};
 use std::fmt;
+use std::io;
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct Program {
     pub declarations: Vec<Declaration>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub enum Declaration {
     Function(FunctionDeclaration),
     Const(ConstDeclaration),
     Record(RecordDeclaration),
-    Error,
+    Enum(EnumDeclaration),
+    Error(common::Span),
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct FunctionDeclaration {
     pub name: SymbolEntry,
     pub parameters: Vec<FunctionParameter>,
     pub return_type: Type,
     pub body: Vec<Statement>,
     pub info: FunctionInfo,
+    pub span: common::Span,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct FunctionInfo {
     pub stack_frame_size: i32,
+    /// The peak Sethi-Ullman register count across the function's body, filled in by
+    /// `allocate_registers_in_program` once the whole body's been walked -- 0 until then. The
+    /// backend can compare this against how many real registers it has available to decide
+    /// whether the function needs to spill.
+    pub max_register_needed: u8,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct FunctionParameter {
     pub name: SymbolEntry,
     pub type_: Type,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct ConstDeclaration {
     pub symbol: SymbolEntry,
     pub type_: Type,
     pub value: Expression,
+    pub span: common::Span,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct RecordDeclaration {
     pub name: SymbolEntry,
     pub fields: Vec<RecordField>,
+    pub span: common::Span,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct RecordField {
     pub name: String,
     pub type_: Type,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
+pub struct EnumDeclaration {
+    pub name: SymbolEntry,
+    pub variants: Vec<EnumVariant>,
+    pub span: common::Span,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct EnumVariant {
+    pub name: String,
+    /// The variant's position among its enum's variants in declaration order, e.g. `0` for `Some`
+    /// and `1` for `None` in `enum Option { Some(i64), None }`. Codegen compares this against the
+    /// runtime-tracked discriminant of a matched value to decide which arm to run.
+    pub tag: i64,
+    /// `None` for a variant with no payload.
+    pub payload: Option<Type>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
 pub enum Statement {
     Assert(AssertStatement),
     Assign(AssignStatement),
@@ -79,78 +114,108 @@ pub enum Statement {
     Let(LetStatement),
     Return(ReturnStatement),
     While(WhileStatement),
-    Error,
+    Error(common::Span),
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct LetStatement {
     pub symbol: SymbolEntry,
     pub type_: Type,
     pub value: Expression,
+    pub span: common::Span,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct AssignStatement {
     pub symbol: SymbolEntry,
     pub value: Expression,
+    pub span: common::Span,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct IfStatement {
     pub condition: Expression,
     pub body: Vec<Statement>,
     pub else_body: Vec<Statement>,
+    pub span: common::Span,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct WhileStatement {
     pub condition: Expression,
     pub body: Vec<Statement>,
+    pub span: common::Span,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct ForStatement {
     pub symbol: SymbolEntry,
     pub symbol2: Option<SymbolEntry>,
     pub iterator: Expression,
     pub body: Vec<Statement>,
+    pub span: common::Span,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct ReturnStatement {
     pub value: Expression,
+    pub span: common::Span,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct AssertStatement {
     pub condition: Expression,
+    pub span: common::Span,
 }
 
-#[derive(Clone, Debug)]
+/// A stable identifier for an `Expression`, assigned in construction order by the analyzer (see
+/// `Analyzer::claim_expr_id`). Since every `Expression` already carries its own mutable fields
+/// (`max_register_needed`, `stack_offset`) rather than being looked up through an arena, `ExprId`
+/// doesn't replace those inline fields or the `Box<Expression>` ownership used throughout this
+/// file -- it exists so a later pass can key a side table (e.g. a lint's notes, a debugger's
+/// breakpoint map) off a node's identity without cloning the node itself.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct ExprId(pub u32);
+
+impl fmt::Display for ExprId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Expression {
+    pub id: ExprId,
     pub kind: ExpressionKind,
     pub type_: Type,
     pub max_register_needed: u8,
     // This field is only used for placing arguments on the stack before calling a function. For
     // the stack offset of a named symbol, check its `SymbolEntry` instead.
     pub stack_offset: i32,
+    pub span: common::Span,
 }
 
 impl Expression {
-    pub fn new(kind: ExpressionKind, type_: Type) -> Self {
+    /// Builds an expression with a placeholder `ExprId(0)` -- callers that need a real, unique
+    /// identity (currently just `Analyzer`) should go through `Analyzer::new_expression` instead,
+    /// which assigns one from its own counter.
+    pub fn new(kind: ExpressionKind, type_: Type, span: common::Span) -> Self {
         Expression {
+            id: ExprId(0),
             kind,
             type_,
             max_register_needed: 0,
             stack_offset: 0,
+            span,
         }
     }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub enum ExpressionKind {
     Boolean(bool),
     Integer(i64),
+    Float(f64),
     String(String),
     Symbol(SymbolEntry),
     Binary(BinaryExpression),
@@ -164,88 +229,114 @@ pub enum ExpressionKind {
     Tuple(TupleLiteral),
     Map(MapLiteral),
     Record(RecordLiteral),
+    ListComprehension(ListComprehension),
     Error,
 }
 
 pub const EXPRESSION_ERROR: Expression = Expression {
+    id: ExprId(0),
     kind: ExpressionKind::Error,
     type_: Type::Error,
     max_register_needed: 0,
     stack_offset: 0,
+    span: common::Span::empty(),
 };
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct BinaryExpression {
     pub op: common::BinaryOpType,
     pub left: Box<Expression>,
     pub right: Box<Expression>,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct ComparisonExpression {
     pub op: common::ComparisonOpType,
     pub left: Box<Expression>,
     pub right: Box<Expression>,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct UnaryExpression {
     pub op: common::UnaryOpType,
     pub operand: Box<Expression>,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct CallExpression {
     pub function: SymbolEntry,
     pub arguments: Vec<Expression>,
     pub variadic: bool,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct IfExpression {
     pub condition: Box<Expression>,
     pub true_value: Box<Expression>,
     pub false_value: Box<Expression>,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct IndexExpression {
     pub value: Box<Expression>,
     pub index: Box<Expression>,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct TupleIndexExpression {
     pub value: Box<Expression>,
     pub index: usize,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct AttributeExpression {
     pub value: Box<Expression>,
     pub attribute: String,
+    /// The field's byte offset from the start of the record's layout -- the sum of
+    /// `storage_size()` for every field declared before it -- so codegen can index into the
+    /// record's stack/heap representation without having to look the field up by name again.
+    pub offset: i32,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct TupleLiteral {
     pub items: Vec<Expression>,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct MapLiteral {
     pub items: Vec<(Expression, Expression)>,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct RecordLiteral {
     pub name: SymbolEntry,
     pub items: Vec<(String, Expression)>,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ListComprehension {
+    pub value: Box<Expression>,
+    pub symbol: SymbolEntry,
+    pub iterator: Box<Expression>,
+    pub condition: Option<Box<Expression>>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub enum Type {
     Boolean,
+    // `I64` is the type of an integer literal with no suffix (or an explicit `i64` suffix/
+    // annotation); the other eight sized integer types below only arise from an explicit
+    // suffix (`5i32`, `10u8`, ...) or type annotation naming them.
     I64,
+    I8,
+    I16,
+    I32,
+    U8,
+    U16,
+    U32,
+    U64,
+    F64,
     String,
     // TODO: this shouldn't be a primitive type
     File,
@@ -263,40 +354,79 @@ pub enum Type {
         parameters: Vec<Type>,
         return_type: Box<Type>,
     },
-    Record(String),
+    // A user-defined aggregate type, registered in `SymbolTable` under `name` by
+    // `add_record_declaration_to_symbol_table` and resolved back to this variant by
+    // `resolve_type` wherever `name` appears as a type annotation.
+    Record {
+        name: String,
+        // In declaration order, since that order also determines the record's stack/heap layout
+        // (see `analyze_attribute_expression`'s field offsets).
+        fields: Vec<(String, Type)>,
+    },
+    Enum(String),
+    // An as-yet-unresolved type, introduced by the analyzer's unification-based inference (see
+    // `Analyzer::unify`) for a `let` that omits its annotation, an empty list or map literal, or a
+    // call to a generic builtin. Every `Variable` is resolved to a concrete type -- or reported as
+    // an error -- by the time `analyzer::analyze` returns; none should reach codegen.
+    Variable(u32),
     Error,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct SymbolEntry {
     pub unique_name: String,
     pub type_: Type,
     pub constant: bool,
     pub external: bool,
+    // Some(number) if this symbol is a raw syscall intrinsic (e.g. `write`, `exit`): calls to it
+    // compile to a direct `syscall` instruction with this number instead of a call to `unique_name`.
+    pub syscall: Option<i64>,
     // The offset of the symbol's location on the stack, relative to the base pointer. Should be a
     // negative number starting at -8. Will be 0 if inapplicable, e.g. for function and type
     // symbols.
     pub stack_offset: i32,
+    pub span: common::Span,
 }
 
 impl SymbolEntry {
+    /// Builtin type symbols (`i64`, `string`, ...) aren't declared anywhere in the program being
+    /// compiled, so they get an empty span.
     pub fn type_(type_: Type) -> Self {
         SymbolEntry {
             unique_name: String::new(),
             type_,
             constant: true,
             external: false,
+            syscall: None,
             stack_offset: 0,
+            span: common::Span::empty(),
         }
     }
 
+    /// Like `type_`, runtime externs have no location in the program being compiled.
     pub fn external(unique_name: &str, type_: Type) -> Self {
         SymbolEntry {
             unique_name: String::from(unique_name),
             type_,
             constant: true,
             external: true,
+            syscall: None,
             stack_offset: 0,
+            span: common::Span::empty(),
+        }
+    }
+
+    /// A symbol that compiles to a raw Linux syscall rather than a call to a C function: `number`
+    /// is the x86-64 syscall number to invoke. Also has no location of its own.
+    pub fn syscall(unique_name: &str, number: i64, type_: Type) -> Self {
+        SymbolEntry {
+            unique_name: String::from(unique_name),
+            type_,
+            constant: true,
+            external: false,
+            syscall: Some(number),
+            stack_offset: 0,
+            span: common::Span::empty(),
         }
     }
 }
@@ -310,6 +440,14 @@ impl Type {
             (_, Any) => true,
             (Boolean, Boolean) => true,
             (I64, I64) => true,
+            (I8, I8) => true,
+            (I16, I16) => true,
+            (I32, I32) => true,
+            (U8, U8) => true,
+            (U16, U16) => true,
+            (U32, U32) => true,
+            (U64, U64) => true,
+            (F64, F64) => true,
             (String, String) => true,
             (File, File) => true,
             (Tuple(ts1), Tuple(ts2)) => {
@@ -324,6 +462,8 @@ impl Type {
                 true
             }
             (List(t1), List(t2)) => t1.matches(t2),
+            (Enum(n1), Enum(n2)) => n1 == n2,
+            (Record { name: n1, .. }, Record { name: n2, .. }) => n1 == n2,
             (
                 Type::Map {
                     key: key1,
@@ -338,6 +478,13 @@ impl Type {
         }
     }
 
+    /// True for `float` and every integer type (`i64` and the sized/unsigned variants) -- the
+    /// types arithmetic and comparison operators are polymorphic over.
+    pub fn is_numeric(&self) -> bool {
+        use Type::*;
+        matches!(self, I64 | I8 | I16 | I32 | U8 | U16 | U32 | U64 | F64)
+    }
+
     /// Returns the number of bytes required to store a value of the type.
     pub fn storage_size(&self) -> i32 {
         use Type::*;
@@ -366,7 +513,8 @@ impl fmt::Display for Declaration {
             Function(declaration) => write!(f, "{}", declaration),
             Const(declaration) => write!(f, "{}", declaration),
             Record(declaration) => write!(f, "{}", declaration),
-            Error => write!(f, "error"),
+            Enum(declaration) => write!(f, "{}", declaration),
+            Error(_) => write!(f, "error"),
         }
     }
 }
@@ -408,6 +556,19 @@ impl fmt::Display for RecordDeclaration {
     }
 }
 
+impl fmt::Display for EnumDeclaration {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "(enum-decl {}", self.name)?;
+        for variant in &self.variants {
+            match &variant.payload {
+                Some(payload) => write!(f, " ({} {})", variant.name, payload)?,
+                None => write!(f, " ({})", variant.name)?,
+            }
+        }
+        write!(f, ")")
+    }
+}
+
 impl fmt::Display for Statement {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         use Statement::*;
@@ -420,7 +581,7 @@ impl fmt::Display for Statement {
             Return(stmt) => write!(f, "{}", stmt),
             Assert(stmt) => write!(f, "{}", stmt),
             Expression(stmt) => write!(f, "{}", stmt),
-            Error => write!(f, "error"),
+            Error(_) => write!(f, "error"),
         }
     }
 }
@@ -499,6 +660,7 @@ impl fmt::Display for ExpressionKind {
         match self {
             Boolean(e) => write!(f, "{}", e),
             Integer(e) => write!(f, "{}", e),
+            Float(e) => write!(f, "{:?}", e),
             String(e) => write!(f, "{:?}", e),
             Symbol(e) => write!(f, "{}", e),
             Binary(e) => write!(f, "{}", e),
@@ -512,6 +674,7 @@ impl fmt::Display for ExpressionKind {
             Tuple(e) => write!(f, "{}", e),
             Map(e) => write!(f, "{}", e),
             Record(e) => write!(f, "{}", e),
+            ListComprehension(e) => write!(f, "{}", e),
             Error => write!(f, "error"),
         }
     }
@@ -606,11 +769,29 @@ impl fmt::Display for RecordLiteral {
     }
 }
 
+impl fmt::Display for ListComprehension {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "(listcomp {} {} {}", self.value, self.symbol, self.iterator)?;
+        if let Some(condition) = &self.condition {
+            write!(f, " {}", condition)?;
+        }
+        write!(f, ")")
+    }
+}
+
 impl fmt::Display for Type {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         use Type::*;
         match self {
             I64 => write!(f, "i64"),
+            I8 => write!(f, "i8"),
+            I16 => write!(f, "i16"),
+            I32 => write!(f, "i32"),
+            U8 => write!(f, "u8"),
+            U16 => write!(f, "u16"),
+            U32 => write!(f, "u32"),
+            U64 => write!(f, "u64"),
+            F64 => write!(f, "float"),
             Boolean => write!(f, "bool"),
             String => write!(f, "string"),
             File => write!(f, "file"),
@@ -642,7 +823,9 @@ impl fmt::Display for Type {
                 }
                 write!(f, "{}>", return_type)
             }
-            Record(name) => write!(f, "{}", name),
+            Record { name, .. } => write!(f, "{}", name),
+            Enum(name) => write!(f, "{}", name),
+            Variable(n) => write!(f, "?{}", n),
             Error => write!(f, "error"),
         }
     }
@@ -661,3 +844,83 @@ fn format_block(f: &mut fmt::Formatter<'_>, block: &[Statement]) -> fmt::Result
     }
     write!(f, ")")
 }
+
+/// Bumped whenever `Program`'s serialized shape changes, so a cache written by an older compiler
+/// is rejected outright instead of being misread by a newer one.
+const AST_CACHE_FORMAT_VERSION: u32 = 1;
+
+#[derive(Serialize)]
+struct CachedProgramRef<'a> {
+    format_version: u32,
+    program: &'a Program,
+}
+
+#[derive(Deserialize)]
+struct CachedProgram {
+    format_version: u32,
+    program: Program,
+}
+
+/// The ways loading a cached AST from disk can fail.
+#[derive(Debug)]
+pub enum CacheError {
+    Io(io::Error),
+    Json(serde_json::Error),
+    VersionMismatch { expected: u32, found: u32 },
+}
+
+impl fmt::Display for CacheError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CacheError::Io(e) => write!(f, "could not read cached AST: {}", e),
+            CacheError::Json(e) => write!(f, "could not parse cached AST: {}", e),
+            CacheError::VersionMismatch { expected, found } => write!(
+                f,
+                "cached AST format version {} is incompatible with this compiler's version {}",
+                found, expected
+            ),
+        }
+    }
+}
+
+impl From<io::Error> for CacheError {
+    fn from(e: io::Error) -> Self {
+        CacheError::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for CacheError {
+    fn from(e: serde_json::Error) -> Self {
+        CacheError::Json(e)
+    }
+}
+
+impl Program {
+    /// Serializes the analyzed AST as JSON, tagged with the current cache format version, so that
+    /// an unchanged source file's tree can be reloaded on a later compiler invocation instead of
+    /// being re-analyzed, or consumed directly by external tooling (a formatter, a linter, an LSP
+    /// server).
+    pub fn to_writer<W: io::Write>(&self, writer: W) -> Result<(), CacheError> {
+        serde_json::to_writer(
+            writer,
+            &CachedProgramRef {
+                format_version: AST_CACHE_FORMAT_VERSION,
+                program: self,
+            },
+        )?;
+        Ok(())
+    }
+
+    /// Reads back a tree written by `to_writer`. Rejects a cache written by a different format
+    /// version rather than risk misinterpreting it as the current one.
+    pub fn from_reader<R: io::Read>(reader: R) -> Result<Self, CacheError> {
+        let cached: CachedProgram = serde_json::from_reader(reader)?;
+        if cached.format_version != AST_CACHE_FORMAT_VERSION {
+            return Err(CacheError::VersionMismatch {
+                expected: AST_CACHE_FORMAT_VERSION,
+                found: cached.format_version,
+            });
+        }
+        Ok(cached.program)
+    }
+}
@@ -0,0 +1,160 @@
+// Copyright 2022 The Venice Authors. All rights reserved.
+// Use of this source code is governed by an MIT-style license that can be
+// found in the LICENSE file.
+//
+// A target-independent interface for lowering a VIL program into a concrete instruction set.
+// `x86.rs` is one implementation of `Backend`; `riscv.rs` is another. Neither this module nor
+// `generate` below knows anything about a specific ISA -- they only drive each `Backend`
+// implementation through a VIL program in the order every target needs to see it, so lowering
+// order (prologue, parameter setup, blocks in sequence, epilogue) can't drift between targets.
+
+use super::vil;
+
+/// What a backend has to provide to turn VIL into its own instructions. Methods are split by VIL
+/// instruction kind (`lower_binary`, `lower_call`, etc.) rather than taking a whole
+/// `vil::InstructionKind`, so each backend's `match` is on the operation it actually needs to
+/// lower, not on VIL's representation of it.
+pub trait Backend {
+    /// Called once at the start of each function, before any other method: gives the backend a
+    /// chance to run its own register allocation over the whole declaration (which may need to
+    /// see every instruction to compute live ranges) and to open the function's entry block.
+    fn start_function(&mut self, declaration: &vil::FunctionDeclaration);
+
+    /// Starts a new labeled block of instructions within the current function.
+    fn start_block(&mut self, name: &str);
+
+    /// Emits the function-entry sequence: saving the caller's frame and reserving stack space for
+    /// locals and spills.
+    fn prologue(&mut self);
+
+    /// Emits the function-exit sequence: the mirror image of `prologue`.
+    fn epilogue(&mut self);
+
+    /// Moves the backend's i'th argument-passing register (0-indexed) to the stack slot at
+    /// `stack_offset`, once at the top of the function.
+    fn lower_param(&mut self, i: u8, stack_offset: i32);
+
+    fn lower_set(&mut self, r: vil::Register, imm: &vil::Immediate);
+    fn lower_move(&mut self, r1: vil::Register, r2: vil::Register);
+    fn lower_binary(
+        &mut self,
+        op: vil::BinaryOp,
+        r1: vil::Register,
+        r2: vil::Register,
+        r3: vil::Register,
+    );
+    fn lower_unary(&mut self, op: vil::UnaryOp, r1: vil::Register, r2: vil::Register);
+    fn lower_load(&mut self, r: vil::Register, offset: vil::MemoryOffset);
+    fn lower_store(&mut self, r: vil::Register, offset: vil::MemoryOffset);
+    fn lower_cmp(&mut self, r1: vil::Register, r2: vil::Register);
+    fn lower_fcmp(&mut self, r1: vil::Register, r2: vil::Register);
+    fn lower_cmp_ordering(&mut self, r1: vil::Register, r2: vil::Register, r3: vil::Register);
+    fn lower_fcmp_ordering(&mut self, r1: vil::Register, r2: vil::Register, r3: vil::Register);
+    fn lower_call(
+        &mut self,
+        destination: vil::Register,
+        label: &vil::Label,
+        offsets: &[vil::MemoryOffset],
+        variadic: bool,
+    );
+    fn lower_jump(&mut self, label: &vil::Label);
+    fn lower_jump_if(
+        &mut self,
+        condition: vil::JumpCondition,
+        true_label: &vil::Label,
+        false_label: &vil::Label,
+    );
+    fn lower_jump_ordering(
+        &mut self,
+        r: vil::Register,
+        less_label: &vil::Label,
+        equal_label: &vil::Label,
+        greater_label: &vil::Label,
+    );
+    fn lower_syscall(
+        &mut self,
+        destination: vil::Register,
+        number: i64,
+        offsets: &[vil::MemoryOffset],
+    );
+
+    /// The physical register (in this backend's own numbering) that its calling convention uses
+    /// to pass the i'th argument (0-indexed).
+    fn param_register(&self, i: u8) -> u8;
+}
+
+/// What codegen.rs's target-agnostic register allocator needs to know about the backend a VIL
+/// program is ultimately headed for, so that pass isn't hard-coded to x86's own register count.
+/// Each backend module that wants codegen's allocator to size itself correctly for that target
+/// exposes one of these (see `x86::X86Config`, `riscv::RiscvConfig`, `aarch64::Aarch64Config`).
+pub trait BackendConfig {
+    /// How many of VIL's virtual registers (see vil.rs's `Register`) the allocator may use for
+    /// this target before it has to start spilling. This is a cap on the *shared* VIL register
+    /// space, not a count of the target's real physical registers -- `Register::ret()` and
+    /// `Register::scratch2()` sit inside it at their fixed indices (see `is_reserved` in
+    /// codegen.rs), and any indices above it are left for the backend's own reserved
+    /// stack-pointer/frame-pointer registers, which the allocator must never be handed.
+    fn register_count(&self) -> u8;
+}
+
+/// Drives `backend` through every function in `program`, dispatching each instruction by kind.
+pub fn generate<B: Backend>(backend: &mut B, program: &vil::Program) {
+    for declaration in &program.declarations {
+        generate_declaration(backend, declaration);
+    }
+}
+
+fn generate_declaration<B: Backend>(backend: &mut B, declaration: &vil::FunctionDeclaration) {
+    backend.start_function(declaration);
+    backend.prologue();
+
+    for (i, parameter) in declaration.parameters.iter().enumerate() {
+        backend.lower_param(u8::try_from(i).unwrap(), parameter.stack_offset);
+    }
+
+    for block in &declaration.blocks {
+        backend.start_block(&block.name);
+        for instruction in &block.instructions {
+            lower_instruction(backend, &instruction.kind);
+        }
+    }
+
+    backend.epilogue();
+}
+
+fn lower_instruction<B: Backend>(backend: &mut B, kind: &vil::InstructionKind) {
+    use vil::InstructionKind::*;
+    match kind {
+        Set(r, imm) => backend.lower_set(*r, imm),
+        Move(r1, r2) => backend.lower_move(*r1, *r2),
+        Binary(op, r1, r2, r3) => backend.lower_binary(*op, *r1, *r2, *r3),
+        Unary(op, r1, r2) => backend.lower_unary(*op, *r1, *r2),
+        Load(r, offset) => backend.lower_load(*r, *offset),
+        Store(r, offset) => backend.lower_store(*r, *offset),
+        Cmp(r1, r2) => backend.lower_cmp(*r1, *r2),
+        FCmp(r1, r2) => backend.lower_fcmp(*r1, *r2),
+        CmpOrdering(r1, r2, r3) => backend.lower_cmp_ordering(*r1, *r2, *r3),
+        FCmpOrdering(r1, r2, r3) => backend.lower_fcmp_ordering(*r1, *r2, *r3),
+        Call {
+            destination,
+            label,
+            offsets,
+            variadic,
+        } => backend.lower_call(*destination, label, offsets, *variadic),
+        Jump(label) => backend.lower_jump(label),
+        JumpIf(condition, true_label, false_label) => {
+            backend.lower_jump_if(*condition, true_label, false_label)
+        }
+        JumpOrdering(r, less_label, equal_label, greater_label) => {
+            backend.lower_jump_ordering(*r, less_label, equal_label, greater_label)
+        }
+        Syscall {
+            destination,
+            number,
+            offsets,
+        } => backend.lower_syscall(*destination, *number, offsets),
+        Phi(..) => panic!(
+            "internal error: phi nodes must be lowered by ssa::out_of_ssa before reaching a backend"
+        ),
+    }
+}
@@ -0,0 +1,833 @@
+// Copyright 2022 The Venice Authors. All rights reserved.
+// Use of this source code is governed by an MIT-style license that can be
+// found in the LICENSE file.
+//
+// Another implementation of the `backend::Backend` trait, alongside `x86.rs`, `riscv.rs`, and
+// `aarch64.rs` -- except where those lower VIL into text that still has to be assembled (or isn't assembled at
+// all yet), this one lowers VIL into a flat array of `Instruction`s that `run` can execute directly,
+// with no host assembler, linker, or even operating system required. That makes it both a portable
+// `venice run` mode and a convenient oracle to differentially test the native backends against.
+//
+// Unlike x86.rs, this backend doesn't need to assign VIL's virtual registers onto a small, fixed
+// set of physical ones: `codegen.rs`'s own allocator has already bounded every register index to a
+// small range before a backend ever sees the program, so the interpreter just gives each call frame
+// an array big enough to hold any register index directly.
+//
+// `Jump`/`JumpIf`/`Call` targets start out as the label names VIL uses (a block name, or for `Call`,
+// a function name); `generate` resolves every one of them to a concrete index into `instructions`
+// once the whole program has been emitted, the same "emit now, patch later" two-pass shape x86.rs
+// uses for its own jump-target fixups, just operating on instruction indices instead of byte
+// offsets.
+
+use super::backend::Backend;
+use super::vil;
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::io::Write;
+
+/// A VIL program lowered into a flat, directly-executable instruction stream. Build one with
+/// `generate`, then hand it to `run`.
+pub struct Program {
+    instructions: Vec<Instruction>,
+    // Keyed by the instruction index a function starts at (the same index `Call` targets resolve
+    // to), so `run` knows how big a frame to allocate for a call without re-walking `instructions`.
+    frame_sizes: HashMap<usize, i32>,
+    // Every string constant from `vil::Program::strings`, laid out back to back (each one
+    // nul-terminated, like a C string literal) so that a register holding the address a `Set`
+    // resolved for one can be handed straight to `write`.
+    static_memory: Vec<u8>,
+    string_addresses: HashMap<String, i64>,
+    entry: Option<usize>,
+}
+
+#[derive(Clone, Debug)]
+enum Instruction {
+    Binary(vil::BinaryOp, vil::Register, vil::Register, vil::Register),
+    Unary(vil::UnaryOp, vil::Register, vil::Register),
+    Cmp(vil::Register, vil::Register),
+    FCmp(vil::Register, vil::Register),
+    CmpOrdering(vil::Register, vil::Register, vil::Register),
+    FCmpOrdering(vil::Register, vil::Register, vil::Register),
+    Jump(usize),
+    JumpIf(vil::JumpCondition, usize, usize),
+    JumpOrdering(vil::Register, usize, usize, usize),
+    Load(vil::Register, vil::MemoryOffset),
+    Move(vil::Register, vil::Register),
+    Set(vil::Register, vil::Immediate),
+    Store(vil::Register, vil::MemoryOffset),
+    // Copies the i'th argument a preceding `Call` passed in into this frame's `stack_offset`.
+    // Emitted once per parameter at the top of a function, mirroring `x86::lower_param`.
+    LoadParam(u8, vil::MemoryOffset),
+    Call {
+        destination: vil::Register,
+        target: CallTarget,
+        offsets: Vec<vil::MemoryOffset>,
+    },
+    Syscall {
+        destination: vil::Register,
+        number: i64,
+        offsets: Vec<vil::MemoryOffset>,
+    },
+    // The mirror image of a function's `LoadParam`s: pops the current frame, and (unless it was the
+    // outermost call) resumes the caller where it left off.
+    Return,
+}
+
+#[derive(Clone, Debug)]
+enum CallTarget {
+    // A Venice function, resolved to the instruction index its first block starts at.
+    Function(usize),
+    // A host builtin, dispatched by the original extern name (see `call_extern`).
+    Extern(String),
+}
+
+/// Lowers `program` into a `bytecode::Program`. Every VIL instruction kind this module doesn't
+/// explicitly mention (`Phi`) never reaches `Generator`: `backend::lower_instruction` already turns
+/// those into an internal error before calling into any `Backend` implementation.
+pub fn generate(program: &vil::Program) -> Program {
+    let mut generator = Generator::new(program.externs.clone());
+    super::backend::generate(&mut generator, program);
+    generator.finish(&program.strings)
+}
+
+/// Runs `program`, starting at its `venice_main` entry point, and returns the value `venice_main`
+/// returned -- or, if the program invoked the raw `exit` syscall first, the status code it exited
+/// with.
+pub fn run(program: &Program) -> i64 {
+    let entry = program
+        .entry
+        .expect("internal error: bytecode program has no venice_main entry point to run");
+    execute(program, entry)
+}
+
+struct ActiveCall {
+    registers: [i64; 256],
+    memory: Vec<u8>,
+    return_pc: usize,
+    destination: vil::Register,
+}
+
+impl ActiveCall {
+    fn new(frame_size: i32, return_pc: usize, destination: vil::Register) -> Self {
+        ActiveCall {
+            registers: [0; 256],
+            memory: vec![0; usize::try_from(frame_size).unwrap()],
+            return_pc,
+            destination,
+        }
+    }
+
+    fn register(&self, r: vil::Register) -> i64 {
+        self.registers[r.index() as usize]
+    }
+
+    fn set_register(&mut self, r: vil::Register, value: i64) {
+        self.registers[r.index() as usize] = value;
+    }
+
+    // VIL's `MemoryOffset`s run from `-8` (the first local claimed) down to `-stack_frame_size`
+    // (the last), i.e. they count down from the top of the frame -- see `codegen.rs`'s
+    // `claim_stack_offset`. So the byte index within `memory` is just the offset added to the
+    // frame's own size.
+    fn memory_index(&self, offset: vil::MemoryOffset) -> usize {
+        usize::try_from(i64::from(i32::try_from(self.memory.len()).unwrap()) + i64::from(offset))
+            .expect("internal error: memory offset out of bounds for this frame")
+    }
+
+    fn load(&self, offset: vil::MemoryOffset) -> i64 {
+        let i = self.memory_index(offset);
+        i64::from_le_bytes(self.memory[i..i + 8].try_into().unwrap())
+    }
+
+    fn store(&mut self, offset: vil::MemoryOffset, value: i64) {
+        let i = self.memory_index(offset);
+        self.memory[i..i + 8].copy_from_slice(&value.to_le_bytes());
+    }
+}
+
+fn execute(program: &Program, entry: usize) -> i64 {
+    let frame_size = *program.frame_sizes.get(&entry).unwrap_or(&0);
+    let mut calls = vec![ActiveCall::new(frame_size, 0, vil::Register::ret())];
+    let mut pending_args: Vec<i64> = Vec::new();
+    // `Cmp`/`FCmp` have nothing to set flags on in this interpreter, so (just like RISC-V's
+    // `last_cmp`, see riscv.rs) they just remember their operand values for the `JumpIf` VIL always
+    // emits immediately afterward.
+    let mut last_cmp: Option<(i64, i64, bool)> = None;
+    let mut pc = entry;
+
+    loop {
+        let frame = calls.last_mut().unwrap();
+        match &program.instructions[pc] {
+            Instruction::Binary(op, r1, r2, r3) => {
+                let (a, b) = (frame.register(*r2), frame.register(*r3));
+                let result = match op {
+                    vil::BinaryOp::Add => a.wrapping_add(b),
+                    vil::BinaryOp::Sub => a.wrapping_sub(b),
+                    vil::BinaryOp::Mul => a.wrapping_mul(b),
+                    vil::BinaryOp::Div => a / b,
+                    vil::BinaryOp::FAdd => {
+                        (f64::from_bits(a as u64) + f64::from_bits(b as u64)).to_bits() as i64
+                    }
+                    vil::BinaryOp::FSub => {
+                        (f64::from_bits(a as u64) - f64::from_bits(b as u64)).to_bits() as i64
+                    }
+                    vil::BinaryOp::FMul => {
+                        (f64::from_bits(a as u64) * f64::from_bits(b as u64)).to_bits() as i64
+                    }
+                    vil::BinaryOp::FDiv => {
+                        (f64::from_bits(a as u64) / f64::from_bits(b as u64)).to_bits() as i64
+                    }
+                };
+                frame.set_register(*r1, result);
+                pc += 1;
+            }
+            Instruction::Unary(op, r1, r2) => {
+                let a = frame.register(*r2);
+                let result = match op {
+                    vil::UnaryOp::Negate => a.wrapping_neg(),
+                    vil::UnaryOp::FNegate => (-f64::from_bits(a as u64)).to_bits() as i64,
+                    vil::UnaryOp::LogicalNot => i64::from(a == 0),
+                };
+                frame.set_register(*r1, result);
+                pc += 1;
+            }
+            Instruction::Cmp(r1, r2) => {
+                last_cmp = Some((frame.register(*r1), frame.register(*r2), false));
+                pc += 1;
+            }
+            Instruction::FCmp(r1, r2) => {
+                last_cmp = Some((frame.register(*r1), frame.register(*r2), true));
+                pc += 1;
+            }
+            Instruction::CmpOrdering(r1, r2, r3) => {
+                let (a, b) = (frame.register(*r2), frame.register(*r3));
+                frame.set_register(*r1, ordering(a, b));
+                pc += 1;
+            }
+            Instruction::FCmpOrdering(r1, r2, r3) => {
+                let (a, b) = (
+                    f64::from_bits(frame.register(*r2) as u64),
+                    f64::from_bits(frame.register(*r3) as u64),
+                );
+                frame.set_register(*r1, ordering(a, b));
+                pc += 1;
+            }
+            Instruction::Jump(target) => pc = *target,
+            Instruction::JumpIf(condition, true_target, false_target) => {
+                let (a, b, is_float) = last_cmp
+                    .expect("internal error: JumpIf with no preceding Cmp/FCmp to read flags from");
+                let taken = if is_float {
+                    compare(
+                        f64::from_bits(a as u64),
+                        f64::from_bits(b as u64),
+                        *condition,
+                    )
+                } else {
+                    compare(a, b, *condition)
+                };
+                pc = if taken { *true_target } else { *false_target };
+            }
+            Instruction::JumpOrdering(r, less_target, equal_target, greater_target) => {
+                pc = match frame.register(*r) {
+                    x if x < 0 => *less_target,
+                    0 => *equal_target,
+                    _ => *greater_target,
+                };
+            }
+            Instruction::Load(r, offset) => {
+                let value = frame.load(*offset);
+                frame.set_register(*r, value);
+                pc += 1;
+            }
+            Instruction::Move(r1, r2) => {
+                frame.set_register(*r1, frame.register(*r2));
+                pc += 1;
+            }
+            Instruction::Set(r, imm) => {
+                let value = match imm {
+                    vil::Immediate::Integer(x) => *x,
+                    vil::Immediate::Float(x) => x.to_bits() as i64,
+                    vil::Immediate::Label(name) => *program.string_addresses.get(name).unwrap_or_else(|| {
+                        panic!(
+                            "internal error: `{}` is not a known string constant (function addresses can't be taken as values)",
+                            name
+                        )
+                    }),
+                };
+                frame.set_register(*r, value);
+                pc += 1;
+            }
+            Instruction::Store(r, offset) => {
+                let value = frame.register(*r);
+                frame.store(*offset, value);
+                pc += 1;
+            }
+            Instruction::LoadParam(i, offset) => {
+                let value = pending_args[*i as usize];
+                frame.store(*offset, value);
+                pc += 1;
+            }
+            Instruction::Call {
+                destination,
+                target,
+                offsets,
+            } => {
+                let args: Vec<i64> = offsets.iter().map(|offset| frame.load(*offset)).collect();
+                match target {
+                    CallTarget::Extern(name) => {
+                        let result = call_extern(program, name, &args);
+                        frame.set_register(*destination, result);
+                        pc += 1;
+                    }
+                    CallTarget::Function(callee_entry) => {
+                        pending_args = args;
+                        let callee_frame_size =
+                            *program.frame_sizes.get(callee_entry).unwrap_or(&0);
+                        calls.push(ActiveCall::new(callee_frame_size, pc + 1, *destination));
+                        pc = *callee_entry;
+                    }
+                }
+            }
+            Instruction::Syscall {
+                destination,
+                number,
+                offsets,
+            } => {
+                let args: Vec<i64> = offsets.iter().map(|offset| frame.load(*offset)).collect();
+                match call_syscall(program, *number, &args) {
+                    SyscallOutcome::Result(value) => {
+                        frame.set_register(*destination, value);
+                        pc += 1;
+                    }
+                    SyscallOutcome::Exit(code) => return code,
+                }
+            }
+            Instruction::Return => {
+                let finished = calls.pop().unwrap();
+                let result = finished.register(vil::Register::ret());
+                if calls.is_empty() {
+                    return result;
+                }
+                let return_pc = finished.return_pc;
+                let destination = finished.destination;
+                calls.last_mut().unwrap().set_register(destination, result);
+                pc = return_pc;
+            }
+        }
+    }
+}
+
+fn compare<T: PartialOrd>(a: T, b: T, condition: vil::JumpCondition) -> bool {
+    match condition {
+        vil::JumpCondition::Eq => a == b,
+        vil::JumpCondition::Neq => a != b,
+        vil::JumpCondition::Gt => a > b,
+        vil::JumpCondition::Gte => a >= b,
+        vil::JumpCondition::Lt => a < b,
+        vil::JumpCondition::Lte => a <= b,
+    }
+}
+
+/// The -1/0/1 three-way result a `CmpOrdering`/`FCmpOrdering` computes, for `JumpOrdering` to
+/// branch on afterward.
+fn ordering<T: PartialOrd>(a: T, b: T) -> i64 {
+    if a < b {
+        -1
+    } else if a > b {
+        1
+    } else {
+        0
+    }
+}
+
+/// Known externs this interpreter can run without the native `libvenice` runtime behind them.
+/// `venice_printint` is the only one whose whole ABI is a plain `i64` in, `i64` out; the rest
+/// (`venice_println`, `venice_string_new`, the list builtins, ...) hand around Venice's boxed string
+/// and list representations, which only the native runtime library knows how to lay out, so this
+/// interpreter can't stand in for them yet.
+fn call_extern(_program: &Program, name: &str, args: &[i64]) -> i64 {
+    match name {
+        "venice_printint" => {
+            println!("{}", args[0]);
+            0
+        }
+        _ => panic!(
+            "internal error: extern `{}` depends on the native Venice runtime and is not supported \
+             by the bytecode interpreter",
+            name
+        ),
+    }
+}
+
+enum SyscallOutcome {
+    Result(i64),
+    Exit(i64),
+}
+
+/// The raw Linux syscalls `analyzer.rs`'s `SymbolTable::builtin_globals` exposes (see its `read`/
+/// `write`/`open`/`close`/`mmap`/`exit` entries), emulated well enough to run a program that sticks
+/// to arithmetic, control flow, and `write`/`exit` -- exactly the no-libc subset the comment above
+/// those entries describes. The rest need real file descriptors or virtual memory this interpreter doesn't have.
+fn call_syscall(program: &Program, number: i64, args: &[i64]) -> SyscallOutcome {
+    match number {
+        1 => {
+            // write(fd, buf, count)
+            let (fd, buf, count) = (args[0], args[1], args[2]);
+            let start = usize::try_from(buf).expect("internal error: negative buffer address");
+            let end = start + usize::try_from(count).expect("internal error: negative write count");
+            let bytes = &program.static_memory[start..end];
+            let written = match fd {
+                1 => std::io::stdout().write(bytes).unwrap(),
+                2 => std::io::stderr().write(bytes).unwrap(),
+                _ => panic!(
+                    "internal error: write to file descriptor {} is not supported by the bytecode interpreter",
+                    fd
+                ),
+            };
+            SyscallOutcome::Result(i64::try_from(written).unwrap())
+        }
+        60 => SyscallOutcome::Exit(args[0]), // exit(code)
+        _ => panic!(
+            "internal error: syscall {} is not yet supported by the bytecode interpreter",
+            number
+        ),
+    }
+}
+
+enum Fixup {
+    Jump(usize, String),
+    JumpIfTrue(usize, String),
+    JumpIfFalse(usize, String),
+    JumpOrderingLess(usize, String),
+    JumpOrderingEqual(usize, String),
+    JumpOrderingGreater(usize, String),
+    Call(usize, String),
+}
+
+struct Generator {
+    instructions: Vec<Instruction>,
+    labels: HashMap<String, usize>,
+    frame_sizes: HashMap<usize, i32>,
+    fixups: Vec<Fixup>,
+    externs: Vec<String>,
+    current_function_entry: usize,
+}
+
+impl Generator {
+    fn new(externs: Vec<String>) -> Self {
+        Generator {
+            instructions: Vec::new(),
+            labels: HashMap::new(),
+            frame_sizes: HashMap::new(),
+            fixups: Vec::new(),
+            externs,
+            current_function_entry: 0,
+        }
+    }
+
+    fn push(&mut self, instruction: Instruction) -> usize {
+        let index = self.instructions.len();
+        self.instructions.push(instruction);
+        index
+    }
+
+    fn label_here(&mut self, name: &str) {
+        self.labels
+            .insert(String::from(name), self.instructions.len());
+    }
+
+    fn finish(mut self, strings: &BTreeMap<String, String>) -> Program {
+        for fixup in &self.fixups {
+            match fixup {
+                Fixup::Jump(i, label) => {
+                    let target = self.resolve_label(label);
+                    self.instructions[*i] = Instruction::Jump(target);
+                }
+                Fixup::JumpIfTrue(i, label) => {
+                    let target = self.resolve_label(label);
+                    if let Instruction::JumpIf(condition, _, false_target) = self.instructions[*i] {
+                        self.instructions[*i] =
+                            Instruction::JumpIf(condition, target, false_target);
+                    }
+                }
+                Fixup::JumpIfFalse(i, label) => {
+                    let target = self.resolve_label(label);
+                    if let Instruction::JumpIf(condition, true_target, _) = self.instructions[*i] {
+                        self.instructions[*i] = Instruction::JumpIf(condition, true_target, target);
+                    }
+                }
+                Fixup::JumpOrderingLess(i, label) => {
+                    let target = self.resolve_label(label);
+                    if let Instruction::JumpOrdering(r, _, equal_target, greater_target) =
+                        self.instructions[*i]
+                    {
+                        self.instructions[*i] =
+                            Instruction::JumpOrdering(r, target, equal_target, greater_target);
+                    }
+                }
+                Fixup::JumpOrderingEqual(i, label) => {
+                    let target = self.resolve_label(label);
+                    if let Instruction::JumpOrdering(r, less_target, _, greater_target) =
+                        self.instructions[*i]
+                    {
+                        self.instructions[*i] =
+                            Instruction::JumpOrdering(r, less_target, target, greater_target);
+                    }
+                }
+                Fixup::JumpOrderingGreater(i, label) => {
+                    let target = self.resolve_label(label);
+                    if let Instruction::JumpOrdering(r, less_target, equal_target, _) =
+                        self.instructions[*i]
+                    {
+                        self.instructions[*i] =
+                            Instruction::JumpOrdering(r, less_target, equal_target, target);
+                    }
+                }
+                Fixup::Call(i, label) => {
+                    let target = self.resolve_label(label);
+                    if let Instruction::Call {
+                        destination,
+                        offsets,
+                        ..
+                    } = &self.instructions[*i]
+                    {
+                        self.instructions[*i] = Instruction::Call {
+                            destination: *destination,
+                            target: CallTarget::Function(target),
+                            offsets: offsets.clone(),
+                        };
+                    }
+                }
+            }
+        }
+
+        let mut static_memory = Vec::new();
+        let mut string_addresses = HashMap::new();
+        for (name, value) in strings {
+            string_addresses.insert(name.clone(), i64::try_from(static_memory.len()).unwrap());
+            static_memory.extend_from_slice(value.as_bytes());
+            static_memory.push(0);
+        }
+
+        Program {
+            instructions: self.instructions,
+            frame_sizes: self.frame_sizes,
+            static_memory,
+            string_addresses,
+            entry: self.labels.get("venice_main").copied(),
+        }
+    }
+
+    fn resolve_label(&self, label: &str) -> usize {
+        *self
+            .labels
+            .get(label)
+            .unwrap_or_else(|| panic!("internal error: no block or function named `{}`", label))
+    }
+}
+
+impl Backend for Generator {
+    fn start_function(&mut self, declaration: &vil::FunctionDeclaration) {
+        self.current_function_entry = self.instructions.len();
+        self.frame_sizes
+            .insert(self.current_function_entry, declaration.stack_frame_size);
+        self.label_here(&declaration.name);
+    }
+
+    fn start_block(&mut self, name: &str) {
+        self.label_here(name);
+    }
+
+    fn prologue(&mut self) {
+        // The interpreter allocates a frame's registers and stack space itself when it pushes an
+        // `ActiveCall`, so there's no separate entry sequence to emit.
+    }
+
+    fn epilogue(&mut self) {
+        self.push(Instruction::Return);
+    }
+
+    fn lower_param(&mut self, i: u8, stack_offset: i32) {
+        self.push(Instruction::LoadParam(i, stack_offset));
+    }
+
+    fn lower_set(&mut self, r: vil::Register, imm: &vil::Immediate) {
+        self.push(Instruction::Set(r, imm.clone()));
+    }
+
+    fn lower_move(&mut self, r1: vil::Register, r2: vil::Register) {
+        self.push(Instruction::Move(r1, r2));
+    }
+
+    fn lower_binary(
+        &mut self,
+        op: vil::BinaryOp,
+        r1: vil::Register,
+        r2: vil::Register,
+        r3: vil::Register,
+    ) {
+        self.push(Instruction::Binary(op, r1, r2, r3));
+    }
+
+    fn lower_unary(&mut self, op: vil::UnaryOp, r1: vil::Register, r2: vil::Register) {
+        self.push(Instruction::Unary(op, r1, r2));
+    }
+
+    fn lower_load(&mut self, r: vil::Register, offset: vil::MemoryOffset) {
+        self.push(Instruction::Load(r, offset));
+    }
+
+    fn lower_store(&mut self, r: vil::Register, offset: vil::MemoryOffset) {
+        self.push(Instruction::Store(r, offset));
+    }
+
+    fn lower_cmp(&mut self, r1: vil::Register, r2: vil::Register) {
+        self.push(Instruction::Cmp(r1, r2));
+    }
+
+    fn lower_fcmp(&mut self, r1: vil::Register, r2: vil::Register) {
+        self.push(Instruction::FCmp(r1, r2));
+    }
+
+    fn lower_cmp_ordering(&mut self, r1: vil::Register, r2: vil::Register, r3: vil::Register) {
+        self.push(Instruction::CmpOrdering(r1, r2, r3));
+    }
+
+    fn lower_fcmp_ordering(&mut self, r1: vil::Register, r2: vil::Register, r3: vil::Register) {
+        self.push(Instruction::FCmpOrdering(r1, r2, r3));
+    }
+
+    fn lower_call(
+        &mut self,
+        destination: vil::Register,
+        label: &vil::Label,
+        offsets: &[vil::MemoryOffset],
+        _variadic: bool,
+    ) {
+        let target = if self.externs.contains(&label.0) {
+            CallTarget::Extern(label.0.clone())
+        } else {
+            CallTarget::Function(0)
+        };
+        let index = self.push(Instruction::Call {
+            destination,
+            target,
+            offsets: offsets.to_vec(),
+        });
+        if !self.externs.contains(&label.0) {
+            self.fixups.push(Fixup::Call(index, label.0.clone()));
+        }
+    }
+
+    fn lower_jump(&mut self, label: &vil::Label) {
+        let index = self.push(Instruction::Jump(0));
+        self.fixups.push(Fixup::Jump(index, label.0.clone()));
+    }
+
+    fn lower_jump_if(
+        &mut self,
+        condition: vil::JumpCondition,
+        true_label: &vil::Label,
+        false_label: &vil::Label,
+    ) {
+        let index = self.push(Instruction::JumpIf(condition, 0, 0));
+        self.fixups
+            .push(Fixup::JumpIfTrue(index, true_label.0.clone()));
+        self.fixups
+            .push(Fixup::JumpIfFalse(index, false_label.0.clone()));
+    }
+
+    fn lower_jump_ordering(
+        &mut self,
+        r: vil::Register,
+        less_label: &vil::Label,
+        equal_label: &vil::Label,
+        greater_label: &vil::Label,
+    ) {
+        let index = self.push(Instruction::JumpOrdering(r, 0, 0, 0));
+        self.fixups
+            .push(Fixup::JumpOrderingLess(index, less_label.0.clone()));
+        self.fixups
+            .push(Fixup::JumpOrderingEqual(index, equal_label.0.clone()));
+        self.fixups
+            .push(Fixup::JumpOrderingGreater(index, greater_label.0.clone()));
+    }
+
+    fn lower_syscall(
+        &mut self,
+        destination: vil::Register,
+        number: i64,
+        offsets: &[vil::MemoryOffset],
+    ) {
+        self.push(Instruction::Syscall {
+            destination,
+            number,
+            offsets: offsets.to_vec(),
+        });
+    }
+
+    fn param_register(&self, i: u8) -> u8 {
+        // This backend has no physical registers of its own to map onto -- arguments travel from
+        // `Call` to the callee's `LoadParam`s through the interpreter's `pending_args`, indexed by
+        // `i` directly -- so there's nothing for a caller of this method to meaningfully do with the
+        // result. Implemented as the identity purely to satisfy the trait.
+        i
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn function(
+        name: &str,
+        stack_frame_size: i32,
+        parameters: Vec<vil::FunctionParameter>,
+        blocks: Vec<(&str, Vec<vil::InstructionKind>)>,
+    ) -> vil::FunctionDeclaration {
+        vil::FunctionDeclaration {
+            name: String::from(name),
+            stack_frame_size,
+            parameters,
+            blocks: blocks
+                .into_iter()
+                .map(|(name, instructions)| vil::Block {
+                    name: String::from(name),
+                    instructions: instructions
+                        .into_iter()
+                        .map(|kind| vil::Instruction {
+                            kind,
+                            comment: String::new(),
+                        })
+                        .collect(),
+                })
+                .collect(),
+        }
+    }
+
+    fn run_program(program: vil::Program) -> i64 {
+        run(&generate(&program))
+    }
+
+    #[test]
+    fn runs_straight_line_arithmetic() {
+        use vil::InstructionKind::*;
+        let r0 = vil::Register::new(0);
+        let r1 = vil::Register::new(1);
+        let r2 = vil::Register::new(2);
+        let program = vil::Program {
+            externs: Vec::new(),
+            strings: BTreeMap::new(),
+            declarations: vec![function(
+                "venice_main",
+                0,
+                Vec::new(),
+                vec![(
+                    "entry",
+                    vec![
+                        Set(r0, vil::Immediate::Integer(40)),
+                        Set(r1, vil::Immediate::Integer(2)),
+                        Binary(vil::BinaryOp::Add, r2, r0, r1),
+                        Move(vil::Register::ret(), r2),
+                    ],
+                )],
+            )],
+        };
+        assert_eq!(run_program(program), 42);
+    }
+
+    #[test]
+    fn resolves_jumps_across_blocks() {
+        use vil::InstructionKind::*;
+        let r0 = vil::Register::new(0);
+        let r1 = vil::Register::new(1);
+        let program = vil::Program {
+            externs: Vec::new(),
+            strings: BTreeMap::new(),
+            declarations: vec![function(
+                "venice_main",
+                0,
+                Vec::new(),
+                vec![
+                    (
+                        "entry",
+                        vec![
+                            Set(r0, vil::Immediate::Integer(1)),
+                            Set(r1, vil::Immediate::Integer(1)),
+                            Cmp(r0, r1),
+                            JumpIf(
+                                vil::JumpCondition::Eq,
+                                vil::Label(String::from("equal")),
+                                vil::Label(String::from("not_equal")),
+                            ),
+                        ],
+                    ),
+                    (
+                        "equal",
+                        vec![
+                            Set(r0, vil::Immediate::Integer(100)),
+                            Move(vil::Register::ret(), r0),
+                            Jump(vil::Label(String::from("exit"))),
+                        ],
+                    ),
+                    (
+                        "not_equal",
+                        vec![
+                            Set(r0, vil::Immediate::Integer(200)),
+                            Move(vil::Register::ret(), r0),
+                            Jump(vil::Label(String::from("exit"))),
+                        ],
+                    ),
+                    ("exit", vec![]),
+                ],
+            )],
+        };
+        assert_eq!(run_program(program), 100);
+    }
+
+    #[test]
+    fn calls_another_function_with_an_argument() {
+        use vil::InstructionKind::*;
+        let r0 = vil::Register::new(0);
+        let r1 = vil::Register::new(1);
+        let program = vil::Program {
+            externs: Vec::new(),
+            strings: BTreeMap::new(),
+            declarations: vec![
+                function(
+                    "venice_main",
+                    8,
+                    Vec::new(),
+                    vec![(
+                        "entry",
+                        vec![
+                            Set(r0, vil::Immediate::Integer(19)),
+                            Store(r0, -8),
+                            Call {
+                                destination: r1,
+                                label: vil::Label(String::from("double")),
+                                offsets: vec![-8],
+                                variadic: false,
+                            },
+                            Move(vil::Register::ret(), r1),
+                        ],
+                    )],
+                ),
+                function(
+                    "double",
+                    8,
+                    vec![vil::FunctionParameter { stack_offset: -8 }],
+                    vec![(
+                        "double_entry",
+                        vec![
+                            Load(r0, -8),
+                            Binary(vil::BinaryOp::Add, r1, r0, r0),
+                            Move(vil::Register::ret(), r1),
+                        ],
+                    )],
+                ),
+            ],
+        };
+        assert_eq!(run_program(program), 38);
+    }
+}
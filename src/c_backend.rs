@@ -0,0 +1,486 @@
+// Copyright 2022 The Venice Authors. All rights reserved.
+// Use of this source code is governed by an MIT-style license that can be
+// found in the LICENSE file.
+//
+// Lowers a parsed Venice program directly from the parse tree into portable C source code, as an
+// alternative ahead-of-time path to the native VIL/x86 pipeline (see codegen.rs and x86.rs). This
+// relies on every `let` statement already carrying a type annotation, which the inference pass in
+// inference.rs guarantees by the time this module runs.
+//
+// The C backend is organized behind the `Backend` trait so that other textual targets (e.g. a
+// JavaScript backend) can be added later without disturbing the driver in main.rs.
+
+use std::collections::BTreeMap;
+
+use super::common;
+use super::errors;
+use super::ptree;
+
+/// A compilation target that lowers a Venice parse tree into another language's source text.
+pub trait Backend {
+    fn generate(&mut self, program: &ptree::Program) -> Result<String, errors::VeniceError>;
+}
+
+/// Generates a C source file from a parse tree using `CBackend`.
+pub fn generate(program: &ptree::Program) -> Result<String, errors::VeniceError> {
+    let mut backend = CBackend::new();
+    backend.generate(program)
+}
+
+/// The subset of Venice types that the C backend knows how to represent.
+#[derive(Clone, Debug)]
+enum CType {
+    I64,
+    Boolean,
+    List(Box<CType>),
+}
+
+impl CType {
+    fn from_ptree_type(type_: &ptree::Type) -> Result<CType, errors::VeniceError> {
+        match &type_.kind {
+            ptree::TypeKind::Literal(s) if s == "i64" => Ok(CType::I64),
+            ptree::TypeKind::Literal(s) if s == "bool" => Ok(CType::Boolean),
+            ptree::TypeKind::Parameterized(p) if p.symbol == "list" && p.parameters.len() == 1 => {
+                Ok(CType::List(Box::new(CType::from_ptree_type(
+                    &p.parameters[0],
+                )?)))
+            }
+            _ => Err(errors::VeniceError::new(
+                "the C backend can only compile i64, bool, and list<T> values",
+                type_.location.clone(),
+            )),
+        }
+    }
+
+    /// A name safe to use as a C identifier fragment, e.g. `i64` or `list_bool`.
+    fn mangled_name(&self) -> String {
+        match self {
+            CType::I64 => String::from("i64"),
+            CType::Boolean => String::from("bool"),
+            CType::List(item) => format!("list_{}", item.mangled_name()),
+        }
+    }
+
+    /// The C type used to spell this type in generated source.
+    fn c_name(&self) -> String {
+        match self {
+            CType::I64 => String::from("int64_t"),
+            CType::Boolean => String::from("bool"),
+            CType::List(_) => format!("venice_{}_t", self.mangled_name()),
+        }
+    }
+}
+
+struct CBackend {
+    // Keyed by mangled name so that each list element type only generates one struct definition.
+    list_types: BTreeMap<String, CType>,
+    prototypes: Vec<String>,
+    bodies: Vec<String>,
+}
+
+impl CBackend {
+    fn new() -> Self {
+        CBackend {
+            list_types: BTreeMap::new(),
+            prototypes: Vec::new(),
+            bodies: Vec::new(),
+        }
+    }
+
+    fn resolve_type(&mut self, type_: &ptree::Type) -> Result<CType, errors::VeniceError> {
+        let ctype = CType::from_ptree_type(type_)?;
+        self.register_type(&ctype);
+        Ok(ctype)
+    }
+
+    fn register_type(&mut self, ctype: &CType) {
+        if let CType::List(item) = ctype {
+            self.register_type(item);
+            self.list_types
+                .entry(ctype.mangled_name())
+                .or_insert_with(|| ctype.clone());
+        }
+    }
+
+    fn generate_function(
+        &mut self,
+        declaration: &ptree::FunctionDeclaration,
+    ) -> Result<(), errors::VeniceError> {
+        let return_type = self.resolve_type(&declaration.return_type)?;
+
+        let mut parameters = Vec::with_capacity(declaration.parameters.len());
+        for parameter in &declaration.parameters {
+            let ctype = self.resolve_type(&parameter.type_)?;
+            parameters.push(format!("{} {}", ctype.c_name(), parameter.name));
+        }
+        let parameter_list = if parameters.is_empty() {
+            String::from("void")
+        } else {
+            parameters.join(", ")
+        };
+
+        let signature = format!(
+            "{} {}({})",
+            return_type.c_name(),
+            declaration.name,
+            parameter_list
+        );
+        self.prototypes.push(format!("{};", signature));
+
+        let mut body = String::new();
+        for statement in &declaration.body {
+            self.generate_statement(statement, 1, &mut body)?;
+        }
+
+        self.bodies.push(format!("{} {{\n{}}}\n", signature, body));
+        Ok(())
+    }
+
+    fn generate_statement(
+        &mut self,
+        statement: &ptree::Statement,
+        indent: usize,
+        out: &mut String,
+    ) -> Result<(), errors::VeniceError> {
+        let pad = "  ".repeat(indent);
+        match statement {
+            ptree::Statement::Let(stmt) => {
+                let type_ = stmt.type_.as_ref().ok_or_else(|| {
+                    errors::VeniceError::new(
+                        "let statement is missing a type annotation",
+                        stmt.location.clone(),
+                    )
+                })?;
+                let ctype = self.resolve_type(type_)?;
+                let value = self.generate_expression(&stmt.value)?;
+                out.push_str(&format!(
+                    "{}{} {} = {};\n",
+                    pad,
+                    ctype.c_name(),
+                    stmt.symbol,
+                    value
+                ));
+            }
+            ptree::Statement::Assign(stmt) => {
+                let name = match &stmt.target.kind {
+                    ptree::ExpressionKind::Symbol(name) => name,
+                    _ => {
+                        return Err(errors::VeniceError::new(
+                            "the C backend can only assign to a plain variable",
+                            stmt.target.location.clone(),
+                        ))
+                    }
+                };
+                let value = self.generate_expression(&stmt.value)?;
+                match stmt.op {
+                    Some(op) => {
+                        let c_op = binary_op_to_c(op, stmt.location.clone())?;
+                        out.push_str(&format!("{}{} {}= {};\n", pad, name, c_op, value));
+                    }
+                    None => out.push_str(&format!("{}{} = {};\n", pad, name, value)),
+                }
+            }
+            ptree::Statement::Expression(expr) => {
+                let value = self.generate_expression(expr)?;
+                out.push_str(&format!("{}{};\n", pad, value));
+            }
+            ptree::Statement::If(stmt) => {
+                let condition = self.generate_expression(&stmt.if_clause.condition)?;
+                out.push_str(&format!("{}if ({}) {{\n", pad, condition));
+                for s in &stmt.if_clause.body {
+                    self.generate_statement(s, indent + 1, out)?;
+                }
+                out.push_str(&format!("{}}}", pad));
+
+                for elif_clause in &stmt.elif_clauses {
+                    let condition = self.generate_expression(&elif_clause.condition)?;
+                    out.push_str(&format!(" else if ({}) {{\n", condition));
+                    for s in &elif_clause.body {
+                        self.generate_statement(s, indent + 1, out)?;
+                    }
+                    out.push_str(&format!("{}}}", pad));
+                }
+
+                if !stmt.else_body.is_empty() {
+                    out.push_str(" else {\n");
+                    for s in &stmt.else_body {
+                        self.generate_statement(s, indent + 1, out)?;
+                    }
+                    out.push_str(&format!("{}}}", pad));
+                }
+                out.push('\n');
+            }
+            ptree::Statement::While(stmt) => {
+                let condition = self.generate_expression(&stmt.condition)?;
+                out.push_str(&format!("{}while ({}) {{\n", pad, condition));
+                for s in &stmt.body {
+                    self.generate_statement(s, indent + 1, out)?;
+                }
+                out.push_str(&format!("{}}}\n", pad));
+            }
+            ptree::Statement::Return(stmt) => {
+                let value = self.generate_expression(&stmt.value)?;
+                out.push_str(&format!("{}return {};\n", pad, value));
+            }
+            ptree::Statement::Assert(stmt) => {
+                let condition = self.generate_expression(&stmt.condition)?;
+                out.push_str(&format!("{}if (!({})) {{\n", pad, condition));
+                out.push_str(&format!(
+                    "{}  fprintf(stderr, \"assertion failed at {}\\n\");\n",
+                    pad, stmt.location
+                ));
+                out.push_str(&format!("{}  abort();\n", pad));
+                out.push_str(&format!("{}}}\n", pad));
+            }
+            ptree::Statement::Break(_) => out.push_str(&format!("{}break;\n", pad)),
+            ptree::Statement::Continue(_) => out.push_str(&format!("{}continue;\n", pad)),
+            ptree::Statement::For(stmt) => {
+                return Err(errors::VeniceError::new(
+                    "the C backend does not yet support for loops",
+                    stmt.location.clone(),
+                ))
+            }
+        }
+        Ok(())
+    }
+
+    fn generate_expression(
+        &mut self,
+        expr: &ptree::Expression,
+    ) -> Result<String, errors::VeniceError> {
+        use ptree::ExpressionKind::*;
+        match &expr.kind {
+            Boolean(x) => Ok(x.to_string()),
+            Integer(x, _) => Ok(x.to_string()),
+            Symbol(name) => Ok(name.clone()),
+            Binary(e) => {
+                let left = self.generate_expression(&e.left)?;
+                let right = self.generate_expression(&e.right)?;
+                let op = binary_op_to_c(e.op, e.location.clone())?;
+                Ok(format!("({} {} {})", left, op, right))
+            }
+            Comparison(e) => {
+                let left = self.generate_expression(&e.left)?;
+                let right = self.generate_expression(&e.right)?;
+                let op = comparison_op_to_c(e.op);
+                Ok(format!("({} {} {})", left, op, right))
+            }
+            Unary(e) => {
+                let operand = self.generate_expression(&e.operand)?;
+                let op = match e.op {
+                    common::UnaryOpType::Negate => "-",
+                    common::UnaryOpType::Not => "!",
+                };
+                Ok(format!("({}{})", op, operand))
+            }
+            Call(e) => {
+                let mut arguments = Vec::with_capacity(e.arguments.len());
+                for argument in &e.arguments {
+                    arguments.push(self.generate_expression(argument)?);
+                }
+                Ok(format!("{}({})", e.function, arguments.join(", ")))
+            }
+            Index(e) => {
+                let value = self.generate_expression(&e.value)?;
+                let index = self.generate_expression(&e.index)?;
+                Ok(format!("({}.data[{}])", value, index))
+            }
+            List(e) => {
+                if e.items.is_empty() {
+                    return Err(errors::VeniceError::new(
+                        "the C backend cannot compile an empty list literal",
+                        expr.location.clone(),
+                    ));
+                }
+
+                let mut items = Vec::with_capacity(e.items.len());
+                for item in &e.items {
+                    items.push(self.generate_expression(item)?);
+                }
+
+                // The element type was already resolved when the enclosing `let` statement's
+                // annotation was, so re-deriving it here just needs the first element's shape;
+                // mismatched elements are a type error the inference pass already caught.
+                let element_type = infer_literal_element_type(&e.items[0])?;
+                self.register_type(&CType::List(Box::new(element_type.clone())));
+
+                Ok(format!(
+                    "(venice_{}_t){{ .length = {}, .data = ({}[]){{{}}} }}",
+                    CType::List(Box::new(element_type.clone())).mangled_name(),
+                    items.len(),
+                    element_type.c_name(),
+                    items.join(", ")
+                ))
+            }
+            _ => Err(errors::VeniceError::new(
+                "the C backend does not yet support this kind of expression",
+                expr.location.clone(),
+            )),
+        }
+    }
+
+    fn render(&self) -> String {
+        let mut out = String::new();
+        out.push_str("#include <stdbool.h>\n");
+        out.push_str("#include <stdint.h>\n");
+        out.push_str("#include <stdio.h>\n");
+        out.push_str("#include <stdlib.h>\n\n");
+
+        for ctype in self.list_types.values() {
+            let element = match ctype {
+                CType::List(item) => item,
+                _ => unreachable!("list_types only ever holds CType::List entries"),
+            };
+            out.push_str(&format!(
+                "typedef struct {{\n  int64_t length;\n  {} *data;\n}} {};\n\n",
+                element.c_name(),
+                ctype.c_name()
+            ));
+        }
+
+        for prototype in &self.prototypes {
+            out.push_str(prototype);
+            out.push('\n');
+        }
+        out.push('\n');
+
+        for body in &self.bodies {
+            out.push_str(body);
+            out.push('\n');
+        }
+
+        out
+    }
+}
+
+impl Backend for CBackend {
+    fn generate(&mut self, program: &ptree::Program) -> Result<String, errors::VeniceError> {
+        for declaration in &program.declarations {
+            match declaration {
+                ptree::Declaration::Function(d) => self.generate_function(d)?,
+                ptree::Declaration::Const(d) => {
+                    return Err(errors::VeniceError::new(
+                        "the C backend does not yet support const declarations",
+                        d.location.clone(),
+                    ))
+                }
+                ptree::Declaration::Record(d) => {
+                    return Err(errors::VeniceError::new(
+                        "the C backend does not yet support record declarations",
+                        d.location.clone(),
+                    ))
+                }
+                ptree::Declaration::Enum(d) => {
+                    return Err(errors::VeniceError::new(
+                        "the C backend does not yet support enum declarations",
+                        d.location.clone(),
+                    ))
+                }
+            }
+        }
+        Ok(self.render())
+    }
+}
+
+fn binary_op_to_c(
+    op: common::BinaryOpType,
+    location: common::Location,
+) -> Result<&'static str, errors::VeniceError> {
+    use common::BinaryOpType::*;
+    match op {
+        Add => Ok("+"),
+        Subtract => Ok("-"),
+        Multiply => Ok("*"),
+        Divide => Ok("/"),
+        Modulo => Ok("%"),
+        And => Ok("&&"),
+        Or => Ok("||"),
+        Concat => Err(errors::VeniceError::new(
+            "the C backend does not yet support string concatenation",
+            location,
+        )),
+    }
+}
+
+fn comparison_op_to_c(op: common::ComparisonOpType) -> &'static str {
+    use common::ComparisonOpType::*;
+    match op {
+        Equals => "==",
+        NotEquals => "!=",
+        GreaterThan => ">",
+        GreaterThanEquals => ">=",
+        LessThan => "<",
+        LessThanEquals => "<=",
+    }
+}
+
+/// Infers the element type of a list literal from its first item, which must be a boolean or
+/// integer literal.
+fn infer_literal_element_type(expr: &ptree::Expression) -> Result<CType, errors::VeniceError> {
+    match &expr.kind {
+        ptree::ExpressionKind::Boolean(_) => Ok(CType::Boolean),
+        ptree::ExpressionKind::Integer(_, _) => Ok(CType::I64),
+        _ => Err(errors::VeniceError::new(
+            "the C backend cannot infer the element type of this list literal",
+            expr.location.clone(),
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::inference;
+    use crate::lexer;
+    use crate::parser;
+
+    fn generate_c(source: &str) -> Result<String, errors::VeniceError> {
+        let lexer = lexer::Lexer::new("<string>", source);
+        let (mut program, parse_errors) = parser::parse(lexer);
+        assert!(
+            parse_errors.is_empty(),
+            "program should parse: {:?}",
+            parse_errors
+        );
+        inference::infer(&mut program).expect("program should type-infer");
+        generate(&program)
+    }
+
+    #[test]
+    fn compiles_a_simple_function() {
+        let c = generate_c("func main() -> i64 {\n  let x: i64 = 1 + 2;\n  return x;\n}\n")
+            .expect("should generate C");
+        assert!(c.contains("int64_t main(void)"));
+        assert!(c.contains("int64_t x = (1 + 2);"));
+        assert!(c.contains("return x;"));
+    }
+
+    #[test]
+    fn compiles_if_and_while() {
+        let c = generate_c(
+            "func main() -> i64 {\n  let i: i64 = 0;\n  while i < 3 {\n    i = i + 1;\n  }\n  if i == 3 {\n    return 0;\n  } else {\n    return 1;\n  }\n}\n",
+        )
+        .expect("should generate C");
+        assert!(c.contains("while ((i < 3))"));
+        assert!(c.contains("if ((i == 3))"));
+        assert!(c.contains("} else {"));
+    }
+
+    #[test]
+    fn compiles_list_literals_with_a_generated_struct() {
+        let c = generate_c(
+            "func main() -> i64 {\n  let xs: list<i64> = [1, 2, 3];\n  return xs[0];\n}\n",
+        )
+        .expect("should generate C");
+        assert!(c.contains("venice_list_i64_t"));
+        assert!(c.contains(".length = 3"));
+    }
+
+    #[test]
+    fn rejects_string_concatenation() {
+        let result =
+            generate_c("func main() -> i64 {\n  let x: i64 = 1 ++ 2;\n  return 0;\n}\n");
+        assert!(result.is_err());
+        assert!(result.unwrap_err().message.contains("string concatenation"));
+    }
+}
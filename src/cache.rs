@@ -0,0 +1,94 @@
+// Copyright 2022 The Venice Authors. All rights reserved.
+// Use of this source code is governed by an MIT-style license that can be
+// found in the LICENSE file.
+//
+// An on-disk cache of compiled artifacts, keyed by a content hash of the source program combined
+// with everything that affects codegen (compiler version, target, flags), so that recompiling an
+// unchanged program can reuse the last build instead of re-running the whole pipeline.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use xxhash_rust::xxh3::xxh3_128;
+
+/// The compiler's own version, folded into every cache key so that upgrading the compiler can't
+/// serve a stale artifact built by an older, possibly incompatible version.
+const COMPILER_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// Identifies one compiled artifact: the source that produced it plus every flag that could
+/// change what gets produced. Two builds with the same key are guaranteed to produce the same
+/// bytes, so the second one can just reuse the first's artifact.
+pub struct CacheKey(String);
+
+impl CacheKey {
+    /// Hashes `source` together with `codegen_flags` (a caller-built summary of every flag that
+    /// affects the generated artifact -- target, `--debug`, `--emit`, and so on) and the compiler
+    /// version.
+    pub fn new(source: &str, codegen_flags: &str) -> Self {
+        let mut buf = Vec::with_capacity(source.len() + codegen_flags.len() + COMPILER_VERSION.len());
+        buf.extend_from_slice(COMPILER_VERSION.as_bytes());
+        buf.push(0);
+        buf.extend_from_slice(codegen_flags.as_bytes());
+        buf.push(0);
+        buf.extend_from_slice(source.as_bytes());
+
+        let digest = xxh3_128(&buf);
+        CacheKey(format!("{:032x}", digest))
+    }
+}
+
+/// A directory of cached artifacts on disk, keyed by `CacheKey`.
+pub struct Cache {
+    dir: PathBuf,
+}
+
+impl Cache {
+    /// Opens the cache directory, creating it if necessary: `$VENICE_CACHE` if set, otherwise
+    /// `~/.cache/venice`.
+    pub fn open() -> std::io::Result<Self> {
+        let dir = cache_dir();
+        fs::create_dir_all(&dir)?;
+        Ok(Cache { dir })
+    }
+
+    /// If `key` names an artifact already in the cache, copies it to `destination` and returns
+    /// `true`. Returns `false` (without touching `destination`) on a cache miss.
+    pub fn fetch(&self, key: &CacheKey, destination: &Path) -> std::io::Result<bool> {
+        let cached_path = self.artifact_path(key);
+        if !cached_path.exists() {
+            return Ok(false);
+        }
+
+        fs::copy(&cached_path, destination)?;
+        Ok(true)
+    }
+
+    /// Stores a copy of the artifact at `artifact` under `key`, so a future build with the same
+    /// key can reuse it.
+    pub fn store(&self, key: &CacheKey, artifact: &Path) -> std::io::Result<()> {
+        fs::copy(artifact, self.artifact_path(key))?;
+        Ok(())
+    }
+
+    /// Deletes every cached artifact, for `venice cache clear`.
+    pub fn clear(&self) -> std::io::Result<()> {
+        for entry in fs::read_dir(&self.dir)? {
+            let entry = entry?;
+            fs::remove_file(entry.path())?;
+        }
+        Ok(())
+    }
+
+    fn artifact_path(&self, key: &CacheKey) -> PathBuf {
+        self.dir.join(&key.0)
+    }
+}
+
+fn cache_dir() -> PathBuf {
+    if let Ok(dir) = std::env::var("VENICE_CACHE") {
+        return PathBuf::from(dir);
+    }
+
+    let home = std::env::var("HOME").unwrap_or_else(|_| String::from("."));
+    Path::new(&home).join(".cache").join("venice")
+}
@@ -11,14 +11,24 @@
 
 use std::collections::BTreeMap;
 use std::collections::HashMap;
+use std::collections::HashSet;
 
 use super::ast;
+use super::backend::BackendConfig;
 use super::common;
 use super::errors;
 use super::vil;
-
-/// Generates a VIL program from an abstract syntax tree.
-pub fn generate(ast: &ast::Program) -> Result<vil::Program, errors::VeniceError> {
+use super::vil_opt;
+
+/// Generates a VIL program from an abstract syntax tree, then runs it through the register
+/// allocator sized for `backend_config`'s target (see `backend::BackendConfig`). When
+/// `checked_arithmetic` is set, every integer division is guarded against a zero divisor (see
+/// `Generator::generate_divzero_check`); release builds can pass `false` to skip the extra checks.
+pub fn generate(
+    ast: &ast::Program,
+    backend_config: &dyn BackendConfig,
+    checked_arithmetic: bool,
+) -> Result<vil::Program, errors::VeniceError> {
     let mut generator = Generator {
         program: vil::Program {
             externs: Vec::new(),
@@ -29,18 +39,25 @@ pub fn generate(ast: &ast::Program) -> Result<vil::Program, errors::VeniceError>
         return_label: vil::Label(String::new()),
         label_counter: 0,
         string_counter: 0,
+        string_labels: HashMap::new(),
+        checked_arithmetic,
+        divzero_trap: None,
+        division_by_zero_error: None,
     };
     generator.generate_program(ast);
 
-    // TODO: Take backend config as an option rather than hard-coding an x86 value here.
-    let mut register_spiller = RegisterSpiller::new(X86_REGISTER_COUNT);
-    register_spiller.spill(&mut generator.program);
+    if let Some(error) = generator.division_by_zero_error {
+        return Err(error);
+    }
+
+    vil_opt::simplify(&mut generator.program);
+
+    let mut register_allocator = RegisterAllocator::new(backend_config.register_count());
+    register_allocator.allocate(&mut generator.program);
 
     Ok(generator.program)
 }
 
-const X86_REGISTER_COUNT: u8 = 14;
-
 struct Generator {
     // The program which is incrementally built up.
     program: vil::Program,
@@ -50,6 +67,32 @@ struct Generator {
     // Counters for generating unique symbols.
     label_counter: u32,
     string_counter: u32,
+    // Reverse map from string contents to the label already claimed for them (see
+    // `claim_string_label`), so identical literals share one `program.strings` entry.
+    string_labels: HashMap<String, String>,
+
+    // Whether integer divisions should be guarded against a zero divisor (see
+    // `generate_divzero_check`).
+    checked_arithmetic: bool,
+    // The current function's shared zero-divisor trap, lazily built by the first checked `Div` it
+    // contains and reused by every other one; reset to `None` at the start of each function.
+    divzero_trap: Option<DivZeroTrap>,
+    // The first compile-time division-by-zero found while constant-folding (see
+    // `try_fold_binary_expression`), if any. `generate` surfaces this as the overall result instead
+    // of the generated program, the same way `errors::VeniceError` is reported anywhere else in the
+    // pipeline -- codegen just hasn't had a reason to produce one until now.
+    division_by_zero_error: Option<errors::VeniceError>,
+}
+
+/// The label and stack slots of a function's shared zero-divisor trap block (see
+/// `Generator::generate_divzero_check`). Holding these in one struct, rather than three loose
+/// fields on `Generator`, keeps "does this function have a trap yet" a single `Option` check.
+#[derive(Clone)]
+struct DivZeroTrap {
+    label: vil::Label,
+    file_slot: vil::MemoryOffset,
+    line_slot: vil::MemoryOffset,
+    column_slot: vil::MemoryOffset,
 }
 
 impl Generator {
@@ -72,6 +115,7 @@ impl Generator {
     fn generate_function_declaration(&mut self, declaration: &ast::FunctionDeclaration) {
         let name = &declaration.name.unique_name;
         self.info = Some(declaration.info.clone());
+        self.divzero_trap = None;
 
         let mut parameters = Vec::new();
         for parameter in &declaration.parameters {
@@ -93,6 +137,29 @@ impl Generator {
 
         self.start_block(label, None);
         self.generate_block(&declaration.body);
+
+        // If any checked `Div` in this function needed it, the trap block goes here: after the
+        // body, before `return_label`. An explicit jump to `return_label` closes out whatever the
+        // body's last block was -- the same target it would have reached by falling through, so
+        // this is a no-op for control flow either way -- which leaves the trap block free to follow
+        // it and end with its own (genuine, never-taken-in-practice) terminator below, so that
+        // `start_block(self.return_label.clone(), None)` keeps relying on the same fallthrough it
+        // always has.
+        if let Some(trap) = self.divzero_trap.clone() {
+            self.start_block(
+                trap.label.clone(),
+                Some(vil::InstructionKind::Jump(self.return_label.clone())),
+            );
+            let destination = vil::Register::scratch();
+            self.push(vil::InstructionKind::Call {
+                destination,
+                label: vil::Label(String::from("venice_trap_divzero")),
+                offsets: vec![trap.file_slot, trap.line_slot, trap.column_slot],
+                variadic: false,
+            });
+            self.push(vil::InstructionKind::Jump(trap.label));
+        }
+
         self.start_block(self.return_label.clone(), None);
     }
 
@@ -110,12 +177,14 @@ impl Generator {
             Integer(x) => {
                 self.push(vil::InstructionKind::Set(r, vil::Immediate::Integer(*x)));
             }
+            Float(x) => {
+                self.push(vil::InstructionKind::Set(r, vil::Immediate::Float(*x)));
+            }
             String(s) => {
-                let label = self.claim_string_label();
-                self.program.strings.insert(label.clone(), s.clone());
+                let label = self.claim_string_label(s);
                 self.push(vil::InstructionKind::Set(r, vil::Immediate::Label(label)));
             }
-            Binary(b) => self.generate_binary_expression(b, r),
+            Binary(b) => self.generate_binary_expression(b, &expr.span, r),
             Unary(e) => self.generate_unary_expression(e, r),
             Comparison(b) => self.generate_comparison_expression(b, r),
             Call(e) => self.generate_call_expression(e, r),
@@ -148,30 +217,89 @@ impl Generator {
         if let ast::ExpressionKind::Comparison(cmp_expr) = &expr.kind {
             let (left, right) =
                 self.generate_generic_binary_expression(&cmp_expr.left, &cmp_expr.right, r);
-            self.push(vil::InstructionKind::Cmp(left, right));
+            if cmp_expr.left.type_.matches(&ast::Type::F64) {
+                self.push(vil::InstructionKind::FCmp(left, right));
+            } else {
+                self.push(vil::InstructionKind::Cmp(left, right));
+            }
             let exit = get_comparison_instruction(cmp_expr.op, true_label, false_label);
             self.push(exit);
+        } else if let ast::ExpressionKind::If(if_expr) = &expr.kind {
+            if is_boolean_literal(&if_expr.false_value, false) {
+                // The analyzer desugars `condition and true_value` to exactly this shape (see
+                // analyzer.rs). Lowering it back into a chain of branches instead of materializing
+                // `condition`'s and `true_value`'s booleans and comparing them each against 1 is
+                // what makes `&&` actually short-circuit: `true_value` is never evaluated at all
+                // when `condition` is false.
+                let mid_label = self.claim_label("and");
+                self.generate_expression_as_condition(
+                    &if_expr.condition,
+                    mid_label.clone(),
+                    false_label.clone(),
+                );
+                self.start_block(mid_label, None);
+                self.generate_expression_as_condition(&if_expr.true_value, true_label, false_label);
+            } else if is_boolean_literal(&if_expr.true_value, true) {
+                // The `or` counterpart: the analyzer desugars `condition or false_value` to this
+                // shape, and this lowers it the same way `and` is lowered above.
+                let mid_label = self.claim_label("or");
+                self.generate_expression_as_condition(
+                    &if_expr.condition,
+                    true_label.clone(),
+                    mid_label.clone(),
+                );
+                self.start_block(mid_label, None);
+                self.generate_expression_as_condition(&if_expr.false_value, true_label, false_label);
+            } else {
+                self.generate_expression_as_condition_fallback(expr, true_label, false_label);
+            }
         } else {
-            let register = self.generate_expression(expr);
-            let scratch = vil::Register::scratch();
-            self.push(vil::InstructionKind::Set(
-                scratch,
-                vil::Immediate::Integer(1),
-            ));
-            self.push(vil::InstructionKind::Cmp(register, scratch));
-            self.push(vil::InstructionKind::JumpIf(
-                vil::JumpCondition::Eq,
-                true_label,
-                false_label,
-            ));
+            self.generate_expression_as_condition_fallback(expr, true_label, false_label);
         }
     }
 
-    fn generate_binary_expression(&mut self, expr: &ast::BinaryExpression, r: vil::Register) {
+    /// Materializes `expr` into a boolean register and compares it against `1`, for conditions
+    /// that aren't a `Comparison` or a recognized `and`/`or` desugaring (see
+    /// `generate_expression_as_condition`) -- an ordinary function call or variable, say.
+    fn generate_expression_as_condition_fallback(
+        &mut self,
+        expr: &ast::Expression,
+        true_label: vil::Label,
+        false_label: vil::Label,
+    ) {
+        let register = self.generate_expression(expr);
+        let scratch = vil::Register::scratch();
+        self.push(vil::InstructionKind::Set(
+            scratch,
+            vil::Immediate::Integer(1),
+        ));
+        self.push(vil::InstructionKind::Cmp(register, scratch));
+        self.push(vil::InstructionKind::JumpIf(
+            vil::JumpCondition::Eq,
+            true_label,
+            false_label,
+        ));
+    }
+
+    fn generate_binary_expression(
+        &mut self,
+        expr: &ast::BinaryExpression,
+        span: &common::Span,
+        r: vil::Register,
+    ) {
+        if self.try_fold_binary_expression(expr, span, r) {
+            return;
+        }
+
         let (left, right) = self.generate_generic_binary_expression(&expr.left, &expr.right, r);
+        let is_float = expr.left.type_.matches(&ast::Type::F64);
 
         use common::BinaryOpType::*;
         let op = match expr.op {
+            Add if is_float => vil::BinaryOp::FAdd,
+            Divide if is_float => vil::BinaryOp::FDiv,
+            Multiply if is_float => vil::BinaryOp::FMul,
+            Subtract if is_float => vil::BinaryOp::FSub,
             Add => vil::BinaryOp::Add,
             Divide => vil::BinaryOp::Div,
             Multiply => vil::BinaryOp::Mul,
@@ -185,9 +313,151 @@ impl Generator {
                 panic!("internal error: operator not implemented: {:?}", expr.op);
             }
         };
+
+        // Floating-point division doesn't need (or get) a trap: dividing by zero produces IEEE 754
+        // infinity or NaN rather than a CPU fault, so there's nothing here to guard against.
+        if matches!(expr.op, Divide) && !is_float && self.checked_arithmetic {
+            self.generate_divzero_check(right, span);
+        }
+
         self.push(vil::InstructionKind::Binary(op, r, left, right));
     }
 
+    /// Folds a `Binary` expression into a single `Set` when both operands are already integer
+    /// literals (see `ast::ExpressionKind::Integer`), instead of emitting arithmetic instructions
+    /// to compute a value the analyzer already knows at compile time. Returns whether it did --
+    /// the caller falls back to its normal, register-based lowering otherwise.
+    ///
+    /// Only integer literals fold here: `And`/`Or` never reach this point as `Binary` nodes (see
+    /// the panic in `generate_binary_expression`), and floating-point arithmetic is left out of
+    /// scope, since float equality makes "is this the same constant" a fuzzier question than it is
+    /// for integers. Division by a literal zero is a compile-time error (see
+    /// `record_division_by_zero`) rather than a folded value, since there's no result to fold to.
+    fn try_fold_binary_expression(
+        &mut self,
+        expr: &ast::BinaryExpression,
+        span: &common::Span,
+        r: vil::Register,
+    ) -> bool {
+        let (left, right) = if let (ast::ExpressionKind::Integer(left), ast::ExpressionKind::Integer(right)) =
+            (&expr.left.kind, &expr.right.kind)
+        {
+            (*left, *right)
+        } else {
+            return false;
+        };
+
+        use common::BinaryOpType::*;
+        let value = match expr.op {
+            Add => left.wrapping_add(right),
+            Subtract => left.wrapping_sub(right),
+            Multiply => left.wrapping_mul(right),
+            Divide if right == 0 => {
+                self.record_division_by_zero(span);
+                0
+            }
+            Divide => left.wrapping_div(right),
+            _ => return false,
+        };
+
+        self.push(vil::InstructionKind::Set(r, vil::Immediate::Integer(value)));
+        true
+    }
+
+    /// Records a compile-time division-by-zero error for `span`, if one hasn't already been
+    /// recorded this compilation (see `Generator::division_by_zero_error` and `codegen::generate`,
+    /// which surfaces the first one as the overall result instead of the generated program).
+    fn record_division_by_zero(&mut self, span: &common::Span) {
+        if self.division_by_zero_error.is_none() {
+            self.division_by_zero_error = Some(errors::VeniceError::new_with_span(
+                "division by zero",
+                span.start.clone(),
+                span.end.clone(),
+            ));
+        }
+    }
+
+    /// Emits a zero-divisor check before a checked integer `Div`: stashes this call site's own
+    /// source location in the function's shared trap slots (see `DivZeroTrap`), compares `divisor`
+    /// against zero, and branches to the trap block -- built once per function, at the end of
+    /// `generate_function_declaration`, and reused by every other checked `Div` in it -- or falls
+    /// through to a fresh block, which the caller (`generate_binary_expression`) fills with the
+    /// `Div` instruction itself right afterward.
+    fn generate_divzero_check(&mut self, divisor: vil::Register, span: &common::Span) {
+        let trap = self.claim_divzero_trap();
+
+        let scratch = vil::Register::scratch();
+        self.push(vil::InstructionKind::Set(scratch, vil::Immediate::Integer(0)));
+        self.push(vil::InstructionKind::Cmp(divisor, scratch));
+
+        let file_label = self.claim_string_label(&span.start.file);
+        self.push(vil::InstructionKind::Set(
+            scratch,
+            vil::Immediate::Label(file_label),
+        ));
+        self.push(vil::InstructionKind::Store(scratch, trap.file_slot));
+        self.push(vil::InstructionKind::Set(
+            scratch,
+            vil::Immediate::Integer(i64::from(span.start.line)),
+        ));
+        self.push(vil::InstructionKind::Store(scratch, trap.line_slot));
+        self.push(vil::InstructionKind::Set(
+            scratch,
+            vil::Immediate::Integer(i64::from(span.start.column)),
+        ));
+        self.push(vil::InstructionKind::Store(scratch, trap.column_slot));
+
+        // The comparison comes last, directly before the branch: the riscv and aarch64 backends
+        // read `Cmp`'s operands only when lowering the `JumpIf` that follows it.
+        self.push(vil::InstructionKind::Set(scratch, vil::Immediate::Integer(0)));
+        self.push(vil::InstructionKind::Cmp(divisor, scratch));
+
+        let continue_label = self.claim_label("div_safe");
+        self.start_block(
+            continue_label.clone(),
+            Some(vil::InstructionKind::JumpIf(
+                vil::JumpCondition::Eq,
+                trap.label,
+                continue_label,
+            )),
+        );
+    }
+
+    /// Returns the current function's shared `DivZeroTrap`, building it (a label plus three stack
+    /// slots to stage a call site's file/line/column before jumping there) the first time it's
+    /// needed. The trap block's own body isn't emitted here -- it's appended once, at the end of
+    /// `generate_function_declaration`, after it's known whether this function needed one at all.
+    fn claim_divzero_trap(&mut self) -> DivZeroTrap {
+        if let Some(trap) = &self.divzero_trap {
+            return trap.clone();
+        }
+
+        let trap = DivZeroTrap {
+            label: self.claim_label("divzero_trap"),
+            file_slot: self.claim_stack_slot(),
+            line_slot: self.claim_stack_slot(),
+            column_slot: self.claim_stack_slot(),
+        };
+        self.program
+            .externs
+            .push(String::from("venice_trap_divzero"));
+        self.divzero_trap = Some(trap.clone());
+        trap
+    }
+
+    /// Claims 8 bytes of stack space beyond the current function's locals, for scratch data that
+    /// codegen itself needs (see `DivZeroTrap`) rather than a named source-level variable. Returns
+    /// the new slot's offset from the frame pointer, following the same `-8, -16, ...` convention
+    /// the analyzer uses for locals (see its `stack_offset`/`stack_frame_size` bookkeeping) -- safe
+    /// to extend here because the register allocator's own spill slots (see `RegisterAllocator`)
+    /// are laid out from whatever `stack_frame_size` holds once `generate_program` has finished,
+    /// not from the value the analyzer originally computed.
+    fn claim_stack_slot(&mut self) -> vil::MemoryOffset {
+        let function = self.current_function();
+        function.stack_frame_size += 8;
+        -function.stack_frame_size
+    }
+
     /// Given a left and right expression and a target register, generates the code for the two
     /// expressions and returns (left, right), the pair of registers that the results will be
     /// placed in.
@@ -217,21 +487,46 @@ impl Generator {
     }
 
     fn generate_unary_expression(&mut self, expr: &ast::UnaryExpression, r: vil::Register) {
+        if self.try_fold_unary_expression(expr, r) {
+            return;
+        }
+
         let operand = self.generate_expression(&expr.operand);
 
         use common::UnaryOpType::*;
         let op = match expr.op {
+            Negate if expr.operand.type_.matches(&ast::Type::F64) => vil::UnaryOp::FNegate,
             Negate => vil::UnaryOp::Negate,
             Not => vil::UnaryOp::LogicalNot,
         };
         self.push(vil::InstructionKind::Unary(op, r, operand));
     }
 
+    /// Folds `Negate` on an integer literal or `Not` on a boolean literal into a single `Set`,
+    /// the same way `try_fold_binary_expression` does for `Binary` nodes. `Negate` on a float
+    /// literal is left unfolded for the same reason float `Binary` operands are (see that
+    /// function's doc comment).
+    fn try_fold_unary_expression(&mut self, expr: &ast::UnaryExpression, r: vil::Register) -> bool {
+        use common::UnaryOpType::*;
+        let value = match (expr.op, &expr.operand.kind) {
+            (Negate, ast::ExpressionKind::Integer(x)) => vil::Immediate::Integer(x.wrapping_neg()),
+            (Not, ast::ExpressionKind::Boolean(x)) => vil::Immediate::Integer(i64::from(!x)),
+            _ => return false,
+        };
+
+        self.push(vil::InstructionKind::Set(r, value));
+        true
+    }
+
     fn generate_comparison_expression(
         &mut self,
         expr: &ast::ComparisonExpression,
         r: vil::Register,
     ) {
+        if self.try_fold_comparison_expression(expr, r) {
+            return;
+        }
+
         let left = self.generate_expression(&expr.left);
         let right = self.generate_expression(&expr.right);
 
@@ -239,7 +534,11 @@ impl Generator {
         let false_label = self.claim_label("eq");
         let end_label = self.claim_label("eq_end");
 
-        self.push(vil::InstructionKind::Cmp(left, right));
+        if expr.left.type_.matches(&ast::Type::F64) {
+            self.push(vil::InstructionKind::FCmp(left, right));
+        } else {
+            self.push(vil::InstructionKind::Cmp(left, right));
+        }
 
         let exit = get_comparison_instruction(expr.op, true_label.clone(), false_label.clone());
         self.start_block(true_label, Some(exit));
@@ -257,9 +556,53 @@ impl Generator {
         );
     }
 
+    /// Folds a `Comparison` between two integer literals, or an equality comparison between two
+    /// boolean literals, into a single `Set`, the same way `try_fold_binary_expression` does for
+    /// `Binary` nodes.
+    fn try_fold_comparison_expression(
+        &mut self,
+        expr: &ast::ComparisonExpression,
+        r: vil::Register,
+    ) -> bool {
+        use common::ComparisonOpType::*;
+        let result = match (&expr.left.kind, &expr.right.kind) {
+            (ast::ExpressionKind::Integer(left), ast::ExpressionKind::Integer(right)) => {
+                match expr.op {
+                    Equals => left == right,
+                    NotEquals => left != right,
+                    GreaterThan => left > right,
+                    GreaterThanEquals => left >= right,
+                    LessThan => left < right,
+                    LessThanEquals => left <= right,
+                }
+            }
+            (ast::ExpressionKind::Boolean(left), ast::ExpressionKind::Boolean(right)) => {
+                match expr.op {
+                    Equals => left == right,
+                    NotEquals => left != right,
+                    _ => return false,
+                }
+            }
+            _ => return false,
+        };
+
+        self.push(vil::InstructionKind::Set(
+            r,
+            vil::Immediate::Integer(i64::from(result)),
+        ));
+        true
+    }
+
+    // Argument count has no cap here: each argument is spilled to its own stack slot and handed to
+    // the backend as a `MemoryOffset` regardless of how many there are, the same way it always has
+    // been for the first six. It's the backend, not this target-agnostic pass, that knows how many
+    // of those offsets its calling convention can fit in registers before the rest have to be
+    // materialized on the stack (see `x86::PARAM_REGISTER_COUNT`), so that's also where the limit
+    // on what's actually supported today is enforced.
     fn generate_call_expression(&mut self, expr: &ast::CallExpression, r: vil::Register) {
-        if expr.arguments.len() > 6 {
-            panic!("internal error: compiler cannot handle more than 6 arguments")
+        if let Some(number) = expr.function.syscall {
+            self.generate_syscall_expression(expr, number, r);
+            return;
         }
 
         let mut offsets = Vec::new();
@@ -291,6 +634,44 @@ impl Generator {
         });
     }
 
+    /// A syscall intrinsic (see `ast::SymbolEntry::syscall`) lowers to `InstructionKind::Syscall`
+    /// instead of `InstructionKind::Call`: there's no C calling convention to match, only the
+    /// kernel's own syscall ABI (which the x86/riscv backends handle when they lower it), but
+    /// arguments still round-trip through stack memory first, the same way `Call`'s do, so the
+    /// backend never needs more than its usual two scratch registers to read them back.
+    fn generate_syscall_expression(
+        &mut self,
+        expr: &ast::CallExpression,
+        number: i64,
+        r: vil::Register,
+    ) {
+        if expr.arguments.len() > 6 {
+            panic!("internal error: a syscall cannot take more than 6 arguments")
+        }
+
+        let mut offsets = Vec::new();
+        for (i, argument) in expr.arguments.iter().enumerate() {
+            let argument_register = self.generate_expression(argument);
+            if argument.stack_offset == 0 {
+                panic!(
+                    "internal error: argument {} has invalid stack offset in syscall to {}",
+                    i, expr.function.unique_name
+                );
+            }
+            self.push(vil::InstructionKind::Store(
+                argument_register,
+                argument.stack_offset,
+            ));
+            offsets.push(argument.stack_offset);
+        }
+
+        self.push(vil::InstructionKind::Syscall {
+            destination: r,
+            number,
+            offsets,
+        });
+    }
+
     fn generate_if_expression(&mut self, expr: &ast::IfExpression, r: vil::Register) {
         let true_label = self.claim_label("if_true");
         let false_label = self.claim_label("if_false");
@@ -381,6 +762,11 @@ impl Generator {
         //
         // end:
 
+        if let Some((outer_cmp, inner_stmt, inner_cmp)) = match_comparison_chain(stmt) {
+            self.generate_comparison_chain(stmt, outer_cmp, inner_stmt, inner_cmp);
+            return;
+        }
+
         let true_label = self.claim_label("if_true");
         let false_label = self.claim_label("if_false");
         let end_label = self.claim_label("if_end");
@@ -406,6 +792,76 @@ impl Generator {
         );
     }
 
+    /// Lowers `if a <op1> b { ... } else if a <op2> b { ... } else { ... }` -- an `else if` chain
+    /// (see `match_comparison_chain`) whose arms compare the identical operand pair -- into a
+    /// single three-way comparison: `a` and `b` are evaluated once, `CmpOrdering`/`FCmpOrdering`
+    /// computes their relative order into a register, and one `JumpOrdering` dispatches straight
+    /// to whichever arm's operator that order satisfies (see `comparison_matches`), instead of
+    /// the two separate `Cmp`/`JumpIf` pairs the naive nested-if lowering would otherwise emit.
+    fn generate_comparison_chain(
+        &mut self,
+        stmt: &ast::IfStatement,
+        outer_cmp: &ast::ComparisonExpression,
+        inner_stmt: &ast::IfStatement,
+        inner_cmp: &ast::ComparisonExpression,
+    ) {
+        let r = vil::Register::new(stmt.condition.max_register_needed);
+        let (left, right) =
+            self.generate_generic_binary_expression(&outer_cmp.left, &outer_cmp.right, r);
+        let is_float = outer_cmp.left.type_.matches(&ast::Type::F64);
+        let ordering = vil::Register::new(stmt.condition.max_register_needed + 1);
+
+        let true_label = self.claim_label("if_true");
+        let elif_true_label = self.claim_label("if_true");
+        let false_label = self.claim_label("if_false");
+        let end_label = self.claim_label("if_end");
+
+        if is_float {
+            self.push(vil::InstructionKind::FCmpOrdering(ordering, left, right));
+        } else {
+            self.push(vil::InstructionKind::CmpOrdering(ordering, left, right));
+        }
+
+        let label_for = |state: Ordering3| -> vil::Label {
+            if comparison_matches(outer_cmp.op, state) {
+                true_label.clone()
+            } else if comparison_matches(inner_cmp.op, state) {
+                elif_true_label.clone()
+            } else {
+                false_label.clone()
+            }
+        };
+        self.push(vil::InstructionKind::JumpOrdering(
+            ordering,
+            label_for(Ordering3::Less),
+            label_for(Ordering3::Equal),
+            label_for(Ordering3::Greater),
+        ));
+
+        // The `JumpOrdering` just pushed already terminates the caller's current block, so this
+        // (and every other `start_block` below that follows it) passes `None`: there's nothing
+        // left to append to the block being closed.
+        self.start_block(true_label, None);
+        self.generate_block(&stmt.body);
+
+        self.start_block(
+            elif_true_label,
+            Some(vil::InstructionKind::Jump(end_label.clone())),
+        );
+        self.generate_block(&inner_stmt.body);
+
+        self.start_block(
+            false_label,
+            Some(vil::InstructionKind::Jump(end_label.clone())),
+        );
+        self.generate_block(&inner_stmt.else_body);
+
+        self.start_block(
+            end_label.clone(),
+            Some(vil::InstructionKind::Jump(end_label)),
+        );
+    }
+
     fn generate_let_statement(&mut self, stmt: &ast::LetStatement) {
         let register = self.generate_expression(&stmt.value);
         self.push_with_comment(
@@ -475,9 +931,19 @@ impl Generator {
         vil::Label(label)
     }
 
-    fn claim_string_label(&mut self) -> String {
+    /// Returns the label for `value` in `program.strings`, minting a fresh one (and recording it
+    /// in `string_labels`) only the first time this exact string is seen. Equal literals -- even
+    /// from unrelated expressions -- end up sharing one entry instead of each getting their own
+    /// duplicated copy of the same data.
+    fn claim_string_label(&mut self, value: &str) -> String {
+        if let Some(label) = self.string_labels.get(value) {
+            return label.clone();
+        }
+
         let label = format!("s_{}", self.string_counter);
         self.string_counter += 1;
+        self.program.strings.insert(label.clone(), String::from(value));
+        self.string_labels.insert(String::from(value), label.clone());
         label
     }
 
@@ -507,80 +973,400 @@ impl Generator {
     }
 }
 
-struct RegisterSpiller {
+struct Interval {
+    register: u8,
+    start: usize,
+    end: usize,
+}
+
+/// Whether `register` is one of the two hard-coded indices (`Register::ret()` and
+/// `Register::scratch2()`) that every backend already treats specially (see vil.rs's
+/// `RETURN_REGISTER_INDEX`/`SCRATCH_REGISTER_INDEX`/`SCRATCH2_REGISTER_INDEX` comment). The
+/// allocator never assigns these to an ordinary virtual register and never spills them; it leaves
+/// occurrences of them exactly as it finds them.
+fn is_reserved(register: u8) -> bool {
+    register == vil::Register::ret().index() || register == vil::Register::scratch2().index()
+}
+
+fn registers_read_and_written(kind: &vil::InstructionKind) -> (Vec<u8>, Option<u8>) {
+    use vil::InstructionKind::*;
+    match kind {
+        Binary(_, r1, r2, r3) => (vec![r2.index(), r3.index()], Some(r1.index())),
+        Unary(_, r1, r2) => (vec![r2.index()], Some(r1.index())),
+        Call { destination, .. } => (Vec::new(), Some(destination.index())),
+        Cmp(r1, r2) => (vec![r1.index(), r2.index()], None),
+        FCmp(r1, r2) => (vec![r1.index(), r2.index()], None),
+        CmpOrdering(r1, r2, r3) => (vec![r2.index(), r3.index()], Some(r1.index())),
+        FCmpOrdering(r1, r2, r3) => (vec![r2.index(), r3.index()], Some(r1.index())),
+        Jump(_) => (Vec::new(), None),
+        JumpIf(..) => (Vec::new(), None),
+        JumpOrdering(r, ..) => (vec![r.index()], None),
+        Load(r, _) => (Vec::new(), Some(r.index())),
+        Move(r1, r2) => (vec![r2.index()], Some(r1.index())),
+        Set(r, _) => (Vec::new(), Some(r.index())),
+        Store(r, _) => (vec![r.index()], None),
+        Syscall { destination, .. } => (Vec::new(), Some(destination.index())),
+        Phi(r, operands) => (
+            operands.iter().map(|(_, reg)| reg.index()).collect(),
+            Some(r.index()),
+        ),
+    }
+}
+
+/// Computes a live interval for every non-reserved register a function uses, by a standard
+/// backward liveness fixpoint over the function's blocks (not just within a single block), so an
+/// interval correctly spans a `Jump`/`JumpIf` edge when the register is still live on the other
+/// side of it.
+fn compute_intervals(declaration: &vil::FunctionDeclaration) -> Vec<Interval> {
+    let block_count = declaration.blocks.len();
+
+    let label_to_block: HashMap<&str, usize> = declaration
+        .blocks
+        .iter()
+        .enumerate()
+        .map(|(i, block)| (block.name.as_str(), i))
+        .collect();
+
+    let mut block_bounds = Vec::with_capacity(block_count);
+    let mut position = 0usize;
+    for block in &declaration.blocks {
+        let start = position;
+        position += block.instructions.len();
+        block_bounds.push((start, position));
+    }
+
+    let successors: Vec<Vec<usize>> = declaration
+        .blocks
+        .iter()
+        .enumerate()
+        .map(
+            |(i, block)| match block.instructions.last().map(|instr| &instr.kind) {
+                Some(vil::InstructionKind::Jump(label)) => vec![label_to_block[label.0.as_str()]],
+                Some(vil::InstructionKind::JumpIf(_, l1, l2)) => {
+                    vec![label_to_block[l1.0.as_str()], label_to_block[l2.0.as_str()]]
+                }
+                _ if i + 1 < block_count => vec![i + 1],
+                _ => Vec::new(),
+            },
+        )
+        .collect();
+
+    let mut def: Vec<HashSet<u8>> = Vec::with_capacity(block_count);
+    let mut use_: Vec<HashSet<u8>> = Vec::with_capacity(block_count);
+    for block in &declaration.blocks {
+        let mut defined_so_far = HashSet::new();
+        let mut block_def = HashSet::new();
+        let mut block_use = HashSet::new();
+        for instruction in &block.instructions {
+            let (used, defined) = registers_read_and_written(&instruction.kind);
+            for r in used {
+                if !defined_so_far.contains(&r) {
+                    block_use.insert(r);
+                }
+            }
+            if let Some(r) = defined {
+                defined_so_far.insert(r);
+                block_def.insert(r);
+            }
+        }
+        def.push(block_def);
+        use_.push(block_use);
+    }
+
+    let mut live_in: Vec<HashSet<u8>> = vec![HashSet::new(); block_count];
+    let mut live_out: Vec<HashSet<u8>> = vec![HashSet::new(); block_count];
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for i in (0..block_count).rev() {
+            let mut new_out = HashSet::new();
+            for &successor in &successors[i] {
+                new_out.extend(live_in[successor].iter().copied());
+            }
+            if new_out != live_out[i] {
+                live_out[i] = new_out;
+                changed = true;
+            }
+
+            let mut new_in = use_[i].clone();
+            for r in &live_out[i] {
+                if !def[i].contains(r) {
+                    new_in.insert(*r);
+                }
+            }
+            if new_in != live_in[i] {
+                live_in[i] = new_in;
+                changed = true;
+            }
+        }
+    }
+
+    let mut first_def: HashMap<u8, usize> = HashMap::new();
+    let mut first_touch: HashMap<u8, usize> = HashMap::new();
+    let mut last_touch: HashMap<u8, usize> = HashMap::new();
+    let mut index = 0usize;
+    for block in &declaration.blocks {
+        for instruction in &block.instructions {
+            let (used, defined) = registers_read_and_written(&instruction.kind);
+            for r in used.iter().copied().chain(defined) {
+                first_touch.entry(r).or_insert(index);
+                last_touch.insert(r, index);
+            }
+            if let Some(r) = defined {
+                first_def.entry(r).or_insert(index);
+            }
+            index += 1;
+        }
+    }
+
+    let mut intervals = Vec::new();
+    for (&register, &last) in &last_touch {
+        if is_reserved(register) {
+            continue;
+        }
+
+        let start = *first_def.get(&register).unwrap_or(&first_touch[&register]);
+        let mut end = last;
+        for (i, (_, end_pos)) in block_bounds.iter().enumerate() {
+            if live_out[i].contains(&register) {
+                end = end.max(end_pos.saturating_sub(1));
+            }
+        }
+        intervals.push(Interval {
+            register,
+            start,
+            end,
+        });
+    }
+    intervals
+}
+
+/// Assigns every VIL register a function uses to a physical register or a stack spill slot, via
+/// linear-scan over live intervals computed across the whole function (not just within a block --
+/// see `compute_intervals`). Spilled registers are materialized as an explicit `Load` before each
+/// use and `Store` after each def, using `Register::scratch()`/`Register::scratch2()` as
+/// transients, since VIL instructions have no inline memory operands to spill into directly.
+/// `Register::ret()` and `Register::scratch2()` are reserved (see `is_reserved`) and pass through
+/// untouched, so this pass never hands either of those indices to an ordinary register.
+struct RegisterAllocator {
     register_count: u8,
+    assigned: HashMap<u8, u8>,
     spilled: HashMap<u8, vil::MemoryOffset>,
     current_stack_offset: i32,
+    // Spill slots freed by an interval that has since expired, available for a later spill to
+    // reuse instead of growing the stack frame further. Every slot is the same 8-byte size (VIL
+    // registers are uniformly one machine word, whether holding an integer or a double), so this
+    // is a single free-list rather than one bucketed by size.
+    free_slots: Vec<vil::MemoryOffset>,
 }
 
-impl RegisterSpiller {
+impl RegisterAllocator {
     fn new(register_count: u8) -> Self {
-        RegisterSpiller {
+        RegisterAllocator {
             register_count,
+            assigned: HashMap::new(),
             spilled: HashMap::new(),
             current_stack_offset: 0,
+            free_slots: Vec::new(),
         }
     }
 
-    fn spill(&mut self, program: &mut vil::Program) {
+    fn allocate(&mut self, program: &mut vil::Program) {
         for declaration in &mut program.declarations {
-            self.spilled.clear();
-            self.current_stack_offset = -(declaration.stack_frame_size + 8);
+            self.allocate_declaration(declaration);
+        }
+    }
+
+    fn allocate_declaration(&mut self, declaration: &mut vil::FunctionDeclaration) {
+        self.assigned = HashMap::new();
+        self.spilled = HashMap::new();
+        self.free_slots = Vec::new();
+        self.current_stack_offset = -(declaration.stack_frame_size + 8);
+
+        let mut intervals = compute_intervals(declaration);
+        intervals.sort_by_key(|interval| interval.start);
+
+        let reserved = [
+            vil::Register::ret().index(),
+            vil::Register::scratch2().index(),
+        ];
+        let mut free_registers: Vec<u8> = (0..self.register_count)
+            .rev()
+            .filter(|i| !reserved.contains(i))
+            .collect();
+        // Which physical register each currently-live interval owns -- unlike `self.assigned`,
+        // entries are removed as soon as an interval expires or is spilled, so a later interval
+        // can reuse the slot. `self.assigned` is write-once per register (beyond the reassignment
+        // a steal performs) since it's the final answer `rewrite_instruction` looks up.
+        let mut current_physical: HashMap<u8, u8> = HashMap::new();
+        let mut active: Vec<Interval> = Vec::new();
+        // The end point of every currently-live spilled register, so its stack slot can be
+        // returned to `free_slots` once nothing live still needs it -- the same expiry `active`
+        // gets, just for stack slots instead of physical registers.
+        let mut spilled_active: Vec<(u8, usize)> = Vec::new();
+
+        for interval in intervals {
+            let mut still_active = Vec::new();
+            for other in active {
+                if other.end < interval.start {
+                    let physical = current_physical.remove(&other.register).unwrap();
+                    free_registers.push(physical);
+                } else {
+                    still_active.push(other);
+                }
+            }
+            active = still_active;
 
-            for block in &mut declaration.blocks {
-                let mut new_instructions = Vec::new();
-                for instruction in &mut block.instructions {
-                    self.spill_instruction(&mut new_instructions, &instruction);
+            spilled_active.retain(|&(register, end)| {
+                if end < interval.start {
+                    let offset = self.spilled[&register];
+                    self.free_slots.push(offset);
+                    false
+                } else {
+                    true
                 }
-                block.instructions = new_instructions;
+            });
+
+            if let Some(physical) = free_registers.pop() {
+                current_physical.insert(interval.register, physical);
+                self.assigned.insert(interval.register, physical);
+                active.push(interval);
+                active.sort_by_key(|other| other.end);
+                continue;
             }
 
-            declaration.stack_frame_size = -(self.current_stack_offset + 8);
+            match active.last().map(|candidate| candidate.end) {
+                Some(candidate_end) if candidate_end > interval.end => {
+                    let candidate = active.pop().unwrap();
+                    let physical = current_physical.remove(&candidate.register).unwrap();
+                    self.assigned.remove(&candidate.register);
+                    let offset = self.claim_stack_offset();
+                    self.spilled.insert(candidate.register, offset);
+                    spilled_active.push((candidate.register, candidate.end));
+
+                    current_physical.insert(interval.register, physical);
+                    self.assigned.insert(interval.register, physical);
+                    active.push(interval);
+                    active.sort_by_key(|other| other.end);
+                }
+                _ => {
+                    let offset = self.claim_stack_offset();
+                    self.spilled.insert(interval.register, offset);
+                    spilled_active.push((interval.register, interval.end));
+                }
+            }
         }
+
+        for block in &mut declaration.blocks {
+            let mut new_instructions = Vec::new();
+            for instruction in &block.instructions {
+                self.rewrite_instruction(&mut new_instructions, instruction);
+            }
+            block.instructions = new_instructions;
+        }
+
+        declaration.stack_frame_size = -(self.current_stack_offset + 8);
     }
 
-    fn spill_instruction(
-        &mut self,
+    /// Reuses a slot freed by an expired spill if one is available, so spills with
+    /// non-overlapping lifetimes share stack space instead of each growing the frame.
+    fn claim_stack_offset(&mut self) -> i32 {
+        if let Some(offset) = self.free_slots.pop() {
+            return offset;
+        }
+        let offset = self.current_stack_offset;
+        self.current_stack_offset -= 8;
+        offset
+    }
+
+    /// Resolves a register occurrence being read: `Register::ret()`/`Register::scratch2()` pass
+    /// through untouched, an allocated register is renamed to its physical index, and a spilled
+    /// one is loaded into a transient (`index` 0 picks `Register::scratch()`, 1 picks
+    /// `Register::scratch2()`, so an instruction reading two spilled registers at once doesn't let
+    /// one reload clobber the other).
+    fn resolve_read(
+        &self,
+        destination: &mut Vec<vil::Instruction>,
+        r: &vil::Register,
+        index: u8,
+    ) -> vil::Register {
+        if is_reserved(r.index()) {
+            return r.clone();
+        }
+        if let Some(physical) = self.assigned.get(&r.index()) {
+            return vil::Register::new(*physical);
+        }
+
+        let offset = *self.spilled.get(&r.index()).unwrap();
+        let scratch = if index == 0 {
+            vil::Register::scratch()
+        } else {
+            vil::Register::scratch2()
+        };
+        destination.push(vil::Instruction {
+            kind: vil::InstructionKind::Load(scratch.clone(), offset),
+            comment: String::from("spilled"),
+        });
+        scratch
+    }
+
+    /// Resolves a register occurrence being written: the register the instruction's own
+    /// destination operand should use (a renamed physical register, a pass-through reserved
+    /// register, or `Register::scratch()` as a transient for a spilled one -- see
+    /// `maybe_store_write`).
+    fn resolve_write(&self, r: &vil::Register) -> vil::Register {
+        if is_reserved(r.index()) {
+            return r.clone();
+        }
+        if let Some(physical) = self.assigned.get(&r.index()) {
+            return vil::Register::new(*physical);
+        }
+        vil::Register::scratch()
+    }
+
+    fn maybe_store_write(
+        &self,
+        destination: &mut Vec<vil::Instruction>,
+        r: &vil::Register,
+        actual: vil::Register,
+    ) {
+        if is_reserved(r.index()) || self.assigned.contains_key(&r.index()) {
+            return;
+        }
+
+        let offset = *self.spilled.get(&r.index()).unwrap();
+        destination.push(vil::Instruction {
+            kind: vil::InstructionKind::Store(actual, offset),
+            comment: String::from("spilled"),
+        });
+    }
+
+    fn rewrite_instruction(
+        &self,
         destination: &mut Vec<vil::Instruction>,
         instruction: &vil::Instruction,
     ) {
         use vil::InstructionKind::*;
+        let comment = instruction.comment.clone();
         match &instruction.kind {
             Binary(op, r1, r2, r3) => {
-                let real_r3 = self.maybe_spill_read_register(destination, &r3, 0);
-                let real_r2 = self.maybe_spill_read_register(destination, &r2, 1);
-
-                if r1.index() >= self.register_count {
-                    let scratch = vil::Register::scratch();
-                    destination.push(vil::Instruction {
-                        kind: Binary(*op, scratch, real_r2, real_r3),
-                        comment: instruction.comment.clone(),
-                    });
-
-                    self.spill_write_register(destination, &r1, scratch);
-                } else {
-                    destination.push(vil::Instruction {
-                        kind: Binary(*op, r1.clone(), real_r2, real_r3),
-                        comment: instruction.comment.clone(),
-                    });
-                }
+                let real_r3 = self.resolve_read(destination, r3, 0);
+                let real_r2 = self.resolve_read(destination, r2, 1);
+                let real_r1 = self.resolve_write(r1);
+                destination.push(vil::Instruction {
+                    kind: Binary(*op, real_r1.clone(), real_r2, real_r3),
+                    comment,
+                });
+                self.maybe_store_write(destination, r1, real_r1);
             }
             Unary(op, r1, r2) => {
-                let real_r2 = self.maybe_spill_read_register(destination, &r2, 0);
-                if r1.index() >= self.register_count {
-                    let scratch = vil::Register::scratch();
-                    destination.push(vil::Instruction {
-                        kind: Unary(*op, scratch, real_r2),
-                        comment: instruction.comment.clone(),
-                    });
-
-                    self.spill_write_register(destination, &r1, scratch);
-                } else {
-                    destination.push(vil::Instruction {
-                        kind: Unary(*op, r1.clone(), real_r2),
-                        comment: instruction.comment.clone(),
-                    });
-                }
+                let real_r2 = self.resolve_read(destination, r2, 0);
+                let real_r1 = self.resolve_write(r1);
+                destination.push(vil::Instruction {
+                    kind: Unary(*op, real_r1.clone(), real_r2),
+                    comment,
+                });
+                self.maybe_store_write(destination, r1, real_r1);
             }
             Call {
                 destination: r,
@@ -588,138 +1374,124 @@ impl RegisterSpiller {
                 offsets,
                 variadic,
             } => {
-                if r.index() >= self.register_count {
-                    let scratch = vil::Register::scratch();
-                    destination.push(vil::Instruction {
-                        kind: Call {
-                            destination: scratch,
-                            label: label.clone(),
-                            offsets: offsets.clone(),
-                            variadic: *variadic,
-                        },
-                        comment: instruction.comment.clone(),
-                    });
-
-                    self.spill_write_register(destination, &r, scratch);
-                } else {
-                    destination.push(instruction.clone());
-                }
+                let real_r = self.resolve_write(r);
+                destination.push(vil::Instruction {
+                    kind: Call {
+                        destination: real_r.clone(),
+                        label: label.clone(),
+                        offsets: offsets.clone(),
+                        variadic: *variadic,
+                    },
+                    comment,
+                });
+                self.maybe_store_write(destination, r, real_r);
             }
             Cmp(r1, r2) => {
-                let real_r2 = self.maybe_spill_read_register(destination, &r2, 0);
-                let real_r1 = self.maybe_spill_read_register(destination, &r1, 1);
-
+                let real_r2 = self.resolve_read(destination, r2, 0);
+                let real_r1 = self.resolve_read(destination, r1, 1);
                 destination.push(vil::Instruction {
                     kind: Cmp(real_r1, real_r2),
-                    comment: instruction.comment.clone(),
+                    comment,
+                });
+            }
+            FCmp(r1, r2) => {
+                let real_r2 = self.resolve_read(destination, r2, 0);
+                let real_r1 = self.resolve_read(destination, r1, 1);
+                destination.push(vil::Instruction {
+                    kind: FCmp(real_r1, real_r2),
+                    comment,
                 });
             }
+            CmpOrdering(r1, r2, r3) => {
+                let real_r3 = self.resolve_read(destination, r3, 0);
+                let real_r2 = self.resolve_read(destination, r2, 1);
+                let real_r1 = self.resolve_write(r1);
+                destination.push(vil::Instruction {
+                    kind: CmpOrdering(real_r1.clone(), real_r2, real_r3),
+                    comment,
+                });
+                self.maybe_store_write(destination, r1, real_r1);
+            }
+            FCmpOrdering(r1, r2, r3) => {
+                let real_r3 = self.resolve_read(destination, r3, 0);
+                let real_r2 = self.resolve_read(destination, r2, 1);
+                let real_r1 = self.resolve_write(r1);
+                destination.push(vil::Instruction {
+                    kind: FCmpOrdering(real_r1.clone(), real_r2, real_r3),
+                    comment,
+                });
+                self.maybe_store_write(destination, r1, real_r1);
+            }
             Load(r, offset) => {
-                if r.index() >= self.register_count {
-                    let scratch = vil::Register::scratch();
-                    destination.push(vil::Instruction {
-                        kind: Load(scratch, *offset),
-                        comment: instruction.comment.clone(),
-                    });
-
-                    self.spill_write_register(destination, &r, scratch);
-                } else {
-                    destination.push(instruction.clone());
-                }
+                let real_r = self.resolve_write(r);
+                destination.push(vil::Instruction {
+                    kind: Load(real_r.clone(), *offset),
+                    comment,
+                });
+                self.maybe_store_write(destination, r, real_r);
             }
             Move(r1, r2) => {
-                let real_r2 = self.maybe_spill_read_register(destination, &r2, 0);
-                if r1.index() >= self.register_count {
-                    let scratch = vil::Register::scratch();
-                    destination.push(vil::Instruction {
-                        kind: Move(scratch, real_r2),
-                        comment: instruction.comment.clone(),
-                    });
-
-                    self.spill_write_register(destination, &r1, scratch);
-                } else {
-                    destination.push(vil::Instruction {
-                        kind: Move(r1.clone(), real_r2),
-                        comment: instruction.comment.clone(),
-                    });
-                }
+                let real_r2 = self.resolve_read(destination, r2, 0);
+                let real_r1 = self.resolve_write(r1);
+                destination.push(vil::Instruction {
+                    kind: Move(real_r1.clone(), real_r2),
+                    comment,
+                });
+                self.maybe_store_write(destination, r1, real_r1);
             }
             Set(r, imm) => {
-                if r.index() >= self.register_count {
-                    let scratch = vil::Register::scratch();
-                    destination.push(vil::Instruction {
-                        kind: Set(scratch, imm.clone()),
-                        comment: instruction.comment.clone(),
-                    });
-
-                    self.spill_write_register(destination, &r, scratch);
-                } else {
-                    destination.push(instruction.clone());
-                }
+                let real_r = self.resolve_write(r);
+                destination.push(vil::Instruction {
+                    kind: Set(real_r.clone(), imm.clone()),
+                    comment,
+                });
+                self.maybe_store_write(destination, r, real_r);
             }
             Store(r, offset) => {
-                let real_r = self.maybe_spill_read_register(destination, &r, 0);
+                let real_r = self.resolve_read(destination, r, 0);
                 destination.push(vil::Instruction {
                     kind: Store(real_r, *offset),
-                    comment: instruction.comment.clone(),
+                    comment,
                 });
             }
+            Syscall {
+                destination: r,
+                number,
+                offsets,
+            } => {
+                let real_r = self.resolve_write(r);
+                destination.push(vil::Instruction {
+                    kind: Syscall {
+                        destination: real_r.clone(),
+                        number: *number,
+                        offsets: offsets.clone(),
+                    },
+                    comment,
+                });
+                self.maybe_store_write(destination, r, real_r);
+            }
             // Explicitly list other instructions so that if I add another instruction I'll be
             // forced to consider it here.
             Jump(..) | JumpIf(..) => {
                 destination.push(instruction.clone());
             }
+            JumpOrdering(r, less_label, equal_label, greater_label) => {
+                let real_r = self.resolve_read(destination, r, 0);
+                destination.push(vil::Instruction {
+                    kind: JumpOrdering(
+                        real_r,
+                        less_label.clone(),
+                        equal_label.clone(),
+                        greater_label.clone(),
+                    ),
+                    comment,
+                });
+            }
+            Phi(..) => panic!(
+                "internal error: phi nodes must be lowered by ssa::out_of_ssa before register allocation"
+            ),
         };
     }
-
-    fn maybe_spill_read_register(
-        &mut self,
-        destination: &mut Vec<vil::Instruction>,
-        r: &vil::Register,
-        index: u8,
-    ) -> vil::Register {
-        if r.index() >= self.register_count {
-            let offset = self.spilled.get(&r.index()).unwrap();
-            let scratch = if index == 0 {
-                vil::Register::scratch()
-            } else {
-                vil::Register::scratch2()
-            };
-            destination.push(vil::Instruction {
-                kind: vil::InstructionKind::Load(scratch.clone(), *offset),
-                comment: String::from("spilled"),
-            });
-            scratch
-        } else {
-            r.clone()
-        }
-    }
-
-    fn spill_write_register(
-        &mut self,
-        destination: &mut Vec<vil::Instruction>,
-        r: &vil::Register,
-        scratch: vil::Register,
-    ) {
-        let offset = if let Some(offset) = self.spilled.get(&r.index()) {
-            *offset
-        } else {
-            let offset = self.claim_stack_offset();
-            self.spilled.insert(r.index(), offset);
-            offset
-        };
-
-        destination.push(vil::Instruction {
-            kind: vil::InstructionKind::Store(scratch, offset),
-            comment: String::from("spilled"),
-        });
-    }
-
-    fn claim_stack_offset(&mut self) -> i32 {
-        let ret = self.current_stack_offset;
-        self.current_stack_offset -= 8;
-        ret
-    }
 }
 
 fn get_comparison_instruction(
@@ -743,3 +1515,304 @@ fn get_comparison_instruction(
         NotEquals => vil::InstructionKind::JumpIf(vil::JumpCondition::Neq, true_label, false_label),
     }
 }
+
+/// The three-way outcome a `CmpOrdering`/`FCmpOrdering` computes for a pair of operands, matching
+/// the three labels a `JumpOrdering` dispatches to. See `comparison_matches`.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Ordering3 {
+    Less,
+    Equal,
+    Greater,
+}
+
+/// Whether `op`, applied to `generate_comparison_chain`'s shared operand pair, holds when that
+/// pair's relative order is `state` -- e.g. `LessThanEquals` holds for both `Less` and `Equal`.
+/// Used to map each of a `JumpOrdering`'s three dispatch labels onto whichever of the chain's two
+/// arms the resulting order actually satisfies.
+fn comparison_matches(op: common::ComparisonOpType, state: Ordering3) -> bool {
+    use common::ComparisonOpType::*;
+    match op {
+        Equals => state == Ordering3::Equal,
+        NotEquals => state != Ordering3::Equal,
+        GreaterThan => state == Ordering3::Greater,
+        GreaterThanEquals => state == Ordering3::Greater || state == Ordering3::Equal,
+        LessThan => state == Ordering3::Less,
+        LessThanEquals => state == Ordering3::Less || state == Ordering3::Equal,
+    }
+}
+
+/// Whether `expr` is the literal `Boolean(value)`, used by `generate_expression_as_condition` to
+/// recognize the shape the analyzer desugars `and`/`or` expressions into.
+fn is_boolean_literal(expr: &ast::Expression, value: bool) -> bool {
+    matches!(&expr.kind, ast::ExpressionKind::Boolean(x) if *x == value)
+}
+
+/// Recognizes `if a <op1> b { ... } else if a <op2> b { ... } else { ... }` -- an `else if` whose
+/// condition is a `Comparison` over the identical operand pair, in the identical order, as the
+/// outer `if`'s own condition. `match_comparison_chain` returns the pieces `generate_comparison_chain`
+/// needs to lower the whole thing without evaluating `a`/`b` twice; anything else (a plain `if`,
+/// or an `else if` comparing different operands) returns `None` and falls back to the ordinary
+/// lowering in `generate_if_statement`.
+fn match_comparison_chain(
+    stmt: &ast::IfStatement,
+) -> Option<(&ast::ComparisonExpression, &ast::IfStatement, &ast::ComparisonExpression)> {
+    let outer_cmp = match &stmt.condition.kind {
+        ast::ExpressionKind::Comparison(cmp) => cmp,
+        _ => return None,
+    };
+    let inner_stmt = match stmt.else_body.as_slice() {
+        [ast::Statement::If(inner_stmt)] => inner_stmt,
+        _ => return None,
+    };
+    let inner_cmp = match &inner_stmt.condition.kind {
+        ast::ExpressionKind::Comparison(cmp) => cmp,
+        _ => return None,
+    };
+
+    if symbol_name(&outer_cmp.left) == symbol_name(&inner_cmp.left)
+        && symbol_name(&outer_cmp.right) == symbol_name(&inner_cmp.right)
+        && symbol_name(&outer_cmp.left).is_some()
+        && symbol_name(&outer_cmp.right).is_some()
+    {
+        Some((outer_cmp, inner_stmt, inner_cmp))
+    } else {
+        None
+    }
+}
+
+/// Returns `expr`'s unique name if it's a bare `Symbol` reference, for comparing whether two
+/// expressions denote the same variable (see `match_comparison_chain`).
+fn symbol_name(expr: &ast::Expression) -> Option<&str> {
+    match &expr.kind {
+        ast::ExpressionKind::Symbol(s) => Some(&s.unique_name),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn block(name: &str, instructions: Vec<vil::InstructionKind>) -> vil::Block {
+        vil::Block {
+            name: String::from(name),
+            instructions: instructions
+                .into_iter()
+                .map(|kind| vil::Instruction {
+                    kind,
+                    comment: String::new(),
+                })
+                .collect(),
+        }
+    }
+
+    fn declaration(name: &str, blocks: Vec<vil::Block>) -> vil::FunctionDeclaration {
+        vil::FunctionDeclaration {
+            name: String::from(name),
+            blocks,
+            stack_frame_size: 0,
+            parameters: Vec::new(),
+        }
+    }
+
+    // Reserved registers (see `is_reserved`) pass through the allocator unchanged and can
+    // legitimately sit above `bound` -- callers only care that *non-reserved* registers were
+    // packed into the budget.
+    fn all_registers_within_bound(declaration: &vil::FunctionDeclaration, bound: u8) -> bool {
+        declaration.blocks.iter().all(|block| {
+            block.instructions.iter().all(|instruction| {
+                let (used, defined) = registers_read_and_written(&instruction.kind);
+                used.iter()
+                    .chain(defined.iter())
+                    .all(|r| is_reserved(*r) || *r < bound)
+            })
+        })
+    }
+
+    #[test]
+    fn packs_non_overlapping_registers_into_few_physical_registers() {
+        // R0 = set 1; R1 = set 2; R2 = add R0, R1 (R0/R1 dead after this); R3 = set 3;
+        // R4 = add R2, R3. R0 and R1 are both still live at the instruction that defines R2
+        // (their last use coincides with its first def), so three registers are simultaneously
+        // relevant there; a budget of 3 should suffice without any spilling.
+        let mut decl = declaration(
+            "f",
+            vec![block(
+                "entry",
+                vec![
+                    vil::InstructionKind::Set(vil::Register::new(0), vil::Immediate::Integer(1)),
+                    vil::InstructionKind::Set(vil::Register::new(1), vil::Immediate::Integer(2)),
+                    vil::InstructionKind::Binary(
+                        vil::BinaryOp::Add,
+                        vil::Register::new(2),
+                        vil::Register::new(0),
+                        vil::Register::new(1),
+                    ),
+                    vil::InstructionKind::Set(vil::Register::new(3), vil::Immediate::Integer(3)),
+                    vil::InstructionKind::Binary(
+                        vil::BinaryOp::Add,
+                        vil::Register::new(4),
+                        vil::Register::new(2),
+                        vil::Register::new(3),
+                    ),
+                ],
+            )],
+        );
+
+        let mut allocator = RegisterAllocator::new(3);
+        allocator.allocate_declaration(&mut decl);
+
+        assert!(all_registers_within_bound(&decl, 3));
+        // No spill slots should have been needed given the register budget is just tight enough.
+        assert_eq!(decl.stack_frame_size, 0);
+    }
+
+    #[test]
+    fn spills_when_too_many_registers_are_live_at_once() {
+        // Five registers defined up front and only consumed afterward: every one of them is live
+        // simultaneously once the fifth is defined, so with a budget of 2 physical registers, at least one
+        // spill is unavoidable. Register numbers start at 20 to steer clear of index 7 and 13,
+        // which are reserved for the scratch/return registers and are never spilled or
+        // renumbered by the allocator.
+        let mut instructions = Vec::new();
+        for i in 0..5u8 {
+            instructions.push(vil::InstructionKind::Set(
+                vil::Register::new(20 + i),
+                vil::Immediate::Integer(i64::from(i)),
+            ));
+        }
+        instructions.push(vil::InstructionKind::Binary(
+            vil::BinaryOp::Add,
+            vil::Register::new(25),
+            vil::Register::new(20),
+            vil::Register::new(21),
+        ));
+        instructions.push(vil::InstructionKind::Binary(
+            vil::BinaryOp::Add,
+            vil::Register::new(26),
+            vil::Register::new(22),
+            vil::Register::new(23),
+        ));
+        instructions.push(vil::InstructionKind::Binary(
+            vil::BinaryOp::Add,
+            vil::Register::new(27),
+            vil::Register::new(24),
+            vil::Register::new(26),
+        ));
+
+        let mut decl = declaration("f", vec![block("entry", instructions)]);
+
+        let mut allocator = RegisterAllocator::new(2);
+        allocator.allocate_declaration(&mut decl);
+
+        assert!(all_registers_within_bound(&decl, 2));
+        assert!(decl.stack_frame_size > 0);
+    }
+
+    #[test]
+    fn extends_live_range_across_a_conditional_branch() {
+        // R0 is defined in `entry`, not touched in `left`, and used only in `merge` -- reached
+        // via `left`'s unconditional jump. Its live range has to span `left` too, or a naive
+        // allocator could steal its physical register for something else defined inside `left`.
+        let entry = block(
+            "entry",
+            vec![
+                vil::InstructionKind::Set(vil::Register::new(0), vil::Immediate::Integer(1)),
+                vil::InstructionKind::Set(vil::Register::new(1), vil::Immediate::Integer(2)),
+                vil::InstructionKind::Cmp(vil::Register::new(1), vil::Register::new(1)),
+                vil::InstructionKind::JumpIf(
+                    vil::JumpCondition::Eq,
+                    vil::Label(String::from("left")),
+                    vil::Label(String::from("merge")),
+                ),
+            ],
+        );
+        let left = block(
+            "left",
+            vec![
+                vil::InstructionKind::Set(vil::Register::new(2), vil::Immediate::Integer(3)),
+                vil::InstructionKind::Jump(vil::Label(String::from("merge"))),
+            ],
+        );
+        let merge = block(
+            "merge",
+            vec![vil::InstructionKind::Unary(
+                vil::UnaryOp::Negate,
+                vil::Register::new(3),
+                vil::Register::new(0),
+            )],
+        );
+
+        let mut decl = declaration("f", vec![entry, left, merge]);
+
+        // A budget of 2 forces register 2 (defined inside `left`) to compete with register 0's
+        // still-live value for a physical slot; if register 0's interval weren't extended across
+        // `left`, the allocator could wrongly let register 2 reuse its slot.
+        let mut allocator = RegisterAllocator::new(2);
+        allocator.allocate_declaration(&mut decl);
+
+        assert!(all_registers_within_bound(&decl, 2));
+    }
+
+    #[test]
+    fn spills_the_longer_lived_interval_when_a_physical_register_is_needed() {
+        // R20 lives from instruction 0 through instruction 3; R21 lives only from instruction 1
+        // through instruction 2. With a budget of one physical register, R21 arrives while R20 is
+        // still active and ending later, so R20 -- not R21 -- is the one that should be moved to
+        // a stack slot, per the "spill whichever interval ends later" rule.
+        let instructions = vec![
+            vil::InstructionKind::Set(vil::Register::new(20), vil::Immediate::Integer(1)),
+            vil::InstructionKind::Set(vil::Register::new(21), vil::Immediate::Integer(2)),
+            vil::InstructionKind::Binary(
+                vil::BinaryOp::Add,
+                vil::Register::new(22),
+                vil::Register::new(20),
+                vil::Register::new(21),
+            ),
+            vil::InstructionKind::Unary(
+                vil::UnaryOp::Negate,
+                vil::Register::new(23),
+                vil::Register::new(20),
+            ),
+        ];
+        let mut decl = declaration("f", vec![block("entry", instructions)]);
+
+        let mut allocator = RegisterAllocator::new(1);
+        allocator.allocate_declaration(&mut decl);
+
+        assert!(allocator.spilled.contains_key(&20));
+        assert!(allocator.assigned.contains_key(&21));
+        assert!(all_registers_within_bound(&decl, 1));
+    }
+
+    #[test]
+    fn reuses_a_freed_spill_slot_for_a_later_non_overlapping_temporary() {
+        // With no physical registers at all (a budget of zero), every register spills
+        // immediately, which isolates `free_slots` from the rest of the allocator: R20 and R30's
+        // live ranges both end before R21 is born, freeing both of their slots, and R21 and R31
+        // (which overlap at R31's defining instruction) each take one of those, so these four
+        // spills should only ever need two distinct stack slots between them.
+        let instructions = vec![
+            vil::InstructionKind::Set(vil::Register::new(20), vil::Immediate::Integer(1)),
+            vil::InstructionKind::Unary(
+                vil::UnaryOp::Negate,
+                vil::Register::new(30),
+                vil::Register::new(20),
+            ),
+            vil::InstructionKind::Set(vil::Register::new(21), vil::Immediate::Integer(2)),
+            vil::InstructionKind::Unary(
+                vil::UnaryOp::Negate,
+                vil::Register::new(31),
+                vil::Register::new(21),
+            ),
+        ];
+        let mut decl = declaration("f", vec![block("entry", instructions)]);
+
+        let mut allocator = RegisterAllocator::new(0);
+        allocator.allocate_declaration(&mut decl);
+
+        assert_eq!(allocator.spilled.len(), 4);
+        let distinct_offsets: std::collections::HashSet<_> = allocator.spilled.values().collect();
+        assert_eq!(distinct_offsets.len(), 2);
+    }
+}
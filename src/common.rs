@@ -2,21 +2,25 @@
 // Use of this source code is governed by an MIT-style license that can be
 // found in the LICENSE file.
 
+use serde::{Deserialize, Serialize};
 use std::fmt;
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Location {
     pub file: String,
     pub column: u32,
     pub line: u32,
+    /// The offset, in bytes, of this location from the start of the file.
+    pub byte_offset: usize,
 }
 
 impl Location {
-    pub fn empty() -> Self {
+    pub const fn empty() -> Self {
         Location {
             file: String::new(),
             column: 0,
             line: 0,
+            byte_offset: 0,
         }
     }
 }
@@ -31,9 +35,53 @@ impl fmt::Display for Location {
     }
 }
 
+/// The `start`..`end` range of source positions an AST node was parsed from, so the analyzer and
+/// codegen can point diagnostics -- or future tooling -- back at the node without re-parsing the
+/// program. This is the same information `ptree::Expression` and `errors::VeniceError` already
+/// track with a `location`/`end_location` pair; `Span` just gives that pair a name so `ast.rs` can
+/// attach it once to every node instead of repeating the two fields everywhere.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Span {
+    pub start: Location,
+    pub end: Location,
+}
+
+impl Span {
+    pub fn new(start: Location, end: Location) -> Self {
+        Span { start, end }
+    }
+
+    /// A zero-width span at a single point, for nodes only one token wide.
+    pub fn at(location: Location) -> Self {
+        Span {
+            end: location.clone(),
+            start: location,
+        }
+    }
+
+    /// An empty span for synthetic nodes with no source text of their own, e.g. builtin symbols or
+    /// the placeholder nodes the analyzer substitutes after a type error.
+    pub const fn empty() -> Self {
+        Span {
+            start: Location::empty(),
+            end: Location::empty(),
+        }
+    }
+}
+
+// Where a node came from is never part of its meaning, so two otherwise-equal nodes are equal
+// regardless of their spans.
+impl PartialEq for Span {
+    fn eq(&self, _other: &Self) -> bool {
+        true
+    }
+}
+
+impl Eq for Span {}
+
 // The parse tree and abstract syntax tree use the same op types, so they are defined here.
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
 pub enum BinaryOpType {
     Add,
     And,
@@ -45,7 +93,7 @@ pub enum BinaryOpType {
     Subtract,
 }
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
 pub enum ComparisonOpType {
     Equals,
     GreaterThan,
@@ -55,7 +103,7 @@ pub enum ComparisonOpType {
     NotEquals,
 }
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
 pub enum UnaryOpType {
     Negate,
     Not,
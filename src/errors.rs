@@ -3,18 +3,204 @@
 // found in the LICENSE file.
 
 use super::common;
+use std::fmt;
+
+/// How serious a diagnostic is. Only `Error` diagnostics cause compilation to fail; the others
+/// exist so the pipeline has somewhere to put non-fatal feedback.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+    Note,
+    Help,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+            Severity::Note => "note",
+            Severity::Help => "help",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+/// A secondary span attached to a diagnostic, e.g. pointing back at the prior definition of a
+/// symbol that a later declaration conflicts with.
+#[derive(Clone, Debug)]
+pub struct Label {
+    pub message: String,
+    pub location: common::Location,
+    pub end_location: Option<common::Location>,
+}
 
 #[derive(Clone, Debug)]
 pub struct VeniceError {
     pub message: String,
+    pub severity: Severity,
     pub location: common::Location,
+    /// The location of the last token of the span the error covers, if the caller knows it.
+    /// When present, diagnostics can underline the whole span instead of just `location`.
+    pub end_location: Option<common::Location>,
+    /// Secondary spans, e.g. "previously defined here", rendered under the primary span.
+    pub labels: Vec<Label>,
+    /// Free-form notes printed after the span, with no location of their own.
+    pub notes: Vec<String>,
 }
 
 impl VeniceError {
     pub fn new(message: &str, location: common::Location) -> Self {
         VeniceError {
+            message: String::from(message),
+            severity: Severity::Error,
+            location,
+            end_location: None,
+            labels: Vec::new(),
+            notes: Vec::new(),
+        }
+    }
+
+    pub fn new_with_span(
+        message: &str,
+        location: common::Location,
+        end_location: common::Location,
+    ) -> Self {
+        VeniceError {
+            message: String::from(message),
+            severity: Severity::Error,
+            location,
+            end_location: Some(end_location),
+            labels: Vec::new(),
+            notes: Vec::new(),
+        }
+    }
+
+    /// Like `new`, but for non-fatal feedback that shouldn't fail compilation.
+    pub fn new_warning(message: &str, location: common::Location) -> Self {
+        VeniceError {
+            message: String::from(message),
+            severity: Severity::Warning,
+            location,
+            end_location: None,
+            labels: Vec::new(),
+            notes: Vec::new(),
+        }
+    }
+
+    /// Attaches a secondary labeled span, e.g. the prior definition of a symbol being redefined.
+    pub fn with_label(
+        mut self,
+        message: &str,
+        location: common::Location,
+        end_location: Option<common::Location>,
+    ) -> Self {
+        self.labels.push(Label {
             message: String::from(message),
             location,
+            end_location,
+        });
+        self
+    }
+
+    /// Attaches a free-form note with no location of its own.
+    pub fn with_note(mut self, note: &str) -> Self {
+        self.notes.push(String::from(note));
+        self
+    }
+
+    /// Renders this diagnostic as a single-line JSON object, for `--error-format=json`. The
+    /// caller is expected to print one of these per line so that diagnostics can be consumed
+    /// incrementally, the same way compiletest consumes rustc's JSON diagnostic stream.
+    pub fn to_json(&self) -> String {
+        let end = self
+            .end_location
+            .as_ref()
+            .map(|location| location.byte_offset)
+            .unwrap_or(self.location.byte_offset);
+        format!(
+            "{{\"message\": {}, \"severity\": {}, \"file\": {}, \"line\": {}, \"column\": {}, \"byte_start\": {}, \"byte_end\": {}}}",
+            json_escape(&self.message),
+            json_escape(&self.severity.to_string()),
+            json_escape(&self.location.file),
+            self.location.line,
+            self.location.column,
+            self.location.byte_offset,
+            end,
+        )
+    }
+
+    /// Renders this diagnostic in the classic caret form: the message, the offending source
+    /// line with a `^^^^` underline under the primary span, and `---` underlines with labels
+    /// under any secondary spans, followed by free-form notes. `source` is the full text of the
+    /// file the diagnostic's locations refer to.
+    pub fn render(&self, source: &str) -> String {
+        let mut out = format!("{}: {} ({})\n", self.severity, self.message, self.location);
+        if let Some(snippet) = render_span(source, &self.location, self.end_location.as_ref(), '^')
+        {
+            out.push_str(&snippet);
+        }
+
+        for label in &self.labels {
+            out.push_str(&format!("note: {} ({})\n", label.message, label.location));
+            if let Some(snippet) =
+                render_span(source, &label.location, label.end_location.as_ref(), '-')
+            {
+                out.push_str(&snippet);
+            }
+        }
+
+        for note in &self.notes {
+            out.push_str(&format!("note: {}\n", note));
+        }
+
+        out
+    }
+}
+
+/// Renders the source line that `location` falls on, with an underline of `underline_char`
+/// beneath the span from `location` to `end_location` (if they share a line; otherwise just
+/// beneath `location` itself). Returns `None` if `location`'s line isn't in `source`, which can
+/// happen for locations synthesized without a real source file, like `Location::empty()`.
+fn render_span(
+    source: &str,
+    location: &common::Location,
+    end_location: Option<&common::Location>,
+    underline_char: char,
+) -> Option<String> {
+    let line_index = (location.line as usize).checked_sub(1)?;
+    let line_text = source.lines().nth(line_index)?;
+
+    let start_column = location.column as usize;
+    let end_column = match end_location {
+        Some(end) if end.line == location.line => end.column as usize,
+        _ => start_column,
+    };
+    let underline_width = end_column.saturating_sub(start_column) + 1;
+
+    Some(format!(
+        "{}\n{}{}\n",
+        line_text,
+        " ".repeat(start_column.saturating_sub(1)),
+        underline_char.to_string().repeat(underline_width)
+    ))
+}
+
+/// Escapes a string as a JSON string literal, including the surrounding quotes.
+fn json_escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            _ => out.push(c),
         }
     }
+    out.push('"');
+    out
 }
@@ -0,0 +1,443 @@
+// Copyright 2022 The Venice Authors. All rights reserved.
+// Use of this source code is governed by an MIT-style license that can be
+// found in the LICENSE file.
+//
+// A tree-walking evaluator that executes a parsed Venice program directly off of the `ptree`,
+// giving `venice` an interpreted execution path alongside ahead-of-time compilation.
+
+use super::common;
+use super::ptree;
+use std::collections::HashMap;
+use std::fmt;
+
+/// A runtime value produced by the evaluator.
+#[derive(Clone, Debug)]
+pub enum Object {
+    Integer(i64),
+    Boolean(bool),
+    Str(String),
+    List(Vec<Object>),
+    Null,
+}
+
+impl fmt::Display for Object {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Object::Integer(x) => write!(f, "{}", x),
+            Object::Boolean(x) => write!(f, "{}", x),
+            Object::Str(x) => write!(f, "{}", x),
+            Object::List(items) => {
+                write!(f, "[")?;
+                for (i, item) in items.iter().enumerate() {
+                    if i != 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", item)?;
+                }
+                write!(f, "]")
+            }
+            Object::Null => write!(f, "null"),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum EvalError {
+    TypeError(String),
+    UndefinedVariable(String),
+    UndefinedFunction(String),
+    AssertionFailed(common::Location),
+    // `Break`, `Continue`, and `Return` are never returned to the caller of `evaluate`; they are
+    // caught by `evaluate_while_statement` and `call_function` respectively. Propagating them as
+    // errors lets them unwind through `?` from the block/statement evaluator without threading a
+    // separate "did we break/return" flag through every statement.
+    Break,
+    Continue,
+    Return(Object),
+}
+
+/// Evaluates a program by calling its `main` function.
+pub fn evaluate(program: &ptree::Program) -> Result<(), EvalError> {
+    let mut evaluator = Evaluator::new(program);
+    evaluator.call_function("main", Vec::new())?;
+    Ok(())
+}
+
+struct Evaluator<'a> {
+    functions: HashMap<String, &'a ptree::FunctionDeclaration>,
+    scopes: Vec<HashMap<String, Object>>,
+}
+
+impl<'a> Evaluator<'a> {
+    fn new(program: &'a ptree::Program) -> Self {
+        let mut functions = HashMap::new();
+        for declaration in &program.declarations {
+            if let ptree::Declaration::Function(d) = declaration {
+                functions.insert(d.name.clone(), d);
+            }
+        }
+
+        Evaluator {
+            functions,
+            scopes: Vec::new(),
+        }
+    }
+
+    fn call_function(&mut self, name: &str, arguments: Vec<Object>) -> Result<Object, EvalError> {
+        let declaration = *self
+            .functions
+            .get(name)
+            .ok_or_else(|| EvalError::UndefinedFunction(String::from(name)))?;
+
+        let mut scope = HashMap::new();
+        for (parameter, argument) in declaration.parameters.iter().zip(arguments) {
+            scope.insert(parameter.name.clone(), argument);
+        }
+
+        self.scopes.push(scope);
+        let result = match self.evaluate_block(&declaration.body) {
+            Ok(()) => Ok(Object::Null),
+            Err(EvalError::Return(value)) => Ok(value),
+            Err(e) => Err(e),
+        };
+        self.scopes.pop();
+        result
+    }
+
+    fn evaluate_block(&mut self, body: &[ptree::Statement]) -> Result<(), EvalError> {
+        for statement in body {
+            self.evaluate_statement(statement)?;
+        }
+        Ok(())
+    }
+
+    fn evaluate_statement(&mut self, statement: &ptree::Statement) -> Result<(), EvalError> {
+        match statement {
+            ptree::Statement::Let(stmt) => {
+                let value = self.evaluate_expression(&stmt.value)?;
+                self.current_scope_mut().insert(stmt.symbol.clone(), value);
+                Ok(())
+            }
+            ptree::Statement::Assign(stmt) => self.evaluate_assign_statement(stmt),
+            ptree::Statement::Expression(expr) => {
+                self.evaluate_expression(expr)?;
+                Ok(())
+            }
+            ptree::Statement::If(stmt) => self.evaluate_if_statement(stmt),
+            ptree::Statement::While(stmt) => self.evaluate_while_statement(stmt),
+            ptree::Statement::Return(stmt) => {
+                let value = self.evaluate_expression(&stmt.value)?;
+                Err(EvalError::Return(value))
+            }
+            ptree::Statement::Assert(stmt) => match self.evaluate_expression(&stmt.condition)? {
+                Object::Boolean(true) => Ok(()),
+                Object::Boolean(false) => {
+                    Err(EvalError::AssertionFailed(stmt.location.clone()))
+                }
+                _ => Err(EvalError::TypeError(String::from(
+                    "assert requires a boolean condition",
+                ))),
+            },
+            ptree::Statement::Break(_) => Err(EvalError::Break),
+            ptree::Statement::Continue(_) => Err(EvalError::Continue),
+            ptree::Statement::For(_) => Err(EvalError::TypeError(String::from(
+                "for loops are not yet supported by the evaluator",
+            ))),
+        }
+    }
+
+    fn evaluate_assign_statement(&mut self, stmt: &ptree::AssignStatement) -> Result<(), EvalError> {
+        let name = match &stmt.target.kind {
+            ptree::ExpressionKind::Symbol(name) => name.clone(),
+            _ => {
+                return Err(EvalError::TypeError(String::from(
+                    "the evaluator can only assign to a plain variable",
+                )))
+            }
+        };
+
+        let value = self.evaluate_expression(&stmt.value)?;
+        let value = if let Some(op) = stmt.op {
+            let current = self.lookup(&name)?;
+            apply_binary_op(op, &current, &value)?
+        } else {
+            value
+        };
+        self.assign(&name, value)
+    }
+
+    fn evaluate_if_statement(&mut self, stmt: &ptree::IfStatement) -> Result<(), EvalError> {
+        if self.evaluate_condition(&stmt.if_clause.condition)? {
+            return self.evaluate_block(&stmt.if_clause.body);
+        }
+
+        for elif_clause in &stmt.elif_clauses {
+            if self.evaluate_condition(&elif_clause.condition)? {
+                return self.evaluate_block(&elif_clause.body);
+            }
+        }
+
+        self.evaluate_block(&stmt.else_body)
+    }
+
+    fn evaluate_while_statement(&mut self, stmt: &ptree::WhileStatement) -> Result<(), EvalError> {
+        while self.evaluate_condition(&stmt.condition)? {
+            match self.evaluate_block(&stmt.body) {
+                Ok(()) => {}
+                Err(EvalError::Break) => break,
+                Err(EvalError::Continue) => continue,
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(())
+    }
+
+    fn evaluate_condition(&mut self, expr: &ptree::Expression) -> Result<bool, EvalError> {
+        match self.evaluate_expression(expr)? {
+            Object::Boolean(b) => Ok(b),
+            _ => Err(EvalError::TypeError(String::from(
+                "condition must be a boolean",
+            ))),
+        }
+    }
+
+    fn evaluate_expression(&mut self, expr: &ptree::Expression) -> Result<Object, EvalError> {
+        match &expr.kind {
+            ptree::ExpressionKind::Boolean(b) => Ok(Object::Boolean(*b)),
+            ptree::ExpressionKind::Integer(x, _) => Ok(Object::Integer(*x)),
+            ptree::ExpressionKind::String(s) => Ok(Object::Str(s.clone())),
+            ptree::ExpressionKind::Symbol(name) => self.lookup(name),
+            ptree::ExpressionKind::Binary(e) => {
+                let left = self.evaluate_expression(&e.left)?;
+                let right = self.evaluate_expression(&e.right)?;
+                apply_binary_op(e.op, &left, &right)
+            }
+            ptree::ExpressionKind::Comparison(e) => {
+                let left = self.evaluate_expression(&e.left)?;
+                let right = self.evaluate_expression(&e.right)?;
+                apply_comparison_op(e.op, &left, &right)
+            }
+            ptree::ExpressionKind::Unary(e) => {
+                let operand = self.evaluate_expression(&e.operand)?;
+                apply_unary_op(e.op, &operand)
+            }
+            ptree::ExpressionKind::Call(e) => self.evaluate_call(e),
+            ptree::ExpressionKind::Index(e) => {
+                let value = self.evaluate_expression(&e.value)?;
+                let index = self.evaluate_expression(&e.index)?;
+                match (value, index) {
+                    (Object::List(items), Object::Integer(i)) => items
+                        .get(i as usize)
+                        .cloned()
+                        .ok_or_else(|| EvalError::TypeError(String::from("list index out of range"))),
+                    _ => Err(EvalError::TypeError(String::from(
+                        "can only index a list with an integer",
+                    ))),
+                }
+            }
+            ptree::ExpressionKind::List(e) => {
+                let mut items = Vec::with_capacity(e.items.len());
+                for item in &e.items {
+                    items.push(self.evaluate_expression(item)?);
+                }
+                Ok(Object::List(items))
+            }
+            _ => Err(EvalError::TypeError(String::from(
+                "this expression is not yet supported by the evaluator",
+            ))),
+        }
+    }
+
+    fn evaluate_call(&mut self, call: &ptree::CallExpression) -> Result<Object, EvalError> {
+        let mut arguments = Vec::with_capacity(call.arguments.len());
+        for argument in &call.arguments {
+            arguments.push(self.evaluate_expression(argument)?);
+        }
+
+        match call.function.as_str() {
+            "print" => {
+                print!("{}", arguments[0]);
+                Ok(Object::Null)
+            }
+            "println" => {
+                println!("{}", arguments[0]);
+                Ok(Object::Null)
+            }
+            "printint" => {
+                println!("{}", arguments[0]);
+                Ok(Object::Null)
+            }
+            "length" => match &arguments[0] {
+                Object::List(items) => Ok(Object::Integer(items.len() as i64)),
+                _ => Err(EvalError::TypeError(String::from("length requires a list"))),
+            },
+            "string_length" => match &arguments[0] {
+                Object::Str(s) => Ok(Object::Integer(s.chars().count() as i64)),
+                _ => Err(EvalError::TypeError(String::from(
+                    "string_length requires a string",
+                ))),
+            },
+            "panic" => match &arguments[0] {
+                Object::Str(s) => Err(EvalError::TypeError(format!("panic: {}", s))),
+                _ => Err(EvalError::TypeError(String::from("panic requires a string"))),
+            },
+            _ => self.call_function(&call.function, arguments),
+        }
+    }
+
+    // `self.scopes` has one frame per *call*, pushed in `call_function` and popped when it
+    // returns -- if/while bodies don't get their own frame. That means the frames below the top
+    // of the stack belong to whichever caller(s) are still running, not to any block enclosing
+    // this one, so only the top frame is in scope here; falling through to the rest would resolve
+    // a callee's free variables against its caller's locals instead of reporting them undefined.
+    fn lookup(&self, name: &str) -> Result<Object, EvalError> {
+        if let Some(value) = self.current_scope().get(name) {
+            return Ok(value.clone());
+        }
+        Err(EvalError::UndefinedVariable(String::from(name)))
+    }
+
+    fn assign(&mut self, name: &str, value: Object) -> Result<(), EvalError> {
+        let scope = self.current_scope_mut();
+        if scope.contains_key(name) {
+            scope.insert(String::from(name), value);
+            return Ok(());
+        }
+        Err(EvalError::UndefinedVariable(String::from(name)))
+    }
+
+    fn current_scope(&self) -> &HashMap<String, Object> {
+        self.scopes.last().expect("no active function call scope")
+    }
+
+    fn current_scope_mut(&mut self) -> &mut HashMap<String, Object> {
+        self.scopes
+            .last_mut()
+            .expect("no active function call scope")
+    }
+}
+
+fn apply_binary_op(
+    op: common::BinaryOpType,
+    left: &Object,
+    right: &Object,
+) -> Result<Object, EvalError> {
+    use common::BinaryOpType::*;
+    match (op, left, right) {
+        (Add, Object::Integer(a), Object::Integer(b)) => Ok(Object::Integer(a + b)),
+        (Subtract, Object::Integer(a), Object::Integer(b)) => Ok(Object::Integer(a - b)),
+        (Multiply, Object::Integer(a), Object::Integer(b)) => Ok(Object::Integer(a * b)),
+        (Divide, Object::Integer(a), Object::Integer(b)) => {
+            if *b == 0 {
+                Err(EvalError::TypeError(String::from("division by zero")))
+            } else {
+                Ok(Object::Integer(a / b))
+            }
+        }
+        (Modulo, Object::Integer(a), Object::Integer(b)) => Ok(Object::Integer(a % b)),
+        (Concat, Object::Str(a), Object::Str(b)) => Ok(Object::Str(format!("{}{}", a, b))),
+        (And, Object::Boolean(a), Object::Boolean(b)) => Ok(Object::Boolean(*a && *b)),
+        (Or, Object::Boolean(a), Object::Boolean(b)) => Ok(Object::Boolean(*a || *b)),
+        _ => Err(EvalError::TypeError(format!(
+            "cannot apply {:?} to {:?} and {:?}",
+            op, left, right
+        ))),
+    }
+}
+
+fn apply_comparison_op(
+    op: common::ComparisonOpType,
+    left: &Object,
+    right: &Object,
+) -> Result<Object, EvalError> {
+    use common::ComparisonOpType::*;
+    let ordering = match (left, right) {
+        (Object::Integer(a), Object::Integer(b)) => a.cmp(b),
+        (Object::Str(a), Object::Str(b)) => a.cmp(b),
+        (Object::Boolean(a), Object::Boolean(b)) => a.cmp(b),
+        _ => {
+            return Err(EvalError::TypeError(String::from(
+                "cannot compare these operand types",
+            )))
+        }
+    };
+
+    let result = match op {
+        Equals => ordering == std::cmp::Ordering::Equal,
+        NotEquals => ordering != std::cmp::Ordering::Equal,
+        GreaterThan => ordering == std::cmp::Ordering::Greater,
+        GreaterThanEquals => ordering != std::cmp::Ordering::Less,
+        LessThan => ordering == std::cmp::Ordering::Less,
+        LessThanEquals => ordering != std::cmp::Ordering::Greater,
+    };
+    Ok(Object::Boolean(result))
+}
+
+fn apply_unary_op(op: common::UnaryOpType, operand: &Object) -> Result<Object, EvalError> {
+    match (op, operand) {
+        (common::UnaryOpType::Negate, Object::Integer(x)) => Ok(Object::Integer(-x)),
+        (common::UnaryOpType::Not, Object::Boolean(b)) => Ok(Object::Boolean(!b)),
+        _ => Err(EvalError::TypeError(format!(
+            "cannot apply {:?} to {:?}",
+            op, operand
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer;
+    use crate::parser;
+
+    fn run(source: &str) -> Result<(), EvalError> {
+        let lexer = lexer::Lexer::new("<string>", source);
+        let (ptree, errors) = parser::parse(lexer);
+        assert!(errors.is_empty(), "program should parse: {:?}", errors);
+        evaluate(&ptree)
+    }
+
+    #[test]
+    fn runs_a_simple_function() {
+        let result = run("func main() -> i64 {\n  let x: i64 = 1 + 2;\n  assert x == 3;\n  return 0;\n}\n");
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn early_return_skips_later_statements() {
+        let result = run(
+            "func main() -> i64 {\n  return 1;\n  assert false;\n  return 0;\n}\n",
+        );
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn while_loop_with_break() {
+        let result = run(
+            "func main() -> i64 {\n  let i: i64 = 0;\n  while true {\n    if i == 3 {\n      break;\n    }\n    i = i + 1;\n  }\n  assert i == 3;\n  return 0;\n}\n",
+        );
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn failing_assertion_is_an_error() {
+        let result = run("func main() -> i64 {\n  assert false;\n  return 0;\n}\n");
+        assert!(matches!(result, Err(EvalError::AssertionFailed(_))));
+    }
+
+    #[test]
+    fn undefined_variable_is_an_error() {
+        let result = run("func main() -> i64 {\n  return x;\n}\n");
+        assert!(matches!(result, Err(EvalError::UndefinedVariable(_))));
+    }
+
+    #[test]
+    fn callee_cannot_see_caller_locals() {
+        // `f` has no `x` of its own; it shouldn't resolve against `main`'s `x`, even though
+        // `main`'s scope is still on `self.scopes` while `f` runs.
+        let result = run(
+            "func f() -> i64 {\n  return x;\n}\nfunc main() -> i64 {\n  let x: i64 = 5;\n  return f();\n}\n",
+        );
+        assert!(matches!(result, Err(EvalError::UndefinedVariable(_))));
+    }
+}
@@ -0,0 +1,376 @@
+// Copyright 2022 The Venice Authors. All rights reserved.
+// Use of this source code is governed by an MIT-style license that can be
+// found in the LICENSE file.
+//
+// Runs over the parse tree after parsing so that a `let` statement's type annotation can be
+// omitted (`let x = 0;`) and inferred from its value instead. This is a narrow, single-pass
+// inference rather than the full type checker in analyzer.rs: it only needs to compute enough of
+// an expression's type to fill in a missing `let` annotation, and it leaves the analyzer to do the
+// authoritative type checking once every annotation is in place.
+
+use super::common;
+use super::errors;
+use super::ptree;
+use std::collections::HashMap;
+
+/// Fills in the type annotation of every `let` statement that omits one. Type errors detected
+/// along the way (a binary operator applied to mismatched types, a list literal that can't be
+/// unified, or a `let` whose value type can't be determined at all) are collected and returned;
+/// the analyzer performs the rest of type checking afterwards.
+pub fn infer(program: &mut ptree::Program) -> Result<(), Vec<errors::VeniceError>> {
+    let mut inferer = Inferer::new(program);
+    for declaration in &mut program.declarations {
+        if let ptree::Declaration::Function(d) = declaration {
+            inferer.infer_function(d);
+        }
+    }
+
+    if inferer.errors.is_empty() {
+        Ok(())
+    } else {
+        Err(inferer.errors)
+    }
+}
+
+/// A minimal representation of a type, just expressive enough to infer `let` annotations.
+#[derive(Clone, Debug, PartialEq)]
+enum Ty {
+    I64,
+    F64,
+    Boolean,
+    String,
+    List(Box<Ty>),
+}
+
+impl Ty {
+    fn from_ptree_type(type_: &ptree::Type) -> Option<Ty> {
+        match &type_.kind {
+            ptree::TypeKind::Literal(s) if s == "i64" => Some(Ty::I64),
+            ptree::TypeKind::Literal(s) if s == "float" => Some(Ty::F64),
+            ptree::TypeKind::Literal(s) if s == "bool" => Some(Ty::Boolean),
+            ptree::TypeKind::Literal(s) if s == "string" => Some(Ty::String),
+            ptree::TypeKind::Parameterized(p) if p.symbol == "list" && p.parameters.len() == 1 => {
+                Ty::from_ptree_type(&p.parameters[0]).map(|t| Ty::List(Box::new(t)))
+            }
+            _ => None,
+        }
+    }
+
+    fn to_ptree_type(&self, location: common::Location) -> ptree::Type {
+        match self {
+            Ty::I64 => ptree::Type {
+                kind: ptree::TypeKind::Literal(String::from("i64")),
+                location,
+            },
+            Ty::F64 => ptree::Type {
+                kind: ptree::TypeKind::Literal(String::from("float")),
+                location,
+            },
+            Ty::Boolean => ptree::Type {
+                kind: ptree::TypeKind::Literal(String::from("bool")),
+                location,
+            },
+            Ty::String => ptree::Type {
+                kind: ptree::TypeKind::Literal(String::from("string")),
+                location,
+            },
+            Ty::List(item) => ptree::Type {
+                kind: ptree::TypeKind::Parameterized(ptree::ParameterizedType {
+                    symbol: String::from("list"),
+                    parameters: vec![item.to_ptree_type(location.clone())],
+                }),
+                location,
+            },
+        }
+    }
+}
+
+impl std::fmt::Display for Ty {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Ty::I64 => write!(f, "i64"),
+            Ty::F64 => write!(f, "float"),
+            Ty::Boolean => write!(f, "bool"),
+            Ty::String => write!(f, "string"),
+            Ty::List(item) => write!(f, "list<{}>", item),
+        }
+    }
+}
+
+struct Inferer {
+    function_return_types: HashMap<String, Ty>,
+    errors: Vec<errors::VeniceError>,
+}
+
+impl Inferer {
+    fn new(program: &ptree::Program) -> Self {
+        let mut function_return_types = HashMap::new();
+        for declaration in &program.declarations {
+            if let ptree::Declaration::Function(d) = declaration {
+                if let Some(ty) = Ty::from_ptree_type(&d.return_type) {
+                    function_return_types.insert(d.name.clone(), ty);
+                }
+            }
+        }
+
+        Inferer {
+            function_return_types,
+            errors: Vec::new(),
+        }
+    }
+
+    fn infer_function(&mut self, declaration: &mut ptree::FunctionDeclaration) {
+        let mut scope = HashMap::new();
+        for parameter in &declaration.parameters {
+            if let Some(ty) = Ty::from_ptree_type(&parameter.type_) {
+                scope.insert(parameter.name.clone(), ty);
+            }
+        }
+
+        self.infer_block(&mut declaration.body, &mut scope);
+    }
+
+    fn infer_block(&mut self, body: &mut [ptree::Statement], scope: &mut HashMap<String, Ty>) {
+        for statement in body {
+            self.infer_statement(statement, scope);
+        }
+    }
+
+    fn infer_statement(&mut self, statement: &mut ptree::Statement, scope: &mut HashMap<String, Ty>) {
+        match statement {
+            ptree::Statement::Let(stmt) => self.infer_let_statement(stmt, scope),
+            ptree::Statement::Assign(stmt) => {
+                self.infer_expression(&stmt.value, scope);
+            }
+            ptree::Statement::Expression(expr) => {
+                self.infer_expression(expr, scope);
+            }
+            ptree::Statement::If(stmt) => {
+                self.infer_expression(&stmt.if_clause.condition, scope);
+                self.infer_block(&mut stmt.if_clause.body, &mut scope.clone());
+                for elif_clause in &mut stmt.elif_clauses {
+                    self.infer_expression(&elif_clause.condition, scope);
+                    self.infer_block(&mut elif_clause.body, &mut scope.clone());
+                }
+                self.infer_block(&mut stmt.else_body, &mut scope.clone());
+            }
+            ptree::Statement::While(stmt) => {
+                self.infer_expression(&stmt.condition, scope);
+                self.infer_block(&mut stmt.body, &mut scope.clone());
+            }
+            ptree::Statement::Return(stmt) => {
+                self.infer_expression(&stmt.value, scope);
+            }
+            ptree::Statement::Assert(stmt) => {
+                self.infer_expression(&stmt.condition, scope);
+            }
+            ptree::Statement::Break(_) | ptree::Statement::Continue(_) => {}
+            ptree::Statement::For(_) => {}
+            ptree::Statement::Match(stmt) => {
+                self.infer_expression(&stmt.value, scope);
+                for arm in &mut stmt.arms {
+                    self.infer_block(&mut arm.body, &mut scope.clone());
+                }
+            }
+        }
+    }
+
+    fn infer_let_statement(&mut self, stmt: &mut ptree::LetStatement, scope: &mut HashMap<String, Ty>) {
+        let value_type = self.infer_expression(&stmt.value, scope);
+        match &stmt.type_ {
+            Some(annotation) => {
+                if let Some(annotation_type) = Ty::from_ptree_type(annotation) {
+                    if let Some(value_type) = &value_type {
+                        if *value_type != annotation_type {
+                            let msg = format!(
+                                "let statement declares type {} but its value has type {}",
+                                annotation_type, value_type
+                            );
+                            self.errors
+                                .push(errors::VeniceError::new(&msg, stmt.location.clone()));
+                        }
+                    }
+                    scope.insert(stmt.symbol.clone(), annotation_type);
+                }
+            }
+            None => match value_type {
+                Some(ty) => {
+                    stmt.type_ = Some(ty.to_ptree_type(stmt.location.clone()));
+                    scope.insert(stmt.symbol.clone(), ty);
+                }
+                None => {
+                    self.errors.push(errors::VeniceError::new(
+                        "cannot infer a type for this let statement; add an explicit annotation",
+                        stmt.location.clone(),
+                    ));
+                }
+            },
+        }
+    }
+
+    fn infer_expression(
+        &mut self,
+        expr: &ptree::Expression,
+        scope: &HashMap<String, Ty>,
+    ) -> Option<Ty> {
+        use ptree::ExpressionKind::*;
+        match &expr.kind {
+            Boolean(_) => Some(Ty::Boolean),
+            Integer(_, _) => Some(Ty::I64),
+            Float(_) => Some(Ty::F64),
+            String(_) => Some(Ty::String),
+            Symbol(name) => scope.get(name).cloned(),
+            Binary(e) => {
+                let left = self.infer_expression(&e.left, scope);
+                let right = self.infer_expression(&e.right, scope);
+                match (left, right) {
+                    (Some(left), Some(right)) if left == right => Some(left),
+                    (Some(left), Some(right)) => {
+                        let msg = format!(
+                            "cannot apply {:?} to mismatched types {} and {}",
+                            e.op, left, right
+                        );
+                        self.errors
+                            .push(errors::VeniceError::new(&msg, e.location.clone()));
+                        None
+                    }
+                    _ => None,
+                }
+            }
+            Comparison(e) => {
+                let left = self.infer_expression(&e.left, scope);
+                let right = self.infer_expression(&e.right, scope);
+                match (left, right) {
+                    (Some(left), Some(right)) if left == right => Some(Ty::Boolean),
+                    (Some(left), Some(right)) => {
+                        let msg = format!(
+                            "cannot compare mismatched types {} and {}",
+                            left, right
+                        );
+                        self.errors
+                            .push(errors::VeniceError::new(&msg, e.location.clone()));
+                        None
+                    }
+                    _ => None,
+                }
+            }
+            Unary(e) => self.infer_expression(&e.operand, scope),
+            Call(e) => {
+                for argument in &e.arguments {
+                    self.infer_expression(argument, scope);
+                }
+                self.function_return_types.get(&e.function).cloned()
+            }
+            Index(e) => {
+                self.infer_expression(&e.index, scope);
+                match self.infer_expression(&e.value, scope) {
+                    Some(Ty::List(item)) => Some(*item),
+                    _ => None,
+                }
+            }
+            List(e) => {
+                if e.items.is_empty() {
+                    self.errors.push(errors::VeniceError::new(
+                        "cannot infer the type of an empty list literal; add an explicit annotation",
+                        expr.location.clone(),
+                    ));
+                    return None;
+                }
+
+                let mut item_type = None;
+                for item in &e.items {
+                    let this_type = self.infer_expression(item, scope);
+                    match (&item_type, &this_type) {
+                        (None, Some(_)) => item_type = this_type,
+                        (Some(t1), Some(t2)) if t1 != t2 => {
+                            let msg =
+                                format!("list literal mixes types {} and {}", t1, t2);
+                            self.errors
+                                .push(errors::VeniceError::new(&msg, item.location.clone()));
+                        }
+                        _ => {}
+                    }
+                }
+                item_type.map(|t| Ty::List(Box::new(t)))
+            }
+            // Tuples, maps, records, and attribute/method-call expressions aren't modeled by this
+            // pass yet; a `let` that needs one of their types to be inferred must still write an
+            // explicit annotation.
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer;
+    use crate::parser;
+
+    fn infer_program(source: &str) -> (ptree::Program, Result<(), Vec<errors::VeniceError>>) {
+        let lexer = lexer::Lexer::new("<string>", source);
+        let (mut program, parse_errors) = parser::parse(lexer);
+        assert!(parse_errors.is_empty(), "program should parse: {:?}", parse_errors);
+        let result = infer(&mut program);
+        (program, result)
+    }
+
+    #[test]
+    fn infers_integer_literal() {
+        let (program, result) =
+            infer_program("func main() -> i64 {\n  let x = 0;\n  return x;\n}\n");
+        assert!(result.is_ok());
+        assert_eq!(format!("{}", program), "(program (func main () (type i64) (let x (type i64) 0) (return x)))");
+    }
+
+    #[test]
+    fn infers_boolean_literal() {
+        let (program, result) =
+            infer_program("func main() -> i64 {\n  let b = true;\n  return 0;\n}\n");
+        assert!(result.is_ok());
+        assert_eq!(format!("{}", program), "(program (func main () (type i64) (let b (type bool) true) (return 0)))");
+    }
+
+    #[test]
+    fn infers_list_literal() {
+        let (program, result) =
+            infer_program("func main() -> i64 {\n  let xs = [1, 2, 3];\n  return 0;\n}\n");
+        assert!(result.is_ok());
+        assert_eq!(
+            format!("{}", program),
+            "(program (func main () (type i64) (let xs (type list (type i64)) (list 1 2 3)) (return 0)))"
+        );
+    }
+
+    #[test]
+    fn infers_from_call_return_type() {
+        let (program, result) = infer_program(
+            "func helper() -> i64 {\n  return 1;\n}\nfunc main() -> i64 {\n  let x = helper();\n  return x;\n}\n",
+        );
+        assert!(result.is_ok());
+        assert_eq!(
+            format!("{}", program),
+            "(program (func helper () (type i64) (return 1)) (func main () (type i64) (let x (type i64) (call helper ())) (return x)))"
+        );
+    }
+
+    #[test]
+    fn mismatched_binary_operands_is_an_error() {
+        let (_, result) =
+            infer_program("func main() -> i64 {\n  let x = 1 + true;\n  return 0;\n}\n");
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn empty_list_literal_cannot_be_inferred() {
+        let (_, result) = infer_program("func main() -> i64 {\n  let xs = [];\n  return 0;\n}\n");
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn written_annotation_is_checked_against_the_value() {
+        let (_, result) =
+            infer_program("func main() -> i64 {\n  let x: bool = 0;\n  return 0;\n}\n");
+        assert!(result.is_err());
+    }
+}
@@ -5,11 +5,14 @@
 // The lexer breaks the input program into a stream of tokens that the parser consumes.
 
 use super::common;
+use super::errors;
+use std::borrow::Cow;
 use std::collections::HashMap;
 
 #[derive(Clone, Copy, Debug, Hash, Eq, PartialEq)]
 pub enum TokenType {
     // Literals
+    Float,
     Integer,
     String,
     Symbol,
@@ -18,15 +21,19 @@ pub enum TokenType {
     // Operators
     Assign,
     Concat,
+    DivideAssign,
     GreaterThan,
     GreaterThanEquals,
     Equals,
     LessThan,
     LessThanEquals,
     Minus,
+    MinusAssign,
+    MultiplyAssign,
     NotEquals,
     Percent,
     Plus,
+    PlusAssign,
     Slash,
     Star,
     // Punctuation
@@ -44,58 +51,124 @@ pub enum TokenType {
     // Keywords
     And,
     Assert,
+    Break,
+    Case,
     Const,
+    Continue,
     Else,
+    Enum,
     For,
     Func,
     If,
     In,
     Let,
+    Match,
     New,
     Not,
     Or,
     Record,
     Return,
     While,
+    // Comments (only produced when the lexer is constructed with comment preservation enabled;
+    // see `Lexer::new_with_comments`)
+    Comment,
+    DocComment,
+    // String interpolation. A string with no `${...}` in it is still a single `String` token as
+    // before; one that does is split into `StringPiece`s around `StringInterpStart`/
+    // `StringInterpEnd`-delimited runs of ordinary tokens. See `Lexer`'s mode stack.
+    StringPiece,
+    StringInterpStart,
+    StringInterpEnd,
     // Miscellaneous
     End,
     Unknown,
 }
 
+// The lexer is normally a flat scanner, but a `${` inside a string literal has to suspend
+// character-level string scanning, run the ordinary tokenizer over the embedded expression, and
+// resume string scanning at the matching `}` -- including when that happens again, recursively,
+// inside the embedded expression (`"${ "${x}" }"`). `mode_stack` is a stack of which of those two
+// scanning strategies is active; the bottom entry is always `Normal` and is never popped.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum LexerMode {
+    Normal,
+    InString,
+}
+
 #[derive(Clone, Debug)]
-pub struct Token {
+pub struct Token<'src> {
     pub type_: TokenType,
-    pub value: String,
+    /// The token's semantic value: decoded escapes for a string, digit separators stripped from a
+    /// number, etc. Borrowed directly out of the source when no such processing is needed, which
+    /// is the common case -- only string literals and numbers with `_` separators allocate.
+    pub value: Cow<'src, str>,
+    /// The literal source text the token was scanned from, untouched. Kept around for
+    /// diagnostics and tooling that want to quote back exactly what the user wrote.
+    pub raw: &'src str,
     pub location: common::Location,
 }
 
-impl Token {
+impl<'src> Token<'src> {
+    /// Builds a token with no underlying source to borrow from. Only meant for test fixtures that
+    /// construct an expected `Token` to compare against one the lexer actually produced --
+    /// `PartialEq` ignores `raw`, so a real caller should go through the lexer instead.
     pub fn new(type_: TokenType, value: String, location: common::Location) -> Self {
+        Token {
+            type_,
+            value: Cow::Owned(value),
+            raw: "",
+            location,
+        }
+    }
+
+    fn with_raw(
+        type_: TokenType,
+        value: Cow<'src, str>,
+        raw: &'src str,
+        location: common::Location,
+    ) -> Self {
         Token {
             type_,
             value,
+            raw,
             location,
         }
     }
 }
 
-impl PartialEq for Token {
+impl PartialEq for Token<'_> {
     fn eq(&self, other: &Self) -> bool {
         self.type_ == other.type_ && self.value == other.value
     }
 }
-impl Eq for Token {}
+impl Eq for Token<'_> {}
 
-pub struct Lexer {
-    // The program is stored as a vector of characters so that we can iterate over Unicode scalar
-    // values in linear time.
-    chars: Vec<char>,
-    // `index` and `start` are indices into the `chars` array, not into the original string.
+pub struct Lexer<'src> {
+    // The program is scanned directly out of this slice rather than copied into a `Vec<char>`, so
+    // `index`/`start` are byte offsets into it (always landing on char boundaries) rather than
+    // character indices.
+    program: &'src str,
     index: usize,
     start: usize,
     location: common::Location,
     start_location: common::Location,
-    token: Token,
+    token: Token<'src>,
+    errors: Vec<errors::VeniceError>,
+    // When set, `next` emits `Comment`/`DocComment` tokens instead of silently skipping over
+    // them. Parsing leaves this off so the grammar never has to account for comments; tooling
+    // that wants to see them (formatters, doc generators) opts in via `new_with_comments`.
+    preserve_comments: bool,
+    // See `LexerMode`. Always has at least one (`Normal`) entry.
+    mode_stack: Vec<LexerMode>,
+    // Parallel to the `Normal` entries in `mode_stack` that were pushed for a `${` interpolation
+    // (the bottom, top-level `Normal` entry has no corresponding entry here): counts unmatched
+    // `{` seen so far while scanning that interpolation's expression, so a `{`/`}` pair that
+    // belongs to the expression itself (e.g. a record literal) isn't mistaken for the
+    // interpolation's closing brace.
+    interpolation_brace_depth: Vec<u32>,
+    // Parallel to `interpolation_brace_depth`: the location of each currently-open `${`, so an
+    // interpolation left open at EOF can be reported with a span back to where it started.
+    interpolation_start_locations: Vec<common::Location>,
 }
 
 lazy_static! {
@@ -129,20 +202,29 @@ lazy_static! {
         m.insert(('<', '='), TokenType::LessThanEquals);
         m.insert(('!', '='), TokenType::NotEquals);
         m.insert(('+', '+'), TokenType::Concat);
+        m.insert(('+', '='), TokenType::PlusAssign);
+        m.insert(('-', '='), TokenType::MinusAssign);
+        m.insert(('*', '='), TokenType::MultiplyAssign);
+        m.insert(('/', '='), TokenType::DivideAssign);
         m
     };
     static ref KEYWORDS: HashMap<String, TokenType> = {
         let mut m = HashMap::new();
         m.insert(String::from("and"), TokenType::And);
         m.insert(String::from("assert"), TokenType::Assert);
+        m.insert(String::from("break"), TokenType::Break);
+        m.insert(String::from("case"), TokenType::Case);
         m.insert(String::from("const"), TokenType::Const);
+        m.insert(String::from("continue"), TokenType::Continue);
         m.insert(String::from("else"), TokenType::Else);
+        m.insert(String::from("enum"), TokenType::Enum);
         m.insert(String::from("false"), TokenType::False);
         m.insert(String::from("for"), TokenType::For);
         m.insert(String::from("func"), TokenType::Func);
         m.insert(String::from("if"), TokenType::If);
         m.insert(String::from("in"), TokenType::In);
         m.insert(String::from("let"), TokenType::Let);
+        m.insert(String::from("match"), TokenType::Match);
         m.insert(String::from("new"), TokenType::New);
         m.insert(String::from("not"), TokenType::Not);
         m.insert(String::from("or"), TokenType::Or);
@@ -154,27 +236,45 @@ lazy_static! {
     };
 }
 
-impl Lexer {
+impl<'src> Lexer<'src> {
     /// Constructs a new lexer. `file` is the name of the file and `program` is the
     /// contents. By convention, if the program does not reside on disk then `file` is
     /// set `<string>`.
-    pub fn new(file: &str, program: &str) -> Self {
+    pub fn new(file: &str, program: &'src str) -> Self {
+        Self::new_impl(file, program, false)
+    }
+
+    /// Like `new`, but comments are surfaced as `Comment`/`DocComment` tokens instead of being
+    /// skipped. Meant for tooling (formatters, doc generators) that needs to see comments; the
+    /// parser always uses `new` so the grammar never has to account for them.
+    pub fn new_with_comments(file: &str, program: &'src str) -> Self {
+        Self::new_impl(file, program, true)
+    }
+
+    fn new_impl(file: &str, program: &'src str, preserve_comments: bool) -> Self {
         let location = common::Location {
             file: String::from(file),
             column: 1,
             line: 1,
+            byte_offset: 0,
         };
         let mut lexer = Lexer {
-            chars: program.chars().collect(),
+            program,
             index: 0,
             start: 0,
             location: location.clone(),
             start_location: location.clone(),
             token: Token {
                 type_: TokenType::Unknown,
-                value: String::new(),
+                value: Cow::Borrowed(""),
+                raw: "",
                 location,
             },
+            errors: Vec::new(),
+            preserve_comments,
+            mode_stack: vec![LexerMode::Normal],
+            interpolation_brace_depth: Vec::new(),
+            interpolation_start_locations: Vec::new(),
         };
         // "Prime the pump" so that we can immediately call token() to retrieve the
         // first token.
@@ -185,25 +285,79 @@ impl Lexer {
     }
 
     /// Returns the current token without advancing.
-    pub fn token(&self) -> Token {
+    pub fn token(&self) -> Token<'src> {
         self.token.clone()
     }
 
+    /// Returns every error accumulated so far (unexpected characters, unclosed strings, malformed
+    /// numbers, bad escapes). Callers typically drive the lexer to completion -- directly or, more
+    /// often, by driving the parser that wraps it -- and then read these once at the end, the same
+    /// way `parser::parse` returns its own `Vec<errors::VeniceError>`.
+    pub fn errors(&self) -> &[errors::VeniceError] {
+        &self.errors
+    }
+
     /// Advances to the next token and returns it.
-    pub fn next(&mut self) -> Token {
-        self.skip_whitespace_and_comments();
+    pub fn next(&mut self) -> Token<'src> {
+        // Whitespace inside a string's content is significant, so this has to come before the
+        // usual whitespace/comment skipping below, not after. The `!self.done()` guard keeps EOF
+        // always producing `End`, even if it happens while a string was left open mid-interpolation.
+        if !self.done() && *self.mode_stack.last().unwrap() == LexerMode::InString {
+            return self.next_in_string();
+        }
 
-        if self.done() {
-            return self.make_token(TokenType::End);
+        if self.preserve_comments {
+            self.skip_whitespace();
+        } else {
+            self.skip_whitespace_and_comments();
         }
 
         self.start = self.index;
         self.start_location = self.location.clone();
 
+        if self.done() {
+            if let Some(start) = self.interpolation_start_locations.first() {
+                self.error_with_span(
+                    "unclosed string interpolation",
+                    start.clone(),
+                    self.location.clone(),
+                );
+                self.mode_stack.truncate(1);
+                self.interpolation_brace_depth.clear();
+                self.interpolation_start_locations.clear();
+            }
+            return self.make_token(TokenType::End);
+        }
+
         let c = self.ch();
 
-        if self.index + 1 < self.chars.len() {
-            let c2 = self.peek(1);
+        if self.preserve_comments && c == '/' {
+            if self.peek(1) == Some('/') {
+                return self.read_line_comment();
+            } else if self.peek(1) == Some('*') {
+                return self.read_block_comment();
+            }
+        }
+
+        // Brace nesting within the expression embedded in a `${...}` has to be tracked so only
+        // the brace that actually closes the interpolation pops back to string scanning --
+        // everything else (a record literal, a block) is just an ordinary token.
+        if let Some(depth) = self.interpolation_brace_depth.last_mut() {
+            if c == '{' {
+                *depth += 1;
+            } else if c == '}' {
+                if *depth == 0 {
+                    self.interpolation_brace_depth.pop();
+                    self.interpolation_start_locations.pop();
+                    self.mode_stack.pop();
+                    self.increment_index();
+                    return self.make_token(TokenType::StringInterpEnd);
+                }
+                *depth -= 1;
+            }
+        }
+
+        if let Some(c2) = self.peek(1) {
             if let Some(type_) = TWO_CHAR_TOKENS.get(&(c, c2)) {
                 self.increment_index();
                 self.increment_index();
@@ -221,48 +375,351 @@ impl Lexer {
         } else if is_symbol_first_character(c) {
             self.read_symbol()
         } else {
+            self.error(
+                &format!("unexpected character {:?}", c),
+                self.start_location.clone(),
+            );
+            self.increment_index();
             self.make_token(TokenType::Unknown)
         }
     }
 
+    // Called when `mode_stack` says we're in the middle of a string's content (as opposed to an
+    // embedded `${...}` expression). Either resumes character-level string scanning, or, if we're
+    // sitting right where a previous call left off at a `${` it deferred, consumes it and opens
+    // the embedded expression.
+    fn next_in_string(&mut self) -> Token<'src> {
+        self.start = self.index;
+        self.start_location = self.location.clone();
+
+        if !self.done() && self.ch() == '$' && self.peek(1) == Some('{') {
+            let start = self.start_location.clone();
+            self.increment_index();
+            self.increment_index();
+            self.mode_stack.push(LexerMode::Normal);
+            self.interpolation_brace_depth.push(0);
+            self.interpolation_start_locations.push(start);
+            return self.make_token(TokenType::StringInterpStart);
+        }
+
+        self.scan_string_content(false)
+    }
+
     pub fn done(&self) -> bool {
-        self.index >= self.chars.len()
+        self.index >= self.program.len()
+    }
+
+    // `0x`/`0o`/`0b` prefixes pick a digit class up front and never fall through to the
+    // fractional/exponent handling below -- Venice has no hex/octal/binary floats.
+    fn read_number(&mut self) -> Token<'src> {
+        if self.ch() == '0' && matches!(self.peek(1), Some('x' | 'o' | 'b')) {
+            return self.read_radix_integer();
+        }
+
+        let mut is_float = false;
+
+        self.read_digits(|c| c.is_ascii_digit());
+
+        // Only commit to a fractional part if a digit actually follows the `.`; otherwise it's a
+        // `Dot` token (method call or field access) and the number stops here.
+        if !self.done() && self.ch() == '.' && self.peek(1).is_some_and(|c| c.is_ascii_digit()) {
+            is_float = true;
+            self.increment_index();
+            self.read_digits(|c| c.is_ascii_digit());
+        }
+
+        if !self.done() && (self.ch() == 'e' || self.ch() == 'E') && self.exponent_follows() {
+            is_float = true;
+            self.increment_index();
+            if self.ch() == '+' || self.ch() == '-' {
+                self.increment_index();
+            }
+            self.read_digits(|c| c.is_ascii_digit());
+        }
+
+        if is_float {
+            self.make_number_token(TokenType::Float)
+        } else {
+            self.read_integer_suffix();
+            self.make_number_token(TokenType::Integer)
+        }
+    }
+
+    fn read_radix_integer(&mut self) -> Token<'src> {
+        let radix = self.peek(1).unwrap();
+        // Move past the `0` and the radix letter.
+        self.increment_index();
+        self.increment_index();
+
+        let is_digit: fn(char) -> bool = match radix {
+            'x' => |c| c.is_ascii_hexdigit(),
+            'o' => |c| ('0'..='7').contains(&c),
+            'b' => |c| c == '0' || c == '1',
+            _ => unreachable!(),
+        };
+
+        let digits_start = self.index;
+        self.read_digits(is_digit);
+        if self.index == digits_start {
+            // `0x`/`0o`/`0b` with no digits after it.
+            self.error_with_span(
+                "expected at least one digit after radix prefix",
+                self.start_location.clone(),
+                self.location.clone(),
+            );
+            return self.make_token(TokenType::Unknown);
+        }
+
+        self.read_integer_suffix();
+        self.make_number_token(TokenType::Integer)
+    }
+
+    // Consumes a width/signedness suffix (`i8`, `u32`, ...) directly following an integer
+    // literal's digits, e.g. the `i32` in `5i32`. Only matches exactly one of the known suffixes,
+    // and only when it isn't itself the start of a longer identifier (so `0x1Fi32able` is left for
+    // the parser to reject rather than silently dropping "able").
+    fn read_integer_suffix(&mut self) {
+        const SUFFIXES: [&str; 8] = ["i8", "i16", "i32", "i64", "u8", "u16", "u32", "u64"];
+        for suffix in SUFFIXES {
+            let is_match = suffix
+                .chars()
+                .enumerate()
+                .all(|(i, c)| self.peek(i) == Some(c));
+            if is_match && !matches!(self.peek(suffix.len()), Some(c) if is_symbol_character(c)) {
+                for _ in 0..suffix.len() {
+                    self.increment_index();
+                }
+                return;
+            }
+        }
     }
 
-    fn read_number(&mut self) -> Token {
-        while !self.done() && self.ch().is_numeric() {
+    // True if the `e`/`E` the caller just saw begins a real exponent (optional sign, then at
+    // least one digit) rather than the start of a trailing symbol, e.g. the `e` in `1e10` vs. the
+    // `else` that could in principle follow a number with no space, like `0else`.
+    fn exponent_follows(&self) -> bool {
+        let mut n = 1;
+        let mut c = self.peek(n);
+        if matches!(c, Some('+') | Some('-')) {
+            n += 1;
+            c = self.peek(n);
+        }
+        matches!(c, Some(c) if c.is_ascii_digit())
+    }
+
+    // Consumes a run of digits (per `is_digit`) and `_` separators, which `make_number_token`
+    // later strips from the token's value.
+    fn read_digits(&mut self, is_digit: fn(char) -> bool) {
+        while !self.done() && (is_digit(self.ch()) || self.ch() == '_') {
             self.increment_index();
         }
-        self.make_token(TokenType::Integer)
     }
 
-    fn read_string(&mut self) -> Token {
+    fn make_number_token(&mut self, type_: TokenType) -> Token<'src> {
+        let raw = &self.program[self.start..self.index];
+        let value = if raw.contains('_') {
+            Cow::Owned(raw.chars().filter(|&c| c != '_').collect())
+        } else {
+            Cow::Borrowed(raw)
+        };
+        self.finish_token(type_, value, raw)
+    }
+
+    // Builds the string's decoded `value` as it scans, so a later pass over the raw source isn't
+    // needed; `raw` is taken as a single slice afterwards since it's never more than a copy of
+    // what was already scanned.
+    fn read_string(&mut self) -> Token<'src> {
         // Move past the opening quotation mark.
         self.increment_index();
-        while !self.done() {
+        self.scan_string_content(true)
+    }
+
+    // Scans a run of string content up to whichever comes first: the closing quote, a `${` that
+    // opens an embedded expression, or an error (unclosed literal/escape). `is_first_piece` is
+    // true when this run starts right after the opening quote -- i.e. no `${` has occurred yet
+    // for this string literal -- which is the only time a plain `TokenType::String` (rather than
+    // a `StringPiece`) can be produced. A later call, resuming after `${...}`'s closing brace, is
+    // always an interior or trailing `StringPiece` instead.
+    fn scan_string_content(&mut self, is_first_piece: bool) -> Token<'src> {
+        let mut value = String::new();
+        loop {
+            if self.done() {
+                self.error_with_span(
+                    "unclosed string literal",
+                    self.start_location.clone(),
+                    self.location.clone(),
+                );
+                break;
+            }
+
             let c = self.ch();
             if c == '"' {
                 self.increment_index();
+                if !is_first_piece {
+                    self.mode_stack.pop();
+                }
+                let raw = &self.program[self.start..self.index];
+                let type_ = if is_first_piece {
+                    TokenType::String
+                } else {
+                    TokenType::StringPiece
+                };
+                return self.finish_token(type_, Cow::Owned(value), raw);
+            } else if c == '$' && self.peek(1) == Some('{') {
+                if is_first_piece {
+                    self.mode_stack.push(LexerMode::InString);
+                }
+                let raw = &self.program[self.start..self.index];
+                return self.finish_token(TokenType::StringPiece, Cow::Owned(value), raw);
+            } else if c == '\n' {
+                self.error_with_span(
+                    "unclosed string literal",
+                    self.start_location.clone(),
+                    self.location.clone(),
+                );
                 break;
             } else if c == '\\' {
-                // TODO: what if backslash is last character in program?
-                self.increment_index();
+                let escape_location = self.location.clone();
                 self.increment_index();
+                if self.done() {
+                    self.error("unterminated escape sequence", escape_location);
+                    break;
+                }
+                if let Some(decoded) = self.read_escape(escape_location) {
+                    value.push(decoded);
+                }
             } else {
+                value.push(c);
                 self.increment_index();
             }
         }
 
-        // TODO: handle unclosed string literals (newlines and EOF)
-        self.make_token(TokenType::String)
+        let raw = &self.program[self.start..self.index];
+        let type_ = if is_first_piece {
+            TokenType::String
+        } else {
+            TokenType::StringPiece
+        };
+        self.finish_token(type_, Cow::Owned(value), raw)
+    }
+
+    // Decodes one escape sequence with the lexer positioned just after the backslash. Returns the
+    // decoded character, or `None` if the escape was malformed (in which case an error has
+    // already been pushed and the caller should simply omit it from the string's value, consistent
+    // with this lexer's accumulate-errors-and-keep-going approach elsewhere).
+    fn read_escape(&mut self, escape_location: common::Location) -> Option<char> {
+        let c = self.ch();
+        self.increment_index();
+        match c {
+            'n' => Some('\n'),
+            't' => Some('\t'),
+            'r' => Some('\r'),
+            '0' => Some('\0'),
+            '\\' => Some('\\'),
+            '"' => Some('"'),
+            '\'' => Some('\''),
+            'x' => {
+                let digits_start = self.index;
+                for _ in 0..2 {
+                    if self.done() || self.ch() == '"' {
+                        break;
+                    }
+                    self.increment_index();
+                }
+                let digits = &self.program[digits_start..self.index];
+                if digits.len() != 2 {
+                    self.error_with_span(
+                        "truncated \\x escape sequence",
+                        escape_location,
+                        self.location.clone(),
+                    );
+                    return None;
+                }
+                match u32::from_str_radix(digits, 16) {
+                    Ok(value) => match char::from_u32(value) {
+                        Some(c) => Some(c),
+                        None => {
+                            self.error_with_span(
+                                "\\x escape is not a valid character",
+                                escape_location,
+                                self.location.clone(),
+                            );
+                            None
+                        }
+                    },
+                    Err(_) => {
+                        self.error_with_span(
+                            "invalid hex digits in \\x escape sequence",
+                            escape_location,
+                            self.location.clone(),
+                        );
+                        None
+                    }
+                }
+            }
+            'u' => {
+                if self.done() || self.ch() != '{' {
+                    self.error_with_span(
+                        "expected { after \\u",
+                        escape_location,
+                        self.location.clone(),
+                    );
+                    return None;
+                }
+                self.increment_index();
+                let digits_start = self.index;
+                while !self.done() && self.ch() != '}' && self.ch() != '"' {
+                    self.increment_index();
+                }
+                if self.done() || self.ch() != '}' {
+                    self.error_with_span(
+                        "truncated \\u{...} escape sequence",
+                        escape_location,
+                        self.location.clone(),
+                    );
+                    return None;
+                }
+                let digits = &self.program[digits_start..self.index];
+                self.increment_index();
+                match u32::from_str_radix(digits, 16) {
+                    Ok(value) => match char::from_u32(value) {
+                        Some(c) => Some(c),
+                        None => {
+                            self.error_with_span(
+                                "\\u{...} escape is not a valid Unicode scalar value",
+                                escape_location,
+                                self.location.clone(),
+                            );
+                            None
+                        }
+                    },
+                    Err(_) => {
+                        self.error_with_span(
+                            "invalid hex digits in \\u{...} escape sequence",
+                            escape_location,
+                            self.location.clone(),
+                        );
+                        None
+                    }
+                }
+            }
+            _ => {
+                self.error_with_span(
+                    &format!("unknown escape sequence \\{}", c),
+                    escape_location,
+                    self.location.clone(),
+                );
+                None
+            }
+        }
     }
 
-    fn read_symbol(&mut self) -> Token {
+    fn read_symbol(&mut self) -> Token<'src> {
         while !self.done() && is_symbol_character(self.ch()) {
             self.increment_index()
         }
-        let value: String = self.chars[self.start..self.index].iter().collect();
-        if let Some(type_) = KEYWORDS.get(&value) {
+        let value = &self.program[self.start..self.index];
+        if let Some(type_) = KEYWORDS.get(value) {
             self.make_token(*type_)
         } else {
             self.make_token(TokenType::Symbol)
@@ -275,57 +732,149 @@ impl Lexer {
                 break;
             } else if self.ch().is_whitespace() {
                 self.increment_index();
-            } else if self.ch() == '/' && self.peek(1) == '/' {
+            } else if self.ch() == '/' && self.peek(1) == Some('/') {
                 while !self.done() && self.ch() != '\n' {
                     self.increment_index();
                 }
+            } else if self.ch() == '/' && self.peek(1) == Some('*') {
+                let start_location = self.location.clone();
+                self.consume_block_comment(start_location);
             } else {
                 break;
             }
         }
     }
 
-    fn make_token(&mut self, type_: TokenType) -> Token {
-        let token = Token::new(
-            type_,
-            self.chars[self.start..self.index].iter().collect(),
-            self.start_location.clone(),
-        );
+    fn skip_whitespace(&mut self) {
+        while !self.done() && self.ch().is_whitespace() {
+            self.increment_index();
+        }
+    }
+
+    // Called with `self.start`/`self.start_location` already pointing at the leading `/` of a
+    // `//` comment. A third `/` (but not a fourth, mirroring how rustdoc treats `////` as a
+    // plain comment rather than a doc comment) marks it as a doc comment.
+    fn read_line_comment(&mut self) -> Token<'src> {
+        let is_doc_comment = self.peek(2) == Some('/') && self.peek(3) != Some('/');
+        while !self.done() && self.ch() != '\n' {
+            self.increment_index();
+        }
+        self.make_token(if is_doc_comment {
+            TokenType::DocComment
+        } else {
+            TokenType::Comment
+        })
+    }
+
+    // Called with `self.start`/`self.start_location` already pointing at the leading `/` of a
+    // `/*` comment.
+    fn read_block_comment(&mut self) -> Token<'src> {
+        self.consume_block_comment(self.start_location.clone());
+        self.make_token(TokenType::Comment)
+    }
+
+    // Consumes a `/* ... */` comment, tracking nesting depth so `/* a /* b */ c */` is consumed
+    // as a single comment. Reports an `unclosed block comment` error, spanning from
+    // `start_location` to EOF, if depth never returns to zero.
+    fn consume_block_comment(&mut self, start_location: common::Location) {
+        self.increment_index();
+        self.increment_index();
+        let mut depth = 1;
+        while depth > 0 {
+            if self.done() {
+                self.error_with_span(
+                    "unclosed block comment",
+                    start_location,
+                    self.location.clone(),
+                );
+                return;
+            } else if self.ch() == '/' && self.peek(1) == Some('*') {
+                self.increment_index();
+                self.increment_index();
+                depth += 1;
+            } else if self.ch() == '*' && self.peek(1) == Some('/') {
+                self.increment_index();
+                self.increment_index();
+                depth -= 1;
+            } else {
+                self.increment_index();
+            }
+        }
+    }
+
+    fn make_token(&mut self, type_: TokenType) -> Token<'src> {
+        let value = &self.program[self.start..self.index];
+        self.finish_token(type_, Cow::Borrowed(value), value)
+    }
+
+    fn finish_token(
+        &mut self,
+        type_: TokenType,
+        value: Cow<'src, str>,
+        raw: &'src str,
+    ) -> Token<'src> {
+        let token = Token::with_raw(type_, value, raw, self.start_location.clone());
         self.start = self.index;
         self.start_location = self.location.clone();
         self.token = token;
         self.token.clone()
     }
 
+    fn error(&mut self, message: &str, location: common::Location) {
+        self.errors
+            .push(errors::VeniceError::new(message, location));
+    }
+
+    fn error_with_span(
+        &mut self,
+        message: &str,
+        location: common::Location,
+        end_location: common::Location,
+    ) {
+        self.errors.push(errors::VeniceError::new_with_span(
+            message,
+            location,
+            end_location,
+        ));
+    }
+
     fn increment_index(&mut self) {
         if self.done() {
             return;
         }
 
+        let width = self.ch().len_utf8();
         if self.ch() == '\n' {
             self.location.column = 1;
             self.location.line += 1;
         } else {
             self.location.column += 1;
         }
-        self.index += 1;
+        self.location.byte_offset += width;
+        self.index += width;
     }
 
     fn ch(&self) -> char {
-        self.chars[self.index]
+        self.program[self.index..].chars().next().unwrap()
     }
 
-    fn peek(&self, n: usize) -> char {
-        self.chars[self.index + n]
+    // Returns the `n`th character after the current one (0-indexed), or `None` if the source ends
+    // first.
+    fn peek(&self, n: usize) -> Option<char> {
+        self.program[self.index..].chars().nth(n)
     }
 }
 
+// Approximates the Unicode `XID_Start`/`XID_Continue` properties with `char::is_alphabetic` and
+// `char::is_alphanumeric`, which are close enough for identifier purposes: they admit accented
+// Latin, Greek, CJK, etc. while still excluding emoji and other non-letter symbols. Keywords are
+// matched separately and remain ASCII-only, so widening these does not affect keyword lookup.
 fn is_symbol_first_character(c: char) -> bool {
-    c.is_ascii_alphabetic() || c == '_'
+    c.is_alphabetic() || c == '_'
 }
 
 fn is_symbol_character(c: char) -> bool {
-    c.is_ascii_alphanumeric() || c == '_'
+    c.is_alphanumeric() || c == '_'
 }
 
 #[cfg(test)]
@@ -369,6 +918,15 @@ mod tests {
         assert_eq!(lexer.next(), token(TokenType::End, ""));
     }
 
+    #[test]
+    fn compound_assignment_operators() {
+        let mut lexer = Lexer::new("<string>", "+= -= *= /=");
+        assert_eq!(lexer.token(), token(TokenType::PlusAssign, "+="));
+        assert_eq!(lexer.next(), token(TokenType::MinusAssign, "-="));
+        assert_eq!(lexer.next(), token(TokenType::MultiplyAssign, "*="));
+        assert_eq!(lexer.next(), token(TokenType::DivideAssign, "/="));
+    }
+
     #[test]
     fn symbols() {
         let mut lexer = Lexer::new("<string>", "_ abc0 lorem_ipsum");
@@ -377,15 +935,31 @@ mod tests {
         assert_eq!(lexer.next(), token(TokenType::Symbol, "lorem_ipsum"));
     }
 
+    #[test]
+    fn unicode_symbols() {
+        let mut lexer = Lexer::new("<string>", "café Σύμβολο 変数");
+        assert_eq!(lexer.token(), token(TokenType::Symbol, "café"));
+        assert_eq!(lexer.next(), token(TokenType::Symbol, "Σύμβολο"));
+        assert_eq!(lexer.next(), token(TokenType::Symbol, "変数"));
+    }
+
+    #[test]
+    fn symbol_cannot_start_with_a_digit() {
+        let mut lexer = Lexer::new("<string>", "9abc");
+        assert_eq!(lexer.token(), token(TokenType::Integer, "9"));
+        assert_eq!(lexer.next(), token(TokenType::Symbol, "abc"));
+    }
+
     #[test]
     fn keywords() {
         let mut lexer = Lexer::new(
             "<string>",
-            "let assert record new and or not if else while for in const func return true false",
+            "let assert record enum new and or not if else while for in const func return true false break continue",
         );
         assert_eq!(lexer.token(), token(TokenType::Let, "let"));
         assert_eq!(lexer.next(), token(TokenType::Assert, "assert"));
         assert_eq!(lexer.next(), token(TokenType::Record, "record"));
+        assert_eq!(lexer.next(), token(TokenType::Enum, "enum"));
         assert_eq!(lexer.next(), token(TokenType::New, "new"));
         assert_eq!(lexer.next(), token(TokenType::And, "and"));
         assert_eq!(lexer.next(), token(TokenType::Or, "or"));
@@ -400,6 +974,8 @@ mod tests {
         assert_eq!(lexer.next(), token(TokenType::Return, "return"));
         assert_eq!(lexer.next(), token(TokenType::True, "true"));
         assert_eq!(lexer.next(), token(TokenType::False, "false"));
+        assert_eq!(lexer.next(), token(TokenType::Break, "break"));
+        assert_eq!(lexer.next(), token(TokenType::Continue, "continue"));
     }
 
     #[test]
@@ -421,14 +997,156 @@ mod tests {
     #[test]
     fn simple_string_literal() {
         let lexer = Lexer::new("<string>", "\"abc\"");
-        assert_eq!(lexer.token(), token(TokenType::String, "\"abc\""));
+        assert_eq!(lexer.token(), token(TokenType::String, "abc"));
+        assert_eq!(lexer.token().raw, "\"abc\"");
     }
 
     #[test]
     fn string_literal_with_backslash() {
-        // A two-character string literal: a backslash followed by a double quote
+        // A two-character raw string literal -- a backslash followed by a double quote -- decodes
+        // to a single double-quote character.
         let lexer = Lexer::new("<string>", r#""\"""#);
-        assert_eq!(lexer.token(), token(TokenType::String, r#""\"""#));
+        assert_eq!(lexer.token(), token(TokenType::String, "\""));
+        assert_eq!(lexer.token().raw, r#""\"""#);
+    }
+
+    #[test]
+    fn string_literal_with_simple_escapes() {
+        let lexer = Lexer::new("<string>", r#""a\nb\tc\rd\\e\0f""#);
+        assert_eq!(lexer.token(), token(TokenType::String, "a\nb\tc\rd\\e\0f"));
+        assert!(lexer.errors().is_empty());
+    }
+
+    #[test]
+    fn string_literal_with_hex_escape() {
+        let lexer = Lexer::new("<string>", r#""\x41\x42""#);
+        assert_eq!(lexer.token(), token(TokenType::String, "AB"));
+        assert!(lexer.errors().is_empty());
+    }
+
+    #[test]
+    fn string_literal_with_unicode_escape() {
+        let lexer = Lexer::new("<string>", r#""\u{1F600}""#);
+        assert_eq!(lexer.token(), token(TokenType::String, "\u{1F600}"));
+        assert!(lexer.errors().is_empty());
+    }
+
+    #[test]
+    fn string_literal_with_truncated_hex_escape_is_an_error() {
+        let lexer = Lexer::new("<string>", r#""\x4""#);
+        assert_eq!(lexer.token().type_, TokenType::String);
+        assert_eq!(lexer.errors().len(), 1);
+        assert_eq!(lexer.errors()[0].message, "truncated \\x escape sequence");
+    }
+
+    #[test]
+    fn string_literal_with_truncated_unicode_escape_is_an_error() {
+        let lexer = Lexer::new("<string>", r#""\u{41""#);
+        assert_eq!(lexer.token().type_, TokenType::String);
+        assert_eq!(lexer.errors().len(), 1);
+        assert_eq!(
+            lexer.errors()[0].message,
+            "truncated \\u{...} escape sequence"
+        );
+    }
+
+    #[test]
+    fn string_literal_with_invalid_unicode_scalar_value_is_an_error() {
+        let lexer = Lexer::new("<string>", r#""\u{D800}""#);
+        assert_eq!(lexer.token().type_, TokenType::String);
+        assert_eq!(lexer.errors().len(), 1);
+        assert_eq!(
+            lexer.errors()[0].message,
+            "\\u{...} escape is not a valid Unicode scalar value"
+        );
+    }
+
+    #[test]
+    fn string_literal_with_unknown_escape_is_an_error() {
+        let lexer = Lexer::new("<string>", r#""\q""#);
+        assert_eq!(lexer.token().type_, TokenType::String);
+        assert_eq!(lexer.errors().len(), 1);
+        assert_eq!(lexer.errors()[0].message, "unknown escape sequence \\q");
+    }
+
+    #[test]
+    fn interpolated_string_literal() {
+        let mut lexer = Lexer::new("<string>", r#""x = ${x}""#);
+        assert_eq!(lexer.token(), token(TokenType::StringPiece, "x = "));
+        assert_eq!(lexer.next(), token(TokenType::StringInterpStart, "${"));
+        assert_eq!(lexer.next(), token(TokenType::Symbol, "x"));
+        assert_eq!(lexer.next(), token(TokenType::StringInterpEnd, "}"));
+        assert_eq!(lexer.next(), token(TokenType::StringPiece, ""));
+        assert_eq!(lexer.next(), token(TokenType::End, ""));
+    }
+
+    #[test]
+    fn interpolated_string_literal_with_multiple_pieces() {
+        let mut lexer = Lexer::new("<string>", r#""a${x}b${y}c""#);
+        assert_eq!(lexer.token(), token(TokenType::StringPiece, "a"));
+        assert_eq!(lexer.next(), token(TokenType::StringInterpStart, "${"));
+        assert_eq!(lexer.next(), token(TokenType::Symbol, "x"));
+        assert_eq!(lexer.next(), token(TokenType::StringInterpEnd, "}"));
+        assert_eq!(lexer.next(), token(TokenType::StringPiece, "b"));
+        assert_eq!(lexer.next(), token(TokenType::StringInterpStart, "${"));
+        assert_eq!(lexer.next(), token(TokenType::Symbol, "y"));
+        assert_eq!(lexer.next(), token(TokenType::StringInterpEnd, "}"));
+        assert_eq!(lexer.next(), token(TokenType::StringPiece, "c"));
+    }
+
+    #[test]
+    fn interpolated_string_literal_with_an_expression() {
+        let mut lexer = Lexer::new("<string>", r#""sum = ${a + b}""#);
+        assert_eq!(lexer.token(), token(TokenType::StringPiece, "sum = "));
+        assert_eq!(lexer.next(), token(TokenType::StringInterpStart, "${"));
+        assert_eq!(lexer.next(), token(TokenType::Symbol, "a"));
+        assert_eq!(lexer.next(), token(TokenType::Plus, "+"));
+        assert_eq!(lexer.next(), token(TokenType::Symbol, "b"));
+        assert_eq!(lexer.next(), token(TokenType::StringInterpEnd, "}"));
+        assert_eq!(lexer.next(), token(TokenType::StringPiece, ""));
+    }
+
+    #[test]
+    fn interpolated_string_literal_with_nested_record_literal_braces() {
+        // The `{`/`}` of the record literal must not be mistaken for the interpolation's closing
+        // brace.
+        let mut lexer = Lexer::new("<string>", r#""${new Point { x: 1 }}""#);
+        assert_eq!(lexer.token(), token(TokenType::StringPiece, ""));
+        assert_eq!(lexer.next(), token(TokenType::StringInterpStart, "${"));
+        assert_eq!(lexer.next(), token(TokenType::New, "new"));
+        assert_eq!(lexer.next(), token(TokenType::Symbol, "Point"));
+        assert_eq!(lexer.next(), token(TokenType::CurlyOpen, "{"));
+        assert_eq!(lexer.next(), token(TokenType::Symbol, "x"));
+        assert_eq!(lexer.next(), token(TokenType::Colon, ":"));
+        assert_eq!(lexer.next(), token(TokenType::Integer, "1"));
+        assert_eq!(lexer.next(), token(TokenType::CurlyClose, "}"));
+        assert_eq!(lexer.next(), token(TokenType::StringInterpEnd, "}"));
+        assert_eq!(lexer.next(), token(TokenType::StringPiece, ""));
+    }
+
+    #[test]
+    fn nested_interpolated_string_literal() {
+        let mut lexer = Lexer::new("<string>", r#""${ "${x}" }""#);
+        assert_eq!(lexer.token(), token(TokenType::StringPiece, ""));
+        assert_eq!(lexer.next(), token(TokenType::StringInterpStart, "${"));
+        assert_eq!(lexer.next(), token(TokenType::StringPiece, ""));
+        assert_eq!(lexer.next(), token(TokenType::StringInterpStart, "${"));
+        assert_eq!(lexer.next(), token(TokenType::Symbol, "x"));
+        assert_eq!(lexer.next(), token(TokenType::StringInterpEnd, "}"));
+        assert_eq!(lexer.next(), token(TokenType::StringPiece, ""));
+        assert_eq!(lexer.next(), token(TokenType::StringInterpEnd, "}"));
+        assert_eq!(lexer.next(), token(TokenType::StringPiece, ""));
+    }
+
+    #[test]
+    fn unclosed_interpolation_at_eof_is_reported() {
+        let mut lexer = Lexer::new("<string>", r#""${x"#);
+        assert_eq!(lexer.token(), token(TokenType::StringPiece, ""));
+        assert_eq!(lexer.next(), token(TokenType::StringInterpStart, "${"));
+        assert_eq!(lexer.next(), token(TokenType::Symbol, "x"));
+        assert_eq!(lexer.next(), token(TokenType::End, ""));
+        assert_eq!(lexer.errors().len(), 1);
+        assert_eq!(lexer.errors()[0].message, "unclosed string interpolation");
     }
 
     #[test]
@@ -437,4 +1155,121 @@ mod tests {
         assert_eq!(lexer.token(), token(TokenType::Symbol, "a"));
         assert_eq!(lexer.next(), token(TokenType::Symbol, "b"));
     }
+
+    #[test]
+    fn ignore_nested_block_comments() {
+        let mut lexer = Lexer::new("<string>", "a /* x /* y */ z */ b");
+        assert_eq!(lexer.token(), token(TokenType::Symbol, "a"));
+        assert_eq!(lexer.next(), token(TokenType::Symbol, "b"));
+    }
+
+    #[test]
+    fn unclosed_block_comment_is_reported() {
+        let mut lexer = Lexer::new("<string>", "a /* x /* y */ z");
+        assert_eq!(lexer.token(), token(TokenType::Symbol, "a"));
+        assert_eq!(lexer.next(), token(TokenType::End, ""));
+        assert_eq!(lexer.errors().len(), 1);
+        assert_eq!(lexer.errors()[0].message, "unclosed block comment");
+    }
+
+    #[test]
+    fn preserved_line_and_doc_comments() {
+        let mut lexer = Lexer::new_with_comments("<string>", "a // plain\n/// docs\nb");
+        assert_eq!(lexer.token(), token(TokenType::Symbol, "a"));
+        assert_eq!(lexer.next(), token(TokenType::Comment, "// plain"));
+        assert_eq!(lexer.next(), token(TokenType::DocComment, "/// docs"));
+        assert_eq!(lexer.next(), token(TokenType::Symbol, "b"));
+    }
+
+    #[test]
+    fn preserved_block_comments() {
+        let mut lexer = Lexer::new_with_comments("<string>", "a /* x /* y */ z */ b");
+        assert_eq!(lexer.token(), token(TokenType::Symbol, "a"));
+        assert_eq!(lexer.next(), token(TokenType::Comment, "/* x /* y */ z */"));
+        assert_eq!(lexer.next(), token(TokenType::Symbol, "b"));
+    }
+
+    #[test]
+    fn radix_integer_literals() {
+        let mut lexer = Lexer::new("<string>", "0x1F 0o17 0b101");
+        assert_eq!(lexer.token(), token(TokenType::Integer, "0x1F"));
+        assert_eq!(lexer.next(), token(TokenType::Integer, "0o17"));
+        assert_eq!(lexer.next(), token(TokenType::Integer, "0b101"));
+    }
+
+    #[test]
+    fn integer_literal_suffixes() {
+        let mut lexer = Lexer::new("<string>", "5i32 10u8 0x1Fu64 3.5i32");
+        assert_eq!(lexer.token(), token(TokenType::Integer, "5i32"));
+        assert_eq!(lexer.next(), token(TokenType::Integer, "10u8"));
+        assert_eq!(lexer.next(), token(TokenType::Integer, "0x1Fu64"));
+        // A suffix is only recognized on an integer literal -- `3.5i32` lexes as the float `3.5`
+        // followed by a separate `i32` symbol.
+        assert_eq!(lexer.next(), token(TokenType::Float, "3.5"));
+        assert_eq!(lexer.next(), token(TokenType::Symbol, "i32"));
+    }
+
+    #[test]
+    fn malformed_radix_integer_literal() {
+        let lexer = Lexer::new("<string>", "0x");
+        assert_eq!(lexer.token(), token(TokenType::Unknown, "0x"));
+        assert_eq!(lexer.errors().len(), 1);
+    }
+
+    #[test]
+    fn unexpected_character_is_reported() {
+        let mut lexer = Lexer::new("<string>", "1 # 2");
+        assert_eq!(lexer.token(), token(TokenType::Integer, "1"));
+        assert_eq!(lexer.next(), token(TokenType::Unknown, "#"));
+        assert_eq!(lexer.next(), token(TokenType::Integer, "2"));
+        assert_eq!(lexer.errors().len(), 1);
+    }
+
+    #[test]
+    fn unclosed_string_literal_at_eof_is_reported() {
+        let lexer = Lexer::new("<string>", "\"abc");
+        assert_eq!(lexer.token().type_, TokenType::String);
+        assert_eq!(lexer.errors().len(), 1);
+    }
+
+    #[test]
+    fn unclosed_string_literal_at_newline_is_reported() {
+        let lexer = Lexer::new("<string>", "\"abc\ndef\"");
+        assert_eq!(lexer.token().type_, TokenType::String);
+        assert_eq!(lexer.errors().len(), 1);
+    }
+
+    #[test]
+    fn unterminated_escape_at_eof_is_reported() {
+        let lexer = Lexer::new("<string>", "\"abc\\");
+        assert_eq!(lexer.token().type_, TokenType::String);
+        assert_eq!(lexer.errors().len(), 1);
+    }
+
+    #[test]
+    fn float_literals() {
+        let mut lexer = Lexer::new("<string>", "1.5 0.25 1e10 1.5e-3 2E+4");
+        assert_eq!(lexer.token(), token(TokenType::Float, "1.5"));
+        assert_eq!(lexer.next(), token(TokenType::Float, "0.25"));
+        assert_eq!(lexer.next(), token(TokenType::Float, "1e10"));
+        assert_eq!(lexer.next(), token(TokenType::Float, "1.5e-3"));
+        assert_eq!(lexer.next(), token(TokenType::Float, "2E+4"));
+    }
+
+    #[test]
+    fn dot_after_integer_is_not_a_float() {
+        // `1.method()` must lex as Integer, Dot, Symbol -- not start of a float.
+        let mut lexer = Lexer::new("<string>", "1.method()");
+        assert_eq!(lexer.token(), token(TokenType::Integer, "1"));
+        assert_eq!(lexer.next(), token(TokenType::Dot, "."));
+        assert_eq!(lexer.next(), token(TokenType::Symbol, "method"));
+    }
+
+    #[test]
+    fn digit_separators_are_stripped() {
+        let mut lexer = Lexer::new("<string>", "1_000_000 0x1_F 3.14_15");
+        assert_eq!(lexer.token(), token(TokenType::Integer, "1000000"));
+        assert_eq!(lexer.next(), token(TokenType::Integer, "0x1F"));
+        assert_eq!(lexer.next(), token(TokenType::Float, "3.1415"));
+    }
 }
@@ -6,23 +6,38 @@ use clap::Parser;
 use std::fs;
 use std::fs::File;
 use std::io::prelude::*;
-use std::io::{BufReader, BufWriter};
-use std::path::PathBuf;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
 use std::process::Command;
 use std::time::Instant;
 
+use vfs::Vfs;
+
 #[macro_use]
 extern crate lazy_static;
 
+mod aarch64;
 mod analyzer;
 mod ast;
+mod backend;
+mod bytecode;
+mod c_backend;
+mod cache;
 mod codegen;
 mod common;
 mod errors;
+mod evaluator;
+mod inference;
 mod lexer;
 mod parser;
 mod ptree;
+mod riscv;
+mod ssa;
+mod vfs;
 mod vil;
+mod vil_opt;
+mod vil_parser;
+mod visitor;
 mod x86;
 
 /// The compiler for the Venice programming language
@@ -40,35 +55,106 @@ struct Cli {
     #[clap(long)]
     debug: bool,
 
-    /// Prints the AST and exits.
-    #[clap(long)]
-    ast: bool,
+    /// Stops the pipeline after the given stage and prints (or writes) its output instead of
+    /// continuing on to produce an executable: "ptree" (the raw parse tree, after type
+    /// inference has filled in omitted annotations), "ast" (the type-checked AST), "vil" (the
+    /// intermediate-language program), "asm" (the generated assembly for `--target`), "obj" (an assembled
+    /// but unlinked object file), or "exe" (the default: link a runnable binary).
+    #[clap(long, default_value = "exe")]
+    emit: String,
+
+    /// Instruction set to compile to: "x86_64" (the default), "riscv64", or "aarch64". Only
+    /// "x86_64" can be assembled and linked into a runnable binary today; "riscv64" and "aarch64"
+    /// can only be used with `--emit=vil` or `--emit=asm`, since neither has an assembler or
+    /// linker wired up yet.
+    #[clap(long, default_value = "x86_64")]
+    target: String,
 
     /// Prints execution time of the different stages of compilation.
     #[clap(long)]
     profile: bool,
+
+    /// Runs the program with the tree-walking evaluator instead of compiling it.
+    #[clap(long)]
+    interpret: bool,
+
+    /// Compiles the program to the portable bytecode format and runs it directly with the
+    /// built-in interpreter, instead of compiling to a native executable. Unlike --target
+    /// x86_64/riscv64, this works on any platform without an assembler or linker.
+    #[clap(long)]
+    run: bool,
+
+    /// Compiles the program to C source code, prints it, and exits.
+    #[clap(long)]
+    emit_c: bool,
+
+    /// Format to print compiler diagnostics in: "human" (the default) or "json" for one
+    /// machine-readable diagnostic object per line.
+    #[clap(long, default_value = "human")]
+    error_format: String,
+
+    /// Skips the build cache, forcing a full recompile even if a cached artifact for this exact
+    /// source and set of flags already exists.
+    #[clap(long)]
+    no_cache: bool,
+
+    /// Guards every integer division against a zero divisor, trapping through the runtime's
+    /// `venice_trap_divzero` instead of letting the CPU fault, at the cost of a comparison and a
+    /// few stores before each division. Off by default; release builds that have already validated
+    /// their inputs can leave this unset to skip the extra checks.
+    #[clap(long)]
+    checked_arithmetic: bool,
 }
 
 fn main() {
+    // `venice cache clear` is handled up front, before `Cli::parse()`, since it has no input
+    // program and doesn't fit the rest of the flat flag-based interface above.
+    let mut raw_args = std::env::args();
+    raw_args.next();
+    if let (Some(first), Some(second)) = (raw_args.next(), raw_args.next()) {
+        if first == "cache" && second == "clear" {
+            let cache = cache::Cache::open().expect("could not open cache directory");
+            cache.clear().expect("could not clear cache directory");
+            std::process::exit(0);
+        }
+    }
+
     let cli = Cli::parse();
 
-    // Open the input file.
-    let file = File::open(&cli.path).expect("could not open file");
-    let mut buf_reader = BufReader::new(file);
-    let mut program = String::new();
-    buf_reader
-        .read_to_string(&mut program)
-        .expect("could not read from file");
+    // Read the input file through the VFS abstraction rather than calling `std::fs` directly, so
+    // that a test can swap in a `vfs::MemoryFs` seeded with the program's source instead of
+    // writing it to disk.
+    let mut source_vfs = vfs::RealFs::new();
+    let file_id = source_vfs.file_id(Path::new(&cli.path));
+    let program =
+        String::from_utf8(source_vfs.load(file_id).to_vec()).expect("source file is not valid UTF-8");
+
+    // Consult the build cache: if the default pipeline (compile and link a native executable)
+    // has already produced this exact output for this exact source and flags, reuse it and skip
+    // compilation entirely. Other `--emit` modes, `--emit-c`, and `--run`/`--interpret` don't produce
+    // a cached artifact here, since they don't reach the final linked executable this caches.
+    let mut output_path = PathBuf::from(&cli.path);
+    output_path.set_extension("");
+    let cacheable = !cli.no_cache && cli.emit == "exe" && !cli.run && !cli.interpret && !cli.emit_c;
+    let codegen_flags = format!(
+        "target={} debug={} error_format={} checked_arithmetic={}",
+        cli.target, cli.debug, cli.error_format, cli.checked_arithmetic
+    );
+    let cache_key = cache::CacheKey::new(&program, &codegen_flags);
+    if cacheable {
+        if let Ok(cache) = cache::Cache::open() {
+            if cache.fetch(&cache_key, &output_path).unwrap_or(false) {
+                std::process::exit(0);
+            }
+        }
+    }
 
     // Lex and parse the program.
     let mut now = Instant::now();
     let lexer = lexer::Lexer::new(&cli.path, &program);
-    let ptree_result = parser::parse(lexer);
-    if let Err(errors) = ptree_result {
-        for error in errors {
-            println!("error: {} ({})", error.message, error.location);
-        }
-        std::process::exit(1);
+    let (mut ptree, parse_errors) = parser::parse(lexer);
+    if !parse_errors.is_empty() {
+        report_errors(&parse_errors, &cli.error_format, &program);
     }
 
     if cli.profile {
@@ -76,15 +162,40 @@ fn main() {
         println!("Parsing: {:.2?}", elapsed);
     }
 
+    // Fill in any `let` statements that omitted their type annotation.
+    if let Err(errors) = inference::infer(&mut ptree) {
+        report_errors(&errors, &cli.error_format, &program);
+    }
+
+    if cli.emit == "ptree" {
+        println!("{}", ptree);
+        std::process::exit(0);
+    }
+
+    if cli.interpret {
+        if let Err(error) = evaluator::evaluate(&ptree) {
+            println!("error: {:?}", error);
+            std::process::exit(1);
+        }
+        std::process::exit(0);
+    }
+
+    if cli.emit_c {
+        match c_backend::generate(&ptree) {
+            Ok(source) => println!("{}", source),
+            Err(error) => {
+                print!("{}", error.render(&program));
+                std::process::exit(1);
+            }
+        }
+        std::process::exit(0);
+    }
+
     // Type-check the program.
     now = Instant::now();
-    let ptree = ptree_result.unwrap();
     let ast_result = analyzer::analyze(&ptree);
     if let Err(errors) = ast_result {
-        for error in errors {
-            println!("error: {} ({})", error.message, error.location);
-        }
-        std::process::exit(1);
+        report_errors(&errors, &cli.error_format, &program);
     }
 
     if cli.profile {
@@ -93,14 +204,35 @@ fn main() {
     }
 
     let ast = ast_result.unwrap();
-    if cli.ast {
+    if cli.emit == "ast" {
         println!("{}", ast);
         std::process::exit(0);
     }
 
-    // Generate a VIL program.
+    // Generate a VIL program, sizing its register allocator for whichever target it's headed for
+    // (see `backend::BackendConfig`) so the allocator isn't hard-coded to x86's register count.
     now = Instant::now();
-    let vil_program = codegen::generate(&ast).unwrap();
+    let backend_config: Box<dyn backend::BackendConfig> = match cli.target.as_str() {
+        "riscv64" => Box::new(riscv::RiscvConfig),
+        "aarch64" => Box::new(aarch64::Aarch64Config),
+        _ => Box::new(x86::X86Config),
+    };
+    let vil_result = codegen::generate(&ast, backend_config.as_ref(), cli.checked_arithmetic);
+    if let Err(error) = vil_result {
+        report_errors(&[error], &cli.error_format, &program);
+    }
+    let vil_program = vil_result.unwrap();
+    if cli.emit == "vil" {
+        println!("{}", vil_program);
+        std::process::exit(0);
+    }
+
+    if cli.run {
+        let bytecode_program = bytecode::generate(&vil_program);
+        let result = bytecode::run(&bytecode_program);
+        std::process::exit(i32::try_from(result).unwrap_or(1));
+    }
+
     if cli.keep_intermediate {
         let mut vil_output_path = PathBuf::from(&cli.path);
         vil_output_path.set_extension("vil");
@@ -118,13 +250,42 @@ fn main() {
         println!("Code generation (VIL): {:.2?}", elapsed);
     }
 
-    // Generate an x86 program.
+    // Lower the VIL program to the selected target's assembly.
     now = Instant::now();
+    if cli.target == "riscv64" || cli.target == "aarch64" {
+        if cli.emit != "asm" {
+            eprintln!(
+                "error: --target={} only supports --emit=vil or --emit=asm",
+                cli.target
+            );
+            std::process::exit(1);
+        }
+
+        let asm = if cli.target == "riscv64" {
+            riscv::generate(&vil_program).unwrap().to_string()
+        } else {
+            aarch64::generate(&vil_program).unwrap().to_string()
+        };
+
+        if cli.profile {
+            let elapsed = now.elapsed();
+            println!("Code generation ({}): {:.2?}", cli.target, elapsed);
+        }
+
+        println!("{}", asm);
+        std::process::exit(0);
+    }
+
     let x86_program = x86::generate(&vil_program).unwrap();
 
     if cli.profile {
         let elapsed = now.elapsed();
-        println!("Code generation (x86): {:.2?}", elapsed);
+        println!("Code generation (x86_64): {:.2?}", elapsed);
+    }
+
+    if cli.emit == "asm" {
+        println!("{}", x86_program);
+        std::process::exit(0);
     }
 
     // Write the assembly program to disk.
@@ -140,8 +301,36 @@ fn main() {
 
     let mut object_output_path = PathBuf::from(&cli.path);
     object_output_path.set_extension("o");
-    let mut output_path = PathBuf::from(&cli.path);
-    output_path.set_extension("");
+
+    if cli.emit == "obj" {
+        // Invoke gcc to assemble the textual assembly program into an object file, without
+        // linking it against the runtime.
+        let mut cmd = Command::new("gcc");
+        if cli.debug {
+            cmd.arg("-g");
+        }
+
+        let mut child = cmd
+            .arg("-c")
+            .arg("-o")
+            .arg(&object_output_path)
+            .arg(&x86_output_path)
+            .spawn()
+            .expect("failed to execute gcc");
+        let error_code = child.wait().expect("failed to wait on child");
+        if !error_code.success() {
+            if let Some(error_code) = error_code.code() {
+                panic!("gcc returned non-zero exit code: {}", error_code);
+            } else {
+                panic!("gcc returned non-zero exit code");
+            }
+        }
+
+        if !cli.keep_intermediate && !cli.debug {
+            let _ = fs::remove_file(&x86_output_path);
+        }
+        std::process::exit(0);
+    }
 
     // Invoke gcc to turn the textual assembly program into a binary executable.
     let mut cmd = Command::new("gcc");
@@ -185,4 +374,26 @@ fn main() {
         }
         let _ = fs::remove_file(&object_output_path);
     }
+
+    if cacheable {
+        if let Ok(cache) = cache::Cache::open() {
+            let _ = cache.store(&cache_key, &output_path);
+        }
+    }
+}
+
+/// Prints a batch of compiler diagnostics in the requested `--error-format` and exits with a
+/// non-zero status code. `format` is `"json"` for one diagnostic object per line (JSONL), or
+/// anything else for the default human-readable rendering (source snippet, caret underline, and
+/// any secondary labels or notes). `source` is the full text of the program the diagnostics
+/// refer to.
+fn report_errors(errors: &[errors::VeniceError], format: &str, source: &str) -> ! {
+    for error in errors {
+        if format == "json" {
+            println!("{}", error.to_json());
+        } else {
+            print!("{}", error.render(source));
+        }
+    }
+    std::process::exit(1);
 }
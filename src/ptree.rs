@@ -15,6 +15,7 @@ pub enum Declaration {
     Function(FunctionDeclaration),
     Const(ConstDeclaration),
     Record(RecordDeclaration),
+    Enum(EnumDeclaration),
 }
 
 #[derive(Debug)]
@@ -58,29 +59,61 @@ pub struct RecordField {
     pub type_: Type,
 }
 
+/// A closed set of named alternatives, each with an optional payload type, e.g.
+/// `enum Option { Some(i64), None }`.
+#[derive(Debug)]
+pub struct EnumDeclaration {
+    pub name: String,
+    pub variants: Vec<EnumVariant>,
+    pub location: common::Location,
+}
+
+#[derive(Debug)]
+pub struct EnumVariant {
+    pub name: String,
+    /// `None` for a variant with no payload, e.g. `None` in `enum Option { Some(i64), None }`.
+    pub payload: Option<Type>,
+}
+
 #[derive(Debug)]
 pub enum Statement {
     Assert(AssertStatement),
     Assign(AssignStatement),
+    Break(BreakStatement),
+    Continue(ContinueStatement),
     Expression(Expression),
     For(ForStatement),
     If(IfStatement),
     Let(LetStatement),
+    Match(MatchStatement),
     Return(ReturnStatement),
     While(WhileStatement),
 }
 
+#[derive(Debug)]
+pub struct BreakStatement {
+    pub location: common::Location,
+}
+
+#[derive(Debug)]
+pub struct ContinueStatement {
+    pub location: common::Location,
+}
+
 #[derive(Debug)]
 pub struct LetStatement {
     pub symbol: String,
-    pub type_: Type,
+    /// `None` when the statement omits its annotation (`let x = 0;`); the inference pass fills
+    /// this in before any later stage of the compiler reads it.
+    pub type_: Option<Type>,
     pub value: Expression,
     pub location: common::Location,
 }
 
 #[derive(Debug)]
 pub struct AssignStatement {
-    pub symbol: String,
+    pub target: Box<Expression>,
+    pub op: Option<common::BinaryOpType>,
     pub value: Expression,
     pub location: common::Location,
 }
@@ -99,6 +132,40 @@ pub struct IfClause {
     pub body: Vec<Statement>,
 }
 
+/// `match <value> { case <pattern> { ... } case <pattern> { ... } }`. There's no separate `else`
+/// arm: a catch-all is just a `Pattern::Wildcard` arm, normally written last.
+#[derive(Debug)]
+pub struct MatchStatement {
+    pub value: Expression,
+    pub arms: Vec<MatchArm>,
+    pub location: common::Location,
+}
+
+#[derive(Debug)]
+pub struct MatchArm {
+    pub pattern: Pattern,
+    pub body: Vec<Statement>,
+    pub location: common::Location,
+}
+
+#[derive(Debug)]
+pub enum Pattern {
+    /// `_`: always matches, binds nothing.
+    Wildcard,
+    /// An integer, boolean, or other constant expression compared for equality against the
+    /// scrutinee.
+    Literal(Expression),
+    /// `Name { field1, field2 }`: matches a record of type `Name`, binding each named field to a
+    /// local variable of the same name.
+    Record { name: String, fields: Vec<String> },
+    /// `Name` or `Name(binding)`: matches an enum value tagged with variant `Name`, optionally
+    /// binding its payload (if it has one) to a local variable.
+    Variant {
+        name: String,
+        binding: Option<String>,
+    },
+}
+
 #[derive(Debug)]
 pub struct WhileStatement {
     pub condition: Expression,
@@ -131,12 +198,16 @@ pub struct AssertStatement {
 pub struct Expression {
     pub kind: ExpressionKind,
     pub location: common::Location,
+    /// The location of the expression's last token, so that diagnostics can underline the whole
+    /// expression (from `location` to `end_location`) instead of just a single point.
+    pub end_location: common::Location,
 }
 
 #[derive(Debug)]
 pub enum ExpressionKind {
     Boolean(bool),
-    Integer(i64),
+    Integer(i64, Option<IntegerSuffix>),
+    Float(f64),
     String(String),
     Symbol(String),
     Binary(BinaryExpression),
@@ -146,7 +217,9 @@ pub enum ExpressionKind {
     Index(IndexExpression),
     TupleIndex(TupleIndexExpression),
     Attribute(AttributeExpression),
+    MethodCall(MethodCallExpression),
     List(ListLiteral),
+    ListComprehension(ListComprehension),
     Tuple(TupleLiteral),
     Map(MapLiteral),
     Record(RecordLiteral),
@@ -203,12 +276,29 @@ pub struct AttributeExpression {
     pub location: common::Location,
 }
 
+#[derive(Debug)]
+pub struct MethodCallExpression {
+    pub receiver: Box<Expression>,
+    pub method: String,
+    pub arguments: Vec<Expression>,
+    pub location: common::Location,
+}
+
 #[derive(Debug)]
 pub struct ListLiteral {
     pub items: Vec<Expression>,
     pub location: common::Location,
 }
 
+#[derive(Debug)]
+pub struct ListComprehension {
+    pub value: Box<Expression>,
+    pub symbol: String,
+    pub iterator: Box<Expression>,
+    pub condition: Option<Box<Expression>>,
+    pub location: common::Location,
+}
+
 #[derive(Debug)]
 pub struct TupleLiteral {
     pub items: Vec<Expression>,
@@ -246,6 +336,20 @@ pub struct ParameterizedType {
     pub parameters: Vec<Type>,
 }
 
+/// The width/signedness suffix attached directly to an integer literal, e.g. the `i32` in
+/// `5i32`. Left as `None` for an unsuffixed literal, which the analyzer defaults to `i64`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IntegerSuffix {
+    I8,
+    I16,
+    I32,
+    I64,
+    U8,
+    U16,
+    U32,
+    U64,
+}
+
 impl fmt::Display for Program {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "(program")?;
@@ -262,6 +366,7 @@ impl fmt::Display for Declaration {
             Declaration::Function(declaration) => write!(f, "{}", declaration),
             Declaration::Const(declaration) => write!(f, "{}", declaration),
             Declaration::Record(declaration) => write!(f, "{}", declaration),
+            Declaration::Enum(declaration) => write!(f, "{}", declaration),
         }
     }
 }
@@ -303,30 +408,64 @@ impl fmt::Display for RecordDeclaration {
     }
 }
 
+impl fmt::Display for EnumDeclaration {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "(enum-decl {}", self.name)?;
+        for variant in &self.variants {
+            match &variant.payload {
+                Some(payload) => write!(f, " ({} {})", variant.name, payload)?,
+                None => write!(f, " ({})", variant.name)?,
+            }
+        }
+        write!(f, ")")
+    }
+}
+
 impl fmt::Display for Statement {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             Statement::Let(stmt) => write!(f, "{}", stmt),
             Statement::Assign(stmt) => write!(f, "{}", stmt),
             Statement::If(stmt) => write!(f, "{}", stmt),
+            Statement::Match(stmt) => write!(f, "{}", stmt),
             Statement::While(stmt) => write!(f, "{}", stmt),
             Statement::For(stmt) => write!(f, "{}", stmt),
             Statement::Return(stmt) => write!(f, "{}", stmt),
             Statement::Assert(stmt) => write!(f, "{}", stmt),
+            Statement::Break(stmt) => write!(f, "{}", stmt),
+            Statement::Continue(stmt) => write!(f, "{}", stmt),
             Statement::Expression(stmt) => write!(f, "{}", stmt),
         }
     }
 }
 
+impl fmt::Display for BreakStatement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "(break)")
+    }
+}
+
+impl fmt::Display for ContinueStatement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "(continue)")
+    }
+}
+
 impl fmt::Display for LetStatement {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "(let {} {} {})", self.symbol, self.type_, self.value)
+        match &self.type_ {
+            Some(type_) => write!(f, "(let {} {} {})", self.symbol, type_, self.value),
+            None => write!(f, "(let {} _ {})", self.symbol, self.value),
+        }
     }
 }
 
 impl fmt::Display for AssignStatement {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "(assign {} {})", self.symbol, self.value)
+        match self.op {
+            Some(op) => write!(f, "(assign-op {:?} {} {})", op, self.target, self.value),
+            None => write!(f, "(assign {} {})", self.target, self.value),
+        }
     }
 }
 
@@ -351,6 +490,34 @@ impl fmt::Display for IfStatement {
     }
 }
 
+impl fmt::Display for MatchStatement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "(match {}", self.value)?;
+        for arm in &self.arms {
+            write!(f, " (case {} ", arm.pattern)?;
+            format_block(f, &arm.body)?;
+            write!(f, ")")?;
+        }
+        write!(f, ")")
+    }
+}
+
+impl fmt::Display for Pattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Pattern::Wildcard => write!(f, "_"),
+            Pattern::Literal(expr) => write!(f, "{}", expr),
+            Pattern::Record { name, fields } => {
+                write!(f, "{} {{{}}}", name, fields.join(", "))
+            }
+            Pattern::Variant { name, binding } => match binding {
+                Some(binding) => write!(f, "{}({})", name, binding),
+                None => write!(f, "{}", name),
+            },
+        }
+    }
+}
+
 impl fmt::Display for WhileStatement {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "(while {} ", self.condition)?;
@@ -394,7 +561,8 @@ impl fmt::Display for ExpressionKind {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             ExpressionKind::Boolean(e) => write!(f, "{}", e),
-            ExpressionKind::Integer(e) => write!(f, "{}", e),
+            ExpressionKind::Integer(e, _) => write!(f, "{}", e),
+            ExpressionKind::Float(e) => write!(f, "{:?}", e),
             ExpressionKind::String(e) => write!(f, "{:?}", e),
             ExpressionKind::Symbol(e) => write!(f, "{}", e),
             ExpressionKind::Binary(e) => write!(f, "{}", e),
@@ -404,7 +572,9 @@ impl fmt::Display for ExpressionKind {
             ExpressionKind::Index(e) => write!(f, "{}", e),
             ExpressionKind::TupleIndex(e) => write!(f, "{}", e),
             ExpressionKind::Attribute(e) => write!(f, "{}", e),
+            ExpressionKind::MethodCall(e) => write!(f, "{}", e),
             ExpressionKind::List(e) => write!(f, "{}", e),
+            ExpressionKind::ListComprehension(e) => write!(f, "{}", e),
             ExpressionKind::Tuple(e) => write!(f, "{}", e),
             ExpressionKind::Map(e) => write!(f, "{}", e),
             ExpressionKind::Record(e) => write!(f, "{}", e),
@@ -461,6 +631,19 @@ impl fmt::Display for AttributeExpression {
     }
 }
 
+impl fmt::Display for MethodCallExpression {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "(method-call {} {} (", self.receiver, self.method)?;
+        for (i, argument) in self.arguments.iter().enumerate() {
+            if i != 0 {
+                write!(f, " ")?;
+            }
+            write!(f, "{}", argument)?;
+        }
+        write!(f, "))")
+    }
+}
+
 impl fmt::Display for ListLiteral {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "(list")?;
@@ -471,6 +654,16 @@ impl fmt::Display for ListLiteral {
     }
 }
 
+impl fmt::Display for ListComprehension {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "(listcomp {} {} {}", self.value, self.symbol, self.iterator)?;
+        if let Some(condition) = &self.condition {
+            write!(f, " {}", condition)?;
+        }
+        write!(f, ")")
+    }
+}
+
 impl fmt::Display for TupleLiteral {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "(list")?;
@@ -0,0 +1,465 @@
+// Copyright 2022 The Venice Authors. All rights reserved.
+// Use of this source code is governed by an MIT-style license that can be
+// found in the LICENSE file.
+//
+// Compiles a VIL program into RV64I(+M) assembly text. This is another implementation of the
+// `backend::Backend` trait, alongside `x86.rs`, `aarch64.rs` and `bytecode.rs`; see `backend.rs`'s
+// module comment for the VIL -> machine code pipeline they plug into.
+//
+// Unlike x86.rs, this backend doesn't assign VIL registers via live-interval linear scan: it maps
+// each one directly onto a fixed physical register (see `REGISTERS`), the same kind of 1:1
+// mapping x86.rs used before its own allocator was added. It also has no machine-code encoder --
+// `--emit=asm` is as far as the pipeline can take a `--target riscv64` program today, since there
+// is no RISC-V assembler or linker wired up yet.
+
+use super::backend::Backend;
+use super::vil;
+use std::fmt;
+
+/// Tells codegen.rs's allocator this target has 14 usable VIL registers (see
+/// `backend::BackendConfig`) -- the same cap as `x86::X86Config`, even though `REGISTERS` below
+/// has 16 entries, since indices 14 and 15 are `sp`/`s0` (the stack and frame pointers), which are
+/// only ever touched directly by `prologue`/`epilogue`, never handed out to an ordinary VIL
+/// register.
+pub struct RiscvConfig;
+
+impl super::backend::BackendConfig for RiscvConfig {
+    fn register_count(&self) -> u8 {
+        14
+    }
+}
+
+pub fn generate(vil: &vil::Program) -> Result<Program, String> {
+    let mut generator = Generator::new();
+    generator.program.externs = vil.externs.clone();
+    super::backend::generate(&mut generator, vil);
+
+    for (string_name, string_value) in &vil.strings {
+        generator.program.data.push(Data {
+            name: string_name.clone(),
+            value: string_value.clone(),
+        });
+    }
+
+    Ok(generator.program)
+}
+
+pub struct Program {
+    externs: Vec<String>,
+    blocks: Vec<Block>,
+    data: Vec<Data>,
+}
+
+pub struct Block {
+    global: bool,
+    label: String,
+    instructions: Vec<Instruction>,
+}
+
+pub struct Data {
+    name: String,
+    value: String,
+}
+
+/// A single RV64I(+M) instruction, in the same three-operand-or-fewer shape the assembler
+/// mnemonics take. `Raw` exists for directives (`li`, `call`, branch pseudo-ops) whose operand
+/// count varies too much to give each its own variant.
+pub enum Instruction {
+    Raw(String),
+}
+
+/// VIL register index -> RV64I register name. Indices 7 and 13 both map to `a0`: those are
+/// `vil::Register`'s own param-0 and return/scratch indices (see vil.rs's
+/// `SCRATCH2_REGISTER_INDEX`/`RETURN_REGISTER_INDEX` comment), which already double up onto a
+/// single x86 register each; RV64I's calling convention uses `a0` for both of those roles too, so
+/// the same doubling falls out naturally here.
+const REGISTERS: &[&str] = &[
+    "t0", "t1", "s2", "s3", "s4", "s5", "s6", "a0", "a1", "a2", "a3", "a4", "a5", "a0", "sp", "s0",
+];
+
+const CALLER_SAVE_REGISTERS: &[u8] = &[0, 1];
+const CALLEE_SAVE_REGISTERS: &[u8] = &[2, 3, 4, 5, 6];
+
+fn register_name(r: vil::Register) -> &'static str {
+    REGISTERS[r.index() as usize]
+}
+
+struct Generator {
+    program: Program,
+    frame_size: i32,
+    /// RV64I has no flags register, so unlike x86's `Cmp`, there's nothing to lower it to on its
+    /// own: `lower_cmp`/`lower_fcmp` just remember their operands here (and whether they came from
+    /// `Cmp` or `FCmp`) for the `JumpIf` that VIL always emits immediately afterward to pick up and
+    /// lower into a single compare-and-branch instruction.
+    last_cmp: Option<(vil::Register, vil::Register, bool)>,
+}
+
+impl Generator {
+    fn new() -> Self {
+        Generator {
+            program: Program {
+                externs: Vec::new(),
+                blocks: Vec::new(),
+                data: Vec::new(),
+            },
+            frame_size: 0,
+            last_cmp: None,
+        }
+    }
+
+    fn push(&mut self, instruction: Instruction) {
+        let index = self.program.blocks.len() - 1;
+        self.program.blocks[index].instructions.push(instruction);
+    }
+
+    fn raw(&mut self, text: String) {
+        self.push(Instruction::Raw(text));
+    }
+}
+
+impl Backend for Generator {
+    fn start_function(&mut self, declaration: &vil::FunctionDeclaration) {
+        // This backend has no spill-slot allocator of its own (see the module comment), so the
+        // frame is just the one VIL already asked for.
+        self.frame_size = declaration.stack_frame_size;
+
+        self.program.blocks.push(Block {
+            global: declaration.name == "venice_main",
+            label: declaration.name.clone(),
+            instructions: Vec::new(),
+        });
+    }
+
+    fn start_block(&mut self, name: &str) {
+        self.program.blocks.push(Block {
+            global: false,
+            label: String::from(name),
+            instructions: Vec::new(),
+        });
+    }
+
+    fn prologue(&mut self) {
+        // Reserve the frame, then save the return address and the caller's frame pointer at its
+        // top -- the standard RV64I function-entry sequence.
+        let total = self.frame_size + 16;
+        self.raw(format!("addi sp, sp, -{}", total));
+        self.raw(format!("sd ra, {}(sp)", total - 8));
+        self.raw(format!("sd s0, {}(sp)", total - 16));
+        self.raw(format!("addi s0, sp, {}", total));
+
+        for callee_save in CALLEE_SAVE_REGISTERS {
+            self.raw(String::from("addi sp, sp, -8"));
+            self.raw(format!("sd {}, 0(sp)", REGISTERS[*callee_save as usize]));
+        }
+    }
+
+    fn epilogue(&mut self) {
+        for callee_save in CALLEE_SAVE_REGISTERS.iter().rev() {
+            self.raw(format!("ld {}, 0(sp)", REGISTERS[*callee_save as usize]));
+            self.raw(String::from("addi sp, sp, 8"));
+        }
+
+        let total = self.frame_size + 16;
+        self.raw(format!("ld ra, {}(sp)", total - 8));
+        self.raw(format!("ld s0, {}(sp)", total - 16));
+        self.raw(format!("addi sp, sp, {}", total));
+        self.raw(String::from("ret"));
+    }
+
+    fn lower_param(&mut self, i: u8, stack_offset: i32) {
+        self.raw(format!(
+            "sd {}, {}(s0)",
+            REGISTERS[self.param_register(i) as usize],
+            stack_offset
+        ));
+    }
+
+    fn lower_set(&mut self, r: vil::Register, imm: &vil::Immediate) {
+        match imm {
+            vil::Immediate::Integer(x) => self.raw(format!("li {}, {}", register_name(r), x)),
+            vil::Immediate::Label(s) => self.raw(format!("la {}, {}", register_name(r), s)),
+            // Floats travel through the same GPRs as integers (see vil.rs's `Immediate::Float`
+            // doc comment), so a float literal is just its bit pattern loaded the same way an
+            // integer one would be; the D-extension instructions that actually operate on it
+            // reinterpret those bits when they move them into a float register.
+            vil::Immediate::Float(x) => {
+                self.raw(format!("li {}, {}", register_name(r), x.to_bits() as i64))
+            }
+        }
+    }
+
+    fn lower_move(&mut self, r1: vil::Register, r2: vil::Register) {
+        self.raw(format!("mv {}, {}", register_name(r1), register_name(r2)));
+    }
+
+    fn lower_binary(
+        &mut self,
+        op: vil::BinaryOp,
+        r1: vil::Register,
+        r2: vil::Register,
+        r3: vil::Register,
+    ) {
+        let (d, a, b) = (register_name(r1), register_name(r2), register_name(r3));
+        let mnemonic = match op {
+            vil::BinaryOp::Add => "add",
+            vil::BinaryOp::Sub => "sub",
+            vil::BinaryOp::Mul => "mul",
+            // RV64I's `div` (in the M extension) leaves a separate `rem` instruction for the
+            // remainder, unlike x86's combined `div`; Venice only needs the quotient here.
+            vil::BinaryOp::Div => "div",
+            vil::BinaryOp::FAdd
+            | vil::BinaryOp::FSub
+            | vil::BinaryOp::FMul
+            | vil::BinaryOp::FDiv => {
+                // RV64D's arithmetic instructions only work on its own `f`-register file, so the
+                // operands' bit patterns have to be moved in (`fmv.d.x`) and the result moved back
+                // out (`fmv.x.d`) around the actual D-extension op; `fa0`/`fa1` are free to use as
+                // scratch here since Venice doesn't otherwise pass arguments in float registers.
+                let fmnemonic = match op {
+                    vil::BinaryOp::FAdd => "fadd.d",
+                    vil::BinaryOp::FSub => "fsub.d",
+                    vil::BinaryOp::FMul => "fmul.d",
+                    vil::BinaryOp::FDiv => "fdiv.d",
+                    _ => unreachable!(),
+                };
+                self.raw(format!("fmv.d.x fa0, {}", a));
+                self.raw(format!("fmv.d.x fa1, {}", b));
+                self.raw(format!("{} fa0, fa0, fa1", fmnemonic));
+                self.raw(format!("fmv.x.d {}, fa0", d));
+                return;
+            }
+        };
+        self.raw(format!("{} {}, {}, {}", mnemonic, d, a, b));
+    }
+
+    fn lower_unary(&mut self, op: vil::UnaryOp, r1: vil::Register, r2: vil::Register) {
+        let (d, a) = (register_name(r1), register_name(r2));
+        match op {
+            vil::UnaryOp::Negate => self.raw(format!("neg {}, {}", d, a)),
+            vil::UnaryOp::LogicalNot => self.raw(format!("seqz {}, {}", d, a)),
+            vil::UnaryOp::FNegate => {
+                self.raw(format!("fmv.d.x fa0, {}", a));
+                self.raw(String::from("fneg.d fa0, fa0"));
+                self.raw(format!("fmv.x.d {}, fa0", d));
+            }
+        }
+    }
+
+    fn lower_load(&mut self, r: vil::Register, offset: vil::MemoryOffset) {
+        self.raw(format!("ld {}, {}(s0)", register_name(r), offset));
+    }
+
+    fn lower_store(&mut self, r: vil::Register, offset: vil::MemoryOffset) {
+        self.raw(format!("sd {}, {}(s0)", register_name(r), offset));
+    }
+
+    fn lower_cmp(&mut self, r1: vil::Register, r2: vil::Register) {
+        self.last_cmp = Some((r1, r2, false));
+    }
+
+    fn lower_fcmp(&mut self, r1: vil::Register, r2: vil::Register) {
+        self.last_cmp = Some((r1, r2, true));
+    }
+
+    fn lower_cmp_ordering(&mut self, r1: vil::Register, r2: vil::Register, r3: vil::Register) {
+        // No single RV64I instruction computes a three-way ordering, so build it out of `slt`
+        // (which, like `feq.d`/`flt.d` below, writes a 0/1 result into a GPR rather than setting
+        // flags): `d = (r2 < r3)`, then reuse the same `t2` scratch `lower_jump_if`'s float path
+        // uses for `(r2 > r3)`, and subtract to land on -1, 0, or 1.
+        let (d, a, b) = (register_name(r1), register_name(r2), register_name(r3));
+        self.raw(format!("slt {}, {}, {}", d, a, b));
+        self.raw(format!("slt t2, {}, {}", b, a));
+        self.raw(format!("sub {}, t2, {}", d, d));
+    }
+
+    fn lower_fcmp_ordering(&mut self, r1: vil::Register, r2: vil::Register, r3: vil::Register) {
+        let (d, a, b) = (register_name(r1), register_name(r2), register_name(r3));
+        self.raw(format!("fmv.d.x fa0, {}", a));
+        self.raw(format!("fmv.d.x fa1, {}", b));
+        self.raw(format!("flt.d {}, fa0, fa1", d));
+        self.raw(String::from("flt.d t2, fa1, fa0"));
+        self.raw(format!("sub {}, t2, {}", d, d));
+    }
+
+    fn lower_call(
+        &mut self,
+        destination: vil::Register,
+        label: &vil::Label,
+        offsets: &[vil::MemoryOffset],
+        _variadic: bool,
+    ) {
+        // Unlike x86.rs, this backend doesn't yet materialize overflow arguments on the stack --
+        // codegen.rs itself no longer caps argument count (see its `generate_call_expression` doc
+        // comment), so a call with more than six arguments would otherwise silently alias two VIL
+        // arguments onto the same `a`-register instead of failing loudly.
+        if offsets.len() > 6 {
+            panic!("internal error: riscv64 backend cannot yet handle more than 6 arguments");
+        }
+
+        for caller_save in CALLER_SAVE_REGISTERS {
+            self.raw(String::from("addi sp, sp, -8"));
+            self.raw(format!("sd {}, 0(sp)", REGISTERS[*caller_save as usize]));
+        }
+
+        for (i, offset) in offsets.iter().enumerate() {
+            let param = REGISTERS[self.param_register(u8::try_from(i).unwrap()) as usize];
+            self.raw(format!("ld {}, {}(s0)", param, offset));
+        }
+
+        self.raw(format!("call {}", label.0));
+
+        for caller_save in CALLER_SAVE_REGISTERS.iter().rev() {
+            self.raw(format!("ld {}, 0(sp)", REGISTERS[*caller_save as usize]));
+            self.raw(String::from("addi sp, sp, 8"));
+        }
+
+        self.raw(format!("mv {}, a0", register_name(destination)));
+    }
+
+    fn lower_jump(&mut self, label: &vil::Label) {
+        self.raw(format!("j {}", label.0));
+    }
+
+    fn lower_jump_if(
+        &mut self,
+        condition: vil::JumpCondition,
+        true_label: &vil::Label,
+        false_label: &vil::Label,
+    ) {
+        let (r1, r2, is_float) = self
+            .last_cmp
+            .take()
+            .expect("internal error: JumpIf with no preceding Cmp");
+
+        if is_float {
+            // RV64D's `feq.d`/`flt.d`/`fle.d` don't set flags; they write a 0/1 result straight
+            // into a GPR, so the branch is two steps instead of one: do the comparison into a
+            // scratch register (`t2`, deliberately outside `REGISTERS` so it can't collide with a
+            // live VIL value), then branch on whether that's zero. There's no `fge.d`/`fgt.d`, so
+            // `Gt`/`Gte` swap their operands and reuse `flt.d`/`fle.d`, mirroring how the integer
+            // path below emulates `ble`/`bgt`.
+            let (fmnemonic, lhs, rhs) = match condition {
+                vil::JumpCondition::Eq | vil::JumpCondition::Neq => ("feq.d", r1, r2),
+                vil::JumpCondition::Lt => ("flt.d", r1, r2),
+                vil::JumpCondition::Gt => ("flt.d", r2, r1),
+                vil::JumpCondition::Lte => ("fle.d", r1, r2),
+                vil::JumpCondition::Gte => ("fle.d", r2, r1),
+            };
+            self.raw(format!("fmv.d.x fa0, {}", register_name(lhs)));
+            self.raw(format!("fmv.d.x fa1, {}", register_name(rhs)));
+            self.raw(format!("{} t2, fa0, fa1", fmnemonic));
+
+            let branch = if matches!(condition, vil::JumpCondition::Neq) {
+                "beqz"
+            } else {
+                "bnez"
+            };
+            self.raw(format!("{} t2, {}", branch, true_label.0));
+            self.raw(format!("j {}", false_label.0));
+            return;
+        }
+
+        // RV64I's branches compare two registers directly (no separate flags step), and only
+        // give us `beq`/`bne`/`blt`/`bge` -- `b(le|gt)` don't exist as real instructions, so
+        // those two swap their operands and use `bge`/`blt` instead, same as the assembler's own
+        // `ble`/`bgt` pseudo-ops do.
+        let (mnemonic, lhs, rhs) = match condition {
+            vil::JumpCondition::Eq => ("beq", r1, r2),
+            vil::JumpCondition::Neq => ("bne", r1, r2),
+            vil::JumpCondition::Lt => ("blt", r1, r2),
+            vil::JumpCondition::Gt => ("blt", r2, r1),
+            vil::JumpCondition::Lte => ("bge", r2, r1),
+            vil::JumpCondition::Gte => ("bge", r1, r2),
+        };
+        self.raw(format!(
+            "{} {}, {}, {}",
+            mnemonic,
+            register_name(lhs),
+            register_name(rhs),
+            true_label.0
+        ));
+        self.raw(format!("j {}", false_label.0));
+    }
+
+    fn lower_jump_ordering(
+        &mut self,
+        r: vil::Register,
+        less_label: &vil::Label,
+        equal_label: &vil::Label,
+        greater_label: &vil::Label,
+    ) {
+        let reg = register_name(r);
+        self.raw(format!("blt {}, zero, {}", reg, less_label.0));
+        self.raw(format!("beq {}, zero, {}", reg, equal_label.0));
+        self.raw(format!("j {}", greater_label.0));
+    }
+
+    fn lower_syscall(
+        &mut self,
+        destination: vil::Register,
+        number: i64,
+        offsets: &[vil::MemoryOffset],
+    ) {
+        if offsets.len() > 6 {
+            panic!("internal error: syscall cannot take more than 6 arguments");
+        }
+
+        // RV64I's syscall convention passes arguments in a0-a5 (the same registers as the regular
+        // calling convention) and the syscall number in a7, then traps with `ecall`; the result
+        // comes back in a0, same as a normal call's return value.
+        for (i, offset) in offsets.iter().enumerate() {
+            let param = REGISTERS[self.param_register(u8::try_from(i).unwrap()) as usize];
+            self.raw(format!("ld {}, {}(s0)", param, offset));
+        }
+
+        self.raw(format!("li a7, {}", number));
+        self.raw(String::from("ecall"));
+        self.raw(format!("mv {}, a0", register_name(destination)));
+    }
+
+    fn param_register(&self, i: u8) -> u8 {
+        i + 7
+    }
+}
+
+impl fmt::Display for Program {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for block in &self.blocks {
+            writeln!(f, "{}", block)?;
+        }
+
+        for datum in &self.data {
+            writeln!(f, "{}", datum)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl fmt::Display for Block {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.global {
+            writeln!(f, ".globl {}", self.label)?;
+        }
+
+        writeln!(f, "{}:", self.label)?;
+        for instruction in &self.instructions {
+            writeln!(f, "  {}", instruction)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Instruction::Raw(text) => write!(f, "{}", text),
+        }
+    }
+}
+
+impl fmt::Display for Data {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, ".{}:\n  .string {:?}", self.name, self.value)
+    }
+}
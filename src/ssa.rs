@@ -0,0 +1,869 @@
+// Copyright 2022 The Venice Authors. All rights reserved.
+// Use of this source code is governed by an MIT-style license that can be
+// found in the LICENSE file.
+//
+// Converts a VIL function into and out of SSA (static single assignment) form. `vil.rs` describes
+// VIL as "broadly similar to LLVM", but until now values were only ever plumbed through memory and
+// ad-hoc `Move`s across blocks, never through `vil::InstructionKind::Phi`. `to_ssa` gives every
+// register exactly one definition site (inserting `Phi`s wherever control flow merges two
+// definitions of the same register), which is the form optimizations like constant propagation and
+// dead-code elimination expect to work over. `out_of_ssa` lowers the `Phi`s back into `Move`s at
+// the end of the relevant predecessor blocks, so the rest of the compiler -- which knows nothing
+// about SSA -- can consume the result exactly as it would any other VIL function.
+
+use super::vil;
+use std::collections::{HashMap, HashSet};
+
+/// Converts `declaration` into SSA form in place.
+///
+/// This follows the classic Cytron et al. construction: build the CFG, compute the dominator tree
+/// (the iterative Cooper-Harvey-Kennedy algorithm, since it's simpler to get right than the
+/// original Lengauer-Tarjan one and VIL functions are small), derive dominance frontiers from it,
+/// place a `Phi` at the iterated dominance frontier of every register's definition set, and then
+/// rename registers with a preorder walk of the dominator tree.
+pub fn to_ssa(declaration: &mut vil::FunctionDeclaration) {
+    if declaration.blocks.is_empty() {
+        return;
+    }
+
+    let cfg = Cfg::build(declaration);
+    let idom = compute_dominator_tree(&cfg);
+    let dom_children = children_from_idom(&idom, cfg.entry);
+    let frontiers = compute_dominance_frontiers(&cfg, &idom);
+
+    let phis_by_block = place_phis(declaration, &cfg, &frontiers);
+    rename(declaration, &cfg, &dom_children, &phis_by_block);
+}
+
+/// Lowers every `Phi` in `declaration` back into ordinary `Move`s, undoing `to_ssa`. A `Phi`'s
+/// operand for a given predecessor becomes a `Move` at the end of that predecessor block -- unless
+/// the predecessor has more than one successor, in which case appending the move there would also
+/// run it along edges that never reach this `Phi`, so the edge is split with a synthetic block
+/// instead (the standard "critical edge" fix for SSA destruction).
+pub fn out_of_ssa(declaration: &mut vil::FunctionDeclaration) {
+    if declaration.blocks.is_empty() {
+        return;
+    }
+
+    let cfg = Cfg::build(declaration);
+
+    // Collect the moves each edge needs, keyed by block name rather than index: index-based keys
+    // would be invalidated by the synthetic blocks spliced in below.
+    let mut edge_moves: HashMap<(String, String), Vec<vil::Instruction>> = HashMap::new();
+    for block in &mut declaration.blocks {
+        let succ_name = block.name.clone();
+        let mut phi_count = 0;
+        for instruction in &block.instructions {
+            if matches!(instruction.kind, vil::InstructionKind::Phi(..)) {
+                phi_count += 1;
+            } else {
+                break;
+            }
+        }
+
+        for instruction in block.instructions.drain(0..phi_count) {
+            if let vil::InstructionKind::Phi(destination, operands) = instruction.kind {
+                for (label, register) in operands {
+                    edge_moves
+                        .entry((label.0, succ_name.clone()))
+                        .or_default()
+                        .push(vil::Instruction {
+                            kind: vil::InstructionKind::Move(destination, register),
+                            comment: String::new(),
+                        });
+                }
+            }
+        }
+    }
+
+    let mut next_synthetic_id = 0usize;
+    for ((pred_name, succ_name), moves) in edge_moves {
+        let pred_index = cfg.index_of[&pred_name];
+        if cfg.successors[pred_index].len() <= 1 {
+            let block = find_block_mut(declaration, &pred_name);
+            let insert_at = terminator_index(block);
+            block.instructions.splice(insert_at..insert_at, moves);
+        } else {
+            let synthetic_name = loop {
+                let candidate = format!("{}.ssa_split.{}", pred_name, next_synthetic_id);
+                next_synthetic_id += 1;
+                if !declaration.blocks.iter().any(|b| b.name == candidate) {
+                    break candidate;
+                }
+            };
+
+            let mut instructions = moves;
+            instructions.push(vil::Instruction {
+                kind: vil::InstructionKind::Jump(vil::Label(succ_name.clone())),
+                comment: String::new(),
+            });
+            declaration.blocks.push(vil::Block {
+                name: synthetic_name.clone(),
+                instructions,
+            });
+
+            retarget(
+                find_block_mut(declaration, &pred_name),
+                &succ_name,
+                &synthetic_name,
+            );
+        }
+    }
+}
+
+fn find_block_mut<'a>(
+    declaration: &'a mut vil::FunctionDeclaration,
+    name: &str,
+) -> &'a mut vil::Block {
+    declaration
+        .blocks
+        .iter_mut()
+        .find(|block| block.name == name)
+        .expect("internal error: block referenced by the CFG no longer exists")
+}
+
+/// Points whichever of a terminator's labels names `from` at `to` instead. Only ever called on a
+/// block with more than one successor, which in VIL always means it ends in a `JumpIf` (two
+/// labels) or a `JumpOrdering` (three).
+fn retarget(block: &mut vil::Block, from: &str, to: &str) {
+    if let Some(instruction) = block.instructions.last_mut() {
+        match &mut instruction.kind {
+            vil::InstructionKind::JumpIf(_, l1, l2) => {
+                if l1.0 == from {
+                    l1.0 = to.to_string();
+                }
+                if l2.0 == from {
+                    l2.0 = to.to_string();
+                }
+                return;
+            }
+            vil::InstructionKind::JumpOrdering(_, l1, l2, l3) => {
+                if l1.0 == from {
+                    l1.0 = to.to_string();
+                }
+                if l2.0 == from {
+                    l2.0 = to.to_string();
+                }
+                if l3.0 == from {
+                    l3.0 = to.to_string();
+                }
+                return;
+            }
+            _ => {}
+        }
+    }
+    unreachable!(
+        "internal error: a block with more than one successor must end in JumpIf or JumpOrdering"
+    );
+}
+
+/// The index a `Phi`-free `Move` should be inserted at to land just before a block's terminator
+/// (or at the very end, for a block that falls through to the next one instead of jumping).
+fn terminator_index(block: &vil::Block) -> usize {
+    match block
+        .instructions
+        .last()
+        .map(|instruction| &instruction.kind)
+    {
+        Some(vil::InstructionKind::Jump(_))
+        | Some(vil::InstructionKind::JumpIf(..))
+        | Some(vil::InstructionKind::JumpOrdering(..)) => block.instructions.len() - 1,
+        _ => block.instructions.len(),
+    }
+}
+
+/// A control-flow graph over a function's blocks, addressed by index into `declaration.blocks`.
+// `successors` and `entry` are also read by `vil_opt::remove_dead_blocks`, which wants the same
+// reachability graph this module already builds for dominance -- everything else here stays
+// private to ssa.rs.
+pub(crate) struct Cfg {
+    names: Vec<String>,
+    index_of: HashMap<String, usize>,
+    pub(crate) successors: Vec<Vec<usize>>,
+    predecessors: Vec<Vec<usize>>,
+    pub(crate) entry: usize,
+}
+
+impl Cfg {
+    /// Builds the CFG from each block's terminating `Jump`/`JumpIf`/`JumpOrdering`, falling through
+    /// to the next block in `declaration.blocks` when a block ends in none of them (mirrors the same convention
+    /// `codegen.rs`'s own register allocator uses to compute live ranges across blocks).
+    pub(crate) fn build(declaration: &vil::FunctionDeclaration) -> Self {
+        let names: Vec<String> = declaration.blocks.iter().map(|b| b.name.clone()).collect();
+        let index_of: HashMap<String, usize> = names
+            .iter()
+            .cloned()
+            .enumerate()
+            .map(|(i, name)| (name, i))
+            .collect();
+
+        let block_count = names.len();
+        let mut successors = vec![Vec::new(); block_count];
+        for (i, block) in declaration.blocks.iter().enumerate() {
+            successors[i] = match block.instructions.last().map(|instr| &instr.kind) {
+                Some(vil::InstructionKind::Jump(label)) => vec![index_of[&label.0]],
+                Some(vil::InstructionKind::JumpIf(_, l1, l2)) => {
+                    vec![index_of[&l1.0], index_of[&l2.0]]
+                }
+                Some(vil::InstructionKind::JumpOrdering(_, l1, l2, l3)) => {
+                    vec![index_of[&l1.0], index_of[&l2.0], index_of[&l3.0]]
+                }
+                _ if i + 1 < block_count => vec![i + 1],
+                _ => Vec::new(),
+            };
+        }
+
+        let mut predecessors = vec![Vec::new(); block_count];
+        for (i, succs) in successors.iter().enumerate() {
+            for &s in succs {
+                predecessors[s].push(i);
+            }
+        }
+
+        Cfg {
+            names,
+            index_of,
+            successors,
+            predecessors,
+            entry: 0,
+        }
+    }
+
+    fn block_count(&self) -> usize {
+        self.names.len()
+    }
+}
+
+/// A depth-first postorder traversal of the CFG from its entry block, used to number blocks for
+/// the dominator-tree fixpoint below. Blocks unreachable from the entry are omitted.
+fn compute_postorder(cfg: &Cfg) -> Vec<usize> {
+    let mut visited = vec![false; cfg.block_count()];
+    let mut postorder = Vec::with_capacity(cfg.block_count());
+    let mut stack: Vec<(usize, usize)> = vec![(cfg.entry, 0)];
+    visited[cfg.entry] = true;
+
+    while let Some(&mut (node, ref mut next_child)) = stack.last_mut() {
+        if *next_child < cfg.successors[node].len() {
+            let child = cfg.successors[node][*next_child];
+            *next_child += 1;
+            if !visited[child] {
+                visited[child] = true;
+                stack.push((child, 0));
+            }
+        } else {
+            postorder.push(node);
+            stack.pop();
+        }
+    }
+
+    postorder
+}
+
+/// The iterative Cooper-Harvey-Kennedy dominator algorithm: process blocks in reverse postorder,
+/// repeatedly intersecting predecessors' current immediate dominators until nothing changes.
+/// Blocks unreachable from the entry are left with `None` and ignored by every later pass.
+fn compute_dominator_tree(cfg: &Cfg) -> Vec<Option<usize>> {
+    let postorder = compute_postorder(cfg);
+    let postorder_number: HashMap<usize, usize> = postorder
+        .iter()
+        .enumerate()
+        .map(|(i, &block)| (block, i))
+        .collect();
+    let reverse_postorder: Vec<usize> = postorder.iter().rev().copied().collect();
+
+    let mut idom: Vec<Option<usize>> = vec![None; cfg.block_count()];
+    idom[cfg.entry] = Some(cfg.entry);
+
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for &block in &reverse_postorder {
+            if block == cfg.entry {
+                continue;
+            }
+
+            let mut new_idom: Option<usize> = None;
+            for &pred in &cfg.predecessors[block] {
+                if idom[pred].is_none() {
+                    continue;
+                }
+                new_idom = Some(match new_idom {
+                    None => pred,
+                    Some(current) => intersect(&idom, &postorder_number, current, pred),
+                });
+            }
+
+            if new_idom.is_some() && idom[block] != new_idom {
+                idom[block] = new_idom;
+                changed = true;
+            }
+        }
+    }
+
+    idom
+}
+
+fn intersect(
+    idom: &[Option<usize>],
+    postorder_number: &HashMap<usize, usize>,
+    mut finger1: usize,
+    mut finger2: usize,
+) -> usize {
+    while finger1 != finger2 {
+        while postorder_number[&finger1] < postorder_number[&finger2] {
+            finger1 = idom[finger1].unwrap();
+        }
+        while postorder_number[&finger2] < postorder_number[&finger1] {
+            finger2 = idom[finger2].unwrap();
+        }
+    }
+    finger1
+}
+
+fn children_from_idom(idom: &[Option<usize>], entry: usize) -> Vec<Vec<usize>> {
+    let mut children = vec![Vec::new(); idom.len()];
+    for (block, parent) in idom.iter().enumerate() {
+        if block == entry {
+            continue;
+        }
+        if let Some(parent) = parent {
+            children[*parent].push(block);
+        }
+    }
+    children
+}
+
+/// The dominance frontier of every block: `frontiers[b]` is every block `x` such that `b`
+/// dominates a predecessor of `x` but does not strictly dominate `x` itself.
+fn compute_dominance_frontiers(cfg: &Cfg, idom: &[Option<usize>]) -> Vec<HashSet<usize>> {
+    let mut frontiers = vec![HashSet::new(); cfg.block_count()];
+
+    for block in 0..cfg.block_count() {
+        if idom[block].is_none() || cfg.predecessors[block].len() < 2 {
+            continue;
+        }
+
+        for &pred in &cfg.predecessors[block] {
+            if idom[pred].is_none() {
+                continue;
+            }
+
+            let mut runner = pred;
+            while Some(runner) != idom[block] {
+                frontiers[runner].insert(block);
+                runner = idom[runner].unwrap();
+            }
+        }
+    }
+
+    frontiers
+}
+
+/// The iterated dominance frontier of `def_blocks`: the fixpoint of repeatedly unioning in the
+/// dominance frontier of every block discovered so far, which is where a register defined in all
+/// of `def_blocks` needs a `Phi`.
+fn iterated_dominance_frontier(
+    frontiers: &[HashSet<usize>],
+    def_blocks: &HashSet<usize>,
+) -> HashSet<usize> {
+    let mut result: HashSet<usize> = HashSet::new();
+    let mut worklist: Vec<usize> = def_blocks.iter().copied().collect();
+    let mut queued: HashSet<usize> = def_blocks.iter().copied().collect();
+
+    while let Some(block) = worklist.pop() {
+        for &frontier_block in &frontiers[block] {
+            if result.insert(frontier_block) && queued.insert(frontier_block) {
+                worklist.push(frontier_block);
+            }
+        }
+    }
+
+    result
+}
+
+/// Inserts a `Phi` at the start of every block in the iterated dominance frontier of each
+/// register's definition set, and returns, for every block that got at least one, the original
+/// (pre-renaming) register each of its new `Phi`s corresponds to -- in the same order the `Phi`s
+/// were inserted, which `rename` needs to tell them apart.
+fn place_phis(
+    declaration: &mut vil::FunctionDeclaration,
+    cfg: &Cfg,
+    frontiers: &[HashSet<usize>],
+) -> HashMap<usize, Vec<u8>> {
+    let mut def_blocks: HashMap<u8, HashSet<usize>> = HashMap::new();
+    for (i, block) in declaration.blocks.iter().enumerate() {
+        for instruction in &block.instructions {
+            if let Some(register) = defined_register(&instruction.kind) {
+                def_blocks.entry(register.index()).or_default().insert(i);
+            }
+        }
+    }
+
+    let mut phis_needed: HashMap<usize, HashSet<u8>> = HashMap::new();
+    for (&register_index, blocks) in &def_blocks {
+        // A register defined in only one block can never be live coming in from more than one
+        // predecessor, so it never needs a Phi.
+        if blocks.len() < 2 {
+            continue;
+        }
+
+        for block in iterated_dominance_frontier(frontiers, blocks) {
+            // A Phi only makes sense where control flow actually merges.
+            if cfg.predecessors[block].len() >= 2 {
+                phis_needed.entry(block).or_default().insert(register_index);
+            }
+        }
+    }
+
+    let mut phis_by_block = HashMap::new();
+    for (block_index, registers) in phis_needed {
+        let mut registers: Vec<u8> = registers.into_iter().collect();
+        registers.sort_unstable();
+
+        let new_instructions: Vec<vil::Instruction> = registers
+            .iter()
+            .map(|&register_index| {
+                let operands = cfg.predecessors[block_index]
+                    .iter()
+                    .map(|&pred| {
+                        (
+                            vil::Label(cfg.names[pred].clone()),
+                            vil::Register::new(register_index),
+                        )
+                    })
+                    .collect();
+                vil::Instruction {
+                    kind: vil::InstructionKind::Phi(vil::Register::new(register_index), operands),
+                    comment: String::new(),
+                }
+            })
+            .collect();
+
+        declaration.blocks[block_index]
+            .instructions
+            .splice(0..0, new_instructions);
+        phis_by_block.insert(block_index, registers);
+    }
+
+    phis_by_block
+}
+
+/// The per-register-name stack of current SSA names used while renaming, plus the counter handing
+/// out fresh register indices (starting above every index the function already used, so a fresh
+/// name can never collide with an original one).
+struct RenameState {
+    stacks: HashMap<u8, Vec<u8>>,
+    next_register: u8,
+}
+
+impl RenameState {
+    fn current(&self, original: u8) -> u8 {
+        *self
+            .stacks
+            .get(&original)
+            .and_then(|stack| stack.last())
+            .unwrap_or(&original)
+    }
+
+    fn push_fresh(&mut self, original: u8) -> u8 {
+        let fresh = self.next_register;
+        self.next_register = self
+            .next_register
+            .checked_add(1)
+            .expect("internal error: ran out of register indices while converting to SSA");
+        self.stacks.entry(original).or_default().push(fresh);
+        fresh
+    }
+}
+
+fn rename(
+    declaration: &mut vil::FunctionDeclaration,
+    cfg: &Cfg,
+    dom_children: &[Vec<usize>],
+    phis_by_block: &HashMap<usize, Vec<u8>>,
+) {
+    let max_register = declaration
+        .blocks
+        .iter()
+        .flat_map(|block| &block.instructions)
+        .flat_map(|instruction| all_registers(&instruction.kind))
+        .map(|register| register.index())
+        .max()
+        .unwrap_or(0);
+
+    let mut state = RenameState {
+        stacks: HashMap::new(),
+        next_register: max_register
+            .checked_add(1)
+            .expect("internal error: ran out of register indices while converting to SSA"),
+    };
+
+    rename_block(
+        declaration,
+        cfg,
+        dom_children,
+        phis_by_block,
+        cfg.entry,
+        &mut state,
+    );
+}
+
+fn rename_block(
+    declaration: &mut vil::FunctionDeclaration,
+    cfg: &Cfg,
+    dom_children: &[Vec<usize>],
+    phis_by_block: &HashMap<usize, Vec<u8>>,
+    block_index: usize,
+    state: &mut RenameState,
+) {
+    let mut pushed: Vec<u8> = Vec::new();
+
+    let phi_count = phis_by_block.get(&block_index).map_or(0, Vec::len);
+    if let Some(originals) = phis_by_block.get(&block_index) {
+        for (i, &original) in originals.iter().enumerate() {
+            let fresh = state.push_fresh(original);
+            pushed.push(original);
+            if let vil::InstructionKind::Phi(r, _) =
+                &mut declaration.blocks[block_index].instructions[i].kind
+            {
+                *r = vil::Register::new(fresh);
+            }
+        }
+    }
+
+    for instruction in declaration.blocks[block_index].instructions[phi_count..].iter_mut() {
+        for used in used_registers_mut(&mut instruction.kind) {
+            *used = vil::Register::new(state.current(used.index()));
+        }
+        if let Some(defined) = defined_register_mut(&mut instruction.kind) {
+            let original = defined.index();
+            let fresh = state.push_fresh(original);
+            pushed.push(original);
+            *defined = vil::Register::new(fresh);
+        }
+    }
+
+    for &succ in &cfg.successors[block_index] {
+        let originals = match phis_by_block.get(&succ) {
+            Some(originals) => originals.clone(),
+            None => continue,
+        };
+        let pred_name = cfg.names[block_index].clone();
+
+        for (i, original) in originals.into_iter().enumerate() {
+            let current = state.current(original);
+            if let vil::InstructionKind::Phi(_, operands) =
+                &mut declaration.blocks[succ].instructions[i].kind
+            {
+                for (label, register) in operands.iter_mut() {
+                    if label.0 == pred_name {
+                        *register = vil::Register::new(current);
+                    }
+                }
+            }
+        }
+    }
+
+    for &child in &dom_children[block_index] {
+        rename_block(declaration, cfg, dom_children, phis_by_block, child, state);
+    }
+
+    for original in pushed {
+        state.stacks.get_mut(&original).unwrap().pop();
+    }
+}
+
+fn defined_register(kind: &vil::InstructionKind) -> Option<vil::Register> {
+    use vil::InstructionKind::*;
+    match kind {
+        Binary(_, r1, _, _) => Some(*r1),
+        Unary(_, r1, _) => Some(*r1),
+        Call { destination, .. } => Some(*destination),
+        Cmp(..) | FCmp(..) | Jump(_) | JumpIf(..) | JumpOrdering(..) | Store(..) => None,
+        CmpOrdering(r1, _, _) | FCmpOrdering(r1, _, _) => Some(*r1),
+        Load(r, _) => Some(*r),
+        Move(r1, _) => Some(*r1),
+        Set(r, _) => Some(*r),
+        Syscall { destination, .. } => Some(*destination),
+        Phi(r, _) => Some(*r),
+    }
+}
+
+fn defined_register_mut(kind: &mut vil::InstructionKind) -> Option<&mut vil::Register> {
+    use vil::InstructionKind::*;
+    match kind {
+        Binary(_, r1, _, _) => Some(r1),
+        Unary(_, r1, _) => Some(r1),
+        Call { destination, .. } => Some(destination),
+        Cmp(..) | FCmp(..) | Jump(_) | JumpIf(..) | JumpOrdering(..) | Store(..) => None,
+        CmpOrdering(r1, _, _) | FCmpOrdering(r1, _, _) => Some(r1),
+        Load(r, _) => Some(r),
+        Move(r1, _) => Some(r1),
+        Set(r, _) => Some(r),
+        Syscall { destination, .. } => Some(destination),
+        Phi(r, _) => Some(r),
+    }
+}
+
+fn used_registers_mut(kind: &mut vil::InstructionKind) -> Vec<&mut vil::Register> {
+    use vil::InstructionKind::*;
+    match kind {
+        Binary(_, _, r2, r3) => vec![r2, r3],
+        Unary(_, _, r2) => vec![r2],
+        Call { .. } | Jump(_) | JumpIf(..) | Set(..) | Load(..) | Syscall { .. } => vec![],
+        Cmp(r1, r2) | FCmp(r1, r2) => vec![r1, r2],
+        CmpOrdering(_, r2, r3) | FCmpOrdering(_, r2, r3) => vec![r2, r3],
+        JumpOrdering(r, ..) => vec![r],
+        Move(_, r2) => vec![r2],
+        Store(r, _) => vec![r],
+        Phi(_, operands) => operands.iter_mut().map(|(_, r)| r).collect(),
+    }
+}
+
+fn all_registers(kind: &vil::InstructionKind) -> Vec<vil::Register> {
+    use vil::InstructionKind::*;
+    match kind {
+        Binary(_, r1, r2, r3) => vec![*r1, *r2, *r3],
+        Unary(_, r1, r2) => vec![*r1, *r2],
+        Call { destination, .. } => vec![*destination],
+        Cmp(r1, r2) | FCmp(r1, r2) => vec![*r1, *r2],
+        CmpOrdering(r1, r2, r3) | FCmpOrdering(r1, r2, r3) => vec![*r1, *r2, *r3],
+        Jump(_) | JumpIf(..) => vec![],
+        JumpOrdering(r, ..) => vec![*r],
+        Load(r, _) => vec![*r],
+        Move(r1, r2) => vec![*r1, *r2],
+        Set(r, _) => vec![*r],
+        Store(r, _) => vec![*r],
+        Syscall { destination, .. } => vec![*destination],
+        Phi(r, operands) => {
+            let mut registers = vec![*r];
+            registers.extend(operands.iter().map(|(_, r)| *r));
+            registers
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A diamond CFG -- `entry` branches to `left` or `right`, both of which jump to `merge` --
+    /// where `R0` is defined differently on each side and read back in `merge`. `to_ssa` should
+    /// insert exactly one `Phi` in `merge` with one operand per branch.
+    fn build_diamond() -> vil::FunctionDeclaration {
+        vil::FunctionDeclaration {
+            name: "main".to_string(),
+            stack_frame_size: 0,
+            parameters: Vec::new(),
+            blocks: vec![
+                vil::Block {
+                    name: "entry".to_string(),
+                    instructions: vec![vil::Instruction {
+                        kind: vil::InstructionKind::JumpIf(
+                            vil::JumpCondition::Eq,
+                            vil::Label("left".to_string()),
+                            vil::Label("right".to_string()),
+                        ),
+                        comment: String::new(),
+                    }],
+                },
+                vil::Block {
+                    name: "left".to_string(),
+                    instructions: vec![
+                        vil::Instruction {
+                            kind: vil::InstructionKind::Set(
+                                vil::Register::new(0),
+                                vil::Immediate::Integer(1),
+                            ),
+                            comment: String::new(),
+                        },
+                        vil::Instruction {
+                            kind: vil::InstructionKind::Jump(vil::Label("merge".to_string())),
+                            comment: String::new(),
+                        },
+                    ],
+                },
+                vil::Block {
+                    name: "right".to_string(),
+                    instructions: vec![
+                        vil::Instruction {
+                            kind: vil::InstructionKind::Set(
+                                vil::Register::new(0),
+                                vil::Immediate::Integer(2),
+                            ),
+                            comment: String::new(),
+                        },
+                        vil::Instruction {
+                            kind: vil::InstructionKind::Jump(vil::Label("merge".to_string())),
+                            comment: String::new(),
+                        },
+                    ],
+                },
+                vil::Block {
+                    name: "merge".to_string(),
+                    instructions: vec![vil::Instruction {
+                        kind: vil::InstructionKind::Unary(
+                            vil::UnaryOp::Negate,
+                            vil::Register::new(1),
+                            vil::Register::new(0),
+                        ),
+                        comment: String::new(),
+                    }],
+                },
+            ],
+        }
+    }
+
+    #[test]
+    fn inserts_a_phi_at_a_diamonds_merge_point() {
+        let mut declaration = build_diamond();
+        to_ssa(&mut declaration);
+
+        let merge = declaration
+            .blocks
+            .iter()
+            .find(|b| b.name == "merge")
+            .unwrap();
+        let phi_count = merge
+            .instructions
+            .iter()
+            .filter(|i| matches!(i.kind, vil::InstructionKind::Phi(..)))
+            .count();
+        assert_eq!(phi_count, 1);
+
+        match &merge.instructions[0].kind {
+            vil::InstructionKind::Phi(_, operands) => {
+                let labels: HashSet<&str> = operands.iter().map(|(l, _)| l.0.as_str()).collect();
+                assert_eq!(labels, HashSet::from(["left", "right"]));
+            }
+            other => panic!("expected a Phi, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn every_register_is_defined_exactly_once_after_to_ssa() {
+        let mut declaration = build_diamond();
+        to_ssa(&mut declaration);
+
+        let mut seen = HashSet::new();
+        for block in &declaration.blocks {
+            for instruction in &block.instructions {
+                if let Some(register) = defined_register(&instruction.kind) {
+                    assert!(
+                        seen.insert(register.index()),
+                        "register {} defined more than once",
+                        register.index()
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn out_of_ssa_removes_every_phi() {
+        let mut declaration = build_diamond();
+        to_ssa(&mut declaration);
+        out_of_ssa(&mut declaration);
+
+        for block in &declaration.blocks {
+            for instruction in &block.instructions {
+                assert!(!matches!(instruction.kind, vil::InstructionKind::Phi(..)));
+            }
+        }
+
+        // Neither `left` nor `right` has more than one successor, so neither edge into `merge` is
+        // critical and no synthetic blocks were needed to carry the Phi's moves.
+        assert_eq!(declaration.blocks.len(), 4);
+    }
+
+    /// `entry` branches directly to `merge` on one side and through `skip` on the other, so
+    /// `merge` has two predecessors (and needs a Phi) while `entry` itself has two successors:
+    /// the entry-to-merge edge is critical and must be split.
+    fn build_critical_edge() -> vil::FunctionDeclaration {
+        vil::FunctionDeclaration {
+            name: "main".to_string(),
+            stack_frame_size: 0,
+            parameters: Vec::new(),
+            blocks: vec![
+                vil::Block {
+                    name: "entry".to_string(),
+                    instructions: vec![
+                        vil::Instruction {
+                            kind: vil::InstructionKind::Set(
+                                vil::Register::new(0),
+                                vil::Immediate::Integer(1),
+                            ),
+                            comment: String::new(),
+                        },
+                        vil::Instruction {
+                            kind: vil::InstructionKind::JumpIf(
+                                vil::JumpCondition::Eq,
+                                vil::Label("merge".to_string()),
+                                vil::Label("skip".to_string()),
+                            ),
+                            comment: String::new(),
+                        },
+                    ],
+                },
+                vil::Block {
+                    name: "skip".to_string(),
+                    instructions: vec![
+                        vil::Instruction {
+                            kind: vil::InstructionKind::Set(
+                                vil::Register::new(0),
+                                vil::Immediate::Integer(2),
+                            ),
+                            comment: String::new(),
+                        },
+                        vil::Instruction {
+                            kind: vil::InstructionKind::Jump(vil::Label("merge".to_string())),
+                            comment: String::new(),
+                        },
+                    ],
+                },
+                vil::Block {
+                    name: "merge".to_string(),
+                    instructions: vec![vil::Instruction {
+                        kind: vil::InstructionKind::Unary(
+                            vil::UnaryOp::Negate,
+                            vil::Register::new(1),
+                            vil::Register::new(0),
+                        ),
+                        comment: String::new(),
+                    }],
+                },
+            ],
+        }
+    }
+
+    #[test]
+    fn out_of_ssa_splits_a_critical_edge() {
+        let mut declaration = build_critical_edge();
+        to_ssa(&mut declaration);
+        out_of_ssa(&mut declaration);
+
+        for block in &declaration.blocks {
+            for instruction in &block.instructions {
+                assert!(!matches!(instruction.kind, vil::InstructionKind::Phi(..)));
+            }
+        }
+
+        // The entry-to-merge edge is critical (entry has two successors, merge has two
+        // predecessors), so it should have been split into one synthetic block; the
+        // skip-to-merge edge isn't, since skip has only one successor.
+        assert_eq!(declaration.blocks.len(), 4);
+
+        let entry = declaration
+            .blocks
+            .iter()
+            .find(|b| b.name == "entry")
+            .unwrap();
+        match &entry.instructions.last().unwrap().kind {
+            vil::InstructionKind::JumpIf(_, l1, l2) => {
+                assert_ne!(l1.0, "merge");
+                assert_eq!(l2.0, "skip");
+            }
+            other => panic!("expected a JumpIf, got {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,120 @@
+// Copyright 2022 The Venice Authors. All rights reserved.
+// Use of this source code is governed by an MIT-style license that can be
+// found in the LICENSE file.
+//
+// A pluggable abstraction over where source files come from, modeled loosely on
+// rust-analyzer's `vfs` crate: every path is interned into a small `FileId`, and a `Vfs`
+// implementation maps a `FileId` to its bytes. `RealFs` reads from disk; `MemoryFs` serves bytes
+// seeded ahead of time, so a test can hand the compiler a program without writing it to disk
+// first.
+//
+// Only the compiler's entry point (reading the path given on the command line) goes through this
+// today -- the lexer and parser already take an in-memory `&str` rather than a path, so there's
+// no filesystem access further down the pipeline left to abstract over. This is also the seam a
+// future module resolver would hang off of, once the compiler supports more than one source file.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// A small integer standing in for an interned path, cheap to copy and to use as a hash key.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+pub struct FileId(u32);
+
+/// Assigns a stable `FileId` to every distinct path it's asked about, so the rest of the compiler
+/// can pass around a `FileId` instead of cloning path strings everywhere.
+#[derive(Default)]
+pub struct PathInterner {
+    paths: Vec<PathBuf>,
+    ids: HashMap<PathBuf, FileId>,
+}
+
+impl PathInterner {
+    pub fn intern(&mut self, path: &Path) -> FileId {
+        if let Some(id) = self.ids.get(path) {
+            return *id;
+        }
+
+        let id = FileId(self.paths.len() as u32);
+        self.paths.push(path.to_path_buf());
+        self.ids.insert(path.to_path_buf(), id);
+        id
+    }
+
+    pub fn path(&self, id: FileId) -> &Path {
+        &self.paths[id.0 as usize]
+    }
+}
+
+/// A source of file contents, keyed by `FileId` rather than by path directly.
+pub trait Vfs {
+    /// Interns `path`, reading and caching its contents if this is the first time it's been
+    /// asked about.
+    fn file_id(&mut self, path: &Path) -> FileId;
+
+    /// The bytes of the file named by `id`, which must have come from this same `Vfs`'s
+    /// `file_id`.
+    fn load(&self, id: FileId) -> &[u8];
+}
+
+/// Reads files from the real filesystem, caching each one's bytes the first time it's loaded.
+#[derive(Default)]
+pub struct RealFs {
+    interner: PathInterner,
+    contents: HashMap<FileId, Vec<u8>>,
+}
+
+impl RealFs {
+    pub fn new() -> Self {
+        RealFs::default()
+    }
+}
+
+impl Vfs for RealFs {
+    fn file_id(&mut self, path: &Path) -> FileId {
+        let id = self.interner.intern(path);
+        self.contents
+            .entry(id)
+            .or_insert_with(|| fs::read(path).expect("could not read from file"));
+        id
+    }
+
+    fn load(&self, id: FileId) -> &[u8] {
+        &self.contents[&id]
+    }
+}
+
+/// Serves file contents seeded in memory ahead of time, for tests and anything else that
+/// shouldn't have to round-trip through the real filesystem.
+#[derive(Default)]
+pub struct MemoryFs {
+    interner: PathInterner,
+    contents: HashMap<FileId, Vec<u8>>,
+}
+
+impl MemoryFs {
+    pub fn new() -> Self {
+        MemoryFs::default()
+    }
+
+    /// Registers `path` as having `contents`, returning the `FileId` it was assigned. Later calls
+    /// to `file_id` with the same path return this same id and `load` returns these same bytes.
+    pub fn seed(&mut self, path: &Path, contents: impl Into<Vec<u8>>) -> FileId {
+        let id = self.interner.intern(path);
+        self.contents.insert(id, contents.into());
+        id
+    }
+}
+
+impl Vfs for MemoryFs {
+    fn file_id(&mut self, path: &Path) -> FileId {
+        self.interner.intern(path)
+    }
+
+    fn load(&self, id: FileId) -> &[u8] {
+        self.contents
+            .get(&id)
+            .map(Vec::as_slice)
+            .unwrap_or_else(|| panic!("no contents seeded for {:?}", self.interner.path(id)))
+    }
+}
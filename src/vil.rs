@@ -68,13 +68,31 @@ pub enum InstructionKind {
         offsets: Vec<MemoryOffset>,
         variadic: bool,
     },
-    // Compares the two registers and sets flags for a subsequent jump operation.
+    // Compares the two registers, interpreted as raw 64-bit integers, and sets flags for a
+    // subsequent jump operation.
     Cmp(Register, Register),
+    // Like `Cmp`, but interprets the two registers as IEEE 754 doubles, so the code generator can
+    // emit a floating-point comparison instead (e.g. `ucomisd` on x86, which sets flags
+    // differently than an integer `cmp` does).
+    FCmp(Register, Register),
+    // CmpOrdering(r1, r2, r3) compares `r2` and `r3`, interpreted as raw 64-bit integers, and sets
+    // `r1` to -1, 0, or 1 depending on whether `r2` is less than, equal to, or greater than `r3`.
+    // Unlike `Cmp`, the result lives in an ordinary register rather than flags, so it can feed a
+    // `JumpOrdering` anywhere later in the block (or be threaded across blocks) instead of only the
+    // instruction immediately following it -- which is what lets the front end collapse a chain of
+    // `else if` arms that all compare the same operand pair into a single comparison.
+    CmpOrdering(Register, Register, Register),
+    // Like `CmpOrdering`, but interprets `r2` and `r3` as IEEE 754 doubles.
+    FCmpOrdering(Register, Register, Register),
     // Unconditionally jumps to the label.
     Jump(Label),
     // Jumps to the first label if the condition is true (according to the flags set  by a previous
     // `Cmp` instruction), to the second label otherwise.
     JumpIf(JumpCondition, Label, Label),
+    // JumpOrdering(r, less, equal, greater) jumps to `less`, `equal`, or `greater` according to
+    // whether `r` (the result of a preceding `CmpOrdering`/`FCmpOrdering`) is negative, zero, or
+    // positive.
+    JumpOrdering(Register, Label, Label, Label),
     // Loads the value at the memory offset into the register.
     Load(Register, MemoryOffset),
     // Move(r1, r2) copies the value in `r2` into `r1`.
@@ -83,6 +101,22 @@ pub enum InstructionKind {
     Set(Register, Immediate),
     // Stores the value in the register into memory at the given offset.
     Store(Register, MemoryOffset),
+    // Invokes a raw Linux syscall with its arguments at the given memory offsets (the same
+    // stack-roundtrip `Call` uses, and for the same reason: it bounds how many registers need to be
+    // live for the backend to read from at once), moving `number` into the syscall-number register
+    // and each argument into the platform's syscall argument registers (in kernel ABI order), then
+    // reading the result back into the destination register.
+    Syscall {
+        destination: Register,
+        number: i64,
+        offsets: Vec<MemoryOffset>,
+    },
+    // Phi(r, operands) sets `r` to whichever `operands` register corresponds to the block control
+    // flow actually arrived from. Only meaningful in a function that `ssa::to_ssa` has converted,
+    // and only ever appears at the start of a block, one per SSA name that's live coming in from
+    // more than one predecessor; `ssa::out_of_ssa` lowers every `Phi` back to ordinary `Move`s
+    // before a backend or `codegen.rs`'s register allocator ever sees one.
+    Phi(Register, Vec<(Label, Register)>),
 }
 
 #[derive(Clone, Copy, Debug)]
@@ -91,12 +125,20 @@ pub enum BinaryOp {
     Div,
     Mul,
     Sub,
+    // The `F`-prefixed variants are the same operations on IEEE 754 doubles instead of 64-bit
+    // integers, so the code generator knows to pick floating-point instructions for them.
+    FAdd,
+    FDiv,
+    FMul,
+    FSub,
 }
 
 #[derive(Clone, Copy, Debug)]
 pub enum UnaryOp {
     LogicalNot,
     Negate,
+    // `Negate` on an IEEE 754 double rather than a 64-bit integer.
+    FNegate,
 }
 
 #[derive(Clone, Copy, Debug)]
@@ -145,6 +187,10 @@ impl Register {
 #[derive(Clone, Debug)]
 pub enum Immediate {
     Integer(i64),
+    /// An IEEE 754 double. A register holds it as its raw 64-bit pattern, in the same
+    /// general-purpose registers integers use; only the `F`-prefixed operations read those bits
+    /// as a float.
+    Float(f64),
     Label(String),
 }
 
@@ -180,6 +226,12 @@ impl fmt::Display for FunctionDeclaration {
         writeln!(f)?;
         writeln!(f, "func {} {{", self.name)?;
         writeln!(f, "  // stack_frame_size = {}", self.stack_frame_size)?;
+        let offsets: Vec<String> = self
+            .parameters
+            .iter()
+            .map(|parameter| parameter.stack_offset.to_string())
+            .collect();
+        writeln!(f, "  // parameters = {}", offsets.join(", "))?;
         for block in &self.blocks {
             write!(f, "{}", block)?;
         }
@@ -217,11 +269,16 @@ impl fmt::Display for InstructionKind {
                     BinaryOp::Div => "div",
                     BinaryOp::Mul => "mul",
                     BinaryOp::Sub => "sub",
+                    BinaryOp::FAdd => "fadd",
+                    BinaryOp::FDiv => "fdiv",
+                    BinaryOp::FMul => "fmul",
+                    BinaryOp::FSub => "fsub",
                 };
                 write!(f, "{} = {} {}, {}", r1, opstr, r2, r3)
             }
             Unary(UnaryOp::LogicalNot, r1, r2) => write!(f, "{} = logical_not {}", r1, r2),
             Unary(UnaryOp::Negate, r1, r2) => write!(f, "{} = negate {}", r1, r2),
+            Unary(UnaryOp::FNegate, r1, r2) => write!(f, "{} = fnegate {}", r1, r2),
             Call {
                 destination,
                 label,
@@ -241,6 +298,9 @@ impl fmt::Display for InstructionKind {
                 fmt::Result::Ok(())
             }
             Cmp(r1, r2) => write!(f, "cmp {}, {}", r1, r2),
+            FCmp(r1, r2) => write!(f, "fcmp {}, {}", r1, r2),
+            CmpOrdering(r1, r2, r3) => write!(f, "{} = cmp_ordering {}, {}", r1, r2, r3),
+            FCmpOrdering(r1, r2, r3) => write!(f, "{} = fcmp_ordering {}, {}", r1, r2, r3),
             Load(r, offset) => write!(f, "{} = load {}", r, offset),
             Jump(label) => write!(f, "jump {}", label),
             JumpIf(cond, l1, l2) => {
@@ -249,14 +309,38 @@ impl fmt::Display for InstructionKind {
                     JumpCondition::Gt => "gt",
                     JumpCondition::Gte => "gte",
                     JumpCondition::Lt => "lt",
-                    JumpCondition::Lte => "gte",
+                    JumpCondition::Lte => "lte",
                     JumpCondition::Neq => "neq",
                 };
                 write!(f, "jump_{} {}, {}", suffix, l1, l2)
             }
+            JumpOrdering(r, less, equal, greater) => {
+                write!(f, "jump_ordering {}, {}, {}, {}", r, less, equal, greater)
+            }
             Move(r1, r2) => write!(f, "{} = move {}", r1, r2),
             Set(r, x) => write!(f, "{} = set {}", r, x),
             Store(r, offset) => write!(f, "store {}, {}", r, offset),
+            Syscall {
+                destination,
+                number,
+                offsets,
+            } => {
+                write!(f, "{} = syscall {}", destination, number)?;
+                for offset in offsets {
+                    write!(f, ", mem[{}]", offset)?;
+                }
+                fmt::Result::Ok(())
+            }
+            Phi(r, operands) => {
+                write!(f, "{} = phi", r)?;
+                for (i, (label, register)) in operands.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, " {}: {}", label, register)?;
+                }
+                fmt::Result::Ok(())
+            }
         }
     }
 }
@@ -272,6 +356,10 @@ impl fmt::Display for Immediate {
         use Immediate::*;
         match self {
             Integer(x) => write!(f, "{}", x),
+            // `{:?}` rather than `{}`: Rust's `Display` for an integral float like `3.0` omits the
+            // decimal point (`"3"`), which would be indistinguishable from an `Integer` immediate
+            // and break the round trip through `vil_parser.rs`.
+            Float(x) => write!(f, "{:?}", x),
             Label(s) => write!(f, "{}", s),
         }
     }
@@ -0,0 +1,174 @@
+// Copyright 2022 The Venice Authors. All rights reserved.
+// Use of this source code is governed by an MIT-style license that can be
+// found in the LICENSE file.
+//
+// A post-generation cleanup pass over a `vil::Program`. `Generator` (see codegen.rs) emits a lot of
+// trivially redundant control flow along the way -- `generate_comparison_expression` and
+// `generate_if_expression` both leave behind blocks whose only job is to jump somewhere else, and
+// `start_block` constantly opens a new block right where the previous one already falls through to
+// it. `simplify` collapses all of that: it threads jumps through chains of trivial blocks, then
+// deletes whatever becomes unreachable, then removes the jumps that are left pointing at the block
+// immediately following them (redundant once the layout has settled). `codegen::generate` runs this
+// before handing the program to its register allocator, so the allocator computes live intervals
+// over the tighter control flow this leaves behind rather than the generator's original output.
+
+use std::collections::HashMap;
+
+use super::ssa;
+use super::vil;
+
+/// Runs the simplification passes over every function in `program`, in place.
+pub fn simplify(program: &mut vil::Program) {
+    for declaration in &mut program.declarations {
+        simplify_function(declaration);
+    }
+}
+
+fn simplify_function(declaration: &mut vil::FunctionDeclaration) {
+    if declaration.blocks.is_empty() {
+        return;
+    }
+
+    make_fallthrough_explicit(declaration);
+    thread_jumps(declaration);
+    remove_dead_blocks(declaration);
+    remove_fallthrough_jumps(declaration);
+}
+
+/// Gives every block but the last an explicit terminator, appending `Jump(next)` wherever a block
+/// currently falls through to the physically following one instead of ending in a `Jump`/`JumpIf`.
+/// This turns every control-flow edge into an ordinary label reference, which is what lets
+/// `thread_jumps` and `remove_dead_blocks` below treat fallthrough and explicit jumps uniformly;
+/// `remove_fallthrough_jumps` undoes it again at the end, once the blocks have their final layout.
+fn make_fallthrough_explicit(declaration: &mut vil::FunctionDeclaration) {
+    let block_count = declaration.blocks.len();
+    for i in 0..block_count.saturating_sub(1) {
+        let has_terminator = matches!(
+            declaration.blocks[i].instructions.last().map(|instr| &instr.kind),
+            Some(vil::InstructionKind::Jump(_))
+                | Some(vil::InstructionKind::JumpIf(..))
+                | Some(vil::InstructionKind::JumpOrdering(..))
+        );
+        if !has_terminator {
+            let next_name = declaration.blocks[i + 1].name.clone();
+            declaration.blocks[i].instructions.push(vil::Instruction {
+                kind: vil::InstructionKind::Jump(vil::Label(next_name)),
+                comment: String::new(),
+            });
+        }
+    }
+}
+
+/// Redirects every `Jump`/`JumpIf` past any chain of blocks whose only instruction is now an
+/// unconditional jump (see `make_fallthrough_explicit` above) straight to the chain's final
+/// destination. The blocks along the way aren't touched here -- once nothing references them
+/// anymore, `remove_dead_blocks` is what actually gets rid of them.
+///
+/// The function's entry block (`declaration.blocks[0]`) is never treated as a redirect target,
+/// even when it's trivial: it's the block the backend falls into right after the function's
+/// prologue (see `backend::generate`), a relationship expressed only in the backend's own
+/// bookkeeping, not in any `Jump`/`JumpIf` this pass could see and rewrite.
+fn thread_jumps(declaration: &mut vil::FunctionDeclaration) {
+    let name_to_index: HashMap<String, usize> = declaration
+        .blocks
+        .iter()
+        .enumerate()
+        .map(|(i, block)| (block.name.clone(), i))
+        .collect();
+
+    let direct: Vec<Option<String>> = declaration
+        .blocks
+        .iter()
+        .enumerate()
+        .map(|(i, block)| match block.instructions.as_slice() {
+            [single] if i != 0 => match &single.kind {
+                vil::InstructionKind::Jump(label) => Some(label.0.clone()),
+                _ => None,
+            },
+            _ => None,
+        })
+        .collect();
+
+    // Follows a block's redirect chain to its final destination, capping the walk at one step per
+    // block so a cycle of trivial blocks (an infinite loop with no other exit) can't hang the
+    // compiler -- it's left pointing at whatever it reaches right before the cap, which is still
+    // correct, since every step along the way preserves where control actually goes.
+    let resolve = |start: &str| -> String {
+        let mut label = start;
+        for _ in 0..direct.len() {
+            match name_to_index.get(label).and_then(|&i| direct[i].as_deref()) {
+                Some(next) if next != label => label = next,
+                _ => break,
+            }
+        }
+        String::from(label)
+    };
+
+    for block in &mut declaration.blocks {
+        for instruction in &mut block.instructions {
+            match &mut instruction.kind {
+                vil::InstructionKind::Jump(label) => {
+                    label.0 = resolve(&label.0);
+                }
+                vil::InstructionKind::JumpIf(_, l1, l2) => {
+                    l1.0 = resolve(&l1.0);
+                    l2.0 = resolve(&l2.0);
+                }
+                vil::InstructionKind::JumpOrdering(_, l1, l2, l3) => {
+                    l1.0 = resolve(&l1.0);
+                    l2.0 = resolve(&l2.0);
+                    l3.0 = resolve(&l3.0);
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Deletes every block unreachable from the function's entry block, by a reachability walk over
+/// the same CFG `ssa.rs` builds for dominance. Reusing `ssa::Cfg` here rather than rebuilding the
+/// same successor computation a third time (`codegen.rs`'s `compute_intervals` already has its own
+/// copy) keeps the "how do blocks chain together" logic defined in one place.
+fn remove_dead_blocks(declaration: &mut vil::FunctionDeclaration) {
+    let cfg = ssa::Cfg::build(declaration);
+
+    let mut reachable = vec![false; cfg.successors.len()];
+    let mut stack = vec![cfg.entry];
+    while let Some(node) = stack.pop() {
+        if reachable[node] {
+            continue;
+        }
+        reachable[node] = true;
+        for &successor in &cfg.successors[node] {
+            if !reachable[successor] {
+                stack.push(successor);
+            }
+        }
+    }
+
+    let mut index = 0;
+    declaration.blocks.retain(|_| {
+        let keep = reachable[index];
+        index += 1;
+        keep
+    });
+}
+
+/// Strips the trailing `Jump(target)` off any block whose `target` is exactly the block that is
+/// now physically next in `declaration.blocks`, letting it fall through instead -- the layout has
+/// settled by this point (after `thread_jumps` and `remove_dead_blocks`), so this is the last
+/// chance for a jump that `make_fallthrough_explicit` added, or that jump-threading redirected
+/// straight into its own physical successor, to turn back into a free fallthrough.
+fn remove_fallthrough_jumps(declaration: &mut vil::FunctionDeclaration) {
+    let names: Vec<String> = declaration.blocks.iter().map(|b| b.name.clone()).collect();
+    for i in 0..names.len().saturating_sub(1) {
+        let next_name = &names[i + 1];
+        let is_fallthrough = matches!(
+            declaration.blocks[i].instructions.last().map(|instr| &instr.kind),
+            Some(vil::InstructionKind::Jump(label)) if &label.0 == next_name
+        );
+        if is_fallthrough {
+            declaration.blocks[i].instructions.pop();
+        }
+    }
+}
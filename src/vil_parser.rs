@@ -0,0 +1,765 @@
+// A textual parser for VIL, the reverse of the `Display` impls in `vil.rs`. It exists mainly so
+// that VIL programs can be round-tripped through their printed form: `parse(text).to_string()`
+// should reproduce `text` for any program the compiler itself emits, which makes it possible to
+// dump a program to a file between compiler passes, inspect or edit it by hand, and feed it back
+// in.
+//
+// The grammar is small enough that it doesn't need its own lexer: `lexer::Lexer` already tokenizes
+// everything VIL's textual syntax uses (registers and labels as `Symbol`s, `mem[...]` as
+// `SquareOpen`/`SquareClose`, etc.), as long as it's constructed with comment preservation enabled
+// so that the `// stack_frame_size = ...` / `// parameters = ...` header comments and per-
+// instruction trailing comments survive as `Comment` tokens instead of being discarded.
+
+use super::common;
+use super::errors;
+use super::lexer;
+use super::lexer::TokenType;
+use super::vil;
+use std::collections::BTreeMap;
+
+/// Parses a whole VIL program, returning everything that parsed successfully alongside every
+/// error encountered along the way, in the same error-accumulating style as `parser::parse`.
+pub fn parse(file: &str, program: &str) -> (vil::Program, Vec<errors::VeniceError>) {
+    let lexer = lexer::Lexer::new_with_comments(file, program);
+    let mut parser = Parser::new(lexer);
+    let vil_program = parser.parse_program();
+
+    let mut all_errors = parser.lexer.errors().to_vec();
+    all_errors.extend(parser.errors.clone());
+    (vil_program, all_errors)
+}
+
+struct Parser<'src> {
+    lexer: lexer::Lexer<'src>,
+    errors: Vec<errors::VeniceError>,
+}
+
+impl<'src> Parser<'src> {
+    fn new(lexer: lexer::Lexer<'src>) -> Self {
+        Parser {
+            lexer,
+            errors: Vec::new(),
+        }
+    }
+
+    fn parse_program(&mut self) -> vil::Program {
+        let mut externs = Vec::new();
+        let mut declarations = Vec::new();
+        let mut strings = BTreeMap::new();
+
+        loop {
+            let token = self.lexer.token();
+            match token.type_ {
+                TokenType::End => break,
+                TokenType::Func => {
+                    if let Ok(declaration) = self.parse_function() {
+                        declarations.push(declaration);
+                    } else {
+                        self.synchronize_top_level();
+                    }
+                }
+                TokenType::Symbol if token.value == "extern" => {
+                    if let Ok(name) = self.parse_extern() {
+                        externs.push(name);
+                    } else {
+                        self.synchronize_top_level();
+                    }
+                }
+                TokenType::Symbol if token.value == "data" => {
+                    if let Ok((name, value)) = self.parse_data() {
+                        strings.insert(name, value);
+                    } else {
+                        self.synchronize_top_level();
+                    }
+                }
+                _ => {
+                    self.unexpected(&token, "extern, func, or data declaration");
+                    self.synchronize_top_level();
+                }
+            }
+        }
+
+        vil::Program {
+            externs,
+            declarations,
+            strings,
+        }
+    }
+
+    /// Advances past a malformed top-level declaration, stopping at the next `extern`/`data`/
+    /// `func` (left unconsumed) or the end of the file.
+    fn synchronize_top_level(&mut self) {
+        self.lexer.next();
+        loop {
+            let token = self.lexer.token();
+            match token.type_ {
+                TokenType::End | TokenType::Func => return,
+                TokenType::Symbol if token.value == "extern" || token.value == "data" => return,
+                _ => {
+                    self.lexer.next();
+                }
+            }
+        }
+    }
+
+    fn parse_extern(&mut self) -> Result<String, ()> {
+        let mut token = self.lexer.next();
+        self.expect_token(&token, TokenType::Symbol, "extern name")?;
+        let name = token.value.into_owned();
+
+        token = self.lexer.next();
+        self.expect_token(&token, TokenType::Semicolon, ";")?;
+        self.lexer.next();
+        Ok(name)
+    }
+
+    fn parse_data(&mut self) -> Result<(String, String), ()> {
+        let mut token = self.lexer.next();
+        self.expect_token(&token, TokenType::Symbol, "data name")?;
+        let name = token.value.into_owned();
+
+        token = self.lexer.next();
+        self.expect_token(&token, TokenType::Assign, "=")?;
+
+        token = self.lexer.next();
+        self.expect_token(&token, TokenType::String, "string literal")?;
+        let value = token.value.into_owned();
+
+        token = self.lexer.next();
+        self.expect_token(&token, TokenType::Semicolon, ";")?;
+        self.lexer.next();
+        Ok((name, value))
+    }
+
+    fn parse_function(&mut self) -> Result<vil::FunctionDeclaration, ()> {
+        let mut token = self.lexer.next();
+        self.expect_token(&token, TokenType::Symbol, "function name")?;
+        let name = token.value.into_owned();
+
+        token = self.lexer.next();
+        self.expect_token(&token, TokenType::CurlyOpen, "{")?;
+        self.lexer.next();
+
+        let stack_frame_size = self.parse_stack_frame_size_comment()?;
+        let parameters = self.parse_parameters_comment()?;
+        let blocks = self.parse_blocks()?;
+
+        Ok(vil::FunctionDeclaration {
+            name,
+            blocks,
+            stack_frame_size,
+            parameters,
+        })
+    }
+
+    fn parse_stack_frame_size_comment(&mut self) -> Result<i32, ()> {
+        let token = self.lexer.token();
+        self.expect_token(&token, TokenType::Comment, "stack_frame_size comment")?;
+
+        let text = token.value.to_string();
+        let rest = match text.strip_prefix("// stack_frame_size = ") {
+            Some(rest) => rest,
+            None => {
+                self.unexpected(&token, "stack_frame_size comment");
+                return Err(());
+            }
+        };
+        let stack_frame_size = match rest.trim().parse() {
+            Ok(value) => value,
+            Err(_) => {
+                self.error(
+                    &format!("invalid stack_frame_size {:?}", rest),
+                    token.location.clone(),
+                );
+                return Err(());
+            }
+        };
+
+        self.lexer.next();
+        Ok(stack_frame_size)
+    }
+
+    fn parse_parameters_comment(&mut self) -> Result<Vec<vil::FunctionParameter>, ()> {
+        let token = self.lexer.token();
+        self.expect_token(&token, TokenType::Comment, "parameters comment")?;
+
+        let text = token.value.to_string();
+        let rest = match text.strip_prefix("// parameters = ") {
+            Some(rest) => rest.trim(),
+            None => {
+                self.unexpected(&token, "parameters comment");
+                return Err(());
+            }
+        };
+
+        let mut parameters = Vec::new();
+        if !rest.is_empty() {
+            for part in rest.split(", ") {
+                match part.trim().parse() {
+                    Ok(stack_offset) => parameters.push(vil::FunctionParameter { stack_offset }),
+                    Err(_) => {
+                        self.error(
+                            &format!("invalid parameter offset {:?}", part),
+                            token.location.clone(),
+                        );
+                        return Err(());
+                    }
+                }
+            }
+        }
+
+        self.lexer.next();
+        Ok(parameters)
+    }
+
+    /// Parses the sequence of labelled blocks that make up a function body, up to and including
+    /// the closing `}`. A block's label and the instructions that start a block are both led by a
+    /// bare `Symbol`, so rather than looking two tokens ahead to tell them apart, this consumes
+    /// the symbol unconditionally and then branches on whatever follows it -- a `Colon` means it
+    /// was a label, anything else means it was the start of an instruction.
+    fn parse_blocks(&mut self) -> Result<Vec<vil::Block>, ()> {
+        let mut blocks: Vec<vil::Block> = Vec::new();
+
+        loop {
+            let token = self.lexer.token();
+            match token.type_ {
+                TokenType::CurlyClose => {
+                    self.lexer.next();
+                    return Ok(blocks);
+                }
+                TokenType::End => {
+                    self.unexpected(&token, "}");
+                    return Err(());
+                }
+                TokenType::Symbol => {
+                    let location = token.location.clone();
+                    let text = token.value.to_string();
+                    let after = self.lexer.next();
+                    if after.type_ == TokenType::Colon {
+                        self.lexer.next();
+                        blocks.push(vil::Block {
+                            name: text,
+                            instructions: Vec::new(),
+                        });
+                    } else if blocks.is_empty() {
+                        self.error("instruction outside of any block", location);
+                        self.synchronize_instruction();
+                    } else {
+                        match self.parse_instruction(&text, location) {
+                            Ok(instruction) => {
+                                blocks.last_mut().unwrap().instructions.push(instruction)
+                            }
+                            Err(()) => self.synchronize_instruction(),
+                        }
+                    }
+                }
+                _ => {
+                    self.unexpected(&token, "block label, instruction, or }");
+                    return Err(());
+                }
+            }
+        }
+    }
+
+    /// Advances past a malformed instruction, stopping at whatever looks like the start of the
+    /// next one (a bare `Symbol`) or the end of the enclosing block.
+    fn synchronize_instruction(&mut self) {
+        self.lexer.next();
+        loop {
+            let token = self.lexer.token();
+            match token.type_ {
+                TokenType::End | TokenType::CurlyClose | TokenType::Symbol => return,
+                _ => {
+                    self.lexer.next();
+                }
+            }
+        }
+    }
+
+    /// Parses one instruction, given its leading symbol (already consumed) and the location it
+    /// started at. The lexer's current token is whatever immediately followed that symbol: an
+    /// `Assign` means `leading` names a destination register, anything else means `leading` is
+    /// itself the opcode of a destination-less instruction like `cmp` or `store`.
+    fn parse_instruction(
+        &mut self,
+        leading: &str,
+        location: common::Location,
+    ) -> Result<vil::Instruction, ()> {
+        let kind = if self.lexer.token().type_ == TokenType::Assign {
+            let register = match parse_register_index(leading) {
+                Some(index) => vil::Register::new(index),
+                None => {
+                    self.error(&format!("expected a register, got {:?}", leading), location);
+                    return Err(());
+                }
+            };
+            self.lexer.next();
+            self.parse_assigned_instruction(register)?
+        } else {
+            self.parse_bare_instruction(leading, location)?
+        };
+
+        let comment = self.parse_trailing_comment();
+        Ok(vil::Instruction { kind, comment })
+    }
+
+    fn parse_assigned_instruction(
+        &mut self,
+        r1: vil::Register,
+    ) -> Result<vil::InstructionKind, ()> {
+        let token = self.lexer.token();
+        self.expect_token(&token, TokenType::Symbol, "opcode")?;
+        let opcode_location = token.location.clone();
+        let opcode = token.value.into_owned();
+        self.lexer.next();
+
+        match opcode.as_str() {
+            "add" | "div" | "mul" | "sub" | "fadd" | "fdiv" | "fmul" | "fsub" => {
+                let op = match opcode.as_str() {
+                    "add" => vil::BinaryOp::Add,
+                    "div" => vil::BinaryOp::Div,
+                    "mul" => vil::BinaryOp::Mul,
+                    "sub" => vil::BinaryOp::Sub,
+                    "fadd" => vil::BinaryOp::FAdd,
+                    "fdiv" => vil::BinaryOp::FDiv,
+                    "fmul" => vil::BinaryOp::FMul,
+                    "fsub" => vil::BinaryOp::FSub,
+                    _ => unreachable!(),
+                };
+                let r2 = self.parse_register()?;
+                self.expect_and_consume(TokenType::Comma, ",")?;
+                let r3 = self.parse_register()?;
+                Ok(vil::InstructionKind::Binary(op, r1, r2, r3))
+            }
+            "logical_not" => {
+                let r2 = self.parse_register()?;
+                Ok(vil::InstructionKind::Unary(
+                    vil::UnaryOp::LogicalNot,
+                    r1,
+                    r2,
+                ))
+            }
+            "negate" => {
+                let r2 = self.parse_register()?;
+                Ok(vil::InstructionKind::Unary(vil::UnaryOp::Negate, r1, r2))
+            }
+            "fnegate" => {
+                let r2 = self.parse_register()?;
+                Ok(vil::InstructionKind::Unary(vil::UnaryOp::FNegate, r1, r2))
+            }
+            "call" | "call_variadic" => {
+                let label = self.parse_label()?;
+                let offsets = self.parse_comma_separated_mem_offsets()?;
+                Ok(vil::InstructionKind::Call {
+                    destination: r1,
+                    label,
+                    offsets,
+                    variadic: opcode == "call_variadic",
+                })
+            }
+            "load" => {
+                let offset = self.parse_offset()?;
+                Ok(vil::InstructionKind::Load(r1, offset))
+            }
+            "move" => {
+                let r2 = self.parse_register()?;
+                Ok(vil::InstructionKind::Move(r1, r2))
+            }
+            "set" => {
+                let immediate = self.parse_immediate()?;
+                Ok(vil::InstructionKind::Set(r1, immediate))
+            }
+            "syscall" => {
+                let number = self.parse_i64()?;
+                let offsets = self.parse_comma_separated_mem_offsets()?;
+                Ok(vil::InstructionKind::Syscall {
+                    destination: r1,
+                    number,
+                    offsets,
+                })
+            }
+            "cmp_ordering" | "fcmp_ordering" => {
+                let r2 = self.parse_register()?;
+                self.expect_and_consume(TokenType::Comma, ",")?;
+                let r3 = self.parse_register()?;
+                if opcode == "cmp_ordering" {
+                    Ok(vil::InstructionKind::CmpOrdering(r1, r2, r3))
+                } else {
+                    Ok(vil::InstructionKind::FCmpOrdering(r1, r2, r3))
+                }
+            }
+            "phi" => {
+                let mut operands = vec![self.parse_phi_operand()?];
+                while self.lexer.token().type_ == TokenType::Comma {
+                    self.lexer.next();
+                    operands.push(self.parse_phi_operand()?);
+                }
+                Ok(vil::InstructionKind::Phi(r1, operands))
+            }
+            _ => {
+                self.error(&format!("unknown opcode {:?}", opcode), opcode_location);
+                Err(())
+            }
+        }
+    }
+
+    fn parse_bare_instruction(
+        &mut self,
+        leading: &str,
+        location: common::Location,
+    ) -> Result<vil::InstructionKind, ()> {
+        match leading {
+            "cmp" => {
+                let r1 = self.parse_register()?;
+                self.expect_and_consume(TokenType::Comma, ",")?;
+                let r2 = self.parse_register()?;
+                Ok(vil::InstructionKind::Cmp(r1, r2))
+            }
+            "fcmp" => {
+                let r1 = self.parse_register()?;
+                self.expect_and_consume(TokenType::Comma, ",")?;
+                let r2 = self.parse_register()?;
+                Ok(vil::InstructionKind::FCmp(r1, r2))
+            }
+            "store" => {
+                let r1 = self.parse_register()?;
+                self.expect_and_consume(TokenType::Comma, ",")?;
+                let offset = self.parse_offset()?;
+                Ok(vil::InstructionKind::Store(r1, offset))
+            }
+            "jump" => {
+                let label = self.parse_label()?;
+                Ok(vil::InstructionKind::Jump(label))
+            }
+            "jump_eq" | "jump_gt" | "jump_gte" | "jump_lt" | "jump_lte" | "jump_neq" => {
+                let condition = match leading {
+                    "jump_eq" => vil::JumpCondition::Eq,
+                    "jump_gt" => vil::JumpCondition::Gt,
+                    "jump_gte" => vil::JumpCondition::Gte,
+                    "jump_lt" => vil::JumpCondition::Lt,
+                    "jump_lte" => vil::JumpCondition::Lte,
+                    "jump_neq" => vil::JumpCondition::Neq,
+                    _ => unreachable!(),
+                };
+                let l1 = self.parse_label()?;
+                self.expect_and_consume(TokenType::Comma, ",")?;
+                let l2 = self.parse_label()?;
+                Ok(vil::InstructionKind::JumpIf(condition, l1, l2))
+            }
+            "jump_ordering" => {
+                let r = self.parse_register()?;
+                self.expect_and_consume(TokenType::Comma, ",")?;
+                let less = self.parse_label()?;
+                self.expect_and_consume(TokenType::Comma, ",")?;
+                let equal = self.parse_label()?;
+                self.expect_and_consume(TokenType::Comma, ",")?;
+                let greater = self.parse_label()?;
+                Ok(vil::InstructionKind::JumpOrdering(r, less, equal, greater))
+            }
+            _ => {
+                self.error(&format!("unknown opcode {:?}", leading), location);
+                Err(())
+            }
+        }
+    }
+
+    fn parse_trailing_comment(&mut self) -> String {
+        let token = self.lexer.token();
+        if token.type_ != TokenType::Comment {
+            return String::new();
+        }
+
+        let text = token.value.to_string();
+        self.lexer.next();
+        text.strip_prefix("// ").unwrap_or(&text).to_string()
+    }
+
+    fn parse_register(&mut self) -> Result<vil::Register, ()> {
+        let token = self.lexer.token();
+        if token.type_ == TokenType::Symbol {
+            if let Some(index) = parse_register_index(&token.value) {
+                self.lexer.next();
+                return Ok(vil::Register::new(index));
+            }
+        }
+        self.unexpected(&token, "register");
+        Err(())
+    }
+
+    /// Parses one `label: register` operand of a `phi` instruction.
+    fn parse_phi_operand(&mut self) -> Result<(vil::Label, vil::Register), ()> {
+        let label = self.parse_label()?;
+        self.expect_and_consume(TokenType::Colon, ":")?;
+        let register = self.parse_register()?;
+        Ok((label, register))
+    }
+
+    fn parse_label(&mut self) -> Result<vil::Label, ()> {
+        let token = self.lexer.token();
+        self.expect_token(&token, TokenType::Symbol, "label")?;
+        let name = token.value.into_owned();
+        self.lexer.next();
+        Ok(vil::Label(name))
+    }
+
+    fn parse_offset(&mut self) -> Result<vil::MemoryOffset, ()> {
+        let mut token = self.lexer.token();
+        let negative = token.type_ == TokenType::Minus;
+        if negative {
+            token = self.lexer.next();
+        }
+        self.expect_token(&token, TokenType::Integer, "integer offset")?;
+
+        let magnitude: i32 = match token.value.parse() {
+            Ok(value) => value,
+            Err(_) => {
+                self.unexpected(&token, "integer offset");
+                return Err(());
+            }
+        };
+        self.lexer.next();
+        Ok(if negative { -magnitude } else { magnitude })
+    }
+
+    fn parse_i64(&mut self) -> Result<i64, ()> {
+        let mut token = self.lexer.token();
+        let negative = token.type_ == TokenType::Minus;
+        if negative {
+            token = self.lexer.next();
+        }
+        self.expect_token(&token, TokenType::Integer, "integer")?;
+
+        let magnitude: i64 = match token.value.parse() {
+            Ok(value) => value,
+            Err(_) => {
+                self.unexpected(&token, "integer");
+                return Err(());
+            }
+        };
+        self.lexer.next();
+        Ok(if negative { -magnitude } else { magnitude })
+    }
+
+    fn parse_immediate(&mut self) -> Result<vil::Immediate, ()> {
+        let mut token = self.lexer.token();
+        let negative = token.type_ == TokenType::Minus;
+        if negative {
+            token = self.lexer.next();
+        }
+
+        match token.type_ {
+            TokenType::Integer => {
+                let magnitude: i64 = match token.value.parse() {
+                    Ok(value) => value,
+                    Err(_) => {
+                        self.unexpected(&token, "integer or label immediate");
+                        return Err(());
+                    }
+                };
+                self.lexer.next();
+                Ok(vil::Immediate::Integer(if negative {
+                    -magnitude
+                } else {
+                    magnitude
+                }))
+            }
+            TokenType::Float => {
+                let magnitude: f64 = match token.value.parse() {
+                    Ok(value) => value,
+                    Err(_) => {
+                        self.unexpected(&token, "integer or label immediate");
+                        return Err(());
+                    }
+                };
+                self.lexer.next();
+                Ok(vil::Immediate::Float(if negative {
+                    -magnitude
+                } else {
+                    magnitude
+                }))
+            }
+            TokenType::Symbol if !negative => {
+                let name = token.value.into_owned();
+                self.lexer.next();
+                Ok(vil::Immediate::Label(name))
+            }
+            _ => {
+                self.unexpected(&token, "integer or label immediate");
+                Err(())
+            }
+        }
+    }
+
+    fn parse_mem_offset(&mut self) -> Result<vil::MemoryOffset, ()> {
+        let token = self.lexer.token();
+        if !(token.type_ == TokenType::Symbol && token.value == "mem") {
+            self.unexpected(&token, "mem[...]");
+            return Err(());
+        }
+        self.lexer.next();
+
+        let open = self.lexer.token();
+        self.expect_token(&open, TokenType::SquareOpen, "[")?;
+        self.lexer.next();
+
+        let offset = self.parse_offset()?;
+
+        let close = self.lexer.token();
+        self.expect_token(&close, TokenType::SquareClose, "]")?;
+        self.lexer.next();
+        Ok(offset)
+    }
+
+    fn parse_comma_separated_mem_offsets(&mut self) -> Result<Vec<vil::MemoryOffset>, ()> {
+        let mut offsets = Vec::new();
+        while self.lexer.token().type_ == TokenType::Comma {
+            self.lexer.next();
+            offsets.push(self.parse_mem_offset()?);
+        }
+        Ok(offsets)
+    }
+
+    fn expect_and_consume(&mut self, type_: TokenType, message: &str) -> Result<(), ()> {
+        let token = self.lexer.token();
+        self.expect_token(&token, type_, message)?;
+        self.lexer.next();
+        Ok(())
+    }
+
+    fn expect_token(
+        &mut self,
+        token: &lexer::Token<'_>,
+        type_: TokenType,
+        message: &str,
+    ) -> Result<(), ()> {
+        if token.type_ == type_ {
+            Ok(())
+        } else {
+            self.unexpected(token, message);
+            Err(())
+        }
+    }
+
+    fn unexpected(&mut self, token: &lexer::Token<'_>, message: &str) {
+        let msg = if token.type_ == TokenType::End {
+            format!("expected {}, got end of file", message)
+        } else {
+            format!("expected {}, got {}", message, token.value)
+        };
+        self.error(&msg, token.location.clone());
+    }
+
+    fn error(&mut self, message: &str, location: common::Location) {
+        self.errors
+            .push(errors::VeniceError::new(message, location));
+    }
+}
+
+fn parse_register_index(text: &str) -> Option<u8> {
+    text.strip_prefix('R')?.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn build_sample_program() -> vil::Program {
+        let mut strings = BTreeMap::new();
+        strings.insert("s0".to_string(), "hello\n".to_string());
+
+        vil::Program {
+            externs: vec!["printf".to_string()],
+            declarations: vec![vil::FunctionDeclaration {
+                name: "main".to_string(),
+                stack_frame_size: 16,
+                parameters: vec![
+                    vil::FunctionParameter { stack_offset: -8 },
+                    vil::FunctionParameter { stack_offset: -16 },
+                ],
+                blocks: vec![
+                    vil::Block {
+                        name: "entry".to_string(),
+                        instructions: vec![
+                            vil::Instruction {
+                                kind: vil::InstructionKind::Set(
+                                    vil::Register::new(0),
+                                    vil::Immediate::Integer(1),
+                                ),
+                                comment: "start the counter at one".to_string(),
+                            },
+                            vil::Instruction {
+                                kind: vil::InstructionKind::Binary(
+                                    vil::BinaryOp::Add,
+                                    vil::Register::new(1),
+                                    vil::Register::new(0),
+                                    vil::Register::new(0),
+                                ),
+                                comment: String::new(),
+                            },
+                            vil::Instruction {
+                                kind: vil::InstructionKind::Cmp(
+                                    vil::Register::new(1),
+                                    vil::Register::new(0),
+                                ),
+                                comment: String::new(),
+                            },
+                            vil::Instruction {
+                                kind: vil::InstructionKind::JumpIf(
+                                    vil::JumpCondition::Lte,
+                                    vil::Label("exit".to_string()),
+                                    vil::Label("entry".to_string()),
+                                ),
+                                comment: String::new(),
+                            },
+                        ],
+                    },
+                    vil::Block {
+                        name: "exit".to_string(),
+                        instructions: vec![vil::Instruction {
+                            kind: vil::InstructionKind::Call {
+                                destination: vil::Register::ret(),
+                                label: vil::Label("printf".to_string()),
+                                offsets: vec![-8, -16],
+                                variadic: true,
+                            },
+                            comment: String::new(),
+                        }],
+                    },
+                ],
+            }],
+            strings,
+        }
+    }
+
+    #[test]
+    fn round_trips_a_program_through_its_textual_form() {
+        let program = build_sample_program();
+        let text = program.to_string();
+
+        let (parsed, errors) = parse("<test>", &text);
+        assert!(errors.is_empty(), "unexpected parse errors: {:?}", errors);
+        assert_eq!(parsed.to_string(), text);
+    }
+
+    #[test]
+    fn preserves_an_instructions_trailing_comment() {
+        let program = build_sample_program();
+        let text = program.to_string();
+
+        let (parsed, _) = parse("<test>", &text);
+        assert_eq!(
+            parsed.declarations[0].blocks[0].instructions[0].comment,
+            "start the counter at one"
+        );
+    }
+
+    #[test]
+    fn reports_a_line_numbered_error_for_an_unknown_opcode() {
+        let text = "\nfunc main {\n  // stack_frame_size = 0\n  // parameters = \n\nentry:\n  R0 = frobnicate R1\n}\n";
+
+        let (_, errors) = parse("<test>", text);
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].message.contains("frobnicate"));
+        assert_eq!(errors[0].location.line, 7);
+    }
+}
@@ -0,0 +1,784 @@
+// Copyright 2022 The Venice Authors. All rights reserved.
+// Use of this source code is governed by an MIT-style license that can be
+// found in the LICENSE file.
+//
+// A generic walk over the abstract syntax tree, so that a pass (codegen, a lint, constant folding,
+// dead-code elimination, ...) only has to override the node kinds it actually cares about instead
+// of hand-writing its own recursive descent over `Statement`/`Expression`/`Declaration`.
+//
+// `Visitor` is for passes that only read the tree; `VisitorMut` is for passes that rewrite it in
+// place, e.g. replacing an `ExpressionKind::Index` with its `venice_list_index` call form. Both
+// traits have a default, do-nothing implementation for every node kind, which calls out to a
+// `walk_*`/`walk_*_mut` free function that recurses into that node's children -- a pass overrides
+// only the `visit_*` methods for the node kinds it's interested in, and calls the matching `walk_*`
+// itself if it still wants to visit the node's children.
+
+use super::ast;
+
+pub trait Visitor {
+    fn visit_program(&mut self, program: &ast::Program) {
+        walk_program(self, program);
+    }
+
+    fn visit_declaration(&mut self, declaration: &ast::Declaration) {
+        walk_declaration(self, declaration);
+    }
+
+    fn visit_function_declaration(&mut self, declaration: &ast::FunctionDeclaration) {
+        walk_function_declaration(self, declaration);
+    }
+
+    fn visit_const_declaration(&mut self, declaration: &ast::ConstDeclaration) {
+        walk_const_declaration(self, declaration);
+    }
+
+    fn visit_record_declaration(&mut self, _declaration: &ast::RecordDeclaration) {}
+
+    fn visit_enum_declaration(&mut self, _declaration: &ast::EnumDeclaration) {}
+
+    fn visit_statement(&mut self, statement: &ast::Statement) {
+        walk_statement(self, statement);
+    }
+
+    fn visit_assert_statement(&mut self, statement: &ast::AssertStatement) {
+        walk_assert_statement(self, statement);
+    }
+
+    fn visit_assign_statement(&mut self, statement: &ast::AssignStatement) {
+        walk_assign_statement(self, statement);
+    }
+
+    fn visit_for_statement(&mut self, statement: &ast::ForStatement) {
+        walk_for_statement(self, statement);
+    }
+
+    fn visit_if_statement(&mut self, statement: &ast::IfStatement) {
+        walk_if_statement(self, statement);
+    }
+
+    fn visit_let_statement(&mut self, statement: &ast::LetStatement) {
+        walk_let_statement(self, statement);
+    }
+
+    fn visit_return_statement(&mut self, statement: &ast::ReturnStatement) {
+        walk_return_statement(self, statement);
+    }
+
+    fn visit_while_statement(&mut self, statement: &ast::WhileStatement) {
+        walk_while_statement(self, statement);
+    }
+
+    fn visit_expression(&mut self, expression: &ast::Expression) {
+        walk_expression(self, expression);
+    }
+
+    fn visit_symbol(&mut self, _symbol: &ast::SymbolEntry) {}
+
+    fn visit_binary_expression(&mut self, expression: &ast::BinaryExpression) {
+        walk_binary_expression(self, expression);
+    }
+
+    fn visit_comparison_expression(&mut self, expression: &ast::ComparisonExpression) {
+        walk_comparison_expression(self, expression);
+    }
+
+    fn visit_unary_expression(&mut self, expression: &ast::UnaryExpression) {
+        walk_unary_expression(self, expression);
+    }
+
+    fn visit_call_expression(&mut self, expression: &ast::CallExpression) {
+        walk_call_expression(self, expression);
+    }
+
+    fn visit_if_expression(&mut self, expression: &ast::IfExpression) {
+        walk_if_expression(self, expression);
+    }
+
+    fn visit_index_expression(&mut self, expression: &ast::IndexExpression) {
+        walk_index_expression(self, expression);
+    }
+
+    fn visit_tuple_index_expression(&mut self, expression: &ast::TupleIndexExpression) {
+        walk_tuple_index_expression(self, expression);
+    }
+
+    fn visit_attribute_expression(&mut self, expression: &ast::AttributeExpression) {
+        walk_attribute_expression(self, expression);
+    }
+
+    fn visit_tuple_literal(&mut self, expression: &ast::TupleLiteral) {
+        walk_tuple_literal(self, expression);
+    }
+
+    fn visit_map_literal(&mut self, expression: &ast::MapLiteral) {
+        walk_map_literal(self, expression);
+    }
+
+    fn visit_record_literal(&mut self, expression: &ast::RecordLiteral) {
+        walk_record_literal(self, expression);
+    }
+
+    fn visit_list_comprehension(&mut self, expression: &ast::ListComprehension) {
+        walk_list_comprehension(self, expression);
+    }
+}
+
+pub fn walk_program<V: Visitor + ?Sized>(visitor: &mut V, program: &ast::Program) {
+    for declaration in &program.declarations {
+        visitor.visit_declaration(declaration);
+    }
+}
+
+pub fn walk_declaration<V: Visitor + ?Sized>(visitor: &mut V, declaration: &ast::Declaration) {
+    use ast::Declaration::*;
+    match declaration {
+        Function(d) => visitor.visit_function_declaration(d),
+        Const(d) => visitor.visit_const_declaration(d),
+        Record(d) => visitor.visit_record_declaration(d),
+        Enum(d) => visitor.visit_enum_declaration(d),
+        Error(_) => {}
+    }
+}
+
+pub fn walk_function_declaration<V: Visitor + ?Sized>(
+    visitor: &mut V,
+    declaration: &ast::FunctionDeclaration,
+) {
+    for statement in &declaration.body {
+        visitor.visit_statement(statement);
+    }
+}
+
+pub fn walk_const_declaration<V: Visitor + ?Sized>(
+    visitor: &mut V,
+    declaration: &ast::ConstDeclaration,
+) {
+    visitor.visit_expression(&declaration.value);
+}
+
+pub fn walk_statement<V: Visitor + ?Sized>(visitor: &mut V, statement: &ast::Statement) {
+    use ast::Statement::*;
+    match statement {
+        Assert(s) => visitor.visit_assert_statement(s),
+        Assign(s) => visitor.visit_assign_statement(s),
+        Expression(expr) => visitor.visit_expression(expr),
+        For(s) => visitor.visit_for_statement(s),
+        If(s) => visitor.visit_if_statement(s),
+        Let(s) => visitor.visit_let_statement(s),
+        Return(s) => visitor.visit_return_statement(s),
+        While(s) => visitor.visit_while_statement(s),
+        Error(_) => {}
+    }
+}
+
+pub fn walk_assert_statement<V: Visitor + ?Sized>(
+    visitor: &mut V,
+    statement: &ast::AssertStatement,
+) {
+    visitor.visit_expression(&statement.condition);
+}
+
+pub fn walk_assign_statement<V: Visitor + ?Sized>(
+    visitor: &mut V,
+    statement: &ast::AssignStatement,
+) {
+    visitor.visit_symbol(&statement.symbol);
+    visitor.visit_expression(&statement.value);
+}
+
+pub fn walk_for_statement<V: Visitor + ?Sized>(visitor: &mut V, statement: &ast::ForStatement) {
+    visitor.visit_symbol(&statement.symbol);
+    if let Some(symbol2) = &statement.symbol2 {
+        visitor.visit_symbol(symbol2);
+    }
+    visitor.visit_expression(&statement.iterator);
+    for s in &statement.body {
+        visitor.visit_statement(s);
+    }
+}
+
+pub fn walk_if_statement<V: Visitor + ?Sized>(visitor: &mut V, statement: &ast::IfStatement) {
+    visitor.visit_expression(&statement.condition);
+    for s in &statement.body {
+        visitor.visit_statement(s);
+    }
+    for s in &statement.else_body {
+        visitor.visit_statement(s);
+    }
+}
+
+pub fn walk_let_statement<V: Visitor + ?Sized>(visitor: &mut V, statement: &ast::LetStatement) {
+    visitor.visit_symbol(&statement.symbol);
+    visitor.visit_expression(&statement.value);
+}
+
+pub fn walk_return_statement<V: Visitor + ?Sized>(
+    visitor: &mut V,
+    statement: &ast::ReturnStatement,
+) {
+    visitor.visit_expression(&statement.value);
+}
+
+pub fn walk_while_statement<V: Visitor + ?Sized>(visitor: &mut V, statement: &ast::WhileStatement) {
+    visitor.visit_expression(&statement.condition);
+    for s in &statement.body {
+        visitor.visit_statement(s);
+    }
+}
+
+pub fn walk_expression<V: Visitor + ?Sized>(visitor: &mut V, expression: &ast::Expression) {
+    use ast::ExpressionKind::*;
+    match &expression.kind {
+        Boolean(_) | Integer(_) | Float(_) | String(_) | Error => {}
+        Symbol(symbol) => visitor.visit_symbol(symbol),
+        Binary(e) => visitor.visit_binary_expression(e),
+        Comparison(e) => visitor.visit_comparison_expression(e),
+        Unary(e) => visitor.visit_unary_expression(e),
+        Call(e) => visitor.visit_call_expression(e),
+        If(e) => visitor.visit_if_expression(e),
+        Index(e) => visitor.visit_index_expression(e),
+        TupleIndex(e) => visitor.visit_tuple_index_expression(e),
+        Attribute(e) => visitor.visit_attribute_expression(e),
+        Tuple(e) => visitor.visit_tuple_literal(e),
+        Map(e) => visitor.visit_map_literal(e),
+        Record(e) => visitor.visit_record_literal(e),
+        ListComprehension(e) => visitor.visit_list_comprehension(e),
+    }
+}
+
+pub fn walk_binary_expression<V: Visitor + ?Sized>(
+    visitor: &mut V,
+    expression: &ast::BinaryExpression,
+) {
+    visitor.visit_expression(&expression.left);
+    visitor.visit_expression(&expression.right);
+}
+
+pub fn walk_comparison_expression<V: Visitor + ?Sized>(
+    visitor: &mut V,
+    expression: &ast::ComparisonExpression,
+) {
+    visitor.visit_expression(&expression.left);
+    visitor.visit_expression(&expression.right);
+}
+
+pub fn walk_unary_expression<V: Visitor + ?Sized>(
+    visitor: &mut V,
+    expression: &ast::UnaryExpression,
+) {
+    visitor.visit_expression(&expression.operand);
+}
+
+pub fn walk_call_expression<V: Visitor + ?Sized>(
+    visitor: &mut V,
+    expression: &ast::CallExpression,
+) {
+    visitor.visit_symbol(&expression.function);
+    for argument in &expression.arguments {
+        visitor.visit_expression(argument);
+    }
+}
+
+pub fn walk_if_expression<V: Visitor + ?Sized>(visitor: &mut V, expression: &ast::IfExpression) {
+    visitor.visit_expression(&expression.condition);
+    visitor.visit_expression(&expression.true_value);
+    visitor.visit_expression(&expression.false_value);
+}
+
+pub fn walk_index_expression<V: Visitor + ?Sized>(
+    visitor: &mut V,
+    expression: &ast::IndexExpression,
+) {
+    visitor.visit_expression(&expression.value);
+    visitor.visit_expression(&expression.index);
+}
+
+pub fn walk_tuple_index_expression<V: Visitor + ?Sized>(
+    visitor: &mut V,
+    expression: &ast::TupleIndexExpression,
+) {
+    visitor.visit_expression(&expression.value);
+}
+
+pub fn walk_attribute_expression<V: Visitor + ?Sized>(
+    visitor: &mut V,
+    expression: &ast::AttributeExpression,
+) {
+    visitor.visit_expression(&expression.value);
+}
+
+pub fn walk_tuple_literal<V: Visitor + ?Sized>(visitor: &mut V, expression: &ast::TupleLiteral) {
+    for item in &expression.items {
+        visitor.visit_expression(item);
+    }
+}
+
+pub fn walk_map_literal<V: Visitor + ?Sized>(visitor: &mut V, expression: &ast::MapLiteral) {
+    for (key, value) in &expression.items {
+        visitor.visit_expression(key);
+        visitor.visit_expression(value);
+    }
+}
+
+pub fn walk_record_literal<V: Visitor + ?Sized>(visitor: &mut V, expression: &ast::RecordLiteral) {
+    visitor.visit_symbol(&expression.name);
+    for (_, value) in &expression.items {
+        visitor.visit_expression(value);
+    }
+}
+
+pub fn walk_list_comprehension<V: Visitor + ?Sized>(
+    visitor: &mut V,
+    expression: &ast::ListComprehension,
+) {
+    visitor.visit_symbol(&expression.symbol);
+    visitor.visit_expression(&expression.iterator);
+    if let Some(condition) = &expression.condition {
+        visitor.visit_expression(condition);
+    }
+    visitor.visit_expression(&expression.value);
+}
+
+/// Like `Visitor`, but for passes that rewrite the tree in place (e.g. desugaring one
+/// `ExpressionKind` into another) instead of only reading it.
+pub trait VisitorMut {
+    fn visit_program_mut(&mut self, program: &mut ast::Program) {
+        walk_program_mut(self, program);
+    }
+
+    fn visit_declaration_mut(&mut self, declaration: &mut ast::Declaration) {
+        walk_declaration_mut(self, declaration);
+    }
+
+    fn visit_function_declaration_mut(&mut self, declaration: &mut ast::FunctionDeclaration) {
+        walk_function_declaration_mut(self, declaration);
+    }
+
+    fn visit_const_declaration_mut(&mut self, declaration: &mut ast::ConstDeclaration) {
+        walk_const_declaration_mut(self, declaration);
+    }
+
+    fn visit_record_declaration_mut(&mut self, _declaration: &mut ast::RecordDeclaration) {}
+
+    fn visit_enum_declaration_mut(&mut self, _declaration: &mut ast::EnumDeclaration) {}
+
+    fn visit_statement_mut(&mut self, statement: &mut ast::Statement) {
+        walk_statement_mut(self, statement);
+    }
+
+    fn visit_assert_statement_mut(&mut self, statement: &mut ast::AssertStatement) {
+        walk_assert_statement_mut(self, statement);
+    }
+
+    fn visit_assign_statement_mut(&mut self, statement: &mut ast::AssignStatement) {
+        walk_assign_statement_mut(self, statement);
+    }
+
+    fn visit_for_statement_mut(&mut self, statement: &mut ast::ForStatement) {
+        walk_for_statement_mut(self, statement);
+    }
+
+    fn visit_if_statement_mut(&mut self, statement: &mut ast::IfStatement) {
+        walk_if_statement_mut(self, statement);
+    }
+
+    fn visit_let_statement_mut(&mut self, statement: &mut ast::LetStatement) {
+        walk_let_statement_mut(self, statement);
+    }
+
+    fn visit_return_statement_mut(&mut self, statement: &mut ast::ReturnStatement) {
+        walk_return_statement_mut(self, statement);
+    }
+
+    fn visit_while_statement_mut(&mut self, statement: &mut ast::WhileStatement) {
+        walk_while_statement_mut(self, statement);
+    }
+
+    /// Called with a `&mut` to the expression itself, so an override can replace the node
+    /// entirely by assigning through it (e.g. desugar `ExpressionKind::Index` into its
+    /// `venice_list_index` call form) instead of only mutating its fields in place.
+    fn visit_expression_mut(&mut self, expression: &mut ast::Expression) {
+        walk_expression_mut(self, expression);
+    }
+
+    fn visit_symbol_mut(&mut self, _symbol: &mut ast::SymbolEntry) {}
+
+    fn visit_binary_expression_mut(&mut self, expression: &mut ast::BinaryExpression) {
+        walk_binary_expression_mut(self, expression);
+    }
+
+    fn visit_comparison_expression_mut(&mut self, expression: &mut ast::ComparisonExpression) {
+        walk_comparison_expression_mut(self, expression);
+    }
+
+    fn visit_unary_expression_mut(&mut self, expression: &mut ast::UnaryExpression) {
+        walk_unary_expression_mut(self, expression);
+    }
+
+    fn visit_call_expression_mut(&mut self, expression: &mut ast::CallExpression) {
+        walk_call_expression_mut(self, expression);
+    }
+
+    fn visit_if_expression_mut(&mut self, expression: &mut ast::IfExpression) {
+        walk_if_expression_mut(self, expression);
+    }
+
+    fn visit_index_expression_mut(&mut self, expression: &mut ast::IndexExpression) {
+        walk_index_expression_mut(self, expression);
+    }
+
+    fn visit_tuple_index_expression_mut(&mut self, expression: &mut ast::TupleIndexExpression) {
+        walk_tuple_index_expression_mut(self, expression);
+    }
+
+    fn visit_attribute_expression_mut(&mut self, expression: &mut ast::AttributeExpression) {
+        walk_attribute_expression_mut(self, expression);
+    }
+
+    fn visit_tuple_literal_mut(&mut self, expression: &mut ast::TupleLiteral) {
+        walk_tuple_literal_mut(self, expression);
+    }
+
+    fn visit_map_literal_mut(&mut self, expression: &mut ast::MapLiteral) {
+        walk_map_literal_mut(self, expression);
+    }
+
+    fn visit_record_literal_mut(&mut self, expression: &mut ast::RecordLiteral) {
+        walk_record_literal_mut(self, expression);
+    }
+
+    fn visit_list_comprehension_mut(&mut self, expression: &mut ast::ListComprehension) {
+        walk_list_comprehension_mut(self, expression);
+    }
+}
+
+pub fn walk_program_mut<V: VisitorMut + ?Sized>(visitor: &mut V, program: &mut ast::Program) {
+    for declaration in &mut program.declarations {
+        visitor.visit_declaration_mut(declaration);
+    }
+}
+
+pub fn walk_declaration_mut<V: VisitorMut + ?Sized>(
+    visitor: &mut V,
+    declaration: &mut ast::Declaration,
+) {
+    use ast::Declaration::*;
+    match declaration {
+        Function(d) => visitor.visit_function_declaration_mut(d),
+        Const(d) => visitor.visit_const_declaration_mut(d),
+        Record(d) => visitor.visit_record_declaration_mut(d),
+        Enum(d) => visitor.visit_enum_declaration_mut(d),
+        Error(_) => {}
+    }
+}
+
+pub fn walk_function_declaration_mut<V: VisitorMut + ?Sized>(
+    visitor: &mut V,
+    declaration: &mut ast::FunctionDeclaration,
+) {
+    for statement in &mut declaration.body {
+        visitor.visit_statement_mut(statement);
+    }
+}
+
+pub fn walk_const_declaration_mut<V: VisitorMut + ?Sized>(
+    visitor: &mut V,
+    declaration: &mut ast::ConstDeclaration,
+) {
+    visitor.visit_expression_mut(&mut declaration.value);
+}
+
+pub fn walk_statement_mut<V: VisitorMut + ?Sized>(visitor: &mut V, statement: &mut ast::Statement) {
+    use ast::Statement::*;
+    match statement {
+        Assert(s) => visitor.visit_assert_statement_mut(s),
+        Assign(s) => visitor.visit_assign_statement_mut(s),
+        Expression(expr) => visitor.visit_expression_mut(expr),
+        For(s) => visitor.visit_for_statement_mut(s),
+        If(s) => visitor.visit_if_statement_mut(s),
+        Let(s) => visitor.visit_let_statement_mut(s),
+        Return(s) => visitor.visit_return_statement_mut(s),
+        While(s) => visitor.visit_while_statement_mut(s),
+        Error(_) => {}
+    }
+}
+
+pub fn walk_assert_statement_mut<V: VisitorMut + ?Sized>(
+    visitor: &mut V,
+    statement: &mut ast::AssertStatement,
+) {
+    visitor.visit_expression_mut(&mut statement.condition);
+}
+
+pub fn walk_assign_statement_mut<V: VisitorMut + ?Sized>(
+    visitor: &mut V,
+    statement: &mut ast::AssignStatement,
+) {
+    visitor.visit_symbol_mut(&mut statement.symbol);
+    visitor.visit_expression_mut(&mut statement.value);
+}
+
+pub fn walk_for_statement_mut<V: VisitorMut + ?Sized>(
+    visitor: &mut V,
+    statement: &mut ast::ForStatement,
+) {
+    visitor.visit_symbol_mut(&mut statement.symbol);
+    if let Some(symbol2) = &mut statement.symbol2 {
+        visitor.visit_symbol_mut(symbol2);
+    }
+    visitor.visit_expression_mut(&mut statement.iterator);
+    for s in &mut statement.body {
+        visitor.visit_statement_mut(s);
+    }
+}
+
+pub fn walk_if_statement_mut<V: VisitorMut + ?Sized>(
+    visitor: &mut V,
+    statement: &mut ast::IfStatement,
+) {
+    visitor.visit_expression_mut(&mut statement.condition);
+    for s in &mut statement.body {
+        visitor.visit_statement_mut(s);
+    }
+    for s in &mut statement.else_body {
+        visitor.visit_statement_mut(s);
+    }
+}
+
+pub fn walk_let_statement_mut<V: VisitorMut + ?Sized>(
+    visitor: &mut V,
+    statement: &mut ast::LetStatement,
+) {
+    visitor.visit_symbol_mut(&mut statement.symbol);
+    visitor.visit_expression_mut(&mut statement.value);
+}
+
+pub fn walk_return_statement_mut<V: VisitorMut + ?Sized>(
+    visitor: &mut V,
+    statement: &mut ast::ReturnStatement,
+) {
+    visitor.visit_expression_mut(&mut statement.value);
+}
+
+pub fn walk_while_statement_mut<V: VisitorMut + ?Sized>(
+    visitor: &mut V,
+    statement: &mut ast::WhileStatement,
+) {
+    visitor.visit_expression_mut(&mut statement.condition);
+    for s in &mut statement.body {
+        visitor.visit_statement_mut(s);
+    }
+}
+
+pub fn walk_expression_mut<V: VisitorMut + ?Sized>(
+    visitor: &mut V,
+    expression: &mut ast::Expression,
+) {
+    use ast::ExpressionKind::*;
+    match &mut expression.kind {
+        Boolean(_) | Integer(_) | Float(_) | String(_) | Error => {}
+        Symbol(symbol) => visitor.visit_symbol_mut(symbol),
+        Binary(e) => visitor.visit_binary_expression_mut(e),
+        Comparison(e) => visitor.visit_comparison_expression_mut(e),
+        Unary(e) => visitor.visit_unary_expression_mut(e),
+        Call(e) => visitor.visit_call_expression_mut(e),
+        If(e) => visitor.visit_if_expression_mut(e),
+        Index(e) => visitor.visit_index_expression_mut(e),
+        TupleIndex(e) => visitor.visit_tuple_index_expression_mut(e),
+        Attribute(e) => visitor.visit_attribute_expression_mut(e),
+        Tuple(e) => visitor.visit_tuple_literal_mut(e),
+        Map(e) => visitor.visit_map_literal_mut(e),
+        Record(e) => visitor.visit_record_literal_mut(e),
+        ListComprehension(e) => visitor.visit_list_comprehension_mut(e),
+    }
+}
+
+pub fn walk_binary_expression_mut<V: VisitorMut + ?Sized>(
+    visitor: &mut V,
+    expression: &mut ast::BinaryExpression,
+) {
+    visitor.visit_expression_mut(&mut expression.left);
+    visitor.visit_expression_mut(&mut expression.right);
+}
+
+pub fn walk_comparison_expression_mut<V: VisitorMut + ?Sized>(
+    visitor: &mut V,
+    expression: &mut ast::ComparisonExpression,
+) {
+    visitor.visit_expression_mut(&mut expression.left);
+    visitor.visit_expression_mut(&mut expression.right);
+}
+
+pub fn walk_unary_expression_mut<V: VisitorMut + ?Sized>(
+    visitor: &mut V,
+    expression: &mut ast::UnaryExpression,
+) {
+    visitor.visit_expression_mut(&mut expression.operand);
+}
+
+pub fn walk_call_expression_mut<V: VisitorMut + ?Sized>(
+    visitor: &mut V,
+    expression: &mut ast::CallExpression,
+) {
+    visitor.visit_symbol_mut(&mut expression.function);
+    for argument in &mut expression.arguments {
+        visitor.visit_expression_mut(argument);
+    }
+}
+
+pub fn walk_if_expression_mut<V: VisitorMut + ?Sized>(
+    visitor: &mut V,
+    expression: &mut ast::IfExpression,
+) {
+    visitor.visit_expression_mut(&mut expression.condition);
+    visitor.visit_expression_mut(&mut expression.true_value);
+    visitor.visit_expression_mut(&mut expression.false_value);
+}
+
+pub fn walk_index_expression_mut<V: VisitorMut + ?Sized>(
+    visitor: &mut V,
+    expression: &mut ast::IndexExpression,
+) {
+    visitor.visit_expression_mut(&mut expression.value);
+    visitor.visit_expression_mut(&mut expression.index);
+}
+
+pub fn walk_tuple_index_expression_mut<V: VisitorMut + ?Sized>(
+    visitor: &mut V,
+    expression: &mut ast::TupleIndexExpression,
+) {
+    visitor.visit_expression_mut(&mut expression.value);
+}
+
+pub fn walk_attribute_expression_mut<V: VisitorMut + ?Sized>(
+    visitor: &mut V,
+    expression: &mut ast::AttributeExpression,
+) {
+    visitor.visit_expression_mut(&mut expression.value);
+}
+
+pub fn walk_tuple_literal_mut<V: VisitorMut + ?Sized>(
+    visitor: &mut V,
+    expression: &mut ast::TupleLiteral,
+) {
+    for item in &mut expression.items {
+        visitor.visit_expression_mut(item);
+    }
+}
+
+pub fn walk_map_literal_mut<V: VisitorMut + ?Sized>(
+    visitor: &mut V,
+    expression: &mut ast::MapLiteral,
+) {
+    for (key, value) in &mut expression.items {
+        visitor.visit_expression_mut(key);
+        visitor.visit_expression_mut(value);
+    }
+}
+
+pub fn walk_record_literal_mut<V: VisitorMut + ?Sized>(
+    visitor: &mut V,
+    expression: &mut ast::RecordLiteral,
+) {
+    visitor.visit_symbol_mut(&mut expression.name);
+    for (_, value) in &mut expression.items {
+        visitor.visit_expression_mut(value);
+    }
+}
+
+pub fn walk_list_comprehension_mut<V: VisitorMut + ?Sized>(
+    visitor: &mut V,
+    expression: &mut ast::ListComprehension,
+) {
+    visitor.visit_symbol_mut(&mut expression.symbol);
+    visitor.visit_expression_mut(&mut expression.iterator);
+    if let Some(condition) = &mut expression.condition {
+        visitor.visit_expression_mut(condition);
+    }
+    visitor.visit_expression_mut(&mut expression.value);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn int_expr(n: i64) -> ast::Expression {
+        ast::Expression::new(
+            ast::ExpressionKind::Integer(n),
+            ast::Type::I64,
+            common::Span::empty(),
+        )
+    }
+
+    use super::super::common;
+
+    #[derive(Default)]
+    struct IntegerCollector {
+        values: Vec<i64>,
+    }
+
+    impl Visitor for IntegerCollector {
+        fn visit_expression(&mut self, expression: &ast::Expression) {
+            if let ast::ExpressionKind::Integer(n) = expression.kind {
+                self.values.push(n);
+            }
+            walk_expression(self, expression);
+        }
+    }
+
+    #[test]
+    fn collects_every_integer_literal_in_a_binary_expression() {
+        let expr = ast::Expression::new(
+            ast::ExpressionKind::Binary(ast::BinaryExpression {
+                op: common::BinaryOpType::Add,
+                left: Box::new(int_expr(1)),
+                right: Box::new(int_expr(2)),
+            }),
+            ast::Type::I64,
+            common::Span::empty(),
+        );
+
+        let mut collector = IntegerCollector::default();
+        collector.visit_expression(&expr);
+
+        assert_eq!(collector.values, vec![1, 2]);
+    }
+
+    struct IntegerDoubler;
+
+    impl VisitorMut for IntegerDoubler {
+        fn visit_expression_mut(&mut self, expression: &mut ast::Expression) {
+            if let ast::ExpressionKind::Integer(n) = &mut expression.kind {
+                *n *= 2;
+            }
+            walk_expression_mut(self, expression);
+        }
+    }
+
+    #[test]
+    fn doubles_every_integer_literal_in_place() {
+        let mut expr = ast::Expression::new(
+            ast::ExpressionKind::Binary(ast::BinaryExpression {
+                op: common::BinaryOpType::Add,
+                left: Box::new(int_expr(1)),
+                right: Box::new(int_expr(2)),
+            }),
+            ast::Type::I64,
+            common::Span::empty(),
+        );
+
+        IntegerDoubler.visit_expression_mut(&mut expr);
+
+        if let ast::ExpressionKind::Binary(binary) = &expr.kind {
+            match (&binary.left.kind, &binary.right.kind) {
+                (ast::ExpressionKind::Integer(left), ast::ExpressionKind::Integer(right)) => {
+                    assert_eq!(*left, 2);
+                    assert_eq!(*right, 4);
+                }
+                _ => panic!("expected integer literals"),
+            }
+        } else {
+            panic!("expected a binary expression");
+        }
+    }
+}
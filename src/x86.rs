@@ -4,12 +4,34 @@
 //
 // Compiles a VIL program into concrete x86 machine code.
 
+use super::backend::Backend;
 use super::vil;
+use std::collections::HashMap;
 use std::fmt;
 
+/// Tells codegen.rs's allocator this target has 14 usable VIL registers (see
+/// `backend::BackendConfig`) -- the value `codegen::generate` used to hard-code before it took a
+/// config instead.
+pub struct X86Config;
+
+impl super::backend::BackendConfig for X86Config {
+    fn register_count(&self) -> u8 {
+        14
+    }
+}
+
 pub fn generate(vil: &vil::Program) -> Result<Program, String> {
     let mut generator = Generator::new();
-    generator.generate_program(vil);
+    generator.program.externs = vil.externs.clone();
+    super::backend::generate(&mut generator, vil);
+
+    for (string_name, string_value) in &vil.strings {
+        generator.program.data.push(Data {
+            name: string_name.clone(),
+            value: DataValue::Str(string_value.clone()),
+        });
+    }
+
     Ok(generator.program)
 }
 
@@ -45,17 +67,22 @@ pub enum Instruction {
     Push(Value),
     Ret,
     SetE(Value),
+    SetG(Value),
+    SetL(Value),
     Sub(Value, Value),
+    Syscall,
     Test(Value, Value),
     Xor(Value, Value),
 }
 
+#[derive(Clone)]
 pub enum Value {
     Immediate(i64),
-    Register(Register),
-    /// Directly holds a register's assembly-language name for special cases, e.g. for byte
-    /// registers like AL.
-    SpecialRegister(String),
+    /// A register operand at the given width -- e.g. `(RAX_REGISTER, Width::Byte)` is `al`,
+    /// `(RAX_REGISTER, Width::Quad)` is `rax`. Addressing registers (inside `Memory` below) don't
+    /// carry a width, since an address computation is always 64-bit regardless of the operand
+    /// size of the instruction that uses it.
+    Register(Register, Width),
     Label(String),
     Memory {
         scale: u8,
@@ -66,14 +93,50 @@ pub enum Value {
 }
 
 impl Value {
-    /// Constructs an x86 register from a VIL register.
-    fn r(r: &vil::Register) -> Self {
-        Value::Register(Register(r.index()))
+    /// Constructs the x86 register for a function's i'th parameter (starting at 0). Only valid
+    /// for `i < PARAM_REGISTER_COUNT`; arguments beyond that are passed on the stack instead (see
+    /// `lower_call`).
+    fn param(i: u8) -> Self {
+        Value::Register(Register(i + 7), Width::Quad)
     }
+}
 
-    /// Constructs the x86 register for a function's i'th parameter (starting at 0).
-    fn param(i: u8) -> Self {
-        Value::Register(Register(i + 7))
+/// How many leading call arguments the SysV x86-64 ABI passes in registers before the rest have
+/// to go on the stack. codegen.rs has no cap of its own on argument count (see its
+/// `generate_call_expression` doc comment) -- this is the one place that boundary is actually
+/// enforced today, since this is the only backend that materializes the overflow on the stack.
+const PARAM_REGISTER_COUNT: usize = 6;
+
+/// The operand size of an instruction or register access: `Byte`/`Word`/`Long`/`Quad` are 8, 16,
+/// 32, and 64 bits respectively, matching the AT&T mnemonic suffixes (`b`/`w`/`l`/`q`) and the
+/// x86-64 sub-register naming scheme (`al`/`ax`/`eax`/`rax`, `r10b`/`r10w`/`r10d`/`r10`, ...).
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Width {
+    Byte,
+    Word,
+    Long,
+    Quad,
+}
+
+impl Width {
+    fn suffix(&self) -> &'static str {
+        match self {
+            Width::Byte => "b",
+            Width::Word => "w",
+            Width::Long => "l",
+            Width::Quad => "q",
+        }
+    }
+}
+
+/// The width to use for an ALU/mov instruction whose operands are `a` and `b`: whichever one is
+/// a register operand determines it (both agree, when both are registers), since `Immediate`,
+/// `Label`, and `Memory` operands don't carry a width of their own.
+fn pick_width(a: &Value, b: &Value) -> Width {
+    match (a, b) {
+        (Value::Register(_, w), _) => *w,
+        (_, Value::Register(_, w)) => *w,
+        _ => Width::Quad,
     }
 }
 
@@ -92,9 +155,187 @@ pub enum DataValue {
 const CALLER_SAVE_REGISTERS: &[u8] = &[0, 1];
 const CALLEE_SAVE_REGISTERS: &[u8] = &[2, 3, 4, 5, 6];
 
+// Register allocation.
+//
+// VIL hands the x86 backend a function whose registers have already been kept under
+// `codegen::X86_REGISTER_COUNT` by codegen.rs's own `RegisterAllocator`, which does real
+// linear-scan allocation over live intervals computed across the whole function. The allocator
+// below runs a second, x86-specific tier over that already-allocated input, with its own smaller
+// physical register set and its own live intervals -- so a function can have far more live values
+// than there are GPRs without every one of them paying a spill's worth of load/store traffic.
+
+/// The registers (in this module's own indexing scheme -- see `Register`'s `physical` method)
+/// available to the allocator below: everything except RSP/RBP, which hold the stack frame, and
+/// the RAX/RDX pair reserved as scratch registers for `div` and `sete`.
+const ALLOCATABLE_REGISTERS: &[u8] = &[0, 1, 2, 3, 4, 5, 6, 7, 8, 10, 11, 12];
+
+/// Where a VIL register ends up after allocation.
+#[derive(Clone, Copy)]
+enum RegisterLocation {
+    Physical(u8),
+    /// A displacement from RBP, to be accessed the same way spilled locals already are.
+    Spill(i32),
+}
+
+struct RegisterAllocation {
+    locations: HashMap<u8, RegisterLocation>,
+    /// Additional stack frame bytes claimed for spill slots, to be added to the function's
+    /// declared `stack_frame_size`.
+    spill_bytes: i32,
+}
+
+/// A VIL register's live range, numbering instructions in block order: `start` is the index of
+/// its first def or use, `end` the index of its last.
+#[derive(Clone, Copy)]
+struct Interval {
+    register: u8,
+    start: usize,
+    end: usize,
+}
+
+/// Assigns every VIL register `declaration` uses to a physical register or a spill slot, via
+/// linear-scan over live intervals computed in block order. Intervals are processed in order of
+/// their start point; an "active" set (sorted by end point) tracks which registers are currently
+/// live, expiring entries whose interval has ended to return their register to the free list.
+/// When no register is free, the active interval with the farthest end is spilled -- which may
+/// be the one just reached, if nothing active outlives it.
+fn allocate_registers(declaration: &vil::FunctionDeclaration) -> RegisterAllocation {
+    let intervals = compute_live_intervals(declaration);
+
+    let mut free_registers: Vec<u8> = ALLOCATABLE_REGISTERS.iter().rev().copied().collect();
+    let mut active: Vec<Interval> = Vec::new();
+    let mut physical_of: HashMap<u8, u8> = HashMap::new();
+    let mut locations: HashMap<u8, RegisterLocation> = HashMap::new();
+
+    let mut next_spill_offset = -(declaration.stack_frame_size + 8);
+    let mut spill_bytes = 0;
+
+    for interval in intervals {
+        active.retain(|other| {
+            if other.end < interval.start {
+                if let Some(physical) = physical_of.remove(&other.register) {
+                    free_registers.push(physical);
+                }
+                false
+            } else {
+                true
+            }
+        });
+
+        if let Some(physical) = free_registers.pop() {
+            physical_of.insert(interval.register, physical);
+            locations.insert(interval.register, RegisterLocation::Physical(physical));
+            active.push(interval);
+            active.sort_by_key(|other| other.end);
+            continue;
+        }
+
+        match active.last().copied() {
+            Some(candidate) if candidate.end > interval.end => {
+                let physical = physical_of.remove(&candidate.register).unwrap();
+                locations.insert(
+                    candidate.register,
+                    RegisterLocation::Spill(claim_spill_slot(
+                        &mut next_spill_offset,
+                        &mut spill_bytes,
+                    )),
+                );
+                active.pop();
+
+                physical_of.insert(interval.register, physical);
+                locations.insert(interval.register, RegisterLocation::Physical(physical));
+                active.push(interval);
+                active.sort_by_key(|other| other.end);
+            }
+            _ => {
+                locations.insert(
+                    interval.register,
+                    RegisterLocation::Spill(claim_spill_slot(
+                        &mut next_spill_offset,
+                        &mut spill_bytes,
+                    )),
+                );
+            }
+        }
+    }
+
+    RegisterAllocation {
+        locations,
+        spill_bytes,
+    }
+}
+
+fn claim_spill_slot(next_offset: &mut i32, spill_bytes: &mut i32) -> i32 {
+    let offset = *next_offset;
+    *next_offset -= 8;
+    *spill_bytes += 8;
+    offset
+}
+
+fn compute_live_intervals(declaration: &vil::FunctionDeclaration) -> Vec<Interval> {
+    let mut bounds: HashMap<u8, (usize, usize)> = HashMap::new();
+    let mut index = 0usize;
+    for block in &declaration.blocks {
+        for instruction in &block.instructions {
+            for register in registers_touched(&instruction.kind) {
+                bounds
+                    .entry(register)
+                    .and_modify(|(_, end)| *end = index)
+                    .or_insert((index, index));
+            }
+            index += 1;
+        }
+    }
+
+    let mut intervals: Vec<Interval> = bounds
+        .into_iter()
+        .map(|(register, (start, end))| Interval {
+            register,
+            start,
+            end,
+        })
+        .collect();
+    intervals.sort_by_key(|interval| interval.start);
+    intervals
+}
+
+/// Every VIL register an instruction reads or writes, in no particular order: good enough to
+/// compute a live range, since we don't need to tell defs from uses apart to do that.
+fn registers_touched(kind: &vil::InstructionKind) -> Vec<u8> {
+    use vil::InstructionKind::*;
+    match kind {
+        Binary(_, r1, r2, r3) => vec![r1.index(), r2.index(), r3.index()],
+        Unary(_, r1, r2) => vec![r1.index(), r2.index()],
+        Call { destination, .. } => vec![destination.index()],
+        Cmp(r1, r2) => vec![r1.index(), r2.index()],
+        FCmp(r1, r2) => vec![r1.index(), r2.index()],
+        CmpOrdering(r1, r2, r3) => vec![r1.index(), r2.index(), r3.index()],
+        FCmpOrdering(r1, r2, r3) => vec![r1.index(), r2.index(), r3.index()],
+        Jump(_) => Vec::new(),
+        JumpIf(..) => Vec::new(),
+        JumpOrdering(r, ..) => vec![r.index()],
+        Load(r, _) => vec![r.index()],
+        Move(r1, r2) => vec![r1.index(), r2.index()],
+        Set(r, _) => vec![r.index()],
+        Store(r, _) => vec![r.index()],
+        Syscall { destination, .. } => vec![destination.index()],
+        Phi(r, operands) => {
+            let mut registers = vec![r.index()];
+            registers.extend(operands.iter().map(|(_, reg)| reg.index()));
+            registers
+        }
+    }
+}
+
 struct Generator {
     program: Program,
     stack_alignment: i64,
+    /// Where each VIL register in the function currently being lowered lives, assigned fresh by
+    /// `allocate_registers` at the start of each `start_function`.
+    register_allocation: RegisterAllocation,
+    /// The current function's stack frame size, including the spill slots `register_allocation`
+    /// claimed on top of `declaration.stack_frame_size`.
+    frame_size: i32,
 }
 
 impl Generator {
@@ -106,289 +347,504 @@ impl Generator {
                 data: Vec::new(),
             },
             stack_alignment: 0,
+            register_allocation: RegisterAllocation {
+                locations: HashMap::new(),
+                spill_bytes: 0,
+            },
+            frame_size: 0,
         }
     }
 
-    fn generate_program(&mut self, vil: &vil::Program) {
-        self.program.externs = vil.externs.clone();
+    /// Resolves a VIL register to the location `allocate_registers` assigned it: either a
+    /// physical x86 register, or a memory operand in this function's spill area.
+    fn r(&self, r: &vil::Register) -> Value {
+        match self.register_allocation.locations.get(&r.index()) {
+            Some(RegisterLocation::Physical(physical)) => {
+                Value::Register(Register(*physical), Width::Quad)
+            }
+            Some(RegisterLocation::Spill(offset)) => Value::Memory {
+                scale: 1,
+                displacement: *offset,
+                base: RBP_REGISTER,
+                index: None,
+            },
+            None => panic!("internal error: register {} was never allocated", r.index()),
+        }
+    }
 
-        for declaration in &vil.declarations {
-            self.generate_declaration(declaration);
+    fn align_stack(&mut self) {
+        let diff = self.stack_alignment % 16;
+        if diff > 0 {
+            self.push_no_stack_align(Instruction::Sub(RSP, Value::Immediate(diff)));
+        }
+    }
+
+    fn unalign_stack(&mut self) {
+        let diff = self.stack_alignment % 16;
+        if diff > 0 {
+            self.push_no_stack_align(Instruction::Add(RSP, Value::Immediate(diff)));
         }
+    }
 
-        for (string_name, string_value) in &vil.strings {
-            self.program.data.push(Data {
-                name: string_name.clone(),
-                value: DataValue::Str(string_value.clone()),
-            });
+    fn push(&mut self, instruction: Instruction) {
+        match instruction {
+            Instruction::Push(_) => {
+                self.stack_alignment += 8;
+            }
+            Instruction::Pop(_) => {
+                self.stack_alignment -= 8;
+            }
+            Instruction::Sub(Value::Register(Register(14), _), Value::Immediate(x)) => {
+                self.stack_alignment += x;
+            }
+            Instruction::Add(Value::Register(Register(14), _), Value::Immediate(x)) => {
+                self.stack_alignment += x;
+            }
+            _ => {}
         }
+
+        let index = self.program.blocks.len() - 1;
+        self.program.blocks[index].instructions.push(instruction);
     }
 
-    fn generate_declaration(&mut self, declaration: &vil::FunctionDeclaration) {
+    fn push_no_stack_align(&mut self, instruction: Instruction) {
+        let index = self.program.blocks.len() - 1;
+        self.program.blocks[index].instructions.push(instruction);
+    }
+}
+
+const RDX_REGISTER: Register = Register(9);
+const RDX: Value = Value::Register(RDX_REGISTER, Width::Quad);
+const RAX_REGISTER: Register = Register(13);
+const RAX: Value = Value::Register(RAX_REGISTER, Width::Quad);
+const RSP_REGISTER: Register = Register(14);
+const RSP: Value = Value::Register(RSP_REGISTER, Width::Quad);
+const RBP_REGISTER: Register = Register(15);
+const RBP: Value = Value::Register(RBP_REGISTER, Width::Quad);
+// The kernel's syscall calling convention uses R10 where the C calling convention uses RCX (the
+// `syscall` instruction itself clobbers RCX), so this is the one syscall argument register that
+// doesn't already have a constant above -- RDI/RSI are `Value::param(0)`/`Value::param(1)`, and
+// R8/R9 are `Value::param(4)`/`Value::param(5)`.
+const R10_REGISTER: Register = Register(0);
+const R10: Value = Value::Register(R10_REGISTER, Width::Quad);
+
+impl Backend for Generator {
+    fn start_function(&mut self, declaration: &vil::FunctionDeclaration) {
         self.stack_alignment = 8;
+        self.register_allocation = allocate_registers(declaration);
+        self.frame_size = declaration.stack_frame_size + self.register_allocation.spill_bytes;
 
-        let block = Block {
+        self.program.blocks.push(Block {
             // TODO: replace this with more robust logic
             global: declaration.name == "venice_main",
             label: declaration.name.clone(),
             instructions: Vec::new(),
-        };
-        self.program.blocks.push(block);
+        });
+    }
 
+    fn start_block(&mut self, name: &str) {
+        self.program.blocks.push(Block {
+            global: false,
+            label: String::from(name),
+            instructions: Vec::new(),
+        });
+    }
+
+    fn prologue(&mut self) {
         self.push(Instruction::Push(RBP));
         self.push(Instruction::Mov(RBP, RSP));
-        let size_as_i64 = i64::try_from(declaration.stack_frame_size).unwrap();
+        let size_as_i64 = i64::try_from(self.frame_size).unwrap();
         self.push(Instruction::Sub(RSP, Value::Immediate(size_as_i64)));
 
-        // Save callee-save registers.
         for callee_save in CALLEE_SAVE_REGISTERS {
-            self.push(Instruction::Push(Value::Register(Register(*callee_save))));
-        }
-
-        // Move parameters from registers onto the stack.
-        for (i, parameter) in declaration.parameters.iter().enumerate() {
-            self.push(Instruction::Mov(
-                Value::Memory {
-                    scale: 1,
-                    displacement: parameter.stack_offset,
-                    base: RBP_REGISTER,
-                    index: None,
-                },
-                Value::param(u8::try_from(i).unwrap()),
-            ));
-        }
-
-        for block in &declaration.blocks {
-            self.generate_block(declaration, block);
+            self.push(Instruction::Push(Value::Register(
+                Register(*callee_save),
+                Width::Quad,
+            )));
         }
+    }
 
-        // Restore callee-save registers.
+    fn epilogue(&mut self) {
         for callee_save in CALLEE_SAVE_REGISTERS.iter().rev() {
-            self.push(Instruction::Pop(Value::Register(Register(*callee_save))));
+            self.push(Instruction::Pop(Value::Register(
+                Register(*callee_save),
+                Width::Quad,
+            )));
         }
 
-        let size_as_i64 = i64::try_from(declaration.stack_frame_size).unwrap();
+        let size_as_i64 = i64::try_from(self.frame_size).unwrap();
         self.push(Instruction::Add(RSP, Value::Immediate(size_as_i64)));
         self.push(Instruction::Pop(RBP));
         self.push(Instruction::Ret);
     }
 
-    fn generate_block(&mut self, declaration: &vil::FunctionDeclaration, block: &vil::Block) {
-        self.program.blocks.push(Block {
-            global: false,
-            label: block.name.clone(),
-            instructions: Vec::new(),
-        });
+    fn lower_param(&mut self, i: u8, stack_offset: i32) {
+        self.push(Instruction::Mov(
+            Value::Memory {
+                scale: 1,
+                displacement: stack_offset,
+                base: RBP_REGISTER,
+                index: None,
+            },
+            Value::param(i),
+        ));
+    }
 
-        for instruction in &block.instructions {
-            self.generate_instruction(declaration, instruction);
+    fn lower_set(&mut self, r: vil::Register, imm: &vil::Immediate) {
+        match imm {
+            vil::Immediate::Integer(x) => {
+                self.push(Instruction::Mov(self.r(&r), Value::Immediate(*x)));
+            }
+            vil::Immediate::Label(s) => {
+                self.push(Instruction::Mov(self.r(&r), Value::Label(s.clone())));
+            }
+            vil::Immediate::Float(x) => {
+                // Floats live in the same general-purpose registers as integers until an
+                // instruction actually needs to compute on them (see vil.rs's `Immediate::Float`
+                // doc comment), so setting one is just moving its bit pattern the same way an
+                // integer immediate would be moved.
+                self.push(Instruction::Mov(
+                    self.r(&r),
+                    Value::Immediate(x.to_bits() as i64),
+                ));
+            }
         }
     }
 
-    fn generate_instruction(
+    fn lower_move(&mut self, r1: vil::Register, r2: vil::Register) {
+        self.push(Instruction::Mov(self.r(&r1), self.r(&r2)));
+    }
+
+    fn lower_binary(
         &mut self,
-        declaration: &vil::FunctionDeclaration,
-        instruction: &vil::Instruction,
+        op: vil::BinaryOp,
+        r1: vil::Register,
+        r2: vil::Register,
+        r3: vil::Register,
     ) {
-        use vil::InstructionKind::*;
-        match &instruction.kind {
-            Set(r, imm) => match imm {
-                vil::Immediate::Integer(x) => {
-                    self.push(Instruction::Mov(Value::r(r), Value::Immediate(*x)));
-                }
-                vil::Immediate::Label(s) => {
-                    self.push(Instruction::Mov(Value::r(r), Value::Label(s.clone())));
-                }
-            },
-            Move(r1, r2) => {
-                self.push(Instruction::Mov(Value::r(r1), Value::r(r2)));
-            }
-            Add(r1, r2, r3) => {
-                self.push(Instruction::Add(Value::r(r2), Value::r(r3)));
-                self.push(Instruction::Mov(Value::r(r1), Value::r(r2)));
+        match op {
+            vil::BinaryOp::Add => {
+                self.push(Instruction::Add(self.r(&r2), self.r(&r3)));
+                self.push(Instruction::Mov(self.r(&r1), self.r(&r2)));
             }
-            Sub(r1, r2, r3) => {
-                self.push(Instruction::Sub(Value::r(r2), Value::r(r3)));
-                self.push(Instruction::Mov(Value::r(r1), Value::r(r2)));
+            vil::BinaryOp::Sub => {
+                self.push(Instruction::Sub(self.r(&r2), self.r(&r3)));
+                self.push(Instruction::Mov(self.r(&r1), self.r(&r2)));
             }
-            Mul(r1, r2, r3) => {
-                self.push(Instruction::IMul(Value::r(r2), Value::r(r3)));
-                self.push(Instruction::Mov(Value::r(r1), Value::r(r2)));
+            vil::BinaryOp::Mul => {
+                self.push(Instruction::IMul(self.r(&r2), self.r(&r3)));
+                self.push(Instruction::Mov(self.r(&r1), self.r(&r2)));
             }
-            Div(r1, r2, r3) => {
+            vil::BinaryOp::Div => {
                 // In x86, `div RXX` computes RDX:RAX / RXX and stores the quotient in RAX and the
                 // remainder in RDX.
                 //
-                // The compiler will never use RAX or RDX for regular expressions, so we don't have
-                // to worry about the case where r1, r2, or r3 is RAX or RDX.
+                // The compiler never assigns RAX or RDX to a regular VIL register, so we don't
+                // have to worry about r1, r2, or r3 already living in one of them.
 
-                // First, we zero out RDX since we are only doing 64-bit division, not 128-bit.
+                // We are only doing 64-bit division, not 128-bit, so zero out RDX first.
                 self.push(Instruction::Xor(RDX, RDX));
 
                 // Move the dividend into RAX.
-                self.push(Instruction::Mov(RAX, Value::r(r2)));
+                self.push(Instruction::Mov(RAX, self.r(&r2)));
 
                 // Divide by the divisor.
-                self.push(Instruction::IDiv(Value::r(r3)));
+                self.push(Instruction::IDiv(self.r(&r3)));
 
                 // Move RAX into the destination register.
-                self.push(Instruction::Mov(Value::r(r1), RAX));
+                self.push(Instruction::Mov(self.r(&r1), RAX));
+            }
+            vil::BinaryOp::FAdd
+            | vil::BinaryOp::FSub
+            | vil::BinaryOp::FMul
+            | vil::BinaryOp::FDiv => {
+                // Unlike the integer ops above, these need SSE2 instructions (addsd/subsd/mulsd/
+                // divsd) operating on the XMM registers, not the encoder's existing GPR-only
+                // instruction set, so there's nothing yet for this backend to lower them to.
+                panic!(
+                    "internal error: floating-point arithmetic is not yet supported by the x86 backend"
+                );
+            }
+        }
+    }
+
+    fn lower_unary(&mut self, op: vil::UnaryOp, r1: vil::Register, r2: vil::Register) {
+        match op {
+            vil::UnaryOp::Negate => {
+                self.push(Instruction::Neg(self.r(&r2)));
+                self.push(Instruction::Mov(self.r(&r1), self.r(&r2)));
             }
-            Negate(r1, r2) => {
-                self.push(Instruction::Neg(Value::r(r2)));
-                self.push(Instruction::Mov(Value::r(r1), Value::r(r2)));
+            vil::UnaryOp::FNegate => {
+                panic!("internal error: floating-point negation is not yet supported by the x86 backend");
             }
-            LogicalNot(r1, r2) => {
+            vil::UnaryOp::LogicalNot => {
                 // XOR RAX with itself to produce 0, then test it against the source register and
                 // set AL to the ZF flag. Since we already zeroed out RAX, all the high bits will
                 // also be 0.
                 self.push(Instruction::Xor(RAX, RAX));
-                self.push(Instruction::Test(RAX, Value::r(r2)));
-                self.push(Instruction::SetE(Value::SpecialRegister(String::from(
-                    "al",
-                ))));
-                self.push(Instruction::Mov(Value::r(r1), RAX));
+                self.push(Instruction::Test(RAX, self.r(&r2)));
+                self.push(Instruction::SetE(Value::Register(
+                    RAX_REGISTER,
+                    Width::Byte,
+                )));
+                self.push(Instruction::Mov(self.r(&r1), RAX));
             }
-            Load(r, offset) => {
-                self.push(Instruction::Mov(
-                    Value::r(r),
-                    Value::Memory {
-                        scale: 1,
-                        displacement: *offset,
-                        base: RBP_REGISTER,
-                        index: None,
-                    },
-                ));
-            }
-            Store(r, offset) => {
-                self.push(Instruction::Mov(
-                    Value::Memory {
-                        scale: 1,
-                        displacement: *offset,
-                        base: RBP_REGISTER,
-                        index: None,
-                    },
-                    Value::r(r),
-                ));
-            }
-            Cmp(r1, r2) => {
-                self.push(Instruction::Cmp(Value::r(r1), Value::r(r2)));
-            }
-            Call {
-                label,
-                registers,
-                variadic,
-            } => {
-                // Save caller-save registers.
-                for caller_save in CALLER_SAVE_REGISTERS {
-                    self.push(Instruction::Push(Value::Register(Register(*caller_save))));
-                }
+        }
+    }
 
-                for (i, register) in registers.iter().enumerate() {
-                    self.push(Instruction::Mov(
-                        Value::param(u8::try_from(i).unwrap()),
-                        Value::r(register),
-                    ));
-                }
+    fn lower_load(&mut self, r: vil::Register, offset: vil::MemoryOffset) {
+        self.push(Instruction::Mov(
+            self.r(&r),
+            Value::Memory {
+                scale: 1,
+                displacement: offset,
+                base: RBP_REGISTER,
+                index: None,
+            },
+        ));
+    }
 
-                if *variadic {
-                    // The System V ABI requires setting AL to the number of vector registers when
-                    // calling a variadic function.
-                    self.push(Instruction::Mov(
-                        Value::SpecialRegister(String::from("al")),
-                        Value::Immediate(0),
-                    ));
-                }
+    fn lower_store(&mut self, r: vil::Register, offset: vil::MemoryOffset) {
+        self.push(Instruction::Mov(
+            Value::Memory {
+                scale: 1,
+                displacement: offset,
+                base: RBP_REGISTER,
+                index: None,
+            },
+            self.r(&r),
+        ));
+    }
 
-                self.align_stack();
-                self.push(Instruction::Call(label.0.clone()));
-                self.unalign_stack();
+    fn lower_cmp(&mut self, r1: vil::Register, r2: vil::Register) {
+        self.push(Instruction::Cmp(self.r(&r1), self.r(&r2)));
+    }
 
-                // Restore caller-save registers.
-                for caller_save in CALLER_SAVE_REGISTERS.iter().rev() {
-                    self.push(Instruction::Pop(Value::Register(Register(*caller_save))));
-                }
-            }
-            Jump(l) => {
-                self.push(Instruction::Jmp(l.0.clone()));
-            }
-            JumpEq(true_label, false_label) => {
-                self.push(Instruction::Je(true_label.0.clone()));
-                self.push(Instruction::Jmp(false_label.0.clone()));
-            }
-            JumpGt(true_label, false_label) => {
-                self.push(Instruction::Jg(true_label.0.clone()));
-                self.push(Instruction::Jmp(false_label.0.clone()));
-            }
-            JumpGte(true_label, false_label) => {
-                self.push(Instruction::Jge(true_label.0.clone()));
-                self.push(Instruction::Jmp(false_label.0.clone()));
-            }
-            JumpLt(true_label, false_label) => {
-                self.push(Instruction::Jl(true_label.0.clone()));
-                self.push(Instruction::Jmp(false_label.0.clone()));
-            }
-            JumpLte(true_label, false_label) => {
-                self.push(Instruction::Jle(true_label.0.clone()));
-                self.push(Instruction::Jmp(false_label.0.clone()));
+    fn lower_fcmp(&mut self, _r1: vil::Register, _r2: vil::Register) {
+        // Needs `ucomisd` (SSE2's unordered float compare) and its own XMM operands, the same
+        // gap that keeps `lower_binary`'s float arms and `lower_unary`'s `FNegate` unimplemented.
+        panic!(
+            "internal error: floating-point comparisons are not yet supported by the x86 backend"
+        );
+    }
+
+    fn lower_cmp_ordering(&mut self, r1: vil::Register, r2: vil::Register, r3: vil::Register) {
+        // The same zero-then-setcc trick `lower_unary`'s `LogicalNot` uses, run twice: RAX picks
+        // up 1 if `r2 > r3`, RDX picks up 1 if `r2 < r3`, and subtracting them leaves -1, 0, or 1
+        // in RAX regardless of which comparison (if either) actually held.
+        self.push(Instruction::Xor(RAX, RAX));
+        self.push(Instruction::Xor(RDX, RDX));
+        self.push(Instruction::Cmp(self.r(&r2), self.r(&r3)));
+        self.push(Instruction::SetG(Value::Register(
+            RAX_REGISTER,
+            Width::Byte,
+        )));
+        self.push(Instruction::SetL(Value::Register(
+            RDX_REGISTER,
+            Width::Byte,
+        )));
+        self.push(Instruction::Sub(RAX, RDX));
+        self.push(Instruction::Mov(self.r(&r1), RAX));
+    }
+
+    fn lower_fcmp_ordering(&mut self, _r1: vil::Register, _r2: vil::Register, _r3: vil::Register) {
+        // Same SSE2 gap as `lower_fcmp`.
+        panic!(
+            "internal error: floating-point comparisons are not yet supported by the x86 backend"
+        );
+    }
+
+    fn lower_call(
+        &mut self,
+        destination: vil::Register,
+        label: &vil::Label,
+        offsets: &[vil::MemoryOffset],
+        variadic: bool,
+    ) {
+        // The call's own destination is overwritten by the `Mov` from RAX at the very end of
+        // this function regardless of what it held before, so there's no need to save and
+        // restore it even if it happens to live in one of the caller-save registers.
+        let destination_physical = match self.r(&destination) {
+            Value::Register(Register(physical), _) => Some(physical),
+            _ => None,
+        };
+
+        // Save caller-save registers.
+        for caller_save in CALLER_SAVE_REGISTERS {
+            if Some(*caller_save) == destination_physical {
+                continue;
             }
-            JumpNeq(true_label, false_label) => {
-                self.push(Instruction::Jne(true_label.0.clone()));
-                self.push(Instruction::Jmp(false_label.0.clone()));
+            self.push(Instruction::Push(Value::Register(
+                Register(*caller_save),
+                Width::Quad,
+            )));
+        }
+
+        let (register_offsets, stack_offsets) = if offsets.len() > PARAM_REGISTER_COUNT {
+            offsets.split_at(PARAM_REGISTER_COUNT)
+        } else {
+            (offsets, &[][..])
+        };
+
+        for (i, offset) in register_offsets.iter().enumerate() {
+            self.push(Instruction::Mov(
+                Value::param(u8::try_from(i).unwrap()),
+                Value::Memory {
+                    scale: 1,
+                    displacement: *offset,
+                    base: RBP_REGISTER,
+                    index: None,
+                },
+            ));
+        }
+
+        // Arguments beyond `PARAM_REGISTER_COUNT` go on the stack instead, pushed in reverse so
+        // the first overflow argument ends up at the lowest address (`[rsp]`) once all of them
+        // are down, matching the System V ABI's left-to-right layout.
+        for offset in stack_offsets.iter().rev() {
+            self.push(Instruction::Push(Value::Memory {
+                scale: 1,
+                displacement: *offset,
+                base: RBP_REGISTER,
+                index: None,
+            }));
+        }
+
+        if variadic {
+            // The System V ABI requires setting AL to the number of vector registers when calling
+            // a variadic function.
+            self.push(Instruction::Mov(
+                Value::Register(RAX_REGISTER, Width::Byte),
+                Value::Immediate(0),
+            ));
+        }
+
+        self.align_stack();
+        self.push(Instruction::Call(label.0.clone()));
+        self.unalign_stack();
+
+        // Deallocate the stack-passed arguments, the same way `unalign_stack`'s own padding is
+        // removed -- as a run of `Pop`s rather than a single `Add(rsp, ...)`, so `stack_alignment`
+        // (which only tracks `Push`/`Pop` correctly; see `push`'s match arms) stays accurate for
+        // any call still to come later in this function. The register popped into is overwritten
+        // again momentarily by the real restore loop just below, so which one it is doesn't
+        // matter.
+        for _ in stack_offsets {
+            self.push(Instruction::Pop(Value::Register(
+                Register(CALLER_SAVE_REGISTERS[0]),
+                Width::Quad,
+            )));
+        }
+
+        // Restore caller-save registers.
+        for caller_save in CALLER_SAVE_REGISTERS.iter().rev() {
+            if Some(*caller_save) == destination_physical {
+                continue;
             }
+            self.push(Instruction::Pop(Value::Register(
+                Register(*caller_save),
+                Width::Quad,
+            )));
         }
+
+        self.push(Instruction::Mov(self.r(&destination), RAX));
     }
 
-    fn align_stack(&mut self) {
-        let diff = self.stack_alignment % 16;
-        if diff > 0 {
-            self.push_no_stack_align(Instruction::Sub(RSP, Value::Immediate(diff)));
-        }
+    fn lower_jump(&mut self, label: &vil::Label) {
+        self.push(Instruction::Jmp(label.0.clone()));
     }
 
-    fn unalign_stack(&mut self) {
-        let diff = self.stack_alignment % 16;
-        if diff > 0 {
-            self.push_no_stack_align(Instruction::Add(RSP, Value::Immediate(diff)));
+    fn lower_jump_if(
+        &mut self,
+        condition: vil::JumpCondition,
+        true_label: &vil::Label,
+        false_label: &vil::Label,
+    ) {
+        match condition {
+            vil::JumpCondition::Eq => self.push(Instruction::Je(true_label.0.clone())),
+            vil::JumpCondition::Gt => self.push(Instruction::Jg(true_label.0.clone())),
+            vil::JumpCondition::Gte => self.push(Instruction::Jge(true_label.0.clone())),
+            vil::JumpCondition::Lt => self.push(Instruction::Jl(true_label.0.clone())),
+            vil::JumpCondition::Lte => self.push(Instruction::Jle(true_label.0.clone())),
+            vil::JumpCondition::Neq => self.push(Instruction::Jne(true_label.0.clone())),
         }
+        self.push(Instruction::Jmp(false_label.0.clone()));
     }
 
-    fn push(&mut self, instruction: Instruction) {
-        match instruction {
-            Instruction::Push(_) => {
-                self.stack_alignment += 8;
-            }
-            Instruction::Pop(_) => {
-                self.stack_alignment -= 8;
-            }
-            Instruction::Sub(Value::Register(Register(14)), Value::Immediate(x)) => {
-                self.stack_alignment += x;
-            }
-            Instruction::Add(Value::Register(Register(14)), Value::Immediate(x)) => {
-                self.stack_alignment += x;
-            }
-            _ => {}
+    fn lower_jump_ordering(
+        &mut self,
+        r: vil::Register,
+        less_label: &vil::Label,
+        equal_label: &vil::Label,
+        greater_label: &vil::Label,
+    ) {
+        self.push(Instruction::Xor(RDX, RDX));
+        self.push(Instruction::Cmp(self.r(&r), RDX));
+        self.push(Instruction::Jl(less_label.0.clone()));
+        self.push(Instruction::Je(equal_label.0.clone()));
+        self.push(Instruction::Jmp(greater_label.0.clone()));
+    }
+
+    fn lower_syscall(
+        &mut self,
+        destination: vil::Register,
+        number: i64,
+        offsets: &[vil::MemoryOffset],
+    ) {
+        if offsets.len() > 6 {
+            panic!("internal error: syscall cannot take more than 6 arguments");
         }
 
-        let index = self.program.blocks.len() - 1;
-        self.program.blocks[index].instructions.push(instruction);
+        // Save the same scratch registers `lower_call` does, since the kernel ABI's own argument
+        // registers (RDI, RSI, RDX, R10, R8, R9) overlap with regular VIL register allocations the
+        // same way the C calling convention's do.
+        for caller_save in CALLER_SAVE_REGISTERS {
+            self.push(Instruction::Push(Value::Register(
+                Register(*caller_save),
+                Width::Quad,
+            )));
+        }
+
+        // The kernel's syscall argument order, left to right.
+        let syscall_arg_registers = [
+            Value::param(0),
+            Value::param(1),
+            RDX,
+            R10,
+            Value::param(4),
+            Value::param(5),
+        ];
+        for (offset, destination_register) in offsets.iter().zip(&syscall_arg_registers) {
+            self.push(Instruction::Mov(
+                destination_register.clone(),
+                Value::Memory {
+                    scale: 1,
+                    displacement: *offset,
+                    base: RBP_REGISTER,
+                    index: None,
+                },
+            ));
+        }
+
+        self.push(Instruction::Mov(RAX, Value::Immediate(number)));
+        self.push(Instruction::Syscall);
+
+        // Restore caller-save registers.
+        for caller_save in CALLER_SAVE_REGISTERS.iter().rev() {
+            self.push(Instruction::Pop(Value::Register(
+                Register(*caller_save),
+                Width::Quad,
+            )));
+        }
+
+        self.push(Instruction::Mov(self.r(&destination), RAX));
     }
 
-    fn push_no_stack_align(&mut self, instruction: Instruction) {
-        let index = self.program.blocks.len() - 1;
-        self.program.blocks[index].instructions.push(instruction);
+    fn param_register(&self, i: u8) -> u8 {
+        Register(i + 7).physical()
     }
 }
 
-const RDX_REGISTER: Register = Register(2);
-const RDX: Value = Value::Register(RDX_REGISTER);
-const RAX_REGISTER: Register = Register(13);
-const RAX: Value = Value::Register(RAX_REGISTER);
-const RSP_REGISTER: Register = Register(14);
-const RSP: Value = Value::Register(RSP_REGISTER);
-const RBP_REGISTER: Register = Register(15);
-const RBP: Value = Value::Register(RBP_REGISTER);
-
 impl fmt::Display for Program {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         for block in &self.blocks {
@@ -417,16 +873,25 @@ impl fmt::Display for Block {
     }
 }
 
+/// The width of a single-operand instruction whose operand is `a`: `a`'s width if it's a register,
+/// `Quad` otherwise (this module never generates a narrower `Immediate`-only unary instruction).
+fn pick_width1(a: &Value) -> Width {
+    match a {
+        Value::Register(_, w) => *w,
+        _ => Width::Quad,
+    }
+}
+
 impl fmt::Display for Instruction {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         use Instruction::*;
         match self {
-            Add(x, y) => write!(f, "addq {}, {}", y, x),
-            And(x, y) => write!(f, "andq {}, {}", y, x),
+            Add(x, y) => write!(f, "add{} {}, {}", pick_width(x, y).suffix(), y, x),
+            And(x, y) => write!(f, "and{} {}, {}", pick_width(x, y).suffix(), y, x),
             Call(l) => write!(f, "call {}", l),
-            Cmp(x, y) => write!(f, "cmpq {}, {}", y, x),
-            IDiv(x) => write!(f, "divq {}", x),
-            IMul(x, y) => write!(f, "imulq {}, {}", y, x),
+            Cmp(x, y) => write!(f, "cmp{} {}, {}", pick_width(x, y).suffix(), y, x),
+            IDiv(x) => write!(f, "div{} {}", pick_width1(x).suffix(), x),
+            IMul(x, y) => write!(f, "imul{} {}, {}", pick_width(x, y).suffix(), y, x),
             Je(l) => write!(f, "je {}", l),
             Jg(l) => write!(f, "jg {}", l),
             Jge(l) => write!(f, "jge {}", l),
@@ -434,24 +899,20 @@ impl fmt::Display for Instruction {
             Jle(l) => write!(f, "jle {}", l),
             Jmp(l) => write!(f, "jmp {}", l),
             Jne(l) => write!(f, "jne {}", l),
-            Mov(x, y) => {
-                // TODO: this logic is brittle, and also needs to be applied to all the other
-                // instructions.
-                if matches!(x, Value::SpecialRegister(_)) || matches!(y, Value::SpecialRegister(_))
-                {
-                    write!(f, "movb {}, {}", y, x)
-                } else {
-                    write!(f, "movq {}, {}", y, x)
-                }
-            }
-            Neg(x) => write!(f, "negq {}", x),
-            Pop(x) => write!(f, "popq {}", x),
-            Push(x) => write!(f, "pushq {}", x),
+            Mov(x, y) => write!(f, "mov{} {}, {}", pick_width(x, y).suffix(), y, x),
+            Neg(x) => write!(f, "neg{} {}", pick_width1(x).suffix(), x),
+            Pop(x) => write!(f, "pop{} {}", pick_width1(x).suffix(), x),
+            Push(x) => write!(f, "push{} {}", pick_width1(x).suffix(), x),
             Ret => write!(f, "retq"),
+            // `sete` always writes a single byte and has no separate size-suffixed form, unlike
+            // the other instructions above.
             SetE(x) => write!(f, "sete {}", x),
-            Sub(x, y) => write!(f, "subq {}, {}", y, x),
-            Test(x, y) => write!(f, "testq {}, {}", y, x),
-            Xor(x, y) => write!(f, "xorq {}, {}", y, x),
+            SetG(x) => write!(f, "setg {}", x),
+            SetL(x) => write!(f, "setl {}", x),
+            Sub(x, y) => write!(f, "sub{} {}, {}", pick_width(x, y).suffix(), y, x),
+            Syscall => write!(f, "syscall"),
+            Test(x, y) => write!(f, "test{} {}, {}", pick_width(x, y).suffix(), y, x),
+            Xor(x, y) => write!(f, "xor{} {}, {}", pick_width(x, y).suffix(), y, x),
         }
     }
 }
@@ -461,8 +922,7 @@ impl fmt::Display for Value {
         use Value::*;
         match self {
             Immediate(x) => write!(f, "${}", x),
-            Register(r) => write!(f, "%{}", r),
-            SpecialRegister(s) => write!(f, "%{}", s),
+            Register(r, w) => write!(f, "%{}", r.name(*w)),
             Label(s) => write!(f, "$.{}", s),
             Memory {
                 scale,
@@ -485,29 +945,85 @@ impl fmt::Display for Value {
     }
 }
 
+impl Register {
+    /// This virtual register's name at the given width, e.g. index 13 (`rax`) is `al`/`ax`/`eax`/
+    /// `rax` at `Byte`/`Word`/`Long`/`Quad` respectively. Used by `Value`'s `Display` impl; bare
+    /// `Register`s (e.g. `Memory`'s addressing fields) are always 64-bit and go through the
+    /// `Display` impl below instead, which just asks for the `Quad` name.
+    fn name(&self, width: Width) -> &'static str {
+        match (self.0, width) {
+            (0, Width::Byte) => "r10b",
+            (0, Width::Word) => "r10w",
+            (0, Width::Long) => "r10d",
+            (0, Width::Quad) => "r10",
+            (1, Width::Byte) => "r11b",
+            (1, Width::Word) => "r11w",
+            (1, Width::Long) => "r11d",
+            (1, Width::Quad) => "r11",
+            (2, Width::Byte) => "r12b",
+            (2, Width::Word) => "r12w",
+            (2, Width::Long) => "r12d",
+            (2, Width::Quad) => "r12",
+            (3, Width::Byte) => "r13b",
+            (3, Width::Word) => "r13w",
+            (3, Width::Long) => "r13d",
+            (3, Width::Quad) => "r13",
+            (4, Width::Byte) => "r14b",
+            (4, Width::Word) => "r14w",
+            (4, Width::Long) => "r14d",
+            (4, Width::Quad) => "r14",
+            (5, Width::Byte) => "r15b",
+            (5, Width::Word) => "r15w",
+            (5, Width::Long) => "r15d",
+            (5, Width::Quad) => "r15",
+            (6, Width::Byte) => "bl",
+            (6, Width::Word) => "bx",
+            (6, Width::Long) => "ebx",
+            (6, Width::Quad) => "rbx",
+            (7, Width::Byte) => "dil",
+            (7, Width::Word) => "di",
+            (7, Width::Long) => "edi",
+            (7, Width::Quad) => "rdi",
+            (8, Width::Byte) => "sil",
+            (8, Width::Word) => "si",
+            (8, Width::Long) => "esi",
+            (8, Width::Quad) => "rsi",
+            (9, Width::Byte) => "dl",
+            (9, Width::Word) => "dx",
+            (9, Width::Long) => "edx",
+            (9, Width::Quad) => "rdx",
+            (10, Width::Byte) => "cl",
+            (10, Width::Word) => "cx",
+            (10, Width::Long) => "ecx",
+            (10, Width::Quad) => "rcx",
+            (11, Width::Byte) => "r8b",
+            (11, Width::Word) => "r8w",
+            (11, Width::Long) => "r8d",
+            (11, Width::Quad) => "r8",
+            (12, Width::Byte) => "r9b",
+            (12, Width::Word) => "r9w",
+            (12, Width::Long) => "r9d",
+            (12, Width::Quad) => "r9",
+            (13, Width::Byte) => "al",
+            (13, Width::Word) => "ax",
+            (13, Width::Long) => "eax",
+            (13, Width::Quad) => "rax",
+            (14, Width::Byte) => "spl",
+            (14, Width::Word) => "sp",
+            (14, Width::Long) => "esp",
+            (14, Width::Quad) => "rsp",
+            (15, Width::Byte) => "bpl",
+            (15, Width::Word) => "bp",
+            (15, Width::Long) => "ebp",
+            (15, Width::Quad) => "rbp",
+            (x, _) => panic!("internal error: register out of range: {}", x),
+        }
+    }
+}
+
 impl fmt::Display for Register {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        match self.0 {
-            0 => write!(f, "r10"),
-            1 => write!(f, "r11"),
-            2 => write!(f, "r12"),
-            3 => write!(f, "r13"),
-            4 => write!(f, "r14"),
-            5 => write!(f, "r15"),
-            6 => write!(f, "rbx"),
-            7 => write!(f, "rdi"),
-            8 => write!(f, "rsi"),
-            9 => write!(f, "rdx"),
-            10 => write!(f, "rcx"),
-            11 => write!(f, "r8"),
-            12 => write!(f, "r9"),
-            13 => write!(f, "rax"),
-            14 => write!(f, "rsp"),
-            15 => write!(f, "rbp"),
-            _x => {
-                panic!("internal error: register out of range: {}", self.0);
-            }
-        }
+        write!(f, "{}", self.name(Width::Quad))
     }
 }
 
@@ -522,3 +1038,974 @@ impl fmt::Display for Data {
         }
     }
 }
+
+// Machine code encoding.
+//
+// The rest of this file turns a `Program` into actual x86-64 instruction bytes instead of the
+// AT&T-syntax text that `fmt::Display` produces above, so Venice can assemble a program without
+// shelling out to an external assembler. `Register`'s index is a purely internal numbering
+// (see its `Display` impl); `physical()` below maps it to the register number the ModRM/SIB/REX
+// fields actually expect.
+
+/// A relocatable buffer of machine code. Labels are recorded as they're laid out; jump, call,
+/// and label-immediate sites are left as zeroed placeholders and recorded as `Fixup`s, then
+/// patched by `resolve()` once every label's final offset is known.
+pub struct Buffer {
+    pub bytes: Vec<u8>,
+    labels: HashMap<String, usize>,
+    fixups: Vec<Fixup>,
+}
+
+enum FixupKind {
+    /// A 32-bit displacement, relative to the byte immediately following the field.
+    Relative,
+    /// A 64-bit absolute address.
+    Absolute,
+}
+
+struct Fixup {
+    /// Byte offset of the start of the relocation field.
+    site: usize,
+    kind: FixupKind,
+    target: String,
+}
+
+impl Buffer {
+    fn new() -> Self {
+        Buffer {
+            bytes: Vec::new(),
+            labels: HashMap::new(),
+            fixups: Vec::new(),
+        }
+    }
+
+    fn emit_byte(&mut self, byte: u8) {
+        self.bytes.push(byte);
+    }
+
+    fn emit_bytes(&mut self, bytes: &[u8]) {
+        self.bytes.extend_from_slice(bytes);
+    }
+
+    fn set_label(&mut self, name: &str) {
+        self.labels.insert(String::from(name), self.bytes.len());
+    }
+
+    /// Emits a zeroed 32-bit placeholder for a near jump or call, and records a fixup to patch
+    /// it in with the relative displacement to `target` once `target`'s offset is known.
+    fn fixup_rel32(&mut self, target: &str) {
+        self.fixups.push(Fixup {
+            site: self.bytes.len(),
+            kind: FixupKind::Relative,
+            target: String::from(target),
+        });
+        self.emit_bytes(&[0, 0, 0, 0]);
+    }
+
+    /// Emits a zeroed 64-bit placeholder for a `mov r64, $label` load of a label's address, and
+    /// records a fixup to patch it in with `target`'s absolute offset.
+    fn fixup_abs64(&mut self, target: &str) {
+        self.fixups.push(Fixup {
+            site: self.bytes.len(),
+            kind: FixupKind::Absolute,
+            target: String::from(target),
+        });
+        self.emit_bytes(&[0, 0, 0, 0, 0, 0, 0, 0]);
+    }
+
+    /// Patches every recorded fixup now that every label has a final offset, panicking if a
+    /// relative displacement doesn't fit in 32 bits.
+    fn resolve(&mut self) {
+        for fixup in &self.fixups {
+            let target_offset = *self
+                .labels
+                .get(&fixup.target)
+                .unwrap_or_else(|| panic!("internal error: undefined label: {}", fixup.target))
+                as i64;
+
+            match fixup.kind {
+                FixupKind::Relative => {
+                    let site_end = (fixup.site + 4) as i64;
+                    let displacement = target_offset - site_end;
+                    let displacement = i32::try_from(displacement).unwrap_or_else(|_| {
+                        panic!(
+                            "internal error: displacement to {} overflows 32 bits",
+                            fixup.target
+                        )
+                    });
+                    self.bytes[fixup.site..fixup.site + 4]
+                        .copy_from_slice(&displacement.to_le_bytes());
+                }
+                FixupKind::Absolute => {
+                    self.bytes[fixup.site..fixup.site + 8]
+                        .copy_from_slice(&target_offset.to_le_bytes());
+                }
+            }
+        }
+    }
+}
+
+impl Program {
+    /// Assembles this program directly into machine code, instead of the AT&T-syntax text that
+    /// `fmt::Display` produces. Each block is laid out in order and its label recorded at its
+    /// starting offset; jump, call, and label-address sites are resolved once every block has
+    /// been encoded. String data is not encoded here: a real object-file writer would place it
+    /// in a separate section, but nothing in this buffer depends on that separation yet.
+    pub fn encode(&self) -> Buffer {
+        let mut buffer = Buffer::new();
+        for block in &self.blocks {
+            buffer.set_label(&block.label);
+            for instruction in &block.instructions {
+                instruction.encode(&mut buffer);
+            }
+        }
+        buffer.resolve();
+        buffer
+    }
+}
+
+impl Register {
+    /// The physical x86-64 register number (0-15) that this virtual register name is assigned
+    /// to, matching the mapping in the `Display` impl above. REX and ModRM/SIB fields split this
+    /// into a high bit (the REX.R/X/B extension) and the low 3 bits (the field itself).
+    fn physical(&self) -> u8 {
+        match self.0 {
+            0 => 10,
+            1 => 11,
+            2 => 12,
+            3 => 13,
+            4 => 14,
+            5 => 15,
+            6 => 3,
+            7 => 7,
+            8 => 6,
+            9 => 2,
+            10 => 1,
+            11 => 8,
+            12 => 9,
+            13 => 0,
+            14 => 4,
+            15 => 5,
+            _ => panic!("internal error: register out of range: {}", self.0),
+        }
+    }
+}
+
+fn rex_byte(w: bool, r: bool, x: bool, b: bool) -> u8 {
+    0x40 | ((w as u8) << 3) | ((r as u8) << 2) | ((x as u8) << 1) | (b as u8)
+}
+
+fn scale_bits(scale: u8) -> u8 {
+    match scale {
+        1 => 0b00,
+        2 => 0b01,
+        4 => 0b10,
+        8 => 0b11,
+        _ => panic!("internal error: unsupported SIB scale: {}", scale),
+    }
+}
+
+/// The ModRM byte (and, for memory operands, the trailing SIB and displacement bytes) for an
+/// instruction whose reg field holds `reg` and whose rm field/memory operand is `rm`.
+struct ModRM {
+    rex_r: bool,
+    rex_x: bool,
+    rex_b: bool,
+    bytes: Vec<u8>,
+}
+
+fn encode_modrm(reg: u8, rm: &Value) -> ModRM {
+    let reg_field = reg & 0x7;
+    let rex_r = reg & 0x8 != 0;
+    match rm {
+        Value::Register(r, _) => {
+            let phys = r.physical();
+            ModRM {
+                rex_r,
+                rex_x: false,
+                rex_b: phys & 0x8 != 0,
+                bytes: vec![0xC0 | (reg_field << 3) | (phys & 0x7)],
+            }
+        }
+        Value::Memory {
+            scale,
+            displacement,
+            base,
+            index,
+        } => {
+            let base_phys = base.physical();
+            let rex_b = base_phys & 0x8 != 0;
+            let needs_sib = base_phys & 0x7 == 4 || index.is_some();
+
+            // RBP/R13 as a base with no displacement would be read as the mod=00, rm=101
+            // RIP-relative encoding instead, so force a one-byte displacement in that case.
+            let forces_disp8 = base_phys & 0x7 == 5 && *displacement == 0;
+
+            let (md, disp_bytes) = if *displacement == 0 && !forces_disp8 {
+                (0b00, Vec::new())
+            } else if let Ok(disp8) = i8::try_from(*displacement) {
+                (0b01, vec![disp8 as u8])
+            } else {
+                (0b10, displacement.to_le_bytes().to_vec())
+            };
+
+            let mut bytes = Vec::new();
+            let rm_field = if needs_sib { 0b100 } else { base_phys & 0x7 };
+            bytes.push((md << 6) | (reg_field << 3) | rm_field);
+
+            let mut rex_x = false;
+            if needs_sib {
+                let (index_field, ss) = match index {
+                    Some(index_register) => {
+                        rex_x = index_register.physical() & 0x8 != 0;
+                        (index_register.physical() & 0x7, scale_bits(*scale))
+                    }
+                    None => (0b100, 0b00),
+                };
+                bytes.push((ss << 6) | (index_field << 3) | (base_phys & 0x7));
+            }
+
+            bytes.extend(disp_bytes);
+
+            ModRM {
+                rex_r,
+                rex_x,
+                rex_b,
+                bytes,
+            }
+        }
+        Value::Label(_) => {
+            panic!("internal error: a label cannot be encoded as a ModRM operand")
+        }
+        Value::Immediate(_) => {
+            panic!("internal error: an immediate cannot be encoded as a ModRM operand")
+        }
+    }
+}
+
+impl Instruction {
+    /// Encodes this instruction as x86-64 machine code bytes, appending them to `buffer`.
+    fn encode(&self, buffer: &mut Buffer) {
+        use Instruction::*;
+        match self {
+            Add(dst, src) => encode_arith(buffer, 0x00, dst, src),
+            And(dst, src) => encode_arith(buffer, 0x20, dst, src),
+            Sub(dst, src) => encode_arith(buffer, 0x28, dst, src),
+            Xor(dst, src) => encode_arith(buffer, 0x30, dst, src),
+            Cmp(dst, src) => encode_arith(buffer, 0x38, dst, src),
+            Test(dst, src) => encode_test(buffer, dst, src),
+            Mov(dst, src) => encode_mov(buffer, dst, src),
+            IMul(dst, src) => {
+                let reg = register_physical(dst);
+                let modrm = encode_modrm(reg, src);
+                buffer.emit_byte(rex_byte(true, modrm.rex_r, modrm.rex_x, modrm.rex_b));
+                buffer.emit_bytes(&[0x0F, 0xAF]);
+                buffer.emit_bytes(&modrm.bytes);
+            }
+            IDiv(x) => encode_unary(buffer, 0x7, x),
+            Neg(x) => encode_unary(buffer, 0x3, x),
+            SetE(x) => {
+                let modrm = encode_modrm(0, x);
+                if modrm.rex_r || modrm.rex_x || modrm.rex_b {
+                    buffer.emit_byte(rex_byte(false, modrm.rex_r, modrm.rex_x, modrm.rex_b));
+                }
+                buffer.emit_bytes(&[0x0F, 0x94]);
+                buffer.emit_bytes(&modrm.bytes);
+            }
+            SetG(x) => {
+                let modrm = encode_modrm(0, x);
+                if modrm.rex_r || modrm.rex_x || modrm.rex_b {
+                    buffer.emit_byte(rex_byte(false, modrm.rex_r, modrm.rex_x, modrm.rex_b));
+                }
+                buffer.emit_bytes(&[0x0F, 0x9F]);
+                buffer.emit_bytes(&modrm.bytes);
+            }
+            SetL(x) => {
+                let modrm = encode_modrm(0, x);
+                if modrm.rex_r || modrm.rex_x || modrm.rex_b {
+                    buffer.emit_byte(rex_byte(false, modrm.rex_r, modrm.rex_x, modrm.rex_b));
+                }
+                buffer.emit_bytes(&[0x0F, 0x9C]);
+                buffer.emit_bytes(&modrm.bytes);
+            }
+            Push(x) => {
+                let phys = register_physical(x);
+                if phys & 0x8 != 0 {
+                    buffer.emit_byte(rex_byte(false, false, false, true));
+                }
+                buffer.emit_byte(0x50 | (phys & 0x7));
+            }
+            Pop(x) => {
+                let phys = register_physical(x);
+                if phys & 0x8 != 0 {
+                    buffer.emit_byte(rex_byte(false, false, false, true));
+                }
+                buffer.emit_byte(0x58 | (phys & 0x7));
+            }
+            Ret => buffer.emit_byte(0xC3),
+            Syscall => buffer.emit_bytes(&[0x0F, 0x05]),
+            Call(label) => {
+                buffer.emit_byte(0xE8);
+                buffer.fixup_rel32(label);
+            }
+            Jmp(label) => {
+                buffer.emit_byte(0xE9);
+                buffer.fixup_rel32(label);
+            }
+            Je(label) => encode_jcc(buffer, 0x84, label),
+            Jg(label) => encode_jcc(buffer, 0x8F, label),
+            Jge(label) => encode_jcc(buffer, 0x8D, label),
+            Jl(label) => encode_jcc(buffer, 0x8C, label),
+            Jle(label) => encode_jcc(buffer, 0x8E, label),
+            Jne(label) => encode_jcc(buffer, 0x85, label),
+        }
+    }
+}
+
+/// The physical register number (with the REX extension bit folded into bit 3) for an operand
+/// that's known to be a register, e.g. the destination of `imul` or `sete`, or either side of an
+/// ALU instruction once the memory operand (if any) has been picked out for the rm slot.
+fn register_physical(value: &Value) -> u8 {
+    match value {
+        Value::Register(r, _) => r.physical(),
+        _ => panic!("internal error: expected a register operand, got {}", value),
+    }
+}
+
+/// Encodes one of the standard ALU instruction groups (`add`, `and`, `sub`, `xor`, `cmp`), all of
+/// which share the same `+0`/`+1`/`+2`/`+3` opcode layout: `base+1` is "store" form (r/m64, r64)
+/// and `base+3` is "load" form (r64, r/m64). Memory can only appear in the rm slot of whichever
+/// form applies, so the store form is used whenever `dst` is memory and the load form otherwise.
+fn encode_arith(buffer: &mut Buffer, base_opcode: u8, dst: &Value, src: &Value) {
+    let (opcode, reg, rm) = if matches!(dst, Value::Memory { .. }) {
+        (base_opcode + 1, src, dst)
+    } else {
+        (base_opcode + 3, dst, src)
+    };
+
+    let reg_field = register_physical(reg);
+    let modrm = encode_modrm(reg_field, rm);
+    buffer.emit_byte(rex_byte(true, modrm.rex_r, modrm.rex_x, modrm.rex_b));
+    buffer.emit_byte(opcode);
+    buffer.emit_bytes(&modrm.bytes);
+}
+
+/// `test r/m64, r64` (opcode `0x85`) is symmetric, so whichever operand is memory (if either)
+/// takes the rm slot and the other takes the reg slot.
+fn encode_test(buffer: &mut Buffer, dst: &Value, src: &Value) {
+    let (reg, rm) = if matches!(dst, Value::Memory { .. }) {
+        (src, dst)
+    } else {
+        (dst, src)
+    };
+
+    let reg_field = register_physical(reg);
+    let modrm = encode_modrm(reg_field, rm);
+    buffer.emit_byte(rex_byte(true, modrm.rex_r, modrm.rex_x, modrm.rex_b));
+    buffer.emit_byte(0x85);
+    buffer.emit_bytes(&modrm.bytes);
+}
+
+/// `idiv`/`neg` (and the rest of the single-operand F7 group) encode which operation they are in
+/// the ModRM reg field instead of the opcode, e.g. `/7` for `idiv`, `/3` for `neg`.
+fn encode_unary(buffer: &mut Buffer, extension: u8, x: &Value) {
+    let modrm = encode_modrm(extension, x);
+    buffer.emit_byte(rex_byte(true, modrm.rex_r, modrm.rex_x, modrm.rex_b));
+    buffer.emit_byte(0xF7);
+    buffer.emit_bytes(&modrm.bytes);
+}
+
+fn encode_jcc(buffer: &mut Buffer, condition: u8, label: &str) {
+    buffer.emit_bytes(&[0x0F, 0x80 | (condition & 0x0F)]);
+    buffer.fixup_rel32(label);
+}
+
+/// `mov`'s operands can be a register/memory pair (store form `0x89` or load form `0x8B`,
+/// chosen the same way as [`encode_arith`]) or an immediate source. A full 64-bit immediate can
+/// only be moved into a register, via `mov r64, imm64` (opcode `0xB8+r`); anything that fits in
+/// 32 bits instead uses the sign-extending `mov r/m64, imm32` (opcode `0xC7 /0`), which also
+/// supports a memory destination. A label source is a deferred 64-bit absolute address, encoded
+/// the same way as an oversized immediate.
+fn encode_mov(buffer: &mut Buffer, dst: &Value, src: &Value) {
+    match src {
+        Value::Label(target) => {
+            let phys = register_physical(dst);
+            buffer.emit_byte(rex_byte(true, false, false, phys & 0x8 != 0));
+            buffer.emit_byte(0xB8 | (phys & 0x7));
+            buffer.fixup_abs64(target);
+        }
+        Value::Immediate(x) => {
+            if let Ok(imm32) = i32::try_from(*x) {
+                let modrm = encode_modrm(0, dst);
+                buffer.emit_byte(rex_byte(true, modrm.rex_r, modrm.rex_x, modrm.rex_b));
+                buffer.emit_byte(0xC7);
+                buffer.emit_bytes(&modrm.bytes);
+                buffer.emit_bytes(&imm32.to_le_bytes());
+            } else {
+                let phys = register_physical(dst);
+                buffer.emit_byte(rex_byte(true, false, false, phys & 0x8 != 0));
+                buffer.emit_byte(0xB8 | (phys & 0x7));
+                buffer.emit_bytes(&x.to_le_bytes());
+            }
+        }
+        _ => encode_arith_like_mov(buffer, dst, src),
+    }
+}
+
+fn encode_arith_like_mov(buffer: &mut Buffer, dst: &Value, src: &Value) {
+    let (opcode, reg, rm) = if matches!(dst, Value::Memory { .. }) {
+        (0x89, src, dst)
+    } else {
+        (0x8B, dst, src)
+    };
+
+    let reg_field = register_physical(reg);
+    let modrm = encode_modrm(reg_field, rm);
+    buffer.emit_byte(rex_byte(true, modrm.rex_r, modrm.rex_x, modrm.rex_b));
+    buffer.emit_byte(opcode);
+    buffer.emit_bytes(&modrm.bytes);
+}
+
+// Self-check decoder.
+//
+// `Instruction::encode` above is the only place that knows how a Venice instruction turns into
+// bytes; nothing checks that those bytes mean what the generator thinks they mean. The decoder
+// below reads a `Buffer`'s bytes back into `DecodedInstruction`s -- a form that mirrors
+// `Instruction`/`Value` closely enough to compare against -- so the test at the bottom of this
+// file can walk a compiled program's instructions and its encoded bytes in lockstep and assert
+// each one decodes back to what was meant to be emitted. It only understands the exact opcode
+// forms `encode` produces (see the match in `decode_one` below); anything else is a bug in either
+// direction, so it panics rather than guessing.
+
+/// An operand as read back from encoded bytes: like `Value`, but registers and memory bases/
+/// indices are physical register numbers (0-15) rather than virtual `Register`s, since decoding
+/// can't recover which VIL register a physical one was assigned to.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum DecodedOperand {
+    Register(u8),
+    Immediate(i64),
+    Memory {
+        scale: u8,
+        displacement: i32,
+        base: u8,
+        index: Option<u8>,
+    },
+}
+
+/// An instruction as read back from encoded bytes, one variant per `Instruction` variant that
+/// `encode` can produce. Branch/call targets are left as the raw relative or absolute values the
+/// bytes actually contain -- resolving them back to a label requires knowing where that label
+/// landed, which only the caller (with the `Buffer` that produced these bytes) can check.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum DecodedInstruction {
+    Add(DecodedOperand, DecodedOperand),
+    And(DecodedOperand, DecodedOperand),
+    Call(i32),
+    Cmp(DecodedOperand, DecodedOperand),
+    IDiv(DecodedOperand),
+    IMul(DecodedOperand, DecodedOperand),
+    Je(i32),
+    Jg(i32),
+    Jge(i32),
+    Jl(i32),
+    Jle(i32),
+    Jmp(i32),
+    Jne(i32),
+    Mov(DecodedOperand, DecodedOperand),
+    Neg(DecodedOperand),
+    Pop(DecodedOperand),
+    Push(DecodedOperand),
+    Ret,
+    SetE(DecodedOperand),
+    SetG(DecodedOperand),
+    SetL(DecodedOperand),
+    Sub(DecodedOperand, DecodedOperand),
+    Syscall,
+    Test(DecodedOperand, DecodedOperand),
+    Xor(DecodedOperand, DecodedOperand),
+}
+
+/// The ModRM/SIB/displacement byte group read back from `bytes` starting at `pos`, the inverse of
+/// `encode_modrm`.
+struct DecodedModRM {
+    /// The full reg field (REX.R already folded in), for instructions that use it as a second
+    /// register operand rather than an opcode extension.
+    reg: u8,
+    rm: DecodedOperand,
+    /// Bytes consumed, starting at `pos`: the ModRM byte itself plus any SIB and displacement
+    /// bytes.
+    len: usize,
+}
+
+fn decode_modrm(bytes: &[u8], pos: usize, rex_r: bool, rex_x: bool, rex_b: bool) -> DecodedModRM {
+    let modrm = bytes[pos];
+    let md = modrm >> 6;
+    let reg_field = (modrm >> 3) & 0x7;
+    let rm_field = modrm & 0x7;
+    let reg = reg_field | ((rex_r as u8) << 3);
+
+    if md == 0b11 {
+        let register = rm_field | ((rex_b as u8) << 3);
+        return DecodedModRM {
+            reg,
+            rm: DecodedOperand::Register(register),
+            len: 1,
+        };
+    }
+
+    if md == 0b00 && rm_field == 0b101 {
+        panic!(
+            "internal error: RIP-relative ModRM encoding while decoding (encoder never emits this)"
+        );
+    }
+
+    let mut i = pos + 1;
+    let (base, index, scale) = if rm_field == 0b100 {
+        let sib = bytes[i];
+        i += 1;
+        let ss = sib >> 6;
+        let index_field = (sib >> 3) & 0x7;
+        let base_field = sib & 0x7;
+        let index = if index_field == 0b100 && !rex_x {
+            None
+        } else {
+            Some(index_field | ((rex_x as u8) << 3))
+        };
+        let scale = match ss {
+            0b00 => 1,
+            0b01 => 2,
+            0b10 => 4,
+            0b11 => 8,
+            _ => unreachable!(),
+        };
+        (base_field | ((rex_b as u8) << 3), index, scale)
+    } else {
+        (rm_field | ((rex_b as u8) << 3), None, 1)
+    };
+
+    let (displacement, disp_len) = match md {
+        0b00 => (0, 0),
+        0b01 => (bytes[i] as i8 as i32, 1),
+        0b10 => (i32::from_le_bytes(bytes[i..i + 4].try_into().unwrap()), 4),
+        _ => unreachable!(),
+    };
+    i += disp_len;
+
+    DecodedModRM {
+        reg,
+        rm: DecodedOperand::Memory {
+            scale,
+            displacement,
+            base,
+            index,
+        },
+        len: i - pos,
+    }
+}
+
+/// Decodes one instruction starting at `bytes[pos]`, returning it along with the offset of the
+/// byte immediately following it. Panics on any opcode outside the set `Instruction::encode` can
+/// produce: an unrecognized opcode here means either the encoder grew a new instruction without a
+/// matching decode path, or the bytes are corrupt, and either way guessing would hide the bug this
+/// decoder exists to catch.
+fn decode_one(bytes: &[u8], pos: usize) -> (DecodedInstruction, usize) {
+    let mut i = pos;
+    let (rex_w, rex_r, rex_x, rex_b) = if bytes[i] & 0xF0 == 0x40 {
+        let rex = bytes[i];
+        i += 1;
+        (
+            rex & 0x08 != 0,
+            rex & 0x04 != 0,
+            rex & 0x02 != 0,
+            rex & 0x01 != 0,
+        )
+    } else {
+        (false, false, false, false)
+    };
+    let _ = rex_w; // every opcode below implies its own operand width; the bit isn't needed again.
+
+    let opcode = bytes[i];
+    i += 1;
+
+    match opcode {
+        0x01 => {
+            let m = decode_modrm(bytes, i, rex_r, rex_x, rex_b);
+            (
+                DecodedInstruction::Add(m.rm, DecodedOperand::Register(m.reg)),
+                i + m.len,
+            )
+        }
+        0x03 => {
+            let m = decode_modrm(bytes, i, rex_r, rex_x, rex_b);
+            (
+                DecodedInstruction::Add(DecodedOperand::Register(m.reg), m.rm),
+                i + m.len,
+            )
+        }
+        0x21 => {
+            let m = decode_modrm(bytes, i, rex_r, rex_x, rex_b);
+            (
+                DecodedInstruction::And(m.rm, DecodedOperand::Register(m.reg)),
+                i + m.len,
+            )
+        }
+        0x23 => {
+            let m = decode_modrm(bytes, i, rex_r, rex_x, rex_b);
+            (
+                DecodedInstruction::And(DecodedOperand::Register(m.reg), m.rm),
+                i + m.len,
+            )
+        }
+        0x29 => {
+            let m = decode_modrm(bytes, i, rex_r, rex_x, rex_b);
+            (
+                DecodedInstruction::Sub(m.rm, DecodedOperand::Register(m.reg)),
+                i + m.len,
+            )
+        }
+        0x2B => {
+            let m = decode_modrm(bytes, i, rex_r, rex_x, rex_b);
+            (
+                DecodedInstruction::Sub(DecodedOperand::Register(m.reg), m.rm),
+                i + m.len,
+            )
+        }
+        0x31 => {
+            let m = decode_modrm(bytes, i, rex_r, rex_x, rex_b);
+            (
+                DecodedInstruction::Xor(m.rm, DecodedOperand::Register(m.reg)),
+                i + m.len,
+            )
+        }
+        0x33 => {
+            let m = decode_modrm(bytes, i, rex_r, rex_x, rex_b);
+            (
+                DecodedInstruction::Xor(DecodedOperand::Register(m.reg), m.rm),
+                i + m.len,
+            )
+        }
+        0x39 => {
+            let m = decode_modrm(bytes, i, rex_r, rex_x, rex_b);
+            (
+                DecodedInstruction::Cmp(m.rm, DecodedOperand::Register(m.reg)),
+                i + m.len,
+            )
+        }
+        0x3B => {
+            let m = decode_modrm(bytes, i, rex_r, rex_x, rex_b);
+            (
+                DecodedInstruction::Cmp(DecodedOperand::Register(m.reg), m.rm),
+                i + m.len,
+            )
+        }
+        0x85 => {
+            let m = decode_modrm(bytes, i, rex_r, rex_x, rex_b);
+            (
+                DecodedInstruction::Test(DecodedOperand::Register(m.reg), m.rm),
+                i + m.len,
+            )
+        }
+        0x89 => {
+            let m = decode_modrm(bytes, i, rex_r, rex_x, rex_b);
+            (
+                DecodedInstruction::Mov(m.rm, DecodedOperand::Register(m.reg)),
+                i + m.len,
+            )
+        }
+        0x8B => {
+            let m = decode_modrm(bytes, i, rex_r, rex_x, rex_b);
+            (
+                DecodedInstruction::Mov(DecodedOperand::Register(m.reg), m.rm),
+                i + m.len,
+            )
+        }
+        0xC7 => {
+            let m = decode_modrm(bytes, i, rex_r, rex_x, rex_b);
+            let imm_start = i + m.len;
+            let imm = i32::from_le_bytes(bytes[imm_start..imm_start + 4].try_into().unwrap());
+            (
+                DecodedInstruction::Mov(m.rm, DecodedOperand::Immediate(imm as i64)),
+                imm_start + 4,
+            )
+        }
+        0xB8..=0xBF => {
+            let register = (opcode - 0xB8) | ((rex_b as u8) << 3);
+            let imm = i64::from_le_bytes(bytes[i..i + 8].try_into().unwrap());
+            (
+                DecodedInstruction::Mov(
+                    DecodedOperand::Register(register),
+                    DecodedOperand::Immediate(imm),
+                ),
+                i + 8,
+            )
+        }
+        0xF7 => {
+            let m = decode_modrm(bytes, i, rex_r, rex_x, rex_b);
+            let end = i + m.len;
+            match m.reg & 0x7 {
+                0x7 => (DecodedInstruction::IDiv(m.rm), end),
+                0x3 => (DecodedInstruction::Neg(m.rm), end),
+                other => panic!(
+                    "internal error: unsupported 0xF7 /{} opcode extension while decoding",
+                    other
+                ),
+            }
+        }
+        0x50..=0x57 => {
+            let register = (opcode - 0x50) | ((rex_b as u8) << 3);
+            (
+                DecodedInstruction::Push(DecodedOperand::Register(register)),
+                i,
+            )
+        }
+        0x58..=0x5F => {
+            let register = (opcode - 0x58) | ((rex_b as u8) << 3);
+            (
+                DecodedInstruction::Pop(DecodedOperand::Register(register)),
+                i,
+            )
+        }
+        0xC3 => (DecodedInstruction::Ret, i),
+        0xE8 => {
+            let rel = i32::from_le_bytes(bytes[i..i + 4].try_into().unwrap());
+            (DecodedInstruction::Call(rel), i + 4)
+        }
+        0xE9 => {
+            let rel = i32::from_le_bytes(bytes[i..i + 4].try_into().unwrap());
+            (DecodedInstruction::Jmp(rel), i + 4)
+        }
+        0x0F => {
+            let opcode2 = bytes[i];
+            i += 1;
+            match opcode2 {
+                0x05 => (DecodedInstruction::Syscall, i),
+                0x94 => {
+                    let m = decode_modrm(bytes, i, rex_r, rex_x, rex_b);
+                    (DecodedInstruction::SetE(m.rm), i + m.len)
+                }
+                0x9F => {
+                    let m = decode_modrm(bytes, i, rex_r, rex_x, rex_b);
+                    (DecodedInstruction::SetG(m.rm), i + m.len)
+                }
+                0x9C => {
+                    let m = decode_modrm(bytes, i, rex_r, rex_x, rex_b);
+                    (DecodedInstruction::SetL(m.rm), i + m.len)
+                }
+                0xAF => {
+                    let m = decode_modrm(bytes, i, rex_r, rex_x, rex_b);
+                    (
+                        DecodedInstruction::IMul(DecodedOperand::Register(m.reg), m.rm),
+                        i + m.len,
+                    )
+                }
+                0x80..=0x8F => {
+                    let rel = i32::from_le_bytes(bytes[i..i + 4].try_into().unwrap());
+                    let end = i + 4;
+                    let instruction = match opcode2 {
+                        0x84 => DecodedInstruction::Je(rel),
+                        0x8F => DecodedInstruction::Jg(rel),
+                        0x8D => DecodedInstruction::Jge(rel),
+                        0x8C => DecodedInstruction::Jl(rel),
+                        0x8E => DecodedInstruction::Jle(rel),
+                        0x85 => DecodedInstruction::Jne(rel),
+                        other => panic!(
+                            "internal error: unsupported jcc condition 0x{:x} while decoding",
+                            other
+                        ),
+                    };
+                    (instruction, end)
+                }
+                other => panic!(
+                    "internal error: unsupported two-byte opcode 0x0F 0x{:x} while decoding",
+                    other
+                ),
+            }
+        }
+        other => panic!(
+            "internal error: unsupported opcode 0x{:x} while decoding",
+            other
+        ),
+    }
+}
+
+/// Decodes every instruction in `bytes` in sequence, from offset 0 to the end. Used by the
+/// round-trip test below to walk a whole compiled program's machine code back into
+/// `DecodedInstruction`s, one per original `Instruction`.
+fn decode_buffer(bytes: &[u8]) -> Vec<DecodedInstruction> {
+    let mut decoded = Vec::new();
+    let mut pos = 0;
+    while pos < bytes.len() {
+        let (instruction, next) = decode_one(bytes, pos);
+        decoded.push(instruction);
+        pos = next;
+    }
+    decoded
+}
+
+#[cfg(test)]
+mod decoder_tests {
+    use super::*;
+    use crate::analyzer;
+    use crate::codegen;
+    use crate::inference;
+    use crate::lexer;
+    use crate::parser;
+
+    fn compile(source: &str) -> Program {
+        let lexer = lexer::Lexer::new("<string>", source);
+        let (mut ptree, parse_errors) = parser::parse(lexer);
+        assert!(
+            parse_errors.is_empty(),
+            "program should parse: {:?}",
+            parse_errors
+        );
+        inference::infer(&mut ptree).expect("program should type-infer");
+        let ast = analyzer::analyze(&ptree).expect("program should analyze");
+        let vil_program =
+            codegen::generate(&ast, &X86Config, false).expect("program should generate VIL");
+        generate(&vil_program).expect("program should generate x86")
+    }
+
+    /// Encodes `program`, decodes the result back, and asserts that every decoded instruction
+    /// matches the `Instruction` that produced it -- including, for calls and jumps, that the
+    /// decoded relative displacement actually lands on the target label's resolved offset. This
+    /// can only pass if `Instruction::encode` and `decode_one` agree on what every opcode means,
+    /// which is the whole point: it's the guard described in the module comment above.
+    fn assert_round_trips(program: &Program) {
+        let buffer = program.encode();
+
+        let instructions: Vec<&Instruction> = program
+            .blocks
+            .iter()
+            .flat_map(|block| block.instructions.iter())
+            .collect();
+
+        let mut pos = 0;
+        for instruction in &instructions {
+            assert!(
+                pos < buffer.bytes.len(),
+                "ran out of bytes decoding {}",
+                instruction
+            );
+            let (decoded, next) = decode_one(&buffer.bytes, pos);
+            assert_decoded_matches(instruction, &decoded, next, &buffer);
+            pos = next;
+        }
+
+        assert_eq!(
+            pos,
+            buffer.bytes.len(),
+            "decoding stopped short of the end of the buffer"
+        );
+    }
+
+    fn assert_decoded_matches(
+        instruction: &Instruction,
+        decoded: &DecodedInstruction,
+        end: usize,
+        buffer: &Buffer,
+    ) {
+        use DecodedInstruction as D;
+        use Instruction as I;
+
+        let expected_displacement = |label: &str| -> i32 {
+            let target = *buffer
+                .labels
+                .get(label)
+                .unwrap_or_else(|| panic!("undefined label: {}", label))
+                as i64;
+            i32::try_from(target - end as i64).expect("displacement should fit in 32 bits")
+        };
+
+        match (instruction, decoded) {
+            (I::Add(dst, src), D::Add(d_dst, d_src))
+            | (I::And(dst, src), D::And(d_dst, d_src))
+            | (I::Sub(dst, src), D::Sub(d_dst, d_src))
+            | (I::Xor(dst, src), D::Xor(d_dst, d_src))
+            | (I::Cmp(dst, src), D::Cmp(d_dst, d_src))
+            | (I::Test(dst, src), D::Test(d_dst, d_src))
+            | (I::Mov(dst, src), D::Mov(d_dst, d_src))
+            | (I::IMul(dst, src), D::IMul(d_dst, d_src)) => {
+                assert_operand(dst, d_dst);
+                assert_operand(src, d_src);
+            }
+            (I::IDiv(x), D::IDiv(d_x))
+            | (I::Neg(x), D::Neg(d_x))
+            | (I::SetE(x), D::SetE(d_x))
+            | (I::SetG(x), D::SetG(d_x))
+            | (I::SetL(x), D::SetL(d_x))
+            | (I::Push(x), D::Push(d_x))
+            | (I::Pop(x), D::Pop(d_x)) => assert_operand(x, d_x),
+            (I::Ret, D::Ret) | (I::Syscall, D::Syscall) => {}
+            (I::Call(label), D::Call(rel))
+            | (I::Jmp(label), D::Jmp(rel))
+            | (I::Je(label), D::Je(rel))
+            | (I::Jg(label), D::Jg(rel))
+            | (I::Jge(label), D::Jge(rel))
+            | (I::Jl(label), D::Jl(rel))
+            | (I::Jle(label), D::Jle(rel))
+            | (I::Jne(label), D::Jne(rel)) => {
+                assert_eq!(*rel, expected_displacement(label));
+            }
+            _ => panic!(
+                "decoded instruction doesn't match what was emitted: emitted {}, decoded {:?}",
+                instruction, decoded
+            ),
+        }
+    }
+
+    fn assert_operand(value: &Value, decoded: &DecodedOperand) {
+        match (value, decoded) {
+            (Value::Register(r, _), DecodedOperand::Register(phys)) => {
+                assert_eq!(r.physical(), *phys);
+            }
+            (Value::Immediate(x), DecodedOperand::Immediate(decoded_x)) => {
+                assert_eq!(*x, *decoded_x);
+            }
+            (Value::Label(_), DecodedOperand::Immediate(_)) => {
+                // A label used as a `mov` source is a 64-bit absolute address, resolved by
+                // `Buffer::resolve` to the label's byte offset: there's no label table here to
+                // check it against, so decoding an immediate at all is as far as this can verify.
+            }
+            (
+                Value::Memory {
+                    scale,
+                    displacement,
+                    base,
+                    index,
+                },
+                DecodedOperand::Memory {
+                    scale: d_scale,
+                    displacement: d_displacement,
+                    base: d_base,
+                    index: d_index,
+                },
+            ) => {
+                assert_eq!(*scale, *d_scale);
+                assert_eq!(*displacement, *d_displacement);
+                assert_eq!(base.physical(), *d_base);
+                assert_eq!(index.as_ref().map(|r| r.physical()), *d_index);
+            }
+            _ => panic!(
+                "operand shape mismatch: emitted {}, decoded {:?}",
+                value, decoded
+            ),
+        }
+    }
+
+    #[test]
+    fn round_trips_calls_and_branches() {
+        let program = compile(
+            "func add(a: i64, b: i64) -> i64 {\n  return a + b;\n}\n\nfunc main() -> i64 {\n  let x: i64 = add(1, 2);\n  if x == 3 {\n    return 0;\n  } else {\n    return 1;\n  }\n}\n",
+        );
+        assert_round_trips(&program);
+    }
+
+    #[test]
+    fn round_trips_loops_and_division() {
+        let program = compile(
+            "func main() -> i64 {\n  let i: i64 = 0;\n  let total: i64 = 0;\n  while i < 5 {\n    total = total + i / 2;\n    i = i + 1;\n  }\n  return total;\n}\n",
+        );
+        assert_round_trips(&program);
+    }
+}
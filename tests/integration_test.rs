@@ -1,12 +1,13 @@
 use assert_cmd::prelude::*;
 use predicates::prelude::*;
 use std::fs;
+use std::path::{Path, PathBuf};
 use std::process::Command;
 
 #[test]
 fn test_hello() {
     let output_path = "examples/hello";
-    let _cleanup = CleanupFile::new(&output_path);
+    let _cleanup = BuildArtifacts::new([output_path]);
 
     let mut cmd = Command::cargo_bin("venice").unwrap();
     cmd.arg("examples/hello.vn");
@@ -16,16 +17,39 @@ fn test_hello() {
     cmd.assert().stdout(predicate::str::diff("Hello, world!\n"));
 }
 
-struct CleanupFile(String);
+/// Tracks every path this test creates and removes them all -- recursing into directories --
+/// once the test finishes, whether it passed or panicked partway through. See the identical
+/// struct in `end_to_end.rs` (duplicated rather than shared, since each file under `tests/`
+/// compiles as its own independent binary) for why it refuses to ever remove `/` or `$HOME`.
+struct BuildArtifacts(Vec<PathBuf>);
 
-impl CleanupFile {
-    fn new(path: &str) -> Self {
-        CleanupFile(String::from(path))
+impl BuildArtifacts {
+    fn new(paths: impl IntoIterator<Item = impl Into<PathBuf>>) -> Self {
+        BuildArtifacts(paths.into_iter().map(Into::into).collect())
     }
 }
 
-impl Drop for CleanupFile {
+impl Drop for BuildArtifacts {
     fn drop(&mut self) {
-        let _ = fs::remove_file(&self.0);
+        for path in &self.0 {
+            assert_not_root(path);
+            if path.is_dir() {
+                let _ = fs::remove_dir_all(path);
+            } else {
+                let _ = fs::remove_file(path);
+            }
+        }
+    }
+}
+
+fn assert_not_root(path: &Path) {
+    let resolved = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    if resolved == Path::new("/") {
+        panic!("refusing to remove the filesystem root: {:?}", path);
+    }
+    if let Ok(home) = std::env::var("HOME") {
+        if resolved == Path::new(&home) {
+            panic!("refusing to remove $HOME: {:?}", path);
+        }
     }
 }